@@ -0,0 +1,63 @@
+//! UBX to RINEX conversion throughput, in epochs per second,
+//! on the bundled capture (GPS L1 C/A and Galileo E1-C), which is shared
+//! with the passive round trip tests.
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+
+use rinex::prelude::{Constellation, Observable};
+
+use std::{io::Cursor, str::FromStr};
+
+use ubx2rinex::{RinexSettings, UbloxSettings, UbxToRinex};
+
+/// 30 epochs at 1 s, 8 satellites per epoch, with navigation messages
+const CAPTURE: &str = "data/UBX/rawx-gps-gal-30s.ubx";
+
+/// Number of epochs in the capture
+const EPOCHS: u64 = 30;
+
+fn conversion(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let bytes = std::fs::read(CAPTURE).unwrap_or_else(|e| panic!("{}: {}", CAPTURE, e));
+
+    let observables = ["C1C", "L1C", "D1C", "S1C"]
+        .iter()
+        .map(|code| Observable::from_str(code).unwrap())
+        .collect::<Vec<_>>();
+
+    let constellations = vec![Constellation::GPS, Constellation::Galileo];
+
+    let settings = RinexSettings {
+        observables: constellations
+            .iter()
+            .map(|constellation| (*constellation, observables.clone()))
+            .collect(),
+        ..Default::default()
+    };
+
+    let ublox = UbloxSettings {
+        constellations,
+        ..Default::default()
+    };
+
+    let mut group = c.benchmark_group("conversion");
+    group.throughput(Throughput::Elements(EPOCHS));
+    group.sample_size(10);
+
+    group.bench_function("gps-gal", |b| {
+        b.iter(|| {
+            runtime.block_on(
+                UbxToRinex::new(Cursor::new(bytes.clone()))
+                    .with_rinex_settings(settings.clone())
+                    .with_ublox_settings(ublox.clone())
+                    .convert_to_memory(),
+            )
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, conversion);
+criterion_main!(benches);
@@ -0,0 +1,450 @@
+#!/usr/bin/env python3
+"""
+Generates rawx-gps-gal-30s.ubx: 30 epochs at 1 s of a static receiver,
+tracking 5 GPS (L1 C/A) and 3 Galileo (E1-C) satellites.
+Used by the passive round trip tests and the conversion benchmark.
+
+Each epoch is streamed like a receiver would: RXM-SFRBX (when a GPS subframe
+completes), RXM-RAWX, NAV-PVT and NAV-EOE. The GPS subframes #1 to #3 describe
+the broadcast ephemeris (LNAV, with valid parity), and the measurements are derived
+from those orbits: geometric range, satellite and receiver clocks, troposphere and ionosphere.
+
+    python3 data/UBX/rawx-gps-gal-30s.py
+"""
+
+import datetime
+import math
+import os
+import struct
+
+EPOCHS = 30
+WEEK = 2345
+TOW = 345600
+LEAP_SECONDS = 18
+
+# WGS84
+MU = 3.986005e14
+OMEGA_E = 7.2921151467e-5
+SPEED_OF_LIGHT = 299792458.0
+WGS84_A = 6378137.0
+WGS84_E2 = 6.69437999014e-3
+
+# L1 / E1 carrier
+L1_FREQUENCY = 1575.42e6
+WAVELENGTH = SPEED_OF_LIGHT / L1_FREQUENCY
+
+# static receiver (Paris), geodetic coordinates
+RX_LAT = 48.8462
+RX_LON = 2.3464
+RX_HEIGHT = 98.0
+
+# receiver clock: offset (m) and drift (m/s)
+RX_CLOCK_OFFSET = 25.0
+RX_CLOCK_DRIFT = 0.12
+
+# ephemeris reference time (s of week), next 2 hour boundary
+TOE = TOW + 7200
+
+# leapSec valid
+REC_STAT = 0x01
+
+# pr valid, cp valid, half cycle
+TRK_STAT = 0x07
+
+# (gnssId, svId): all tracked on signal #0
+SATELLITES = [(0, 2), (0, 5), (0, 12), (0, 25), (0, 29), (2, 3), (2, 8), (2, 13)]
+
+
+def ubx_frame(class_id, msg_id, payload):
+    body = bytes([class_id, msg_id]) + struct.pack("<H", len(payload)) + payload
+
+    ck_a = ck_b = 0
+
+    for byte in body:
+        ck_a = (ck_a + byte) & 0xFF
+        ck_b = (ck_b + ck_a) & 0xFF
+
+    return b"\xb5\x62" + body + bytes([ck_a, ck_b])
+
+
+def quantize(value, scale):
+    return int(round(value / scale))
+
+
+class Orbit:
+    """Broadcast orbit, in the LNAV units (semicircles), quantized like the broadcast"""
+
+    def __init__(self, n, gnss_id, azimuth):
+        self.iodc = 40 + n
+        self.toe = TOE
+
+        self.sqrt_a = quantize(5440.588 if gnss_id == 2 else 5153.65, 2**-19) * 2**-19
+        self.e = quantize(0.002 + 0.0015 * n, 2**-33) * 2**-33
+        self.i0 = quantize((56.0 if gnss_id == 2 else 55.0) / 180.0, 2**-31) * 2**-31
+        self.omega = quantize(0.35 - 0.11 * n, 2**-31) * 2**-31
+        self.dn = quantize(1.4e-9, 2**-43) * 2**-43
+        self.omega_dot = quantize(-2.6e-9, 2**-43) * 2**-43
+        self.idot = quantize(1.2e-11, 2**-43) * 2**-43
+        self.cuc = quantize(-1.1e-6 + 2.0e-7 * n, 2**-29) * 2**-29
+        self.cus = quantize(7.5e-6 - 1.0e-6 * n, 2**-29) * 2**-29
+        self.cic = quantize(4.1e-8, 2**-29) * 2**-29
+        self.cis = quantize(-2.2e-8, 2**-29) * 2**-29
+        self.crc = quantize(231.5 - 9.0 * n, 2**-5) * 2**-5
+        self.crs = quantize(-18.4 + 3.0 * n, 2**-5) * 2**-5
+
+        self.tgd = quantize(-5.6e-9 + 1.0e-9 * n, 2**-31) * 2**-31
+        self.af0 = quantize(-1.9e-4 + 6.5e-5 * n, 2**-31) * 2**-31
+        self.af1 = quantize(-3.4e-12 + 1.1e-12 * n, 2**-43) * 2**-43
+        self.af2 = 0.0
+
+        # places the satellite above the receiver, towards this azimuth
+        self.omega0, self.m0 = 0.0, 0.0
+        best = None
+
+        for i in range(72):
+            for j in range(180):
+                self.omega0 = quantize(-1.0 + i / 36.0, 2**-31) * 2**-31
+                self.m0 = quantize(-1.0 + j / 90.0, 2**-31) * 2**-31
+
+                elevation, az = look_angles(self.position(TOW)[0])
+                error = abs((az - azimuth + 180.0) % 360.0 - 180.0)
+
+                if 35.0 <= elevation <= 75.0 and (best is None or error < best[0]):
+                    best = (error, self.omega0, self.m0)
+
+        _, self.omega0, self.m0 = best
+
+    def clock_offset(self, t):
+        dt = t - self.toe
+        return self.af0 + self.af1 * dt + self.af2 * dt * dt
+
+    def position(self, t):
+        """ECEF position (m) at transmission time t (s of week), IS-GPS-200 table 20-IV"""
+        a = self.sqrt_a**2
+        tk = t - self.toe
+
+        n = math.sqrt(MU / a**3) + self.dn * math.pi
+        mk = self.m0 * math.pi + n * tk
+
+        ek = mk
+        for _ in range(10):
+            ek = mk + self.e * math.sin(ek)
+
+        nu = math.atan2(math.sqrt(1.0 - self.e**2) * math.sin(ek), math.cos(ek) - self.e)
+        phi = nu + self.omega * math.pi
+
+        uk = phi + self.cus * math.sin(2 * phi) + self.cuc * math.cos(2 * phi)
+        rk = a * (1.0 - self.e * math.cos(ek)) + self.crs * math.sin(2 * phi) + self.crc * math.cos(2 * phi)
+        ik = (
+            self.i0 * math.pi
+            + self.idot * math.pi * tk
+            + self.cis * math.sin(2 * phi)
+            + self.cic * math.cos(2 * phi)
+        )
+
+        xp, yp = rk * math.cos(uk), rk * math.sin(uk)
+
+        omega_k = (
+            self.omega0 * math.pi
+            + (self.omega_dot * math.pi - OMEGA_E) * tk
+            - OMEGA_E * self.toe
+        )
+
+        x = xp * math.cos(omega_k) - yp * math.cos(ik) * math.sin(omega_k)
+        y = xp * math.sin(omega_k) + yp * math.cos(ik) * math.cos(omega_k)
+        z = yp * math.sin(ik)
+
+        return (x, y, z), ek
+
+
+def receiver_ecef():
+    lat, lon = math.radians(RX_LAT), math.radians(RX_LON)
+    n = WGS84_A / math.sqrt(1.0 - WGS84_E2 * math.sin(lat) ** 2)
+
+    return (
+        (n + RX_HEIGHT) * math.cos(lat) * math.cos(lon),
+        (n + RX_HEIGHT) * math.cos(lat) * math.sin(lon),
+        (n * (1.0 - WGS84_E2) + RX_HEIGHT) * math.sin(lat),
+    )
+
+
+def look_angles(position):
+    """(elevation, azimuth) in degrees, as seen from the receiver"""
+    rx = receiver_ecef()
+    dx, dy, dz = (position[i] - rx[i] for i in range(3))
+
+    lat, lon = math.radians(RX_LAT), math.radians(RX_LON)
+
+    east = -math.sin(lon) * dx + math.cos(lon) * dy
+    north = (
+        -math.sin(lat) * math.cos(lon) * dx
+        - math.sin(lat) * math.sin(lon) * dy
+        + math.cos(lat) * dz
+    )
+    up = math.cos(lat) * math.cos(lon) * dx + math.cos(lat) * math.sin(lon) * dy + math.sin(lat) * dz
+
+    elevation = math.degrees(math.atan2(up, math.hypot(east, north)))
+    azimuth = math.degrees(math.atan2(east, north)) % 360.0
+
+    return elevation, azimuth
+
+
+def pseudo_range(orbit, t_rx):
+    """Pseudo range (m) and elevation (deg) at reception time t_rx (s of week, GPST)"""
+    rx = receiver_ecef()
+
+    tau = 0.075
+    for _ in range(5):
+        position, _ = orbit.position(t_rx - tau)
+
+        # earth rotation during the propagation
+        theta = OMEGA_E * tau
+        x = position[0] * math.cos(theta) + position[1] * math.sin(theta)
+        y = -position[0] * math.sin(theta) + position[1] * math.cos(theta)
+
+        rho = math.dist((x, y, position[2]), rx)
+        tau = rho / SPEED_OF_LIGHT
+
+    elevation, _ = look_angles(position)
+    obliquity = 1.0 / math.sin(math.radians(elevation))
+
+    troposphere = 2.4 * obliquity
+    ionosphere = 3.1 * obliquity
+
+    rx_clock = RX_CLOCK_OFFSET + RX_CLOCK_DRIFT * (t_rx - TOW)
+    sv_clock = SPEED_OF_LIGHT * orbit.clock_offset(t_rx - tau)
+
+    return rho + rx_clock - sv_clock + troposphere + ionosphere, ionosphere, elevation
+
+
+def rxm_rawx(k, orbits):
+    payload = struct.pack("<dHbBBB2x", TOW + k, WEEK, LEAP_SECONDS, len(SATELLITES), REC_STAT, 0x01)
+
+    for n, (gnss_id, sv_id) in enumerate(SATELLITES):
+        orbit = orbits[n]
+
+        pr, ionosphere, elevation = pseudo_range(orbit, TOW + k)
+        pr_next, _, _ = pseudo_range(orbit, TOW + k + 0.01)
+
+        # carrier phase advance (ionosphere), with an integer ambiguity
+        cp = (pr - 2.0 * ionosphere) / WAVELENGTH - 1024 * (n + 1)
+        dop = -(pr_next - pr) / 0.01 / WAVELENGTH
+
+        lock_time = min(20000 + 3000 * n + k * 1000, 64500)
+        cno = int(round(28.0 + 22.0 * math.sin(math.radians(elevation))))
+
+        payload += struct.pack(
+            "<ddfBBBBHBBBBBx",
+            pr,
+            cp,
+            dop,
+            gnss_id,
+            sv_id,
+            0,
+            0,
+            lock_time,
+            cno,
+            3,
+            2,
+            4,
+            TRK_STAT,
+        )
+
+    return ubx_frame(0x02, 0x15, payload)
+
+
+def nav_pvt(k):
+    t_utc = (
+        datetime.datetime(1980, 1, 6)
+        + datetime.timedelta(weeks=WEEK, seconds=TOW + k - LEAP_SECONDS)
+    )
+
+    payload = struct.pack(
+        "<IHBBBBBBIiBBBBiiiiIIiiiiiIIHBx4xiHH",
+        (TOW + k) * 1000,
+        t_utc.year,
+        t_utc.month,
+        t_utc.day,
+        t_utc.hour,
+        t_utc.minute,
+        t_utc.second,
+        0x07,  # validDate, validTime, fullyResolved
+        20,  # tAcc (ns)
+        0,  # nano
+        3,  # 3D fix
+        0x01,  # gnssFixOK
+        0xE0,  # confirmed date & time
+        len(SATELLITES),
+        int(round(RX_LON * 1e7)),
+        int(round(RX_LAT * 1e7)),
+        int(round(RX_HEIGHT * 1e3)),
+        int(round((RX_HEIGHT - 44.6) * 1e3)),
+        1500,  # hAcc (mm)
+        2300,  # vAcc (mm)
+        0,  # velN
+        0,  # velE
+        0,  # velD
+        0,  # gSpeed
+        0,  # headMot
+        80,  # sAcc (mm/s)
+        18000000,  # headAcc
+        120,  # pDOP
+        0,  # flags3
+        0,  # headVeh
+        0,  # magDec
+        0,  # magAcc
+    )
+
+    return ubx_frame(0x01, 0x07, payload)
+
+
+def nav_eoe(k):
+    return ubx_frame(0x01, 0x61, struct.pack("<I", (TOW + k) * 1000))
+
+
+def bits(value, width):
+    return [(value >> (width - 1 - i)) & 1 for i in range(width)]
+
+
+def lnav_word(data, d29, d30):
+    """30 bit LNAV word: 24 data bits (not inverted) and their parity (IS-GPS-200 table 20-XIV)"""
+    d = [None] + data
+
+    def xor(*indexes):
+        return sum(d[i] for i in indexes) & 1
+
+    parity = [
+        d29 ^ xor(1, 2, 3, 5, 6, 10, 11, 12, 13, 14, 17, 18, 20, 23),
+        d30 ^ xor(2, 3, 4, 6, 7, 11, 12, 13, 14, 15, 18, 19, 21, 24),
+        d29 ^ xor(1, 3, 4, 5, 7, 8, 12, 13, 14, 15, 16, 19, 20, 22),
+        d30 ^ xor(2, 4, 5, 6, 8, 9, 13, 14, 15, 16, 17, 20, 21, 23),
+        d30 ^ xor(1, 3, 5, 6, 7, 9, 10, 14, 15, 16, 17, 18, 21, 22, 24),
+        d29 ^ xor(3, 5, 6, 8, 9, 10, 11, 13, 15, 19, 22, 23, 24),
+    ]
+
+    word = 0
+    for bit in data + parity:
+        word = (word << 1) | bit
+
+    return word, parity[4], parity[5]
+
+
+def lnav_subframe(fields, d29, d30):
+    """Encodes 10 words of (value, width) fields. Words #2 and #10 end with
+    the 2 bits that null their own D29 and D30, as specified."""
+    words = []
+
+    for i, word_fields in enumerate(fields):
+        data = []
+        for value, width in word_fields:
+            data += bits(value & ((1 << width) - 1), width)
+
+        if i in (1, 9):
+            for t in range(4):
+                word, p29, p30 = lnav_word(data[:22] + bits(t, 2), d29, d30)
+                if p29 == 0 and p30 == 0:
+                    break
+        else:
+            word, p29, p30 = lnav_word(data, d29, d30)
+
+        assert len(data) == 24 or i in (1, 9)
+
+        words.append(word)
+        d29, d30 = p29, p30
+
+    return words, d29, d30
+
+
+def gps_subframe(subframe, orbit, tow_count):
+    """GPS LNAV subframe #1, #2 or #3"""
+    tlm = [(0x8B, 8), (0x1234, 14), (0, 1), (0, 1)]
+    how = [(tow_count, 17), (0, 1), (1, 1), (subframe, 3), (0, 2)]
+
+    iode = orbit.iodc & 0xFF
+
+    if subframe == 1:
+        af0 = quantize(orbit.af0, 2**-31)
+        words = [
+            [((WEEK % 1024), 10), (1, 2), (0, 4), (0, 6), (orbit.iodc >> 8, 2)],
+            [(0, 1), (0, 23)],
+            [(0, 24)],
+            [(0, 24)],
+            [(0, 16), (quantize(orbit.tgd, 2**-31), 8)],
+            [(orbit.iodc & 0xFF, 8), (quantize(orbit.toe, 2**4), 16)],
+            [(quantize(orbit.af2, 2**-55), 8), (quantize(orbit.af1, 2**-43), 16)],
+            [(af0, 22), (0, 2)],
+        ]
+    elif subframe == 2:
+        m0 = quantize(orbit.m0, 2**-31)
+        e = quantize(orbit.e, 2**-33)
+        sqrt_a = quantize(orbit.sqrt_a, 2**-19)
+        words = [
+            [(iode, 8), (quantize(orbit.crs, 2**-5), 16)],
+            [(quantize(orbit.dn, 2**-43), 16), (m0 >> 24, 8)],
+            [(m0, 24)],
+            [(quantize(orbit.cuc, 2**-29), 16), (e >> 24, 8)],
+            [(e, 24)],
+            [(quantize(orbit.cus, 2**-29), 16), (sqrt_a >> 24, 8)],
+            [(sqrt_a, 24)],
+            [(quantize(orbit.toe, 2**4), 16), (0, 1), (0, 5), (0, 2)],
+        ]
+    else:
+        omega0 = quantize(orbit.omega0, 2**-31)
+        i0 = quantize(orbit.i0, 2**-31)
+        omega = quantize(orbit.omega, 2**-31)
+        words = [
+            [(quantize(orbit.cic, 2**-29), 16), (omega0 >> 24, 8)],
+            [(omega0, 24)],
+            [(quantize(orbit.cis, 2**-29), 16), (i0 >> 24, 8)],
+            [(i0, 24)],
+            [(quantize(orbit.crc, 2**-5), 16), (omega >> 24, 8)],
+            [(omega, 24)],
+            [(quantize(orbit.omega_dot, 2**-43), 24)],
+            [(iode, 8), (quantize(orbit.idot, 2**-43), 14), (0, 2)],
+        ]
+
+    return [tlm, how] + words
+
+
+def rxm_sfrbx(sv_id, words):
+    # gnssId, svId, reserved, freqId, numWords, chn, version, reserved
+    payload = struct.pack("<BBBBBBBB", 0, sv_id, 0, 0, len(words), sv_id, 0x02, 0)
+
+    for word in words:
+        payload += struct.pack("<I", word)
+
+    return ubx_frame(0x02, 0x13, payload)
+
+
+if __name__ == "__main__":
+    path = os.path.join(os.path.dirname(os.path.abspath(__file__)), "rawx-gps-gal-30s.ubx")
+
+    orbits = [
+        Orbit(n, gnss_id, 360.0 * n / len(SATELLITES) + 15.0)
+        for n, (gnss_id, _) in enumerate(SATELLITES)
+    ]
+
+    # parity state of each GPS satellite
+    parity = {sv_id: (0, 0) for (gnss_id, sv_id) in SATELLITES if gnss_id == 0}
+
+    with open(path, "wb") as fd:
+        for k in range(EPOCHS):
+            t = TOW + k
+
+            # subframes #1 to #3 start at TOW (6 s each), released once complete
+            if k > 0 and k % 6 == 0 and k // 6 <= 3:
+                subframe = k // 6
+
+                for n, (gnss_id, sv_id) in enumerate(SATELLITES):
+                    if gnss_id != 0:
+                        continue
+
+                    fields = gps_subframe(subframe, orbits[n], t // 6)
+                    words, d29, d30 = lnav_subframe(fields, *parity[sv_id])
+                    parity[sv_id] = (d29, d30)
+
+                    fd.write(rxm_sfrbx(sv_id, words))
+
+            fd.write(rxm_rawx(k, orbits))
+            fd.write(nav_pvt(k))
+            fd.write(nav_eoe(k))
@@ -1,355 +1,926 @@
-use clap::{Arg, ArgAction, ArgMatches, ColorChoice, Command};
-use rinex::prelude::{Constellation, Duration, Observable, TimeScale};
+use clap::{Arg, ArgAction, ArgMatches, ColorChoice, Command, parser::ValueSource, value_parser};
+use log::LevelFilter;
+use rinex::prelude::{Constellation, Duration, Observable, Rinex, SV, TimeScale};
 
-use crate::{
+use ubx2rinex::{
     UbloxSettings,
-    collecter::settings::{HealthMask, Settings as RinexSettings},
-    utils::SignalCarrier,
+    collecter::{
+        Fragments,
+        settings::{FlushPolicy, HealthMask, PhaseCenter, Settings as RinexSettings},
+    },
+    device::{ACK_TIMEOUT, SerialSettings},
+    ubx::InfLevel,
+    utils::{
+        ObservableKind, SignalCarrier, dedup_observables, igs_antenna, is_country_code,
+        is_igs_antenna,
+    },
 };
 
 use std::{collections::HashMap, str::FromStr};
 
+use thiserror::Error;
+
+/// Default data silence threshold, in active mode
+const DATA_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Command line [CliError]s, reported to the user on startup
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("--{option}: invalid value \"{value}\": {reason}")]
+    InvalidValue {
+        option: &'static str,
+        value: String,
+        reason: String,
+    },
+
+    #[error("configuration file \"{path}\": {reason}")]
+    Config { path: String, reason: String },
+
+    #[error("reference RINEX \"{path}\": {reason}")]
+    Reference { path: String, reason: String },
+
+    #[error("{0}")]
+    Usage(String),
+}
+
 pub struct Cli {
     /// Arguments passed by user
     matches: ArgMatches,
+
+    /// Name of each station
+    station_names: Vec<String>,
+
+    /// Serial port baud rate
+    baud_rate: Option<u32>,
+
+    /// Data silence threshold
+    data_timeout: std::time::Duration,
+
+    /// Acknowledgment timeout
+    ack_timeout: std::time::Duration,
+
+    /// [UbloxSettings]
+    ublox_settings: UbloxSettings,
+
+    /// [RinexSettings]
+    rinex_settings: RinexSettings,
 }
 
 impl Cli {
-    /// Build new command line interface
-    pub fn new() -> Self {
-        Self {
-            matches: {
-                Command::new("ubx2rinex")
-                    .author("Guillaume W. Bres, <guillaume.bressaix@gmail.com>")
-                    .version(env!("CARGO_PKG_VERSION"))
-                    .about("U-Blox stream to RINEX collecter")
-                    .color(ColorChoice::Always)
-                    .arg_required_else_help(true)
-                    .next_help_heading("Serial port (Active device, GNSS module)")
-                    .arg(
-                        Arg::new("port")
-                            .short('p')
-                            .long("port")
-                            .value_name("PORT")
-                            .required_unless_present_any(&["file"])
-                            .help("Define serial port. Example /dev/ttyUSB0 on Linux")
-                    )
-                    .arg(
-                        Arg::new("baudrate")
-                            .short('b')
-                            .long("baud")
-                            .required(false)
-                            .value_name("Baudrate (u32)")
-                            .help("Define serial port baud rate. Communications will not work if your U-Blox streams at a different data-rate. By default we use 115_200"),
-                    )
-                    .next_help_heading("Constellation selection")
-                    .arg(
-                        Arg::new("gps")
-                            .long("gps")
-                            .action(ArgAction::SetTrue)
-                            .help("Activate GPS constellation.
+    /// Build new command line interface. All options are validated at this point.
+    pub fn new() -> Result<Self, CliError> {
+        let command = Command::new("ubx2rinex")
+            .author("Guillaume W. Bres, <guillaume.bressaix@gmail.com>")
+            .version(env!("CARGO_PKG_VERSION"))
+            .about("U-Blox stream to RINEX collecter")
+            .color(ColorChoice::Always)
+            .arg_required_else_help(true)
+            .arg(
+                Arg::new("log-format")
+                    .long("log-format")
+                    .value_name("text|json")
+                    .value_parser(["text", "json"])
+                    .default_value("text")
+                    .help("Define the log format. \"json\" emits one JSON record per line (timestamp, level, module, message,
+and epoch/sv fields when the message describes them), which is suited for log aggregators."),
+            )
+            .arg(
+                Arg::new("quiet")
+                    .short('q')
+                    .long("quiet")
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with("verbose")
+                    .help("Disable all application logs. $RUST_LOG, when defined, takes precedence."),
+            )
+            .arg(
+                Arg::new("verbose")
+                    .short('v')
+                    .long("verbose")
+                    .action(ArgAction::Count)
+                    .help("Increase the logs sensitivity: -v (info), -vv (debug), -vvv (trace),
+without defining $RUST_LOG. $RUST_LOG, when defined, takes precedence."),
+            )
+            .arg(
+                Arg::new("dump-packets")
+                    .long("dump-packets")
+                    .action(ArgAction::SetTrue)
+                    .help("Print each decoded UBX packet (class, ID and decoded fields) on stdout, while collecting.
+This helps diagnosing a receiver configuration, for example when expected messages are not arriving.
+Combine it with --no-obs to only dump the packets."),
+            )
+            .arg(
+                Arg::new("capture-inf")
+                    .long("capture-inf")
+                    .value_name("error,warning,notice")
+                    .value_delimiter(',')
+                    .value_parser(["error", "warning", "notice"])
+                    .action(ArgAction::Append)
+                    .help("Capture the UBX-INF messages of these severities in the Observation RINEX header comments,
+so the receiver state can be diagnosed afterwards. For example --capture-inf error,warning.
+The receiver must be configured to output UBX-INF (they are only logged otherwise)."),
+            )
+            .arg(
+                Arg::new("config")
+                    .long("config")
+                    .value_name("FILENAME")
+                    .help("Load options from a TOML configuration file, for example a fixed station deployment.
+Each key is the long name of an option (port, sampling, observables..), flags are booleans
+and repeated options are arrays. Options defined on the command line take precedence.
+Refer to the README for an example."),
+            )
+            .arg(
+                Arg::new("precision")
+                    .long("precision")
+                    .value_name("DURATION")
+                    .required(false)
+                    .help("Precision of the epochs reported in the logs. By default, it follows the sampling period
+for sub-second sampling (for example, 100 ms at 10 Hz) and is otherwise 1 s."),
+            )
+            .arg(
+                Arg::new("channel-capacity")
+                    .long("channel-capacity")
+                    .value_name("N")
+                    .value_parser(value_parser!(usize))
+                    .default_value("128")
+                    .help("Capacity of the internal channels, between the U-Blox stream and the RINEX collecters.
+Increase this value for high rate, multi-GNSS collection."),
+            )
+            .next_help_heading("Serial port (Active device, GNSS module)")
+            .arg(
+                Arg::new("port")
+                    .short('p')
+                    .long("port")
+                    .value_name("PORT")
+                    .action(ArgAction::Append)
+                    .required_unless_present_any(&["file"])
+                    .help("Define serial port. Example /dev/ttyUSB0 on Linux.
+Several ports may be defined (for example base and rover), each one producing its own RINEX set:
+each --port then requires its own --name, in the same order.")
+            )
+            .arg(
+                Arg::new("baudrate")
+                    .short('b')
+                    .long("baud")
+                    .required(false)
+                    .value_name("Baudrate (u32)")
+                    .help("Define serial port baud rate. Communications will not work if your U-Blox streams at a different data-rate. By default we use 115_200"),
+            )
+            .arg(
+                Arg::new("data-bits")
+                    .long("data-bits")
+                    .value_name("BITS")
+                    .value_parser(["7", "8"])
+                    .required(false)
+                    .help("Serial port data bits (default: 8)."),
+            )
+            .arg(
+                Arg::new("parity")
+                    .long("parity")
+                    .value_name("PARITY")
+                    .value_parser(["none", "odd", "even"])
+                    .required(false)
+                    .help("Serial port parity (default: none)."),
+            )
+            .arg(
+                Arg::new("stop-bits")
+                    .long("stop-bits")
+                    .value_name("BITS")
+                    .value_parser(["1", "2"])
+                    .required(false)
+                    .help("Serial port stop bits (default: 1)."),
+            )
+            .arg(
+                Arg::new("flow-control")
+                    .long("flow-control")
+                    .value_name("FLOW")
+                    .value_parser(["none", "software", "hardware"])
+                    .required(false)
+                    .help("Serial port flow control (default: none), for example \"hardware\" for RTS/CTS.
+This only applies to the host side: the framing options apply to both the host and the receiver."),
+            )
+            .arg(
+                Arg::new("ack-timeout")
+                    .long("ack-timeout")
+                    .value_name("DURATION")
+                    .required(false)
+                    .help("Maximal duration we wait for the device to acknowledge each configuration request (default: 3 s).
+The application exits when the device does not respond in time, typically due to incorrect baud rate."),
+            )
+            .arg(
+                Arg::new("ack-retries")
+                    .long("ack-retries")
+                    .value_name("N")
+                    .value_parser(value_parser!(u8))
+                    .default_value("2")
+                    .help("Number of times a configuration request is sent again, when the device
+does not acknowledge it within --ack-timeout. A rejected request is not sent again."),
+            )
+            .arg(
+                Arg::new("raw-out")
+                    .long("raw-out")
+                    .value_name("FILENAME")
+                    .required(false)
+                    .conflicts_with("file")
+                    .help("Copy the raw UBX stream into a file, while collecting RINEX.
+This allows reprocessing the capture later on, with different options.
+Only applies to active mode (serial port)."),
+            )
+            .arg(
+                Arg::new("data-timeout")
+                    .long("data-timeout")
+                    .value_name("DURATION")
+                    .required(false)
+                    .conflicts_with("file")
+                    .help("Warn when no valid UBX packet was received for this duration (default: 10 s),
+for example when the antenna or the device was disconnected. Only applies to active mode (serial port)."),
+            )
+            .arg(
+                Arg::new("exit-on-timeout")
+                    .long("exit-on-timeout")
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with("file")
+                    .help("Exit (releasing all pending content) when no valid UBX packet was received for --data-timeout."),
+            )
+            .next_help_heading("Constellation selection")
+            .arg(
+                Arg::new("gps")
+                    .long("gps")
+                    .action(ArgAction::SetTrue)
+                    .help("Activate GPS constellation.
 When working from UBX files, this serves as a data filter.")
-                            .required_unless_present_any(["file", "galileo", "beidou", "qzss", "glonass", "sbas", "irnss"]),
-                    )
-                    .arg(
-                        Arg::new("galileo")
-                            .long("galileo")
-                            .action(ArgAction::SetTrue)
-                            .help("Activate Galileo constellation.
+                    .required_unless_present_any(["file", "galileo", "beidou", "qzss", "glonass", "sbas", "irnss"]),
+            )
+            .arg(
+                Arg::new("galileo")
+                    .long("galileo")
+                    .action(ArgAction::SetTrue)
+                    .help("Activate Galileo constellation.
 When working from UBX files, this serves as a data filter.")
-                            .required_unless_present_any(["file", "gps", "beidou", "qzss", "glonass", "sbas", "irnss"]),
-                    )
-                    .arg(
-                        Arg::new("bds")
-                            .long("bds")
-                            .action(ArgAction::SetTrue)
-                            .help("Activate BDS (BeiDou) constellation.
+                    .required_unless_present_any(["file", "gps", "beidou", "qzss", "glonass", "sbas", "irnss"]),
+            )
+            .arg(
+                Arg::new("bds")
+                    .long("bds")
+                    .action(ArgAction::SetTrue)
+                    .help("Activate BDS (BeiDou) constellation.
 When working from UBX files, this serves as a data filter.")
-                            .required_unless_present_any(["file", "galileo", "gps", "qzss", "glonass", "sbas", "irnss"]),
-                    )
-                    .arg(
-                        Arg::new("qzss")
-                            .long("qzss")
-                            .action(ArgAction::SetTrue)
-                            .help("Activate QZSS constellation.
+                    .required_unless_present_any(["file", "galileo", "gps", "qzss", "glonass", "sbas", "irnss"]),
+            )
+            .arg(
+                Arg::new("qzss")
+                    .long("qzss")
+                    .action(ArgAction::SetTrue)
+                    .help("Activate QZSS constellation.
 When working from UBX files, this serves as a data filter.")
-                            .required_unless_present_any(["file", "galileo", "gps", "bds", "glonass", "sbas", "irnss"]),
-                    )
-                    .arg(
-                        Arg::new("glonass")
-                            .long("glonass")
-                            .action(ArgAction::SetTrue)
-                            .help("Activate Glonass constellation.
+                    .required_unless_present_any(["file", "galileo", "gps", "bds", "glonass", "sbas", "irnss"]),
+            )
+            .arg(
+                Arg::new("glonass")
+                    .long("glonass")
+                    .action(ArgAction::SetTrue)
+                    .help("Activate Glonass constellation.
 When working from UBX files, this serves as a data filter.")
-                            .required_unless_present_any(["file", "galileo", "gps", "bds", "qzss", "sbas", "irnss"]),
-                    )
-                    .arg(
-                        Arg::new("sbas")
-                            .long("sbas")
-                            .action(ArgAction::SetTrue)
-                            .help("Activate SBAS augmentation.
+                    .required_unless_present_any(["file", "galileo", "gps", "bds", "qzss", "sbas", "irnss"]),
+            )
+            .arg(
+                Arg::new("sbas")
+                    .long("sbas")
+                    .action(ArgAction::SetTrue)
+                    .help("Activate SBAS augmentation.
 When working from UBX files, this serves as a data filter.")
-                            .required_unless_present_any(["file", "galileo", "gps", "bds", "qzss", "glonass", "irnss"]),
-                    )
-                    .arg(
-                        Arg::new("irnss")
-                            .long("irnss")
-                            .action(ArgAction::SetTrue)
-                            .help("Activate IRNSS/NAVIC constellation.
+                    .required_unless_present_any(["file", "galileo", "gps", "bds", "qzss", "glonass", "irnss"]),
+            )
+            .arg(
+                Arg::new("irnss")
+                    .long("irnss")
+                    .action(ArgAction::SetTrue)
+                    .help("Activate IRNSS/NAVIC constellation.
 When working from UBX files, this serves as a data filter.")
-                            .required_unless_present_any(["file", "galileo", "gps", "bds", "qzss", "glonass", "sbas"]),
-                    )
-                    .next_help_heading("Signal selection")
-                    .arg(
-                        Arg::new("l1")
-                            .long("l1")
-                            .action(ArgAction::SetTrue)
-                            .help("Activate L1 signal for all constellations. Not required when operating from UBX files.")
-                            .required_unless_present_any(["file", "l2", "l5"]),
-                    )
-                    .arg(
-                        Arg::new("l2")
-                            .long("l2")
-                            .action(ArgAction::SetTrue)
-                            .help("Activate L2 signal for all constellations. Not required when operating from UBX files.")
-                            .required_unless_present_any(["file", "l1", "l5"]),
-                    )
-                    .arg(
-                        Arg::new("l5")
-                            .long("l5")
-                            .action(ArgAction::SetTrue)
-                            .help("Activate L5 signal for all constellations. Requires F9 or F10 series. Not required when operating from UBX files")
-                            .required_unless_present_any(["file", "l1", "l2"]),
-                    )
-                    .next_help_heading("U-Blox configuration")
-                    .arg(
-                        Arg::new("profile")
-                            .long("prof")
-                            .action(ArgAction::Set)
-                            .help("Define user profile. Default is set to \"portable\""),
-                    )
-                    .arg(
-                        Arg::new("rx-clock")
-                            .long("rx-clock")
-                            .action(ArgAction::SetTrue)
-                            .help("Resolve clock state and capture it. Disabled by default"),
-                    )
-                    .arg(
-                        Arg::new("anti-spoofing")
-                            .long("anti-spoofing")
-                            .action(ArgAction::SetTrue)
-                            .help("Makes sure anti jamming/spoofing is enabled. When enabled, it is automatically emphasized in the collected RINEX."))
-                    .arg(
-                        Arg::new("model")
-                            .short('m')
-                            .long("model")
-                            .required(false)
-                            .value_name("Receiver model/name/label")
-                            .help("Define the name or label of this receiver. Customizes your RINEX content. For example \"M8T\" when using an undefined M8-T device.")
-                    )
-                    .arg(
-                        Arg::new("antenna")
-                            .short('a')
-                            .long("antenna")
-                            .required(false)
-                            .value_name("Receiver antenna model/name/label")
-                            .help("Define the name or label of antenna attached to this receiver.
-Customizes your RINEX content."))
-                    .next_help_heading("File interface (Passive mode)")
-                    .arg(
-                        Arg::new("file")
-                            .long("file")
-                            .short('f')
-                            .value_name("FILENAME")
-                            .action(ArgAction::Append)
-                            .required_unless_present_any(&["port"])
-                            .help("Load a single UBX file. You can load as many as needed.
+                    .required_unless_present_any(["file", "galileo", "gps", "bds", "qzss", "glonass", "sbas"]),
+            )
+            .next_help_heading("Signal selection")
+            .arg(
+                Arg::new("l1")
+                    .long("l1")
+                    .action(ArgAction::SetTrue)
+                    .help("Activate L1 signal for all constellations. Not required when operating from UBX files.")
+                    .required_unless_present_any(["file", "l2", "l5"]),
+            )
+            .arg(
+                Arg::new("l2")
+                    .long("l2")
+                    .action(ArgAction::SetTrue)
+                    .help("Activate L2 signal for all constellations. Not required when operating from UBX files.")
+                    .required_unless_present_any(["file", "l1", "l5"]),
+            )
+            .arg(
+                Arg::new("l5")
+                    .long("l5")
+                    .action(ArgAction::SetTrue)
+                    .help("Activate L5 signal for all constellations. Requires F9 or F10 series. Not required when operating from UBX files")
+                    .required_unless_present_any(["file", "l1", "l2"]),
+            )
+            .next_help_heading("U-Blox configuration")
+            .arg(
+                Arg::new("profile")
+                    .long("prof")
+                    .action(ArgAction::Set)
+                    .help("Define user profile. Default is set to \"portable\""),
+            )
+            .arg(
+                Arg::new("rx-clock")
+                    .long("rx-clock")
+                    .action(ArgAction::SetTrue)
+                    .help("Resolve clock state and capture it. Disabled by default"),
+            )
+            .arg(
+                Arg::new("no-nav-eoe")
+                    .long("no-nav-eoe")
+                    .action(ArgAction::SetTrue)
+                    .help("For receivers that do not support UBX-NAV-EOE: end of epochs are then derived from the RXM-RAWX (or NAV-PVT) epoch stream.
+Note that this fallback is automatically activated, when NAV-EOE is not received in timely manner."),
+            )
+            .arg(
+                Arg::new("no-nav-pvt")
+                    .long("no-nav-pvt")
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with("velocity-csv")
+                    .help("Do not stream UBX-NAV-PVT, to reduce the serial load at high sampling rates.
+The country code is then no longer inferred from the position (see --country).
+Only applies to active mode (serial port)."),
+            )
+            .arg(
+                Arg::new("persist")
+                    .long("persist")
+                    .action(ArgAction::SetTrue)
+                    .help("Also write the constellation and signal settings to the BBR and Flash layers,
+so a deployed station retains them across power cycles (by default, they are only applied to RAM).
+Only applies to active mode (serial port)."),
+            )
+            .arg(
+                Arg::new("no-nav-sat")
+                    .long("no-nav-sat")
+                    .action(ArgAction::SetTrue)
+                    .help("Do not stream UBX-NAV-SAT, to reduce the serial load at high sampling rates.
+Only applies to active mode (serial port)."),
+            )
+            .arg(
+                Arg::new("anti-spoofing")
+                    .long("anti-spoofing")
+                    .action(ArgAction::SetTrue)
+                    .help("Makes sure anti jamming/spoofing is enabled. When enabled, it is automatically emphasized in the collected RINEX."))
+            .arg(
+                Arg::new("spectrum")
+                    .long("spectrum")
+                    .value_name("FILEPATH")
+                    .required(false)
+                    .action(ArgAction::Set)
+                    .help("Periodically poll the RF telemetry (MON-RF) and log it to this CSV file:
+noise level, AGC, CW jamming indicator and I/Q imbalance, for each RF block. Useful to hunt RF interferences.
+Not available in passive mode."))
+            .arg(
+                Arg::new("velocity-csv")
+                    .long("velocity-csv")
+                    .value_name("FILEPATH")
+                    .required(false)
+                    .action(ArgAction::Set)
+                    .help("Log the receiver velocity (NAV-PVT) to this CSV file:
+north, east and down velocity, ground speed (m/s) and heading of motion (degrees), for each navigation solution."))
+            .arg(
+                Arg::new("time-pulse-csv")
+                    .long("time-pulse-csv")
+                    .value_name("FILEPATH")
+                    .required(false)
+                    .action(ArgAction::Set)
+                    .help("Log the time pulse (TIM-TP) to this CSV file: epoch of the next pulse and its quantization error (ps),
+once per second. Timing receivers may use it to refine the receiver clock state."))
+            .arg(
+                Arg::new("qc")
+                    .long("qc")
+                    .value_name("FILEPATH")
+                    .required(false)
+                    .action(ArgAction::Set)
+                    .help("Release a quality control report to this file at the end of the run:
+observation completeness, mean C/N0 and multipath (code minus carrier RMS), per satellite."))
+            .arg(
+                Arg::new("model")
+                    .short('m')
+                    .long("model")
+                    .required(false)
+                    .value_name("Receiver model/name/label")
+                    .help("Define the name or label of this receiver. Customizes your RINEX content. For example \"M8T\" when using an undefined M8-T device.")
+            )
+            .arg(
+                Arg::new("antenna")
+                    .short('a')
+                    .long("antenna")
+                    .required(false)
+                    .value_name("Receiver antenna model/name/label")
+                    .help("Define the name or label of antenna attached to this receiver.
+Customizes your RINEX content. Geodetic processing expects IGS antenna types,
+for example \"TRM59800.00     NONE\": a warning is emitted otherwise."))
+            .arg(
+                Arg::new("radome")
+                    .long("radome")
+                    .required(false)
+                    .requires("antenna")
+                    .value_name("CODE")
+                    .help("Radome code (4 characters) appended to the --antenna type, following the IGS convention.
+Use \"NONE\" when the antenna has no radome."))
+            .arg(
+                Arg::new("phase-center")
+                    .long("phase-center")
+                    .required(false)
+                    .action(ArgAction::Append)
+                    .value_name("SYS OBS N E U")
+                    .help("Antenna phase center offset of an observable, with respect to the antenna reference point,
+as North, East and Up offsets in meters (RINEX3+). For example --phase-center \"G L1C 0.0012 -0.0005 0.0871\".
+Repeat this option for each observable."))
+            .next_help_heading("File interface (Passive mode)")
+            .arg(
+                Arg::new("file")
+                    .long("file")
+                    .short('f')
+                    .value_name("FILENAME")
+                    .action(ArgAction::Append)
+                    .required_unless_present_any(&["port"])
+                    .help("Load a single UBX file. You can load as many as needed.
 Each file descriptor is consumed one after the other (no priority). To obtain valid results,
 you might have to load them in correct chronological order (sampling order).
 Gzip compressed UBX files are natively supported but they must be terminated with '.gz'.
 You still have to select the constellation you are interested in (at least one).
 You don't have to select a signal.")
-                    )
-                    .next_help_heading("RINEX Collection")
-                    .arg(
-                        Arg::new("name")
-                            .long("name")
-                            .short('n')
-                            .required(false)
-                            .action(ArgAction::Set)
-                            .help("Define a custom name. To respect standard naming conventions,
+            )
+            .arg(
+                Arg::new("strict")
+                    .long("strict")
+                    .action(ArgAction::SetTrue)
+                    .help("Input files that overlap in time are a fatal error: the collection stops
+and ubx2rinex exits with a non zero code. By default, we only emit a warning, because the output RINEX will contain duplicate epochs.")
+            )
+            .arg(
+                Arg::new("keep-going")
+                    .long("keep-going")
+                    .action(ArgAction::SetTrue)
+                    .requires("file")
+                    .help("Skip the input files that cannot be opened or read (corrupt), with a warning,
+rather than aborting: one bad file does not compromise the rest of the batch.")
+            )
+            .arg(
+                Arg::new("replay-rate")
+                    .long("replay-rate")
+                    .value_name("RATE")
+                    .value_parser(value_parser!(f64))
+                    .requires("file")
+                    .help("Pace the input files to real time (1.0), or a multiple of it (for example 10.0),
+rather than converting them as fast as possible. This turns a file into a simulated live feed,
+for example to test downstream consumers with --flush-policy epoch.")
+            )
+            .arg(
+                Arg::new("parallel")
+                    .long("parallel")
+                    .action(ArgAction::SetTrue)
+                    .requires("file")
+                    .conflicts_with_all(["continuous", "crx", "merge", "sbas-log", "velocity-csv", "time-pulse-csv", "qc", "keep-going", "replay-rate"])
+                    .help("Decode the input files concurrently (one task per file), then merge them
+into a single file per kind. The input files must be sorted chronologically and must not overlap in time.")
+            )
+            .next_help_heading("RINEX Collection")
+            .arg(
+                Arg::new("name")
+                    .long("name")
+                    .short('n')
+                    .required(false)
+                    .action(ArgAction::Append)
+                    .help("Define a custom name. To respect standard naming conventions,
 this should be a 4 letter code, usually named after your geodetic marker.
-When not defined, the default value is \"UBXR\".")
-                    )
-                    .arg(
-                        Arg::new("prefix")
-                            .long("prefix")
-                            .required(false)
-                            .help("Custom directory prefix for output products. Default is none!"),
-                    )
-                    .arg(
-                        Arg::new("period")
-                            .long("period")
-                            .action(ArgAction::Set)
-                            .required(false)
-                            .help("Define snapshot (=collection) period.
+When not defined, the default value is \"UBXR\".
+When collecting several serial ports, define one name per --port, in the same order.")
+            )
+            .arg(
+                Arg::new("prefix")
+                    .long("prefix")
+                    .required(false)
+                    .help("Custom directory prefix for output products. Default is none!"),
+            )
+            .arg(
+                Arg::new("period")
+                    .long("period")
+                    .action(ArgAction::Set)
+                    .required(false)
+                    .help("Define snapshot (=collection) period.
 The snapshot period defines the total duration of your RINEX file and how often it is released.
 Our default snapshot period is set to 1 hour.
 Modify this value to 24hours for standard daily files, with --period \"24 h\".
 Other example, 12h period: --period \"12 h\".
-Other example, half hour period: --period \"30 mins\".")
-                    )
-                    .arg(
-                        Arg::new("v2")
-                            .long("v2")
-                            .action(ArgAction::SetTrue)
-                            .help("Downgrade RINEX revision to V2. You can also upgrade to RINEX V4 with --v4.
+Other example, half hour period: --period \"30 mins\".
+With V3 file names, the period must be a whole number of minutes, hours or days (up to 99).")
+            )
+            .arg(
+                Arg::new("continuous")
+                    .long("continuous")
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with("period")
+                    .help("Continuous (permanent station) collection: a new daily file is started at each UTC midnight.
+When restarting during the day, the existing file of that day is continued (appended to) rather than truncated.
+The collection settings should not be modified in between.")
+            )
+            .arg(
+                Arg::new("merge")
+                    .long("merge")
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with("crx")
+                    .help("Once the collection is over, merge the files produced during this session
+into a single daily file (one per kind and per day). The merged files are then removed.")
+            )
+            .arg(
+                Arg::new("v2")
+                    .long("v2")
+                    .action(ArgAction::SetTrue)
+                    .help("Downgrade RINEX revision to V2. You can also upgrade to RINEX V4 with --v4.
 We use V3 by default, because very few tools support V4 properly to this day.
 You should not use --v2 with multi band devices (>M8).")
-                    )
-                    .arg(
-                        Arg::new("v4")
-                            .long("v4")
-                            .action(ArgAction::SetTrue)
-                            .help("Upgrade RINEX revision to V4. You can also downgrade to RINEX V2 with --v2.
+            )
+            .arg(
+                Arg::new("v4")
+                    .long("v4")
+                    .action(ArgAction::SetTrue)
+                    .help("Upgrade RINEX revision to V4. You can also downgrade to RINEX V2 with --v2.
 We use V3 by default, because very few tools support V4 properly to this day.")
-                    )
-                    .arg(
-                        Arg::new("long")
-                            .short('l')
-                            .long("long")
-                            .action(ArgAction::SetTrue)
-                            .help("Prefer long (V3 like) file names over short (V2) file names.
-You must define a Country code to obtain a valid file name.")
-                    )
-                    .arg(
-                        Arg::new("gzip")
-                            .long("gzip")
-                            .action(ArgAction::SetTrue)
-                            .help("Gzip compress the RINEX output.
+            )
+            .arg(
+                Arg::new("long")
+                    .short('l')
+                    .long("long")
+                    .action(ArgAction::SetTrue)
+                    .help("Prefer long (V3 like) file names over short (V2) file names.
+You should define a Country code (--country) to obtain a valid file name.
+Otherwise, it is inferred from the first position fix, or \"FRA\" is used (with a warning).")
+            )
+            .arg(
+                Arg::new("filename-template")
+                    .long("filename-template")
+                    .value_name("TEMPLATE")
+                    .conflicts_with("long")
+                    .help("Custom file name template, rather than the standard file names, for archives that follow their own scheme.
+Supported tokens: {name}, {yyyy} (year), {doy} (day of year), {hh} (hour of day, UTC),
+{sys} (constellation letter, M when mixed) and {type} (O, D for CRINEX, or N).
+Example: --filename-template \"{name}{yyyy}{doy}{hh}.{type}\". .gz is appended when compressing.")
+            )
+            .arg(
+                Arg::new("gzip")
+                    .long("gzip")
+                    .action(ArgAction::SetTrue)
+                    .help("Gzip compress the RINEX output.
 You can combine this to CRINEX compression for maximal signal storage effiency."))
-                    .arg(
-                        Arg::new("country")
-                            .short('c')
-                            .action(ArgAction::Set)
-                            .help("Specify country code (3 letter) in case of V3 file name. Default: \"FRA\"")
-                    )
-                    .arg(
-                        Arg::new("agency")
-                            .long("agency")
-                            .action(ArgAction::Set)
-                            .required(false)
-                            .help("Define name of your Agency, to be used in all Headers"),
-                    )
-                    .arg(
-                        Arg::new("observer")
-                            .long("observer")
-                            .action(ArgAction::Set)
-                            .required(false)
-                            .help("Define name of Observer, to be used in all Headers"),
-                    )
-                    .arg(
-                        Arg::new("operator")
-                            .long("operator")
-                            .action(ArgAction::Set)
-                            .required(false)
-                            .help("Define name of Operator, to be used in all Headers"),
-                    )
-                    .arg(
-                        Arg::new("comment")
-                            .long("comment")
-                            .action(ArgAction::Set)
-                            .required(false)
-                            .help("Add one custom comment to your RINEX Header,
+            .arg(
+                Arg::new("gzip-obs")
+                    .long("gzip-obs")
+                    .action(ArgAction::SetTrue)
+                    .help("Gzip compress the Observation RINEX only. --gzip compresses all outputs."))
+            .arg(
+                Arg::new("gzip-nav")
+                    .long("gzip-nav")
+                    .action(ArgAction::SetTrue)
+                    .help("Gzip compress the Navigation RINEX only. --gzip compresses all outputs."))
+            .arg(
+                Arg::new("gzip-on-close")
+                    .long("gzip-on-close")
+                    .action(ArgAction::SetTrue)
+                    .help("Write the current file plain and only gzip compress it once closed (end of session, or file rotation).
+The file being collected can then be monitored (tail -f, for example)."))
+            .arg(
+                Arg::new("country")
+                    .short('c')
+                    .long("country")
+                    .action(ArgAction::Set)
+                    .help("Specify country code (3 letter) in case of V3 file name.
+By default, it is inferred from the first position fix (NAV-PVT), or \"FRA\" when that is not possible.")
+            )
+            .arg(
+                Arg::new("agency")
+                    .long("agency")
+                    .action(ArgAction::Set)
+                    .required(false)
+                    .help("Define name of your Agency, to be used in all Headers"),
+            )
+            .arg(
+                Arg::new("observer")
+                    .long("observer")
+                    .action(ArgAction::Set)
+                    .required(false)
+                    .help("Define name of Observer, to be used in all Headers"),
+            )
+            .arg(
+                Arg::new("operator")
+                    .long("operator")
+                    .action(ArgAction::Set)
+                    .required(false)
+                    .help("Define name of Operator, to be used in all Headers"),
+            )
+            .arg(
+                Arg::new("comment")
+                    .long("comment")
+                    .action(ArgAction::Set)
+                    .required(false)
+                    .help("Add one custom comment to your RINEX Header,
 to be wrapped into several lines if it exceeds 60 characters."))
-                    .next_help_heading("Observations collection (signal sampling)")
-                    .arg(
-                        Arg::new("no-obs")
-                            .long("no-obs")
-                            .action(ArgAction::SetTrue)
-                            .help("Disable Observation RINEX collection. You can use this if you intend to collect Ephemerides only for example"),
-                    )
+            .next_help_heading("Observations collection (signal sampling)")
+            .arg(
+                Arg::new("no-obs")
+                    .long("no-obs")
+                    .action(ArgAction::SetTrue)
+                    .help("Disable Observation RINEX collection. You can use this if you intend to collect Ephemerides only for example"),
+            )
+            .arg(
+                Arg::new("obs")
+                    .long("obs")
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with("no-obs")
+                    .help("Collect Observation RINEX along --nav, when deserializing files.
+When deserializing files, --nav alone only produces Navigation RINEX."),
+            )
+            .arg(
+                Arg::new("observables-from-header")
+                    .long("observables-from-header")
+                    .value_name("FILEPATH")
+                    .required(false)
+                    .action(ArgAction::Set)
+                    .help("Adopt the observables (SYS / # / OBS TYPES) of this reference Observation RINEX,
+rather than the ones derived from the selected signals. Columns are kept in the same order,
+so the output matches an existing network. Observables the receiver does not produce are left blank.")
+            )
+            .arg(
+                Arg::new("sampling")
+                    .short('s')
+                    .long("sampling")
+                    .required(false)
+                    .help("Define sampling interval. Default value is 30s (standard low-rate RINEX).")
+            )
+            .arg(
+                Arg::new("no-phase")
+                    .long("no-phase")
+                    .action(ArgAction::SetTrue)
+                    .help("Do not track signal phase")
+            )
+            .arg(
+                Arg::new("no-pr")
+                    .long("no-pr")
+                    .action(ArgAction::SetTrue)
+                    .help("Do not decode pseudo range")
+            )
+            .arg(
+                Arg::new("no-dop")
+                    .long("no-dop")
+                    .action(ArgAction::SetTrue)
+                    .help("Do not track doppler shifts")
+            )
+            .arg(
+                Arg::new("no-ssi")
+                    .long("no-ssi")
+                    .action(ArgAction::SetTrue)
+                    .help("Do not save SSI (received power) estimates")
+            )
+            .arg(
+                Arg::new("obs-only-sv")
+                    .long("obs-only-sv")
+                    .value_name("SV")
+                    .value_delimiter(',')
+                    .action(ArgAction::Append)
+                    .help("Limit the observations to these satellites only, for example --obs-only-sv G01,G02,E14")
+            )
+            .arg(
+                Arg::new("exclude-sv")
+                    .long("exclude-sv")
+                    .value_name("SV")
+                    .value_delimiter(',')
+                    .action(ArgAction::Append)
+                    .help("Exclude these satellites from the observations, for example --exclude-sv G04,E14")
+            )
+            .arg(
+                Arg::new("flush-policy")
+                    .long("flush-policy")
+                    .value_name("epoch|periodic|close")
+                    .value_parser(["epoch", "periodic", "close"])
+                    .help("Define when the Observation RINEX is flushed to the disk.
+\"epoch\": after each epoch, which is the default for serial port (live tailing).
+\"periodic\": every 10 seconds.
+\"close\": when the file is released, which is the default for file conversion (throughput).")
+            )
+            .arg(
+                Arg::new("raw-snr")
+                    .long("raw-snr")
+                    .action(ArgAction::SetTrue)
+                    .help("Declare the unit of the SSI observables, which carry the C/N0 in dB-Hz (RINEX3+).")
+            )
+            .arg(
+                Arg::new("epoch-flag-on-clk-reset")
+                    .long("epoch-flag-on-clk-reset")
+                    .action(ArgAction::SetTrue)
+                    .help("Flag the observation epoch where the receiver clock was reset (cycle slip flag),
+rather than reporting it as a regular epoch.")
+            )
+            .arg(
+                Arg::new("align-epochs")
+                    .long("align-epochs")
+                    .action(ArgAction::SetTrue)
+                    .help("Snap the observation epochs (receiver time) to the nominal sampling grid, removing the sub-sample receiver clock jitter.
+The offset of each receiver epoch to the grid is reported in the receiver clock offset field.")
+            )
+            .arg(
+                Arg::new("observables-summary")
+                    .long("observables-summary")
+                    .action(ArgAction::SetTrue)
+                    .help("Print a summary of the collected observations at the end of the run:
+satellites and observables per constellation, number of epochs, time span and data gaps.")
+            )
+            .arg(
+                Arg::new("observable-map")
+                    .long("observable-map")
+                    .required(false)
+                    .action(ArgAction::Append)
+                    .value_name("SIGNAL=OBS")
+                    .help("Overrides the observable code of a signal (RINEX3+), for example --observable-map \"BDS_B1I_D1=C1I\".
+The band and attribute apply to all observables of this signal (C1I, L1I, D1I and S1I here).
+Signals are named after the receiver signals: GPS_L1_CA, GAL_E1_C, BDS_B1I_D1, BDS_B1I_D2, BDS_B1C, GLO_L1_OF..
+Repeat this option for each signal."))
+            .arg(
+                Arg::new("scale")
+                    .long("scale")
+                    .value_name("OBSERVABLE:FACTOR")
+                    .value_delimiter(',')
+                    .action(ArgAction::Append)
+                    .help("Scale these observables on write, and declare it in the header (RINEX3+).
+For example --scale L1C:1000. Valid factors are 10, 100 and 1000.")
+            )
+            .arg(
+                Arg::new("timescale")
+                    .long("timescale")
+                    .required(false)
+                    .help("Express your observations in given Timescale.
+Default value is GPST."
+            ))
+            .arg(
+                Arg::new("rawx-timescale")
+                    .long("rawx-timescale")
+                    .value_name("gpst|gst|bdt")
+                    .value_parser(["gpst", "gst", "bdt"])
+                    .help("Timescale the receiver counts the RXM-RAWX week and time of week in, which it does not declare.
+Receivers that do not track GPS count in the system time of the only constellation they track (GST or BDT).
+In active mode, this is read from the receiver configuration (MON-GNSS). Otherwise, default value is GPST."),
+            )
+            .arg(
+                Arg::new("crx")
+                    .long("crx")
+                    .action(ArgAction::SetTrue)
+                    .help("Activate CRINEX compression, for optimized RINEX size. Disabled by default."),
+            )
+            .arg(
+                Arg::new("crinex-version")
+                    .long("crinex-version")
+                    .value_name("MAJOR")
+                    .value_parser(["1", "3"])
+                    .requires("crx")
+                    .help("Override the declared CRINEX revision (CRINEX VERS / TYPE).
+By default, CRINEX 1.0 is used for RINEX V2 and CRINEX 3.0 for RINEX V3 and V4."),
+            )
+            .next_help_heading("Navigation messages collection")
                     .arg(
-                        Arg::new("sampling")
-                            .short('s')
-                            .long("sampling")
+                        Arg::new("nav")
+                            .long("nav")
                             .required(false)
-                            .help("Define sampling interval. Default value is 30s (standard low-rate RINEX).")
-                    )
-                    .arg(
-                        Arg::new("no-phase")
-                            .long("no-phase")
                             .action(ArgAction::SetTrue)
-                            .help("Do not track signal phase")
+                            .help("Activate Navigation messages collection, which is not enabled by default.")
                     )
                     .arg(
-                        Arg::new("no-pr")
-                            .long("no-pr")
+                        Arg::new("poll-eph")
+                            .long("poll-eph")
+                            .required(false)
                             .action(ArgAction::SetTrue)
-                            .help("Do not decode pseudo range")
+                            .requires("nav")
+                            .help("Periodically request ephemeris from the receiver (MGA-GPS-EPH polling).
+Glonass ephemeris are not polled: they are only collected from the broadcast.
+This allows a cold receiver to produce a complete Navigation file without waiting for the complete broadcast cycle (about 12.5 minutes).
+MGA messages are assistance (input) messages: most firmwares do not answer this poll.
+Only applies to active (serial) mode.")
                     )
                     .arg(
-                        Arg::new("no-dop")
-                            .long("no-dop")
-                            .action(ArgAction::SetTrue)
-                            .help("Do not track doppler shifts")
+                        Arg::new("nav-constellations")
+                            .long("nav-constellations")
+                            .value_name("CONSTELLATION")
+                            .value_delimiter(',')
+                            .action(ArgAction::Append)
+                            .requires("nav")
+                            .help("Limit the Navigation RINEX to these constellations, for example --nav-constellations gps,gal.
+Each constellation must be activated. By default, all activated constellations are collected.")
                     )
                     .arg(
-                        Arg::new("no-ssi")
-                            .long("no-ssi")
-                            .action(ArgAction::SetTrue)
-                            .help("Do not save SSI (received power) estimates")
+                        Arg::new("sbas-log")
+                            .long("sbas-log")
+                            .value_name("FILEPATH")
+                            .required(false)
+                            .action(ArgAction::Set)
+                            .help("Decode SBAS L1 messages (RXM-SFRBX) and log them to this CSV file,
+with their reception time, message type (MT) and complete payload. This is independent of the Navigation RINEX.
+SBAS needs to be selected (--sbas) in active mode.")
                     )
                     .arg(
-                        Arg::new("timescale")
-                            .long("timescale")
+                        Arg::new("nav-period")
+                            .long("nav-period")
                             .required(false)
-                            .help("Express your observations in given Timescale.
-Default value is GPST."
-                    ))
-                    .arg(
-                        Arg::new("crx")
-                            .long("crx")
-                            .action(ArgAction::SetTrue)
-                            .help("Activate CRINEX compression, for optimized RINEX size. Disabled by default."),
-                    )
-                    .next_help_heading("Navigation messages collection")
-                            .arg(
-                                Arg::new("nav")
-                                    .long("nav")
-                                    .required(false)
-                                    .action(ArgAction::SetTrue)
-                                    .help("Activate Navigation messages collection, which is not enabled by default.")
-                            )
-                            .arg(
-                                Arg::new("nav-period")
-                                    .long("nav-period")
-                                    .required(false)
-                                    .action(ArgAction::Set)
-                                    .help("Define how often Navigation messages (ephemeris, etc..) are dumped into resulting RINEX.
+                            .action(ArgAction::Set)
+                            .help("Define how often Navigation messages (ephemeris, etc..) are dumped into resulting RINEX.
 Dumping period is always aligned to midnight. When time is reached, we dump the first message received of each kind (no fancy logic).
 This value needs to be under the message validity for correct post processed navigation.
 By default, we use a 2h message rate, which is more than enough considering all navigation messages.
 But you can customize that, either to reduce the output file size, or increase the message rate.
 Example --nav-period \"1 hour\" to reduce to 1hr message period.
 Example --nav-period \"30 mins\" to reduce to 30min message period."))
-                            .arg(
-                                Arg::new("healthy-only")
-                                    .long("healthy")
-                                    .required(false)
-                                    .action(ArgAction::SetTrue)
-                                    .help("Dump messages for healthy satellites only.
+                    .arg(
+                        Arg::new("decimate-nav")
+                            .long("decimate-nav")
+                            .required(false)
+                            .action(ArgAction::SetTrue)
+                            .help("Do not repeat an ephemeris that was already written for this satellite (same IODE/IODC),
+even when the --nav-period is reached. The Navigation RINEX then contains one record per actual ephemeris set."))
+                    .arg(
+                        Arg::new("healthy-only")
+                            .long("healthy")
+                            .required(false)
+                            .action(ArgAction::SetTrue)
+                            .help("Dump messages for healthy satellites only.
 This is currently limited to the Navigation message collection and does not impact signal collection."))
-                            .arg(
-                                Arg::new("unhealthy-only")
-                                    .long("unhealthy")
-                                    .required(false)
-                                    .action(ArgAction::SetTrue)
-                                    .help("Dump messages for unhealthy or beta-tested satellites only.
+                    .arg(
+                        Arg::new("unhealthy-only")
+                            .long("unhealthy")
+                            .required(false)
+                            .action(ArgAction::SetTrue)
+                            .help("Dump messages for unhealthy or beta-tested satellites only.
 This is currently limited to the Navigation message collection and does not impact signal collection."))
-                    .get_matches()
-            },
+;
+
+        // options of the configuration file are inserted ahead of the command line,
+        // which remains the reference for every option it defines.
+        let args = config_args(&command, std::env::args().collect())?;
+
+        let mut cli = Self {
+            matches: command.get_matches_from(args),
+            station_names: Vec::new(),
+            baud_rate: None,
+            data_timeout: DATA_TIMEOUT,
+            ack_timeout: ACK_TIMEOUT,
+            ublox_settings: Default::default(),
+            rinex_settings: Default::default(),
+        };
+
+        cli.station_names = cli.parse_station_names()?;
+        cli.baud_rate = cli.parse_baud_rate()?;
+
+        if cli.serial_ports().len() > 1 && cli.raw_output().is_some() {
+            return Err(CliError::Usage(
+                "--raw-out is limited to a single --port".to_string(),
+            ));
+        }
+
+        if let Some(dt) = cli.duration("data-timeout")? {
+            cli.data_timeout = std::time::Duration::from_nanos(dt.total_nanoseconds() as u64);
         }
+
+        if let Some(dt) = cli.duration("ack-timeout")? {
+            cli.ack_timeout = std::time::Duration::from_nanos(dt.total_nanoseconds() as u64);
+        }
+
+        if let Some(rate) = cli.replay_rate()
+            && (rate <= 0.0 || !rate.is_finite())
+        {
+            return Err(CliError::InvalidValue {
+                option: "replay-rate",
+                value: rate.to_string(),
+                reason: "must be positive".to_string(),
+            });
+        }
+
+        cli.ublox_settings = cli.build_ublox_settings()?;
+        cli.rinex_settings = cli.build_rinex_settings()?;
+
+        Ok(cli)
+    }
+
+    /// Parses the [Duration] of this option, if defined
+    fn duration(&self, option: &'static str) -> Result<Option<Duration>, CliError> {
+        let Some(value) = self.matches.get_one::<String>(option) else {
+            return Ok(None);
+        };
+
+        value
+            .trim()
+            .parse::<Duration>()
+            .map(Some)
+            .map_err(|e| CliError::InvalidValue {
+                option,
+                value: value.to_string(),
+                reason: e.to_string(),
+            })
     }
 
     /// Returns User serial port specification
@@ -357,6 +928,96 @@ This is currently limited to the Navigation message collection and does not impa
         self.matches.get_one::<String>("port")
     }
 
+    /// Returns all serial ports, one per station
+    pub fn serial_ports(&self) -> Vec<&String> {
+        self.matches
+            .get_many::<String>("port")
+            .map(|ports| ports.collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the name of each station: one per serial port,
+    /// or a single one in passive mode
+    pub fn station_names(&self) -> Vec<String> {
+        self.station_names.clone()
+    }
+
+    /// Several stations require distinct names, so they produce distinct RINEX sets.
+    fn parse_station_names(&self) -> Result<Vec<String>, CliError> {
+        let stations = self.serial_ports().len().max(1);
+
+        let names = self
+            .matches
+            .get_many::<String>("name")
+            .map(|names| names.cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        if stations == 1 {
+            return Ok(vec![
+                names.first().cloned().unwrap_or_else(|| "UBXR".to_string()),
+            ]);
+        }
+
+        if names.len() != stations {
+            return Err(CliError::Usage(format!(
+                "{} serial ports require {} distinct --name (one per --port)",
+                stations, stations
+            )));
+        }
+
+        for (i, name) in names.iter().enumerate() {
+            if names[..i].contains(name) {
+                return Err(CliError::Usage(format!(
+                    "--name \"{}\" is used by several serial ports",
+                    name
+                )));
+            }
+        }
+
+        Ok(names)
+    }
+
+    /// Returns true if logs should be formatted as newline delimited JSON
+    pub fn json_logs(&self) -> bool {
+        self.matches
+            .get_one::<String>("log-format")
+            .is_some_and(|format| format == "json")
+    }
+
+    /// Returns the log level selected with -q / -v, if any
+    pub fn log_level(&self) -> Option<LevelFilter> {
+        if self.matches.get_flag("quiet") {
+            return Some(LevelFilter::Off);
+        }
+
+        match self.matches.get_count("verbose") {
+            0 => None,
+            1 => Some(LevelFilter::Info),
+            2 => Some(LevelFilter::Debug),
+            _ => Some(LevelFilter::Trace),
+        }
+    }
+
+    /// Returns the internal channels capacity
+    pub fn channel_capacity(&self) -> usize {
+        *self.matches.get_one::<usize>("channel-capacity").unwrap()
+    }
+
+    /// Returns raw UBX output file path, if any
+    pub fn raw_output(&self) -> Option<&String> {
+        self.matches.get_one::<String>("raw-out")
+    }
+
+    /// Returns the data silence threshold, in active mode
+    pub fn data_timeout(&self) -> std::time::Duration {
+        self.data_timeout
+    }
+
+    /// True if we should exit when the data feed is silent
+    pub fn exit_on_timeout(&self) -> bool {
+        self.matches.get_flag("exit-on-timeout")
+    }
+
     /// Input file paths
     pub fn filepaths(&self) -> Vec<&String> {
         if let Some(fp) = self.matches.get_many::<String>("file") {
@@ -366,13 +1027,115 @@ This is currently limited to the Navigation message collection and does not impa
         }
     }
 
+    /// True if overlapping input files should be a fatal error
+    pub fn strict(&self) -> bool {
+        self.matches.get_flag("strict")
+    }
+
+    /// True if unreadable input files should be skipped rather than fatal
+    pub fn keep_going(&self) -> bool {
+        self.matches.get_flag("keep-going")
+    }
+
+    /// Returns the replay rate (multiple of real time), if any
+    pub fn replay_rate(&self) -> Option<f64> {
+        self.matches.get_one::<f64>("replay-rate").copied()
+    }
+
+    /// True if input files should be decoded concurrently
+    pub fn parallel(&self) -> bool {
+        self.matches.get_flag("parallel")
+    }
+
+    /// Returns RF telemetry log file path, if any
+    pub fn spectrum(&self) -> Option<&String> {
+        self.matches.get_one::<String>("spectrum")
+    }
+
+    /// Returns velocity log file path, if any
+    pub fn velocity_csv(&self) -> Option<&String> {
+        self.matches.get_one::<String>("velocity-csv")
+    }
+
+    /// Returns time pulse log file path, if any
+    pub fn time_pulse_csv(&self) -> Option<&String> {
+        self.matches.get_one::<String>("time-pulse-csv")
+    }
+
+    /// Returns QC report file path, if any
+    pub fn qc(&self) -> Option<&String> {
+        self.matches.get_one::<String>("qc")
+    }
+
+    /// Returns SBAS message log file path, if any
+    pub fn sbas_log(&self) -> Option<&String> {
+        self.matches.get_one::<String>("sbas-log")
+    }
+
     /// Returns User baud rate specification
     pub fn baud_rate(&self) -> Option<u32> {
-        let baud = self.matches.get_one::<String>("baudrate")?;
-        let baud = baud
-            .parse::<u32>()
-            .unwrap_or_else(|e| panic!("Invalid baud rate value: {}", e));
-        Some(baud)
+        self.baud_rate
+    }
+
+    fn parse_baud_rate(&self) -> Result<Option<u32>, CliError> {
+        let Some(baud) = self.matches.get_one::<String>("baudrate") else {
+            return Ok(None);
+        };
+
+        baud.parse::<u32>()
+            .map(Some)
+            .map_err(|e| CliError::InvalidValue {
+                option: "baudrate",
+                value: baud.to_string(),
+                reason: e.to_string(),
+            })
+    }
+
+    /// Returns the serial port [SerialSettings]
+    pub fn serial_settings(&self) -> SerialSettings {
+        let mut settings = SerialSettings::default();
+
+        if let Some(bits) = self.matches.get_one::<String>("data-bits") {
+            settings.data_bits = match bits.as_str() {
+                "7" => serialport::DataBits::Seven,
+                _ => serialport::DataBits::Eight,
+            };
+        }
+
+        if let Some(parity) = self.matches.get_one::<String>("parity") {
+            settings.parity = match parity.as_str() {
+                "odd" => serialport::Parity::Odd,
+                "even" => serialport::Parity::Even,
+                _ => serialport::Parity::None,
+            };
+        }
+
+        if let Some(bits) = self.matches.get_one::<String>("stop-bits") {
+            settings.stop_bits = match bits.as_str() {
+                "2" => serialport::StopBits::Two,
+                _ => serialport::StopBits::One,
+            };
+        }
+
+        if let Some(flow) = self.matches.get_one::<String>("flow-control") {
+            settings.flow_control = match flow.as_str() {
+                "software" => serialport::FlowControl::Software,
+                "hardware" => serialport::FlowControl::Hardware,
+                _ => serialport::FlowControl::None,
+            };
+        }
+
+        settings
+    }
+
+    /// Returns the number of retries of the configuration requests
+    pub fn ack_retries(&self) -> u8 {
+        *self.matches.get_one::<u8>("ack-retries").unwrap()
+    }
+
+    /// Returns acknowledgment timeout, for the configuration requests
+    pub fn ack_timeout(&self) -> std::time::Duration {
+        self.ack_timeout
     }
 
     fn gps(&self) -> bool {
@@ -403,7 +1166,8 @@ This is currently limited to the Navigation message collection and does not impa
         self.matches.get_flag("irnss")
     }
 
-    fn constellations(&self) -> Vec<Constellation> {
+    /// [Constellation]s explicitly selected by user
+    fn selected_constellations(&self) -> Vec<Constellation> {
         let mut constellations = Vec::<Constellation>::with_capacity(4);
 
         if self.gps() {
@@ -428,6 +1192,12 @@ This is currently limited to the Navigation message collection and does not impa
             constellations.push(Constellation::IRNSS);
         }
 
+        constellations
+    }
+
+    fn constellations(&self) -> Vec<Constellation> {
+        let mut constellations = self.selected_constellations();
+
         if self.serial_port().is_none() {
             // we're in passive mode
             if constellations.is_empty() {
@@ -449,6 +1219,56 @@ This is currently limited to the Navigation message collection and does not impa
         constellations
     }
 
+    /// Observation RINEX collection, which is the default mode. When deserializing
+    /// files, --nav alone only produces Navigation RINEX (unless --obs).
+    fn rawxm(&self) -> bool {
+        if self.matches.get_flag("no-obs") {
+            return false;
+        }
+
+        let passive = self.serial_port().is_none();
+
+        !(passive && self.matches.get_flag("nav") && !self.matches.get_flag("obs"))
+    }
+
+    /// UBX-INF severities captured in the header comments
+    fn capture_inf(&self) -> Result<Vec<InfLevel>, CliError> {
+        match self.matches.get_many::<String>("capture-inf") {
+            Some(levels) => levels
+                .map(|level| InfLevel::from_str(level).map_err(CliError::Usage))
+                .collect(),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// [Constellation]s collected in the Navigation RINEX
+    fn nav_constellations(&self) -> Result<Vec<Constellation>, CliError> {
+        let constellations = self.constellations();
+
+        match self.matches.get_many::<String>("nav-constellations") {
+            Some(selection) => selection
+                .map(|c| {
+                    let constellation =
+                        Constellation::from_str(c.trim()).map_err(|e| CliError::InvalidValue {
+                            option: "nav-constellations",
+                            value: c.to_string(),
+                            reason: e.to_string(),
+                        })?;
+
+                    if !constellations.contains(&constellation) {
+                        return Err(CliError::Usage(format!(
+                            "--nav-constellations: {} is not activated",
+                            constellation
+                        )));
+                    }
+
+                    Ok(constellation)
+                })
+                .collect(),
+            None => Ok(constellations),
+        }
+    }
+
     fn l1(&self) -> bool {
         if self.serial_port().is_none() {
             !self.matches.get_flag("l2") && !self.matches.get_flag("l5")
@@ -489,9 +1309,21 @@ This is currently limited to the Navigation message collection and does not impa
         self.matches.get_flag("no-phase")
     }
 
-    fn observables(&self) -> HashMap<Constellation, Vec<Observable>> {
-        let v2 = self.matches.get_flag("v2");
+    /// Returns the [Observable]s to collect, in RINEX revision `major`.
+    /// Signals sharing an observable (V2) only declare it once.
+    fn observables(&self, major: u8) -> HashMap<Constellation, Vec<Observable>> {
+        let mut ret = self.signal_observables(major);
+
+        for observables in ret.values_mut() {
+            dedup_observables(observables);
+        }
+
+        ret
+    }
 
+    /// Returns the [Observable] of each selected signal, in RINEX revision `major`.
+    /// Several signals may share the same observable in V2.
+    fn signal_observables(&self, major: u8) -> HashMap<Constellation, Vec<Observable>> {
         let mut gps_observables = vec![];
         let mut gal_observables = vec![];
         let mut glo_observables = vec![];
@@ -507,35 +1339,29 @@ This is currently limited to the Navigation message collection and does not impa
         if self.l1() {
             if constellations.contains(&Constellation::GPS) {
                 if !self.no_phase() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::GPS_L1_CA.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid GPS-L1 observable");
+                    let observable =
+                        SignalCarrier::GPS_L1_CA.to_observable(ObservableKind::PhaseRange, major);
 
                     gps_observables.push(observable);
                 }
 
                 if !self.no_pr() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::GPS_L1_CA.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid GPS-C1 observable");
+                    let observable =
+                        SignalCarrier::GPS_L1_CA.to_observable(ObservableKind::PseudoRange, major);
 
                     gps_observables.push(observable);
                 }
 
                 if !self.no_dop() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::GPS_L1_CA.to_doppler_observable(v2))
-                            .expect("internal error: invalid GPS-D1 observable");
+                        SignalCarrier::GPS_L1_CA.to_observable(ObservableKind::Doppler, major);
 
                     gps_observables.push(observable);
                 }
 
                 if !self.no_ssi() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::GPS_L1_CA.to_ssi_observable(v2))
-                            .expect("internal error: invalid GPS-S1 observable");
+                        SignalCarrier::GPS_L1_CA.to_observable(ObservableKind::SSI, major);
 
                     gps_observables.push(observable);
                 }
@@ -543,61 +1369,49 @@ This is currently limited to the Navigation message collection and does not impa
 
             if constellations.contains(&Constellation::Galileo) {
                 if !self.no_phase() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::GAL_E1_C.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid GAL-L1 observable");
+                    let observable =
+                        SignalCarrier::GAL_E1_C.to_observable(ObservableKind::PhaseRange, major);
 
                     gal_observables.push(observable);
 
-                    let observable = Observable::from_str(
-                        &SignalCarrier::GAL_E1_B.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid GAL-L1 observable");
+                    let observable =
+                        SignalCarrier::GAL_E1_B.to_observable(ObservableKind::PhaseRange, major);
 
                     gal_observables.push(observable);
                 }
 
                 if !self.no_pr() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::GAL_E1_C.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid GAL-C1 observable");
+                    let observable =
+                        SignalCarrier::GAL_E1_C.to_observable(ObservableKind::PseudoRange, major);
 
                     gal_observables.push(observable);
 
-                    let observable = Observable::from_str(
-                        &SignalCarrier::GAL_E1_B.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid GAL-C1 observable");
+                    let observable =
+                        SignalCarrier::GAL_E1_B.to_observable(ObservableKind::PseudoRange, major);
 
                     gal_observables.push(observable);
                 }
 
                 if !self.no_dop() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::GAL_E1_C.to_doppler_observable(v2))
-                            .expect("internal error: invalid GAL-D1 observable");
+                        SignalCarrier::GAL_E1_C.to_observable(ObservableKind::Doppler, major);
 
                     gal_observables.push(observable);
 
                     let observable =
-                        Observable::from_str(&SignalCarrier::GAL_E1_B.to_doppler_observable(v2))
-                            .expect("internal error: invalid GAL-D1 observable");
+                        SignalCarrier::GAL_E1_B.to_observable(ObservableKind::Doppler, major);
 
                     gal_observables.push(observable);
                 }
 
                 if !self.no_ssi() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::GAL_E1_C.to_ssi_observable(v2))
-                            .expect("internal error: invalid GAL-S1 observable");
+                        SignalCarrier::GAL_E1_C.to_observable(ObservableKind::SSI, major);
 
                     gal_observables.push(observable);
 
                     let observable =
-                        Observable::from_str(&SignalCarrier::GAL_E1_B.to_ssi_observable(v2))
-                            .expect("internal error: invalid GAL-S1 observable");
+                        SignalCarrier::GAL_E1_B.to_observable(ObservableKind::SSI, major);
 
                     gal_observables.push(observable);
                 }
@@ -605,58 +1419,46 @@ This is currently limited to the Navigation message collection and does not impa
 
             if constellations.contains(&Constellation::BeiDou) {
                 if !self.no_phase() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::BDS_B1I_D1.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid BDS-B1 observable");
+                    let observable =
+                        SignalCarrier::BDS_B1I_D1.to_observable(ObservableKind::PhaseRange, major);
 
                     bds_observables.push(observable);
 
-                    let observable = Observable::from_str(
-                        &SignalCarrier::BDS_B1I_D2.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid BDS-B1 observable");
+                    let observable =
+                        SignalCarrier::BDS_B1I_D2.to_observable(ObservableKind::PhaseRange, major);
 
                     bds_observables.push(observable);
                 }
                 if !self.no_pr() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::BDS_B1I_D1.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid BDS-B1 observable");
+                    let observable =
+                        SignalCarrier::BDS_B1I_D1.to_observable(ObservableKind::PseudoRange, major);
 
                     bds_observables.push(observable);
 
-                    let observable = Observable::from_str(
-                        &SignalCarrier::BDS_B1I_D2.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid BDS-B1 observable");
+                    let observable =
+                        SignalCarrier::BDS_B1I_D2.to_observable(ObservableKind::PseudoRange, major);
 
                     bds_observables.push(observable);
                 }
                 if !self.no_dop() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::BDS_B1I_D1.to_doppler_observable(v2))
-                            .expect("internal error: invalid BDS-B1 observable");
+                        SignalCarrier::BDS_B1I_D1.to_observable(ObservableKind::Doppler, major);
 
                     bds_observables.push(observable);
 
                     let observable =
-                        Observable::from_str(&SignalCarrier::BDS_B1I_D2.to_doppler_observable(v2))
-                            .expect("internal error: invalid BDS-B1 observable");
+                        SignalCarrier::BDS_B1I_D2.to_observable(ObservableKind::Doppler, major);
 
                     bds_observables.push(observable);
                 }
                 if !self.no_ssi() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::BDS_B1I_D1.to_ssi_observable(v2))
-                            .expect("internal error: invalid BDS-S1 observable");
+                        SignalCarrier::BDS_B1I_D1.to_observable(ObservableKind::SSI, major);
 
                     bds_observables.push(observable);
 
                     let observable =
-                        Observable::from_str(&SignalCarrier::BDS_B1I_D2.to_ssi_observable(v2))
-                            .expect("internal error: invalid BDS-S1 observable");
+                        SignalCarrier::BDS_B1I_D2.to_observable(ObservableKind::SSI, major);
 
                     bds_observables.push(observable);
                 }
@@ -664,32 +1466,26 @@ This is currently limited to the Navigation message collection and does not impa
 
             if constellations.contains(&Constellation::SBAS) {
                 if !self.no_phase() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::SBAS_L1_CA.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid SBAS-L1 observable");
+                    let observable =
+                        SignalCarrier::SBAS_L1_CA.to_observable(ObservableKind::PhaseRange, major);
 
                     sbas_observables.push(observable);
                 }
                 if !self.no_pr() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::SBAS_L1_CA.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid SBAS-C1 observable");
+                    let observable =
+                        SignalCarrier::SBAS_L1_CA.to_observable(ObservableKind::PseudoRange, major);
 
                     sbas_observables.push(observable);
                 }
                 if !self.no_dop() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::SBAS_L1_CA.to_doppler_observable(v2))
-                            .expect("internal error: invalid SBAS-C1 observable");
+                        SignalCarrier::SBAS_L1_CA.to_observable(ObservableKind::Doppler, major);
 
                     sbas_observables.push(observable);
                 }
                 if !self.no_ssi() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::SBAS_L1_CA.to_ssi_observable(v2))
-                            .expect("internal error: invalid SBAS-S1 observable");
+                        SignalCarrier::SBAS_L1_CA.to_observable(ObservableKind::SSI, major);
 
                     sbas_observables.push(observable);
                 }
@@ -697,58 +1493,46 @@ This is currently limited to the Navigation message collection and does not impa
 
             if constellations.contains(&Constellation::QZSS) {
                 if !self.no_phase() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::QZSS_L1_CA.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid QZSS-L1 observable");
+                    let observable =
+                        SignalCarrier::QZSS_L1_CA.to_observable(ObservableKind::PhaseRange, major);
 
                     qzss_observables.push(observable);
 
-                    let observable = Observable::from_str(
-                        &SignalCarrier::QZSS_L1_S.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid QZSS-L1 observable");
+                    let observable =
+                        SignalCarrier::QZSS_L1_S.to_observable(ObservableKind::PhaseRange, major);
 
                     qzss_observables.push(observable);
                 }
                 if !self.no_pr() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::QZSS_L1_CA.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid QZSS-C1 observable");
+                    let observable =
+                        SignalCarrier::QZSS_L1_CA.to_observable(ObservableKind::PseudoRange, major);
 
                     qzss_observables.push(observable);
 
-                    let observable = Observable::from_str(
-                        &SignalCarrier::QZSS_L1_S.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid QZSS-C1 observable");
+                    let observable =
+                        SignalCarrier::QZSS_L1_S.to_observable(ObservableKind::PseudoRange, major);
 
                     qzss_observables.push(observable);
                 }
                 if !self.no_dop() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::QZSS_L1_CA.to_doppler_observable(v2))
-                            .expect("internal error: invalid QZSS-D1 observable");
+                        SignalCarrier::QZSS_L1_CA.to_observable(ObservableKind::Doppler, major);
 
                     qzss_observables.push(observable);
 
                     let observable =
-                        Observable::from_str(&SignalCarrier::QZSS_L1_S.to_doppler_observable(v2))
-                            .expect("internal error: invalid QZSS-D1 observable");
+                        SignalCarrier::QZSS_L1_S.to_observable(ObservableKind::Doppler, major);
 
                     qzss_observables.push(observable);
                 }
                 if !self.no_ssi() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::QZSS_L1_CA.to_ssi_observable(v2))
-                            .expect("internal error: invalid QZSS-S1 observable");
+                        SignalCarrier::QZSS_L1_CA.to_observable(ObservableKind::SSI, major);
 
                     qzss_observables.push(observable);
 
                     let observable =
-                        Observable::from_str(&SignalCarrier::QZSS_L1_S.to_ssi_observable(v2))
-                            .expect("internal error: invalid QZSS-S1 observable");
+                        SignalCarrier::QZSS_L1_S.to_observable(ObservableKind::SSI, major);
 
                     qzss_observables.push(observable);
                 }
@@ -756,32 +1540,26 @@ This is currently limited to the Navigation message collection and does not impa
 
             if constellations.contains(&Constellation::Glonass) {
                 if !self.no_phase() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::GLO_L1_OF.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid GLO-L1 observable");
+                    let observable =
+                        SignalCarrier::GLO_L1_OF.to_observable(ObservableKind::PhaseRange, major);
 
                     glo_observables.push(observable);
                 }
                 if !self.no_pr() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::GLO_L1_OF.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid GLO-C1 observable");
+                    let observable =
+                        SignalCarrier::GLO_L1_OF.to_observable(ObservableKind::PseudoRange, major);
 
                     glo_observables.push(observable);
                 }
                 if !self.no_dop() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::GLO_L1_OF.to_doppler_observable(v2))
-                            .expect("internal error: invalid GLO-D1 observable");
+                        SignalCarrier::GLO_L1_OF.to_observable(ObservableKind::Doppler, major);
 
                     glo_observables.push(observable);
                 }
                 if !self.no_ssi() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::GLO_L1_OF.to_ssi_observable(v2))
-                            .expect("internal error: invalid GLO-S1 observable");
+                        SignalCarrier::GLO_L1_OF.to_observable(ObservableKind::SSI, major);
 
                     glo_observables.push(observable);
                 }
@@ -791,61 +1569,49 @@ This is currently limited to the Navigation message collection and does not impa
         if self.l2() {
             if constellations.contains(&Constellation::GPS) {
                 if !self.no_phase() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::GPS_L2_CL.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid GPS-L2 observable");
+                    let observable =
+                        SignalCarrier::GPS_L2_CL.to_observable(ObservableKind::PhaseRange, major);
 
                     gps_observables.push(observable);
 
-                    let observable = Observable::from_str(
-                        &SignalCarrier::GPS_L2_CM.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid GPS-L2 observable");
+                    let observable =
+                        SignalCarrier::GPS_L2_CM.to_observable(ObservableKind::PhaseRange, major);
 
                     gps_observables.push(observable);
                 }
 
                 if !self.no_pr() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::GPS_L2_CL.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid GPS-L2 observable");
+                    let observable =
+                        SignalCarrier::GPS_L2_CL.to_observable(ObservableKind::PseudoRange, major);
 
                     gps_observables.push(observable);
 
-                    let observable = Observable::from_str(
-                        &SignalCarrier::GPS_L2_CM.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid GPS-L2 observable");
+                    let observable =
+                        SignalCarrier::GPS_L2_CM.to_observable(ObservableKind::PseudoRange, major);
 
                     gps_observables.push(observable);
                 }
 
                 if !self.no_dop() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::GPS_L2_CL.to_doppler_observable(v2))
-                            .expect("internal error: invalid GPS-D2 observable");
+                        SignalCarrier::GPS_L2_CL.to_observable(ObservableKind::Doppler, major);
 
                     gps_observables.push(observable);
 
                     let observable =
-                        Observable::from_str(&SignalCarrier::GPS_L2_CM.to_doppler_observable(v2))
-                            .expect("internal error: invalid GPS-D2 observable");
+                        SignalCarrier::GPS_L2_CM.to_observable(ObservableKind::Doppler, major);
 
                     gps_observables.push(observable);
                 }
 
                 if !self.no_ssi() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::GPS_L2_CL.to_ssi_observable(v2))
-                            .expect("internal error: invalid GPS-S2 observable");
+                        SignalCarrier::GPS_L2_CL.to_observable(ObservableKind::SSI, major);
 
                     gps_observables.push(observable);
 
                     let observable =
-                        Observable::from_str(&SignalCarrier::GPS_L2_CM.to_ssi_observable(v2))
-                            .expect("internal error: invalid GPS-S2 observable");
+                        SignalCarrier::GPS_L2_CM.to_observable(ObservableKind::SSI, major);
 
                     gps_observables.push(observable);
                 }
@@ -853,58 +1619,46 @@ This is currently limited to the Navigation message collection and does not impa
 
             if constellations.contains(&Constellation::BeiDou) {
                 if !self.no_phase() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::BDS_B2I_D1.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid BDS-B2 observable");
+                    let observable =
+                        SignalCarrier::BDS_B2I_D1.to_observable(ObservableKind::PhaseRange, major);
 
                     bds_observables.push(observable);
 
-                    let observable = Observable::from_str(
-                        &SignalCarrier::BDS_B2I_D2.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid BDS-B2 observable");
+                    let observable =
+                        SignalCarrier::BDS_B2I_D2.to_observable(ObservableKind::PhaseRange, major);
 
                     bds_observables.push(observable);
                 }
                 if !self.no_pr() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::BDS_B2I_D1.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid BDS-B1 observable");
+                    let observable =
+                        SignalCarrier::BDS_B2I_D1.to_observable(ObservableKind::PseudoRange, major);
 
                     bds_observables.push(observable);
 
-                    let observable = Observable::from_str(
-                        &SignalCarrier::BDS_B2I_D2.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid BDS-B1 observable");
+                    let observable =
+                        SignalCarrier::BDS_B2I_D2.to_observable(ObservableKind::PseudoRange, major);
 
                     bds_observables.push(observable);
                 }
                 if !self.no_dop() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::BDS_B2I_D1.to_doppler_observable(v2))
-                            .expect("internal error: invalid BDS-B2 observable");
+                        SignalCarrier::BDS_B2I_D1.to_observable(ObservableKind::Doppler, major);
 
                     bds_observables.push(observable);
 
                     let observable =
-                        Observable::from_str(&SignalCarrier::BDS_B2I_D2.to_doppler_observable(v2))
-                            .expect("internal error: invalid BDS-B2 observable");
+                        SignalCarrier::BDS_B2I_D2.to_observable(ObservableKind::Doppler, major);
 
                     bds_observables.push(observable);
                 }
                 if !self.no_ssi() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::BDS_B2I_D1.to_ssi_observable(v2))
-                            .expect("internal error: invalid BDS-S2 observable");
+                        SignalCarrier::BDS_B2I_D1.to_observable(ObservableKind::SSI, major);
 
                     bds_observables.push(observable);
 
                     let observable =
-                        Observable::from_str(&SignalCarrier::BDS_B2I_D2.to_ssi_observable(v2))
-                            .expect("internal error: invalid BDS-S2 observable");
+                        SignalCarrier::BDS_B2I_D2.to_observable(ObservableKind::SSI, major);
 
                     bds_observables.push(observable);
                 }
@@ -912,58 +1666,46 @@ This is currently limited to the Navigation message collection and does not impa
 
             if constellations.contains(&Constellation::QZSS) {
                 if !self.no_phase() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::QZSS_L2_CL.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid QZSS-L2 observable");
+                    let observable =
+                        SignalCarrier::QZSS_L2_CL.to_observable(ObservableKind::PhaseRange, major);
 
                     qzss_observables.push(observable);
 
-                    let observable = Observable::from_str(
-                        &SignalCarrier::QZSS_L2_CM.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid QZSS-L2 observable");
+                    let observable =
+                        SignalCarrier::QZSS_L2_CM.to_observable(ObservableKind::PhaseRange, major);
 
                     qzss_observables.push(observable);
                 }
                 if !self.no_pr() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::QZSS_L2_CL.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid QZSS-C2 observable");
+                    let observable =
+                        SignalCarrier::QZSS_L2_CL.to_observable(ObservableKind::PseudoRange, major);
 
                     qzss_observables.push(observable);
 
-                    let observable = Observable::from_str(
-                        &SignalCarrier::QZSS_L2_CM.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid QZSS-C2 observable");
+                    let observable =
+                        SignalCarrier::QZSS_L2_CM.to_observable(ObservableKind::PseudoRange, major);
 
                     qzss_observables.push(observable);
                 }
                 if !self.no_dop() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::QZSS_L2_CL.to_doppler_observable(v2))
-                            .expect("internal error: invalid QZSS-D2 observable");
+                        SignalCarrier::QZSS_L2_CL.to_observable(ObservableKind::Doppler, major);
 
                     qzss_observables.push(observable);
 
                     let observable =
-                        Observable::from_str(&SignalCarrier::QZSS_L2_CM.to_doppler_observable(v2))
-                            .expect("internal error: invalid QZSS-D2 observable");
+                        SignalCarrier::QZSS_L2_CM.to_observable(ObservableKind::Doppler, major);
 
                     qzss_observables.push(observable);
                 }
                 if !self.no_ssi() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::QZSS_L2_CL.to_ssi_observable(v2))
-                            .expect("internal error: invalid QZSS-S2 observable");
+                        SignalCarrier::QZSS_L2_CL.to_observable(ObservableKind::SSI, major);
 
                     qzss_observables.push(observable);
 
                     let observable =
-                        Observable::from_str(&SignalCarrier::QZSS_L2_CM.to_ssi_observable(v2))
-                            .expect("internal error: invalid QZSS-S2 observable");
+                        SignalCarrier::QZSS_L2_CM.to_observable(ObservableKind::SSI, major);
 
                     qzss_observables.push(observable);
                 }
@@ -971,32 +1713,26 @@ This is currently limited to the Navigation message collection and does not impa
 
             if constellations.contains(&Constellation::Glonass) {
                 if !self.no_phase() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::GLO_L2_OF.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid GLO-L2 observable");
+                    let observable =
+                        SignalCarrier::GLO_L2_OF.to_observable(ObservableKind::PhaseRange, major);
 
                     glo_observables.push(observable);
                 }
                 if !self.no_pr() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::GLO_L2_OF.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid GLO-C2 observable");
+                    let observable =
+                        SignalCarrier::GLO_L2_OF.to_observable(ObservableKind::PseudoRange, major);
 
                     glo_observables.push(observable);
                 }
                 if !self.no_dop() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::GLO_L2_OF.to_doppler_observable(v2))
-                            .expect("internal error: invalid GLO-D2 observable");
+                        SignalCarrier::GLO_L2_OF.to_observable(ObservableKind::Doppler, major);
 
                     glo_observables.push(observable);
                 }
                 if !self.no_ssi() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::GLO_L2_OF.to_ssi_observable(v2))
-                            .expect("internal error: invalid GLO-S2 observable");
+                        SignalCarrier::GLO_L2_OF.to_observable(ObservableKind::SSI, major);
 
                     glo_observables.push(observable);
                 }
@@ -1006,119 +1742,144 @@ This is currently limited to the Navigation message collection and does not impa
         if self.l5() {
             if constellations.contains(&Constellation::GPS) {
                 if !self.no_phase() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::GPS_L5_I.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid GPS-L5 observable");
+                    let observable =
+                        SignalCarrier::GPS_L5_I.to_observable(ObservableKind::PhaseRange, major);
 
                     gps_observables.push(observable);
 
-                    let observable = Observable::from_str(
-                        &SignalCarrier::GPS_L5_Q.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid GPS-L5 observable");
+                    let observable =
+                        SignalCarrier::GPS_L5_Q.to_observable(ObservableKind::PhaseRange, major);
 
                     gps_observables.push(observable);
                 }
 
                 if !self.no_pr() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::GPS_L5_I.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid GPS-C5 observable");
+                    let observable =
+                        SignalCarrier::GPS_L5_I.to_observable(ObservableKind::PseudoRange, major);
 
                     gps_observables.push(observable);
 
-                    let observable = Observable::from_str(
-                        &SignalCarrier::GPS_L5_Q.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid GPS-C5 observable");
+                    let observable =
+                        SignalCarrier::GPS_L5_Q.to_observable(ObservableKind::PseudoRange, major);
 
                     gps_observables.push(observable);
                 }
 
                 if !self.no_dop() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::GPS_L5_I.to_doppler_observable(v2))
-                            .expect("internal error: invalid GPS-D5 observable");
+                        SignalCarrier::GPS_L5_I.to_observable(ObservableKind::Doppler, major);
 
                     gps_observables.push(observable);
 
                     let observable =
-                        Observable::from_str(&SignalCarrier::GPS_L5_Q.to_doppler_observable(v2))
-                            .expect("internal error: invalid GPS-D5 observable");
+                        SignalCarrier::GPS_L5_Q.to_observable(ObservableKind::Doppler, major);
 
                     gps_observables.push(observable);
                 }
                 if !self.no_ssi() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::GPS_L5_I.to_ssi_observable(v2))
-                            .expect("internal error: invalid GPS-S5 observable");
+                        SignalCarrier::GPS_L5_I.to_observable(ObservableKind::SSI, major);
 
                     gps_observables.push(observable);
 
                     let observable =
-                        Observable::from_str(&SignalCarrier::GPS_L5_Q.to_ssi_observable(v2))
-                            .expect("internal error: invalid GPS-S5 observable");
+                        SignalCarrier::GPS_L5_Q.to_observable(ObservableKind::SSI, major);
 
                     gps_observables.push(observable);
                 }
             }
+            if constellations.contains(&Constellation::Galileo) {
+                if !self.no_phase() {
+                    let observable =
+                        SignalCarrier::GAL_E5A_I.to_observable(ObservableKind::PhaseRange, major);
+
+                    gal_observables.push(observable);
+
+                    let observable =
+                        SignalCarrier::GAL_E5A_Q.to_observable(ObservableKind::PhaseRange, major);
+
+                    gal_observables.push(observable);
+                }
+
+                if !self.no_pr() {
+                    let observable =
+                        SignalCarrier::GAL_E5A_I.to_observable(ObservableKind::PseudoRange, major);
+
+                    gal_observables.push(observable);
+
+                    let observable =
+                        SignalCarrier::GAL_E5A_Q.to_observable(ObservableKind::PseudoRange, major);
+
+                    gal_observables.push(observable);
+                }
+
+                if !self.no_dop() {
+                    let observable =
+                        SignalCarrier::GAL_E5A_I.to_observable(ObservableKind::Doppler, major);
+
+                    gal_observables.push(observable);
+
+                    let observable =
+                        SignalCarrier::GAL_E5A_Q.to_observable(ObservableKind::Doppler, major);
+
+                    gal_observables.push(observable);
+                }
+
+                if !self.no_ssi() {
+                    let observable =
+                        SignalCarrier::GAL_E5A_I.to_observable(ObservableKind::SSI, major);
+
+                    gal_observables.push(observable);
+
+                    let observable =
+                        SignalCarrier::GAL_E5A_Q.to_observable(ObservableKind::SSI, major);
+
+                    gal_observables.push(observable);
+                }
+            }
 
             if constellations.contains(&Constellation::QZSS) {
                 if !self.no_phase() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::QZSS_L5_I.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid QZSS-L5 observable");
+                    let observable =
+                        SignalCarrier::QZSS_L5_I.to_observable(ObservableKind::PhaseRange, major);
 
                     qzss_observables.push(observable);
 
-                    let observable = Observable::from_str(
-                        &SignalCarrier::QZSS_L5_Q.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid QZSS-L5 observable");
+                    let observable =
+                        SignalCarrier::QZSS_L5_Q.to_observable(ObservableKind::PhaseRange, major);
 
                     qzss_observables.push(observable);
                 }
                 if !self.no_pr() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::QZSS_L5_I.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid QZSS-C5 observable");
+                    let observable =
+                        SignalCarrier::QZSS_L5_I.to_observable(ObservableKind::PseudoRange, major);
 
                     qzss_observables.push(observable);
 
-                    let observable = Observable::from_str(
-                        &SignalCarrier::QZSS_L5_Q.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid QZSS-C5 observable");
+                    let observable =
+                        SignalCarrier::QZSS_L5_Q.to_observable(ObservableKind::PseudoRange, major);
 
                     qzss_observables.push(observable);
                 }
                 if !self.no_dop() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::QZSS_L5_I.to_doppler_observable(v2))
-                            .expect("internal error: invalid QZSS-D5 observable");
+                        SignalCarrier::QZSS_L5_I.to_observable(ObservableKind::Doppler, major);
 
                     qzss_observables.push(observable);
 
                     let observable =
-                        Observable::from_str(&SignalCarrier::QZSS_L5_Q.to_doppler_observable(v2))
-                            .expect("internal error: invalid QZSS-D5 observable");
+                        SignalCarrier::QZSS_L5_Q.to_observable(ObservableKind::Doppler, major);
 
                     qzss_observables.push(observable);
                 }
                 if !self.no_ssi() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::QZSS_L5_I.to_ssi_observable(v2))
-                            .expect("internal error: invalid QZSS-S5 observable");
+                        SignalCarrier::QZSS_L5_I.to_observable(ObservableKind::SSI, major);
 
                     qzss_observables.push(observable);
 
                     let observable =
-                        Observable::from_str(&SignalCarrier::QZSS_L5_Q.to_ssi_observable(v2))
-                            .expect("internal error: invalid QZSS-S5 observable");
+                        SignalCarrier::QZSS_L5_Q.to_observable(ObservableKind::SSI, major);
 
                     qzss_observables.push(observable);
                 }
@@ -1126,32 +1887,26 @@ This is currently limited to the Navigation message collection and does not impa
 
             if constellations.contains(&Constellation::IRNSS) {
                 if !self.no_phase() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::NAVIC_L5_A.to_phase_range_observable(v2),
-                    )
-                    .expect("internal error: invalid NAVIC-L5 observable");
+                    let observable =
+                        SignalCarrier::NAVIC_L5_A.to_observable(ObservableKind::PhaseRange, major);
 
                     irnss_observables.push(observable);
                 }
                 if !self.no_pr() {
-                    let observable = Observable::from_str(
-                        &SignalCarrier::NAVIC_L5_A.to_pseudo_range_observable(v2),
-                    )
-                    .expect("internal error: invalid NAVIC-C5 observable");
+                    let observable =
+                        SignalCarrier::NAVIC_L5_A.to_observable(ObservableKind::PseudoRange, major);
 
                     irnss_observables.push(observable);
                 }
                 if !self.no_dop() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::NAVIC_L5_A.to_doppler_observable(v2))
-                            .expect("internal error: invalid NAVIC-D5 observable");
+                        SignalCarrier::NAVIC_L5_A.to_observable(ObservableKind::Doppler, major);
 
                     irnss_observables.push(observable);
                 }
                 if !self.no_ssi() {
                     let observable =
-                        Observable::from_str(&SignalCarrier::NAVIC_L5_A.to_ssi_observable(v2))
-                            .expect("internal error: invalid NAVIC-S5 observable");
+                        SignalCarrier::NAVIC_L5_A.to_observable(ObservableKind::SSI, major);
 
                     irnss_observables.push(observable);
                 }
@@ -1217,29 +1972,115 @@ This is currently limited to the Navigation message collection and does not impa
         ret
     }
 
-    fn timescale(&self) -> TimeScale {
-        if let Some(ts) = self.matches.get_one::<String>("timescale") {
-            let ts = TimeScale::from_str(ts.trim())
-                .unwrap_or_else(|e| panic!("Invalid timescale: {}", e));
-            ts
-        } else {
-            TimeScale::GPST
+    /// Observables of the reference Observation RINEX, when requested.
+    fn reference_observables(
+        &self,
+        major: u8,
+    ) -> Result<Option<HashMap<Constellation, Vec<Observable>>>, CliError> {
+        let Some(filepath) = self.matches.get_one::<String>("observables-from-header") else {
+            return Ok(None);
+        };
+
+        let error = |reason: String| CliError::Reference {
+            path: filepath.to_string(),
+            reason,
+        };
+
+        let rinex = Rinex::from_file(filepath).map_err(|e| error(e.to_string()))?;
+
+        let reference = rinex.header.version.major;
+
+        if (reference == 2) != (major == 2) {
+            return Err(error(format!(
+                "V{} observables cannot describe a V{} file",
+                reference, major
+            )));
+        }
+
+        let obs = rinex
+            .header
+            .obs
+            .ok_or_else(|| error("not an Observation RINEX".to_string()))?;
+
+        if obs.codes.is_empty() {
+            return Err(error("does not declare any observable".to_string()));
         }
+
+        Ok(Some(obs.codes))
     }
 
-    fn sampling_period(&self) -> Duration {
-        if let Some(sampling) = self.matches.get_one::<String>("sampling") {
-            let dt = sampling
-                .trim()
-                .parse::<Duration>()
-                .unwrap_or_else(|e| panic!("Invalid duration: {}", e));
+    /// Fails when a selected [Constellation] has no observable with the selected signals,
+    /// which would otherwise produce a useless RINEX header.
+    fn check_observables(
+        &self,
+        observables: &HashMap<Constellation, Vec<Observable>>,
+    ) -> Result<(), CliError> {
+        if !self.rawxm() {
+            return Ok(());
+        }
 
-            if dt.total_nanoseconds() < 50_000_000 {
-                panic!("Sampling period is limited to 50ms");
+        for constellation in self.selected_constellations() {
+            if observables
+                .get(&constellation)
+                .is_none_or(|obs| obs.is_empty())
+            {
+                return Err(CliError::Usage(format!(
+                    "no observable for {} with the selected signals (--l1, --l2, --l5) and options: select another signal",
+                    constellation
+                )));
             }
-            dt
+        }
+
+        Ok(())
+    }
+
+    fn timescale(&self) -> Result<TimeScale, CliError> {
+        match self.matches.get_one::<String>("timescale") {
+            Some(ts) => TimeScale::from_str(ts.trim()).map_err(|e| CliError::InvalidValue {
+                option: "timescale",
+                value: ts.to_string(),
+                reason: e.to_string(),
+            }),
+            None => Ok(TimeScale::GPST),
+        }
+    }
+
+    /// RXM-RAWX [TimeScale], when specified
+    fn rawx_timescale(&self) -> Option<TimeScale> {
+        match self.matches.get_one::<String>("rawx-timescale")?.as_str() {
+            "gst" => Some(TimeScale::GST),
+            "bdt" => Some(TimeScale::BDT),
+            _ => Some(TimeScale::GPST),
+        }
+    }
+
+    fn sampling_period(&self) -> Result<Duration, CliError> {
+        match self.duration("sampling")? {
+            Some(dt) => {
+                if dt.total_nanoseconds() < 50_000_000 {
+                    return Err(CliError::Usage(
+                        "Sampling period is limited to 50ms".to_string(),
+                    ));
+                }
+                Ok(dt)
+            },
+            None => Ok(Duration::from_milliseconds(30_000.0)),
+        }
+    }
+
+    /// Precision of the epochs reported in the logs: follows the sampling
+    /// period for sub-second sampling, unless specified.
+    fn precision(&self, sampling_period: Duration) -> Result<Duration, CliError> {
+        if let Some(precision) = self.duration("precision")? {
+            Ok(precision)
         } else {
-            Duration::from_milliseconds(30_000.0)
+            let second = Duration::from_seconds(1.0);
+
+            if sampling_period < second {
+                Ok(sampling_period)
+            } else {
+                Ok(second)
+            }
         }
     }
 
@@ -1255,18 +2096,31 @@ This is currently limited to the Navigation message collection and does not impa
     }
 
     pub fn ublox_settings(&self) -> UbloxSettings {
-        let sampling_period = self.sampling_period();
-        UbloxSettings {
+        self.ublox_settings.clone()
+    }
+
+    fn build_ublox_settings(&self) -> Result<UbloxSettings, CliError> {
+        let sampling_period = self.sampling_period()?;
+        Ok(UbloxSettings {
             l1: self.l1(),
             l2: self.l2(),
             l5: self.l5(),
             sampling_period,
-            rawxm: !self.matches.get_flag("no-obs"),
+            rawxm: self.rawxm(),
             ephemeris: self.matches.get_flag("nav"),
-            timescale: self.timescale(),
+            poll_ephemeris: self.matches.get_flag("poll-eph"),
+            nav_eoe: !self.matches.get_flag("no-nav-eoe"),
+            nav_pvt: !self.matches.get_flag("no-nav-pvt"),
+            nav_sat: !self.matches.get_flag("no-nav-sat"),
+            sbas_log: self.sbas_log().is_some(),
+            dump_packets: self.matches.get_flag("dump-packets"),
+            time_pulse: self.time_pulse_csv().is_some(),
+            timescale: self.timescale()?,
             constellations: self.constellations(),
+            nav_constellations: self.nav_constellations()?,
             rx_clock: self.matches.get_flag("rx-clock"),
             solutions_ratio: Self::solutions_ratio(sampling_period),
+            precision: self.precision(sampling_period)?,
             sn: None,
             firmware: None,
             model: if let Some(model) = self.matches.get_one::<String>("model") {
@@ -1274,38 +2128,332 @@ This is currently limited to the Navigation message collection and does not impa
             } else {
                 None
             },
-            antenna: if let Some(antenna) = self.matches.get_one::<String>("antenna") {
-                Some(antenna.to_string())
+            antenna: self.antenna(),
+            persist: self.matches.get_flag("persist"),
+            capture_inf: self.capture_inf()?,
+            rawx_timescale: self.rawx_timescale(),
+        })
+    }
+
+    /// Returns the antenna type, with its radome code when specified.
+    fn antenna(&self) -> Option<String> {
+        let model = self.matches.get_one::<String>("antenna")?;
+
+        let antenna = match self.matches.get_one::<String>("radome") {
+            Some(radome) => igs_antenna(model, radome),
+            None => model.to_string(),
+        };
+
+        Some(antenna)
+    }
+
+    /// Returns the country code of the V3 file names, "FRA" by default
+    fn country(&self) -> Result<String, CliError> {
+        match self.matches.get_one::<String>("country") {
+            Some(country) => {
+                if !is_country_code(country) {
+                    return Err(CliError::InvalidValue {
+                        option: "country",
+                        value: country.to_string(),
+                        reason: "expecting 3 upper case letters (ISO 3166-1 alpha-3)".to_string(),
+                    });
+                }
+
+                Ok(country.to_string())
+            },
+            None => Ok("FRA".to_string()),
+        }
+    }
+
+    /// Returns the warnings about the requested options, which do not prevent the collection.
+    /// They are reported once the logger is deployed.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        // geodetic processing expects IGS conformant antenna types
+        if let Some(antenna) = &self.ublox_settings.antenna
+            && !is_igs_antenna(antenna)
+        {
+            warnings.push(format!(
+                "antenna \"{}\" does not follow the IGS naming convention (for example \"TRM59800.00     NONE\")",
+                antenna
+            ));
+        }
+
+        if self.matches.get_flag("long") && self.matches.get_one::<String>("country").is_none() {
+            if self.ublox_settings.nav_pvt {
+                warnings.push(
+                    "--long without --country: the country code is inferred from the first position fix, \"FRA\" is used until then"
+                        .to_string(),
+                );
             } else {
-                None
+                warnings.push(
+                    "--long without --country: the default \"FRA\" country code is used"
+                        .to_string(),
+                );
+            }
+        }
+
+        // V2 gathers several signals under a single observable
+        if self.rinex_settings.major == 2 && !self.matches.contains_id("observables-from-header") {
+            for (constellation, mut observables) in self.signal_observables(2) {
+                let collisions = dedup_observables(&mut observables)
+                    .iter()
+                    .map(|observable| observable.to_string())
+                    .collect::<Vec<_>>();
+
+                if !collisions.is_empty() {
+                    warnings.push(format!(
+                        "{} - several signals share the V2 observables {}: only the first signal is reported, use V3 to collect them all",
+                        constellation,
+                        collisions.join(" ")
+                    ));
+                }
+            }
+        }
+
+        warnings
+    }
+
+    /// Verifies the requested navigation collection can be represented.
+    /// RINEX V2 files describe a single constellation: each has its own file.
+    fn validate_navigation(&self, settings: &RinexSettings) -> Result<(), CliError> {
+        if !self.matches.get_flag("nav") {
+            return Ok(());
+        }
+
+        let constellations = self.nav_constellations()?;
+
+        let file_constellations = if constellations.len() == 1 || settings.major == 2 {
+            constellations
+        } else {
+            vec![Constellation::Mixed]
+        };
+
+        if let Some(template) = &settings.filename_template {
+            if self.rawxm() && !template.contains("{type}") {
+                return Err(CliError::Usage(
+                    "--filename-template must contain {type} to distinguish Observation and Navigation files".to_string(),
+                ));
+            }
+
+            if file_constellations.len() > 1 && !template.contains("{sys}") {
+                return Err(CliError::Usage(
+                    "--filename-template must contain {sys} to distinguish the RINEX V2 Navigation files of each constellation".to_string(),
+                ));
+            }
+
+            return Ok(());
+        }
+
+        if file_constellations.len() > 1 && !settings.short_filename {
+            return Err(CliError::Usage(
+                "long file names cannot distinguish the RINEX V2 Navigation files of each constellation: use short file names, or --filename-template with {sys}".to_string(),
+            ));
+        }
+
+        for constellation in file_constellations {
+            if settings.short_filename && settings.nav_v2_suffix(constellation).is_none() {
+                return Err(CliError::Usage(format!(
+                    "{} navigation cannot be described by a RINEX V{} short file name: use --long",
+                    constellation, settings.major
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses list of [SV] from given argument
+    fn satellites(&self, arg: &'static str) -> Result<Vec<SV>, CliError> {
+        if let Some(satellites) = self.matches.get_many::<String>(arg) {
+            satellites
+                .map(|sv| {
+                    SV::from_str(sv.trim()).map_err(|e| CliError::InvalidValue {
+                        option: arg,
+                        value: sv.to_string(),
+                        reason: e.to_string(),
+                    })
+                })
+                .collect()
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Parses the observables scaling factors
+    fn scale_factors(&self) -> Result<Vec<(Observable, u16)>, CliError> {
+        let mut factors = Vec::new();
+
+        if let Some(scales) = self.matches.get_many::<String>("scale") {
+            for scale in scales {
+                let error = |reason: String| CliError::InvalidValue {
+                    option: "scale",
+                    value: scale.to_string(),
+                    reason,
+                };
+
+                let (observable, factor) = scale
+                    .trim()
+                    .split_once(':')
+                    .ok_or_else(|| error("expecting OBSERVABLE:FACTOR".to_string()))?;
+
+                let observable = Observable::from_str(observable.trim())
+                    .map_err(|e| error(format!("invalid observable: {}", e)))?;
+
+                let factor = factor
+                    .trim()
+                    .parse::<u16>()
+                    .map_err(|e| error(format!("invalid scaling factor: {}", e)))?;
+
+                if ![10, 100, 1000].contains(&factor) {
+                    return Err(error("use 10, 100 or 1000".to_string()));
+                }
+
+                factors.push((observable, factor));
+            }
+        }
+
+        Ok(factors)
+    }
+
+    fn observable_map(&self) -> Result<Vec<(SignalCarrier, String)>, CliError> {
+        let mut map = Vec::new();
+
+        if let Some(values) = self.matches.get_many::<String>("observable-map") {
+            for value in values {
+                let error = |reason: String| CliError::InvalidValue {
+                    option: "observable-map",
+                    value: value.to_string(),
+                    reason,
+                };
+
+                let Some((signal, observable)) = value.split_once('=') else {
+                    return Err(error("expecting \"SIGNAL=OBS\"".to_string()));
+                };
+
+                let carrier = SignalCarrier::from_str(signal).map_err(error)?;
+
+                let observable = observable.trim();
+
+                if observable.len() != 3 || !observable.is_ascii() {
+                    return Err(error("expecting a RINEX3 observable".to_string()));
+                }
+
+                Observable::from_str(observable)
+                    .map_err(|e| error(format!("invalid observable: {}", e)))?;
+
+                if map.iter().any(|(remapped, _)| *remapped == carrier) {
+                    return Err(error("signal remapped twice".to_string()));
+                }
+
+                map.push((carrier, observable[1..].to_string()));
+            }
+        }
+
+        Ok(map)
+    }
+
+    fn phase_centers(&self) -> Result<Vec<PhaseCenter>, CliError> {
+        let mut phase_centers = Vec::new();
+
+        if let Some(values) = self.matches.get_many::<String>("phase-center") {
+            for value in values {
+                let error = |reason: String| CliError::InvalidValue {
+                    option: "phase-center",
+                    value: value.to_string(),
+                    reason,
+                };
+
+                let items = value.split_ascii_whitespace().collect::<Vec<_>>();
+
+                if items.len() != 5 {
+                    return Err(error("expecting \"SYS OBS N E U\"".to_string()));
+                }
+
+                let constellation = Constellation::from_str(items[0])
+                    .map_err(|e| error(format!("invalid constellation: {}", e)))?;
+
+                let observable = Observable::from_str(items[1])
+                    .map_err(|e| error(format!("invalid observable: {}", e)))?;
+
+                let mut offsets = [0.0; 3];
+
+                for (offset, item) in offsets.iter_mut().zip(&items[2..]) {
+                    *offset = item
+                        .parse::<f64>()
+                        .map_err(|e| error(format!("invalid offset: {}", e)))?;
+                }
+
+                phase_centers.push(PhaseCenter {
+                    constellation,
+                    observable,
+                    neu: (offsets[0], offsets[1], offsets[2]),
+                });
+            }
+        }
+
+        Ok(phase_centers)
+    }
+
+    /// Returns the [FlushPolicy]: live tailing on serial port, throughput on file conversion
+    fn flush_policy(&self) -> Result<FlushPolicy, CliError> {
+        match self.matches.get_one::<String>("flush-policy") {
+            Some(policy) => FlushPolicy::from_str(policy).map_err(CliError::Usage),
+            None => {
+                if self.serial_port().is_some() {
+                    Ok(FlushPolicy::Epoch)
+                } else {
+                    Ok(FlushPolicy::Close)
+                }
             },
         }
     }
 
     pub fn rinex_settings(&self) -> RinexSettings {
-        RinexSettings {
+        self.rinex_settings.clone()
+    }
+
+    fn build_rinex_settings(&self) -> Result<RinexSettings, CliError> {
+        let major = if self.matches.get_flag("v4") {
+            4
+        } else if self.matches.get_flag("v2") {
+            2
+        } else {
+            3
+        };
+
+        let mut settings = RinexSettings {
             short_filename: !self.matches.get_flag("long"),
-            gzip: self.matches.get_flag("gzip"),
+            gzip: self.matches.get_flag("gzip") || self.matches.get_flag("gzip-obs"),
+            gzip_nav: self.matches.get_flag("gzip") || self.matches.get_flag("gzip-nav"),
+            gzip_on_close: self.matches.get_flag("gzip-on-close"),
             crinex: self.matches.get_flag("crx"),
-            timescale: self.timescale(),
-            observables: self.observables(),
-            major: if self.matches.get_flag("v4") {
-                4
-            } else if self.matches.get_flag("v2") {
-                2
-            } else {
-                3
-            },
+            crinex_version: self
+                .matches
+                .get_one::<String>("crinex-version")
+                .map(|version| version.parse::<u8>().unwrap()),
+            raw_snr: self.matches.get_flag("raw-snr"),
+            only_sv: self.satellites("obs-only-sv")?,
+            exclude_sv: self.satellites("exclude-sv")?,
+            flush_policy: self.flush_policy()?,
+            epoch_flag_on_clk_reset: self.matches.get_flag("epoch-flag-on-clk-reset"),
+            align_epochs: self.matches.get_flag("align-epochs"),
+            observables_summary: self.matches.get_flag("observables-summary"),
+            scale_factors: self.scale_factors()?,
+            phase_centers: self.phase_centers()?,
+            observable_map: self.observable_map()?,
+            timescale: self.timescale()?,
+            observables: self.observables(major),
+            reference_layout: self.matches.contains_id("observables-from-header"),
+            major,
             header_comment: if let Some(comment) = self.matches.get_one::<String>("comment") {
                 Some(comment.to_string())
             } else {
                 None
             },
-            country: if let Some(country) = self.matches.get_one::<String>("country") {
-                country.to_string()
-            } else {
-                "FRA".to_string()
-            },
+            country: self.country()?,
+            infer_country: self.matches.get_one::<String>("country").is_none(),
             agency: if let Some(agency) = self.matches.get_one::<String>("agency") {
                 Some(agency.to_string())
             } else {
@@ -1321,25 +2469,31 @@ This is currently limited to the Navigation message collection and does not impa
             } else {
                 None
             },
+            filename_template: self.matches.get_one::<String>("filename-template").cloned(),
             name: if let Some(name) = self.matches.get_one::<String>("name") {
                 name.to_string()
             } else {
                 "UBXR".to_string()
             },
-            period: if let Some(period) = self.matches.get_one::<String>("period") {
-                period.trim().parse::<Duration>().unwrap_or_else(|e| {
-                    panic!("not a valid duration: {}", e);
-                })
+            continuous: self.matches.get_flag("continuous"),
+            sampling: self.sampling_period()?,
+            period: if self.matches.get_flag("continuous") {
+                Duration::from_days(1.0)
             } else {
-                Duration::from_hours(1.0)
+                self.duration("period")?
+                    .unwrap_or(Duration::from_hours(1.0))
             },
-            nav_period: if let Some(period) = self.matches.get_one::<String>("nav-period") {
-                period.trim().parse::<Duration>().unwrap_or_else(|e| {
-                    panic!("not a valid duration: {}", e);
-                })
+            nav_period: self
+                .duration("nav-period")?
+                .unwrap_or(Duration::from_hours(2.0)),
+            decimate_nav: self.matches.get_flag("decimate-nav"),
+            fragments: if self.matches.get_flag("merge") {
+                Some(Fragments::default())
             } else {
-                Duration::from_hours(2.0)
+                None
             },
+            products: None,
+            records: None,
             health_mask: {
                 if self.matches.get_flag("healthy-only") {
                     HealthMask::HealthyOnly
@@ -1349,6 +2503,140 @@ This is currently limited to the Navigation message collection and does not impa
                     HealthMask::Any
                 }
             },
+        };
+
+        if let Some(observables) = self.reference_observables(settings.major)? {
+            settings.observables = observables;
+        } else {
+            settings.remap_observables();
+            self.check_observables(&settings.observables)?;
+        }
+
+        if settings.gzip_on_close && !settings.gzip && !settings.gzip_nav {
+            return Err(CliError::Usage(
+                "--gzip-on-close requires --gzip, --gzip-obs or --gzip-nav".to_string(),
+            ));
+        }
+
+        if settings.major == 2 && !settings.observable_map.is_empty() {
+            return Err(CliError::Usage(
+                "--observable-map requires RINEX V3 or V4".to_string(),
+            ));
         }
+
+        if settings.major == 2 && !settings.scale_factors.is_empty() {
+            return Err(CliError::Usage(
+                "--scale requires RINEX V3 or V4".to_string(),
+            ));
+        }
+
+        if !settings.short_filename
+            && settings.filename_template.is_none()
+            && settings.period_token().is_none()
+        {
+            return Err(CliError::Usage(format!(
+                "--period {} cannot be represented in a V3 file name: use a whole number of minutes, hours or days (up to 99)",
+                settings.period
+            )));
+        }
+
+        self.validate_navigation(&settings)?;
+        Ok(settings)
     }
 }
+
+/// Inserts the options of the configuration file (--config), if any, ahead of
+/// the command line `args`. Options defined on the command line are not inserted,
+/// neither are the options that conflict with the command line.
+/// Flags cannot be negated: a flag of the file (`true`) cannot be disabled on the command line.
+fn config_args(command: &Command, mut args: Vec<String>) -> Result<Vec<String>, CliError> {
+    let cmdline = command
+        .clone()
+        .ignore_errors(true)
+        .try_get_matches_from(args.iter())
+        .ok();
+
+    let Some(path) = cmdline
+        .as_ref()
+        .and_then(|matches| matches.get_one::<String>("config"))
+    else {
+        return Ok(args);
+    };
+
+    let error = |reason: String| CliError::Config {
+        path: path.to_string(),
+        reason,
+    };
+
+    let content = std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+
+    let table = content
+        .parse::<toml::Table>()
+        .map_err(|e| error(e.to_string()))?;
+
+    let mut options = Vec::<String>::new();
+
+    for (key, value) in table.iter() {
+        if key == "config" {
+            return Err(error(
+                "nested configuration files are not supported".to_string(),
+            ));
+        }
+
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(key.as_str()))
+            .ok_or_else(|| error(format!("unknown option \"{}\"", key)))?;
+
+        let on_cmdline = |arg: &Arg| {
+            cmdline
+                .as_ref()
+                .and_then(|matches| matches.value_source(arg.get_id().as_str()))
+                == Some(ValueSource::CommandLine)
+        };
+
+        let conflicts = |a: &Arg, b: &Arg| {
+            command
+                .get_arg_conflicts_with(a)
+                .iter()
+                .any(|conflict| conflict.get_id() == b.get_id())
+        };
+
+        let overridden = command.get_arguments().any(|other| {
+            on_cmdline(other)
+                && (other.get_id() == arg.get_id()
+                    || conflicts(arg, other)
+                    || conflicts(other, arg))
+        });
+
+        if overridden {
+            continue; // command line takes precedence
+        }
+
+        let values = match value {
+            toml::Value::Array(values) => values.iter().collect::<Vec<_>>(),
+            value => vec![value],
+        };
+
+        for value in values {
+            match value {
+                toml::Value::Boolean(true) => options.push(format!("--{}", key)),
+                toml::Value::Boolean(false) => {},
+                toml::Value::Integer(count) if matches!(arg.get_action(), ArgAction::Count) => {
+                    for _ in 0..*count {
+                        options.push(format!("--{}", key));
+                    }
+                },
+                toml::Value::String(value) => options.push(format!("--{}={}", key, value)),
+                toml::Value::Integer(value) => options.push(format!("--{}={}", key, value)),
+                toml::Value::Float(value) => options.push(format!("--{}={}", key, value)),
+                value => {
+                    return Err(error(format!("invalid value for \"{}\": {}", key, value)));
+                },
+            }
+        }
+    }
+
+    args.splice(1..1, options);
+    Ok(args)
+}
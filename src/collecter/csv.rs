@@ -0,0 +1,107 @@
+use log::error;
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use tokio::sync::mpsc::Receiver as Rx;
+
+use crate::collecter::Message;
+
+/// [Collecter] of one [Message] stream, as CSV: one line per message of interest
+pub struct Collecter {
+    /// [Message]ing handle
+    rx: Rx<Message>,
+
+    /// Output file
+    fd: BufWriter<File>,
+
+    /// Logged content, for error reports
+    name: &'static str,
+
+    /// Formats a [Message] of interest as a CSV line, ignores the others
+    line: fn(&Message) -> Option<String>,
+}
+
+impl Collecter {
+    /// Builds new [Collecter], writing the CSV header line right away
+    pub fn new(
+        path: &str,
+        header: &str,
+        name: &'static str,
+        rx: Rx<Message>,
+        line: fn(&Message) -> Option<String>,
+    ) -> Self {
+        let fd =
+            File::create(path).unwrap_or_else(|e| panic!("Failed to open \"{}\": {}", path, e));
+
+        let mut fd = BufWriter::new(fd);
+
+        writeln!(fd, "{}", header)
+            .unwrap_or_else(|e| panic!("Failed to write \"{}\": {}", path, e));
+
+        Self { rx, fd, name, line }
+    }
+
+    pub async fn run(&mut self) {
+        loop {
+            match self.rx.recv().await {
+                Some(Message::Shutdown) | None => {
+                    let _ = self.fd.flush();
+                    return;
+                },
+                Some(message) => {
+                    if let Some(line) = (self.line)(&message)
+                        && let Err(e) = writeln!(self.fd, "{}", line)
+                    {
+                        error!("failed to log {}: {}", self.name, e);
+                    }
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Collecter;
+    use crate::collecter::Message;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn csv_logger() {
+        let path = std::env::temp_dir().join("ubx2rinex-csv-logger.csv");
+        let path = path.to_string_lossy().to_string();
+
+        let (tx, rx) = mpsc::channel(8);
+
+        let mut collecter =
+            Collecter::new(
+                &path,
+                "epoch,comment",
+                "comments",
+                rx,
+                |message| match message {
+                    Message::HeaderComment(comment) => Some(format!("-,{}", comment)),
+                    _ => None,
+                },
+            );
+
+        tx.send(Message::HeaderComment("first".to_string()))
+            .await
+            .unwrap();
+        tx.send(Message::EndofEpoch()).await.unwrap();
+        tx.send(Message::HeaderComment("second".to_string()))
+            .await
+            .unwrap();
+        tx.send(Message::Shutdown).await.unwrap();
+
+        collecter.run().await;
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(content, "epoch,comment\n-,first\n-,second\n");
+    }
+}
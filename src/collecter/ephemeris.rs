@@ -4,12 +4,24 @@ use gnss_protos::{
     GpsQzssFrame, GpsQzssFrame1, GpsQzssFrame2, GpsQzssFrame3, GpsQzssHow, GpsQzssSubframe,
 };
 
-use ublox::rxm_sfrbx::RxmSfrbxInterpreted;
+use ublox::{mga_gps_eph::MgaGpsEphRef, rxm_sfrbx::RxmSfrbxInterpreted};
 
 use rinex::navigation::{Ephemeris as RINEX, OrbitItem};
 
 use std::collections::HashMap;
 
+use thiserror::Error;
+
+/// Reasons why pending subframes do not form an [Ephemeris]
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum EphError {
+    #[error("missing subframe #{0}")]
+    MissingSubframe(u8),
+
+    #[error("IOD mismatch: IODC={iodc}, IODE={iode2} (subframe #2), IODE={iode3} (subframe #3)")]
+    IodMismatch { iodc: u16, iode2: u8, iode3: u8 },
+}
+
 #[derive(Debug, Default, Copy, Clone)]
 pub struct GpsQzssEphemeris {
     pub how: GpsQzssHow,
@@ -27,6 +39,53 @@ impl GpsQzssEphemeris {
         week as u32 + rollover * 1024
     }
 
+    /// Builds [GpsQzssEphemeris] from a UBX-MGA-GPS-EPH assistance message.
+    /// MGA does not carry the week number, which is provided by the receiver time.
+    pub fn from_mga_gps_eph(week: u16, mga: &MgaGpsEphRef) -> Self {
+        // IODE is the 8 LSB of IODC
+        let iode = (mga.iodc() & 0xff) as u8;
+
+        Self {
+            how: Default::default(),
+            frame1: GpsQzssFrame1 {
+                week,
+                ura: mga.ura_index() as _,
+                health: mga.sv_health() as _,
+                tgd: mga.tgd(),
+                iodc: mga.iodc(),
+                toc: mga.toc() as _,
+                af2: mga.af2(),
+                af1: mga.af1(),
+                af0: mga.af0(),
+                ..Default::default()
+            },
+            frame2: GpsQzssFrame2 {
+                iode,
+                crs: mga.crs(),
+                dn: mga.delta_n(),
+                m0: mga.m0(),
+                cuc: mga.cuc(),
+                e: mga.e(),
+                cus: mga.cus(),
+                sqrt_a: mga.sqrt_a(),
+                toe: mga.toe() as _,
+                ..Default::default()
+            },
+            frame3: GpsQzssFrame3 {
+                iode,
+                cic: mga.cic(),
+                omega0: mga.omega0(),
+                cis: mga.cis(),
+                i0: mga.i0(),
+                crc: mga.crc(),
+                omega: mga.omega(),
+                omega_dot: mga.omega_dot(),
+                idot: mga.idot(),
+                ..Default::default()
+            },
+        }
+    }
+
     /// Converts [Ephemeris] to (Epoch=ToC, [RINEX])
     pub fn to_rinex(&self, now: Epoch) -> (Epoch, RINEX) {
         let week =
@@ -146,23 +205,27 @@ impl PendingGpsQzssFrame {
         }
     }
 
-    pub fn validate(&self) -> Option<GpsQzssEphemeris> {
-        let frame1 = self.frame1?;
-        let frame2 = self.frame2?;
-        let frame3 = self.frame3?;
-
-        if frame2.iode == frame3.iode {
-            if frame1.iodc as u8 == frame2.iode {
-                return Some(GpsQzssEphemeris {
-                    how: self.how,
-                    frame1,
-                    frame2,
-                    frame3,
-                });
-            }
+    /// Returns the [GpsQzssEphemeris] once all subframes were received
+    /// and describe the same issue of data.
+    pub fn validate(&self) -> Result<GpsQzssEphemeris, EphError> {
+        let frame1 = self.frame1.ok_or(EphError::MissingSubframe(1))?;
+        let frame2 = self.frame2.ok_or(EphError::MissingSubframe(2))?;
+        let frame3 = self.frame3.ok_or(EphError::MissingSubframe(3))?;
+
+        if frame2.iode != frame3.iode || frame1.iodc as u8 != frame2.iode {
+            return Err(EphError::IodMismatch {
+                iodc: frame1.iodc,
+                iode2: frame2.iode,
+                iode3: frame3.iode,
+            });
         }
 
-        None
+        Ok(GpsQzssEphemeris {
+            how: self.how,
+            frame1,
+            frame2,
+            frame3,
+        })
     }
 }
 
@@ -172,11 +235,11 @@ pub enum PendingFrame {
 }
 
 impl PendingFrame {
-    pub fn validate(&self) -> Option<Ephemeris> {
+    pub fn validate(&self) -> Result<Ephemeris, EphError> {
         match self {
             Self::GpsQzss(pending) => {
                 let validated = pending.validate()?;
-                Some(Ephemeris::GpsQzss(validated))
+                Ok(Ephemeris::GpsQzss(validated))
             },
         }
     }
@@ -188,3 +251,35 @@ impl PendingFrame {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{EphError, PendingGpsQzssFrame};
+
+    #[test]
+    fn ephemeris_validation() {
+        let mut pending = PendingGpsQzssFrame::default();
+        assert_eq!(pending.validate().err(), Some(EphError::MissingSubframe(1)));
+
+        pending.frame1 = Some(Default::default());
+        pending.frame3 = Some(Default::default());
+        assert_eq!(pending.validate().err(), Some(EphError::MissingSubframe(2)));
+
+        let mut frame2 = Default::default();
+        pending.frame2 = Some(frame2);
+        assert!(pending.validate().is_ok());
+
+        // new issue of data, not completely received yet
+        frame2.iode = 12;
+        pending.frame2 = Some(frame2);
+
+        assert_eq!(
+            pending.validate().err(),
+            Some(EphError::IodMismatch {
+                iodc: 0,
+                iode2: 12,
+                iode3: 0,
+            })
+        );
+    }
+}
@@ -1,16 +1,71 @@
 use flate2::{Compression, write::GzEncoder};
-use std::fs::File;
+use std::{
+    fs::{File, OpenOptions, create_dir_all, remove_file},
+    io::Write,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use crate::collecter::settings::Settings;
+
+/// RINEX [Products] gathered in memory, rather than written to files
+#[derive(Debug, Clone, Default)]
+pub struct Products {
+    /// (file name, content) pairs, in order of production
+    files: Arc<Mutex<Vec<(String, Vec<u8>)>>>,
+}
+
+impl Products {
+    /// Opens a new [MemoryFile]
+    fn open(&self, filename: &str) -> MemoryFile {
+        let mut files = self.files.lock().unwrap();
+
+        files.push((filename.to_string(), Vec::new()));
+
+        MemoryFile {
+            index: files.len() - 1,
+            products: self.clone(),
+        }
+    }
+
+    /// Returns all files, as (file name, content) pairs, in order of production
+    pub fn files(&self) -> Vec<(String, Vec<u8>)> {
+        self.files.lock().unwrap().clone()
+    }
+}
+
+/// [MemoryFile] is one file of [Products]
+pub struct MemoryFile {
+    index: usize,
+    products: Products,
+}
+
+impl Write for MemoryFile {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        let mut files = self.products.files.lock().unwrap();
+        files[self.index].1.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
 
 pub enum FileDescriptor {
     Plain(File),
     Gzip(GzEncoder<File>),
+    Memory(MemoryFile),
+    MemoryGzip(GzEncoder<MemoryFile>),
 }
 
-impl std::io::Write for FileDescriptor {
+impl Write for FileDescriptor {
     fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
         match self {
             Self::Plain(w) => w.write(data),
             Self::Gzip(w) => w.write(data),
+            Self::Memory(w) => w.write(data),
+            Self::MemoryGzip(w) => w.write(data),
         }
     }
 
@@ -18,12 +73,74 @@ impl std::io::Write for FileDescriptor {
         match self {
             Self::Plain(w) => w.flush(),
             Self::Gzip(w) => w.flush(),
+            Self::Memory(w) => w.flush(),
+            Self::MemoryGzip(w) => w.flush(),
         }
     }
 }
 
 impl FileDescriptor {
+    /// Opens a new [FileDescriptor], either in memory or on disk, according to [Settings]
+    pub fn open(settings: &Settings, filename: &str) -> Self {
+        match &settings.products {
+            Some(products) => Self::in_memory(settings.gzip, filename, products),
+            None if settings.compresses_on_close() => {
+                Self::new(false, &settings.working_filename(filename))
+            },
+            None => Self::new(settings.gzip, filename),
+        }
+    }
+
+    /// Opens an existing file, to continue it, according to [Settings].
+    /// When compressing on close, the continuation is appended to the working file,
+    /// which may have been left by an interrupted session.
+    pub fn reopen(settings: &Settings, filename: &str) -> Self {
+        if settings.compresses_on_close() {
+            Self::append(false, &settings.working_filename(filename))
+        } else {
+            Self::append(settings.gzip, filename)
+        }
+    }
+
+    /// Opens a new [MemoryFile] within [Products]
+    pub fn in_memory(gzip: bool, filename: &str, products: &Products) -> Self {
+        let fd = products.open(filename);
+
+        if gzip {
+            let compression = Compression::new(5);
+            Self::MemoryGzip(GzEncoder::new(fd, compression))
+        } else {
+            Self::Memory(fd)
+        }
+    }
+
+    /// Opens a file, to append to it. It is created when it does not exist yet.
+    /// A gzip file is continued with a new gzip member.
+    pub fn append(gzip: bool, filename: &str) -> Self {
+        let fd = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(filename)
+            .unwrap_or_else(|e| panic!("Failed to open \"{}\": {}", filename, e));
+
+        if gzip {
+            let compression = Compression::new(5);
+            Self::Gzip(GzEncoder::new(fd, compression))
+        } else {
+            Self::Plain(fd)
+        }
+    }
+
     pub fn new(gzip: bool, filename: &str) -> Self {
+        // output directory may not exist yet
+        if let Some(parent) = Path::new(filename).parent()
+            && !parent.as_os_str().is_empty()
+        {
+            create_dir_all(parent).unwrap_or_else(|e| {
+                panic!("Failed to create directory \"{}\": {}", parent.display(), e)
+            });
+        }
+
         let fd = File::create(&filename)
             .unwrap_or_else(|e| panic!("Failed to open \"{}\": {}", filename, e));
 
@@ -35,3 +152,151 @@ impl FileDescriptor {
         }
     }
 }
+
+/// Compresses the plain `working` file into the gzip `filename`, once it is closed,
+/// then removes the working file. A continued file is extended with a new gzip member,
+/// or created when the interrupted session did not compress it yet.
+pub fn compress_on_close(working: &str, filename: &str, continued: bool) -> std::io::Result<()> {
+    let fd = if continued {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(filename)?
+    } else {
+        File::create(filename)?
+    };
+
+    let mut fd = GzEncoder::new(fd, Compression::new(5));
+    std::io::copy(&mut File::open(working)?, &mut fd)?;
+    fd.finish()?;
+
+    remove_file(working)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FileDescriptor, Products, compress_on_close};
+    use flate2::read::MultiGzDecoder;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn creates_output_directory() {
+        let root = std::env::temp_dir().join("ubx2rinex-fd-test");
+        let _ = std::fs::remove_dir_all(&root);
+
+        let path = root.join("a").join("b").join("TEST.obs");
+
+        let mut fd = FileDescriptor::new(false, path.to_str().unwrap());
+        fd.write_all(b"test").unwrap();
+        fd.flush().unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"test");
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn appends_to_existing_file() {
+        let root = std::env::temp_dir().join("ubx2rinex-fd-append");
+        let _ = std::fs::remove_dir_all(&root);
+
+        let path = root.join("TEST.obs");
+        let filename = path.to_str().unwrap();
+
+        let mut fd = FileDescriptor::new(false, filename);
+        fd.write_all(b"header\n").unwrap();
+        drop(fd);
+
+        let mut fd = FileDescriptor::append(false, filename);
+        fd.write_all(b"epoch\n").unwrap();
+        drop(fd);
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"header\nepoch\n");
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn continues_interrupted_working_file() {
+        let root = std::env::temp_dir().join("ubx2rinex-fd-interrupted");
+        let _ = std::fs::remove_dir_all(&root);
+
+        let working = root.join("TEST.obs");
+        let filename = root.join("TEST.obs.gz");
+        let (working, filename) = (working.to_str().unwrap(), filename.to_str().unwrap());
+
+        // interrupted session: the working file was never compressed
+        let mut fd = FileDescriptor::new(false, working);
+        fd.write_all(b"header\n").unwrap();
+        drop(fd);
+
+        let mut fd = FileDescriptor::append(false, working);
+        fd.write_all(b"epoch\n").unwrap();
+        drop(fd);
+
+        compress_on_close(working, filename, true).unwrap();
+
+        let mut content = String::new();
+
+        MultiGzDecoder::new(std::fs::File::open(filename).unwrap())
+            .read_to_string(&mut content)
+            .unwrap();
+
+        assert_eq!(content, "header\nepoch\n");
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn compresses_working_file_on_close() {
+        let root = std::env::temp_dir().join("ubx2rinex-fd-gzip-on-close");
+        let _ = std::fs::remove_dir_all(&root);
+
+        let working = root.join("TEST.obs");
+        let filename = root.join("TEST.obs.gz");
+        let (working, filename) = (working.to_str().unwrap(), filename.to_str().unwrap());
+
+        let mut fd = FileDescriptor::new(false, working);
+        fd.write_all(b"header\n").unwrap();
+        drop(fd);
+
+        compress_on_close(working, filename, false).unwrap();
+        assert!(!std::path::Path::new(working).exists());
+
+        // continued: new gzip member
+        let mut fd = FileDescriptor::new(false, working);
+        fd.write_all(b"epoch\n").unwrap();
+        drop(fd);
+
+        compress_on_close(working, filename, true).unwrap();
+
+        let mut content = String::new();
+
+        MultiGzDecoder::new(std::fs::File::open(filename).unwrap())
+            .read_to_string(&mut content)
+            .unwrap();
+
+        assert_eq!(content, "header\nepoch\n");
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn in_memory_products() {
+        let products = Products::default();
+
+        let mut fd = FileDescriptor::in_memory(false, "TEST.obs", &products);
+        fd.write_all(b"test").unwrap();
+
+        let mut fd = FileDescriptor::in_memory(false, "TEST.nav", &products);
+        fd.write_all(b"nav").unwrap();
+
+        assert_eq!(
+            products.files(),
+            vec![
+                ("TEST.obs".to_string(), b"test".to_vec()),
+                ("TEST.nav".to_string(), b"nav".to_vec()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Header records that [rinex::prelude::Header] does not describe (yet)
+//! are inserted right before the END OF HEADER marker.
+
+use flate2::{Compression, read::MultiGzDecoder, write::GzEncoder};
+
+use std::{
+    fs::{File, remove_file, rename},
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+};
+
+/// Formats a single header record: 60 characters of content, followed by its label.
+pub fn record(content: &str, label: &str) -> String {
+    format!("{:<60}{}", content, label)
+}
+
+/// Inserts the formatted `records` right before "END OF HEADER"
+pub fn insert_records(header: &str, records: &[String]) -> String {
+    let mut ret = String::with_capacity(header.len() + records.len() * 81);
+
+    for line in header.lines() {
+        if line.ends_with("END OF HEADER") {
+            for record in records.iter() {
+                ret.push_str(record);
+                ret.push('\n');
+            }
+        }
+
+        ret.push_str(line);
+        ret.push('\n');
+    }
+
+    ret
+}
+
+/// Inserts the formatted `records` in the header of a complete file, once it is closed.
+/// Used for the records that are only known at the end of the collection.
+/// The completed file is written next to the original, which it then replaces:
+/// the original is preserved should the rewrite fail.
+pub fn rewrite_header(filename: &str, gzip: bool, records: &[String]) -> std::io::Result<()> {
+    let tmp = format!("{}.tmp", filename);
+
+    let reader: Box<dyn Read> = if gzip {
+        Box::new(MultiGzDecoder::new(File::open(filename)?))
+    } else {
+        Box::new(File::open(filename)?)
+    };
+
+    let mut reader = BufReader::new(reader);
+    let fd = BufWriter::new(File::create(&tmp)?);
+
+    let written = if gzip {
+        let mut fd = GzEncoder::new(fd, Compression::new(5));
+
+        copy_with_header_records(&mut reader, &mut fd, records)
+            .and_then(|_| fd.finish())
+            .and_then(|mut fd| fd.flush())
+    } else {
+        let mut fd = fd;
+        copy_with_header_records(&mut reader, &mut fd, records).and_then(|_| fd.flush())
+    };
+
+    if let Err(e) = written {
+        let _ = remove_file(&tmp);
+        return Err(e);
+    }
+
+    rename(&tmp, filename)
+}
+
+/// Copies complete file content, inserting the formatted `records` in its header section.
+/// The body is streamed.
+fn copy_with_header_records<R: BufRead, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    records: &[String],
+) -> std::io::Result<()> {
+    let mut header = String::new();
+
+    loop {
+        let size = reader.read_line(&mut header)?;
+
+        if size == 0 || header.trim_end().ends_with("END OF HEADER") {
+            break;
+        }
+    }
+
+    writer.write_all(insert_records(&header, records).as_bytes())?;
+    std::io::copy(reader, writer)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{copy_with_header_records, insert_records, record, rewrite_header};
+    use std::io::Cursor;
+
+    #[test]
+    fn record_insertion() {
+        let header = format!(
+            "{}\n{}\n",
+            record("G    4 C1C L1C D1C S1C", "SYS / # / OBS TYPES"),
+            record("", "END OF HEADER"),
+        );
+
+        let content = insert_records(&header, &[record("DBHZ", "SIGNAL STRENGTH UNIT")]);
+
+        let lines = content.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("SYS / # / OBS TYPES"));
+        assert_eq!(
+            lines[1],
+            "DBHZ                                                        SIGNAL STRENGTH UNIT"
+        );
+        assert!(lines[2].ends_with("END OF HEADER"));
+    }
+
+    #[test]
+    fn header_rewrite() {
+        let content = format!(
+            "{}\n{}\n> 2020 01 01 00 00  0.0000000  0  1\nG01  20000000.000\n",
+            record("G    1 C1C", "SYS / # / OBS TYPES"),
+            record("", "END OF HEADER"),
+        );
+
+        let mut rewritten = Vec::new();
+
+        copy_with_header_records(
+            &mut Cursor::new(content.as_bytes()),
+            &mut rewritten,
+            &[record("     1", "# OF SATELLITES")],
+        )
+        .unwrap();
+
+        let content = String::from_utf8(rewritten).unwrap();
+        let lines = content.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 5);
+        assert!(lines[1].ends_with("# OF SATELLITES"));
+        assert!(lines[2].ends_with("END OF HEADER"));
+        assert_eq!(lines[4], "G01  20000000.000");
+    }
+
+    #[test]
+    fn header_rewrite_in_place() {
+        let root = std::env::temp_dir().join("ubx2rinex-header-rewrite");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+
+        let path = root.join("TEST.obs");
+        let filename = path.to_str().unwrap();
+
+        let content = format!(
+            "{}\n{}\nG01  20000000.000\n",
+            record("G    1 C1C", "SYS / # / OBS TYPES"),
+            record("", "END OF HEADER"),
+        );
+
+        std::fs::write(&path, &content).unwrap();
+
+        rewrite_header(filename, false, &[record("     1", "# OF SATELLITES")]).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines = content.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].ends_with("# OF SATELLITES"));
+        assert_eq!(lines[3], "G01  20000000.000");
+
+        // the temporary file was renamed
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 1);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,66 @@
+use std::sync::{Arc, Mutex};
+
+use rinex::{
+    prelude::{Header, Rinex},
+    record::Record,
+};
+
+/// RINEX [Records] gathered in memory, as [Rinex] structures,
+/// rather than formatted to files
+#[derive(Debug, Clone, Default)]
+pub struct Records {
+    /// [Rinex] structures, in order of production
+    rinex: Arc<Mutex<Vec<Rinex>>>,
+}
+
+impl Records {
+    /// Opens a new [Rinex], returns its index
+    pub(crate) fn open(&self, header: Header, record: Record) -> usize {
+        let mut rinex = self.rinex.lock().unwrap();
+        rinex.push(Rinex::new(header, record));
+        rinex.len() - 1
+    }
+
+    /// Modifies the [Rinex] at this index
+    pub(crate) fn with<F: FnOnce(&mut Rinex)>(&self, index: usize, f: F) {
+        let mut rinex = self.rinex.lock().unwrap();
+
+        if let Some(rinex) = rinex.get_mut(index) {
+            f(rinex);
+        }
+    }
+
+    /// Returns all [Rinex], in order of production
+    pub fn rinex(&self) -> Vec<Rinex> {
+        self.rinex.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Records;
+    use rinex::{
+        prelude::{Header, RinexType},
+        record::Record,
+    };
+
+    #[test]
+    fn records_indexing() {
+        let records = Records::default();
+
+        let mut header = Header::default();
+        header.rinex_type = RinexType::ObservationData;
+
+        let obs = records.open(header.clone(), Record::ObsRecord(Default::default()));
+
+        header.rinex_type = RinexType::NavigationData;
+        let nav = records.open(header, Record::NavRecord(Default::default()));
+
+        assert_eq!((obs, nav), (0, 1));
+
+        let rinex = records.rinex();
+        assert_eq!(rinex.len(), 2);
+        assert!(rinex[0].is_observation_rinex());
+        assert!(rinex[1].is_navigation_rinex());
+    }
+}
@@ -0,0 +1,151 @@
+//! Files produced during a session may be merged into daily files (--merge),
+//! once the collection is over.
+
+use flate2::read::MultiGzDecoder;
+use log::{error, info};
+
+use std::{
+    fs::{File, remove_file},
+    io::{BufReader, BufWriter, Read, Write},
+    sync::{Arc, Mutex},
+};
+
+use rinex::{merge::Merge, prelude::Rinex};
+
+use crate::collecter::fd::FileDescriptor;
+
+/// RINEX [Fragments] produced during this session, to be merged into daily files
+#[derive(Debug, Clone, Default)]
+pub struct Fragments {
+    /// (daily file name, fragment file name) pairs, in order of production
+    files: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl Fragments {
+    /// Declares a new fragment of this daily file
+    pub fn push(&self, daily: &str, filename: &str) {
+        let mut files = self.files.lock().unwrap();
+        files.push((daily.to_string(), filename.to_string()));
+    }
+
+    /// Returns the fragments of each daily file, in order of production
+    pub fn daily_files(&self) -> Vec<(String, Vec<String>)> {
+        let mut ret = Vec::<(String, Vec<String>)>::new();
+
+        for (daily, filename) in self.files.lock().unwrap().iter() {
+            match ret.iter_mut().find(|(name, _)| name == daily) {
+                Some((_, fragments)) => fragments.push(filename.clone()),
+                None => ret.push((daily.clone(), vec![filename.clone()])),
+            }
+        }
+
+        ret
+    }
+
+    /// Merges all fragments into their daily file. Fragments are removed
+    /// once the daily file has been produced, and preserved otherwise.
+    /// Observation and Navigation files may be compressed differently:
+    /// gzip files are identified by their name.
+    pub fn merge(&self) {
+        for (daily, fragments) in self.daily_files() {
+            if fragments == [daily.clone()] {
+                continue; // already complete
+            }
+
+            let gzip = daily.ends_with(".gz");
+
+            match merge_files(&daily, &fragments, gzip) {
+                Ok(_) => {
+                    info!("merged {} file(s) into \"{}\"", fragments.len(), daily);
+
+                    for fragment in fragments.iter().filter(|fragment| **fragment != daily) {
+                        if let Err(e) = remove_file(fragment) {
+                            error!("failed to remove \"{}\": {}", fragment, e);
+                        }
+                    }
+                },
+                Err(e) => {
+                    error!("failed to merge \"{}\": {}", daily, e);
+                },
+            }
+        }
+    }
+}
+
+/// Parses a produced file
+fn parse_file(filename: &str, gzip: bool) -> std::io::Result<Rinex> {
+    let mut content = Vec::new();
+
+    if gzip {
+        MultiGzDecoder::new(File::open(filename)?).read_to_end(&mut content)?;
+    } else {
+        File::open(filename)?.read_to_end(&mut content)?;
+    }
+
+    let mut reader = BufReader::new(content.as_slice());
+
+    Rinex::parse(&mut reader).map_err(|e| std::io::Error::other(format!("\"{}\": {}", filename, e)))
+}
+
+/// Merges all `fragments` (in order) into the `daily` file
+fn merge_files(daily: &str, fragments: &[String], gzip: bool) -> std::io::Result<()> {
+    let mut merged = Option::<Rinex>::None;
+
+    for fragment in fragments.iter() {
+        let rinex = parse_file(fragment, gzip)?;
+
+        match &mut merged {
+            Some(merged) => {
+                merged
+                    .merge_mut(&rinex)
+                    .map_err(|e| std::io::Error::other(format!("\"{}\": {}", fragment, e)))?;
+            },
+            None => {
+                merged = Some(rinex);
+            },
+        }
+    }
+
+    let Some(merged) = merged else {
+        return Ok(());
+    };
+
+    // fully formatted, before the daily file (which may be a fragment) is overwritten
+    let mut buf = BufWriter::new(Vec::new());
+
+    merged
+        .format(&mut buf)
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+
+    let content = buf.into_inner().map_err(|e| e.into_error())?;
+
+    // the gzip stream is completed when released
+    let mut fd = FileDescriptor::new(gzip, daily);
+    fd.write_all(&content)?;
+    fd.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::Fragments;
+
+    #[test]
+    fn daily_fragments() {
+        let fragments = Fragments::default();
+
+        fragments.push("DAY1", "DAY1-A");
+        fragments.push("DAY1", "DAY1-B");
+        fragments.push("DAY2", "DAY2");
+
+        assert_eq!(
+            fragments.daily_files(),
+            vec![
+                (
+                    "DAY1".to_string(),
+                    vec!["DAY1-A".to_string(), "DAY1-B".to_string()]
+                ),
+                ("DAY2".to_string(), vec!["DAY2".to_string()]),
+            ]
+        );
+    }
+}
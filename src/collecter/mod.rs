@@ -4,14 +4,31 @@ use rinex::{
 };
 
 mod fd;
+mod header;
+mod merge;
+mod summary;
 
+pub mod csv;
 pub mod ephemeris;
+pub mod memory;
 pub mod navigation;
 pub mod observation;
+pub mod qc;
 pub mod rawxm;
+pub mod sbas;
 pub mod settings;
+pub mod spectrum;
+pub mod timepulse;
+pub mod velocity;
+
+pub use fd::Products;
+pub use merge::Fragments;
 
 use rawxm::Rawxm;
+use sbas::SbasMessage;
+use spectrum::RfBlock;
+use timepulse::TimePulse;
+use velocity::Velocity;
 
 pub enum Message {
     /// [Message::Shutdown] catches Ctrl+C interruptions
@@ -23,15 +40,36 @@ pub enum Message {
     /// [Message::EndofEpoch] notification
     EndofEpoch(),
 
-    /// New clock state [s]
-    Clock(f64),
+    /// New clock state [s], at [Epoch]
+    Clock((Epoch, f64)),
 
     /// New [Rawxm] measurements
     Measurement(Rawxm),
 
+    /// External event (TIM-TM2) [Epoch]
+    ExternalEvent(Epoch),
+
+    /// Receiver clock reset [Epoch]
+    ClockReset(Epoch),
+
+    /// New [SbasMessage]
+    Sbas(SbasMessage),
+
+    /// New MON-RF [RfBlock]
+    Spectrum(RfBlock),
+
+    /// New NAV-PVT [Velocity]
+    Velocity(Velocity),
+
+    /// New TIM-TP [TimePulse]
+    TimePulse(TimePulse),
+
     /// Firmware version notification
     FirmwareVersion(String),
 
+    /// Country code, inferred from the position fix
+    Country(String),
+
     /// New [Ephemeris] notification
     Ephemeris((Epoch, SV, Ephemeris)),
 }
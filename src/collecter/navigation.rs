@@ -3,19 +3,24 @@ use std::{
     io::{BufWriter, Write},
 };
 
-use log::{debug, error};
+use log::{debug, error, info};
 
 use rinex::{
     error::FormattingError,
-    navigation::{Ephemeris, NavMessageType},
+    navigation::{Ephemeris, NavFrame, NavFrameType, NavKey, NavMessageType, OrbitItem},
     prelude::{Constellation, Epoch, Header, RinexType, SV, Version},
+    record::Record,
 };
 
 use tokio::{sync::mpsc::Receiver as Rx, sync::watch::Receiver as WatchRx};
 
 use crate::{
     UbloxSettings,
-    collecter::{Message, fd::FileDescriptor, settings::Settings},
+    collecter::{
+        Message,
+        fd::{FileDescriptor, compress_on_close},
+        settings::Settings,
+    },
 };
 
 pub struct Collecter {
@@ -25,9 +30,6 @@ pub struct Collecter {
     /// Latest [Epoch] received from U-Blox
     epoch: Option<Epoch>,
 
-    /// True when Header has been released for this period
-    header_released: bool,
-
     /// Receiver channel
     rx: Rx<Message>,
 
@@ -43,11 +45,41 @@ pub struct Collecter {
     /// Custom header comments
     header_comments: Vec<String>,
 
-    /// Current [FileDescriptor] handle
-    fd: Option<BufWriter<FileDescriptor>>,
+    /// Current [NavFile]s, per header [Constellation].
+    /// A file is present once its header has been released for this period.
+    files: HashMap<Constellation, NavFile>,
 
     /// Last message released, per SV
     latest_release: HashMap<SV, Epoch>,
+
+    /// Issue of data of the last message released, per SV
+    latest_iod: HashMap<SV, IssueOfData>,
+}
+
+/// Navigation file being produced
+#[derive(Default)]
+struct NavFile {
+    /// [FileDescriptor] handle
+    fd: Option<BufWriter<FileDescriptor>>,
+
+    /// In-memory record, in place of [FileDescriptor]
+    record: Option<usize>,
+
+    /// File name, and whether it continues a previous session
+    filename: Option<(String, bool)>,
+}
+
+/// (IODE, IODC) of an [Ephemeris]
+type IssueOfData = (Option<u16>, Option<u16>);
+
+/// Returns the [IssueOfData] of this [Ephemeris]
+fn issue_of_data(ephemeris: &Ephemeris) -> IssueOfData {
+    let value = |key: &str| match ephemeris.orbits.get(key) {
+        Some(OrbitItem::F64(value)) => Some(*value as u16),
+        _ => None,
+    };
+
+    (value("iode"), value("iodc"))
 }
 
 impl Collecter {
@@ -60,23 +92,90 @@ impl Collecter {
     ) -> Self {
         Self {
             rx,
-            settings,
-            fd: None,
+            settings: settings.navigation(),
+            files: Default::default(),
             shutdown,
             ubx_settings: ublox,
-            header_released: false,
             epoch: Default::default(),
             first_epoch: Default::default(),
             latest_release: Default::default(),
+            latest_iod: Default::default(),
             header_comments: Default::default(),
         }
     }
 
-    /// Obtain a new [FileDescriptor]
-    fn fd(&self) -> FileDescriptor {
+    /// Header [Constellation]
+    fn constellation(&self) -> Constellation {
+        if self.ubx_settings.nav_constellations.len() == 1 {
+            self.ubx_settings.nav_constellations[0]
+        } else {
+            Constellation::Mixed
+        }
+    }
+
+    /// Header [Constellation] of the file describing this [SV].
+    /// RINEX V2 files describe a single constellation: each has its own file.
+    fn file_constellation(&self, sv: SV) -> Constellation {
+        if self.settings.major == 2 && self.ubx_settings.nav_constellations.len() > 1 {
+            sv.constellation
+        } else {
+            self.constellation()
+        }
+    }
+
+    /// Obtain a new [FileDescriptor], for this header [Constellation].
+    /// Returns true when the file is being continued (continuous mode),
+    /// in which case it already has a header.
+    fn fd(&self, constellation: Constellation) -> std::io::Result<(FileDescriptor, String, bool)> {
         let epoch = self.epoch.unwrap();
-        let filename = self.settings.filename(true, epoch);
-        FileDescriptor::new(self.settings.gzip, &filename)
+        let filename = self.settings.filename(true, constellation, epoch)?;
+
+        self.settings
+            .declare_fragment(true, constellation, epoch, &filename);
+
+        let continued = self.settings.continues(&filename);
+
+        let fd = if continued {
+            info!("{} - continuing \"{}\"", epoch, filename);
+            FileDescriptor::reopen(&self.settings, &filename)
+        } else {
+            FileDescriptor::open(&self.settings, &filename)
+        };
+
+        Ok((fd, filename, continued))
+    }
+
+    /// Closes all current files. In --gzip-on-close mode,
+    /// the working files are compressed.
+    fn close(&mut self) {
+        for (_, file) in self.files.drain() {
+            if let Some(mut fd) = file.fd {
+                let _ = fd.flush();
+            }
+
+            if let Some((filename, continued)) = file.filename
+                && self.settings.compresses_on_close()
+            {
+                let working = self.settings.working_filename(&filename);
+
+                if let Err(e) = compress_on_close(&working, &filename, continued) {
+                    error!("failed to compress \"{}\": {}", working, e);
+                }
+            }
+        }
+    }
+
+    /// Closes the current files, at UTC midnight in continuous mode.
+    /// Each daily file gathers all ephemeris again.
+    fn rotate(&mut self, epoch: Epoch) {
+        self.close();
+
+        debug!("{} - new day: closing NAV file", epoch);
+
+        self.first_epoch = Some(epoch);
+        self.epoch = Some(epoch);
+        self.latest_release.clear();
+        self.latest_iod.clear();
     }
 
     pub async fn run(&mut self) {
@@ -87,6 +186,12 @@ impl Collecter {
                         self.ubx_settings.firmware = Some(version.to_string());
                     },
 
+                    Message::Country(code) => {
+                        if self.settings.infer_country {
+                            self.settings.country = code;
+                        }
+                    },
+
                     Message::HeaderComment(comment) => {
                         if self.header_comments.len() < 16 {
                             self.header_comments.push(comment);
@@ -99,18 +204,26 @@ impl Collecter {
                             self.epoch = Some(epoch);
                         }
 
-                        if !self.header_released {
-                            match self.release_header() {
+                        if let Some(first_epoch) = self.first_epoch
+                            && !self.files.is_empty()
+                            && self.settings.rotates(first_epoch, epoch)
+                        {
+                            self.rotate(epoch);
+                        }
+
+                        let constellation = self.file_constellation(sv);
+
+                        if !self.files.contains_key(&constellation) {
+                            match self.release_header(constellation) {
                                 Ok(_) => {
-                                    debug!("{} - NAV header released", epoch);
+                                    debug!("{} - {} NAV header released", epoch, constellation);
                                 },
                                 Err(e) => {
+                                    // retried on next ephemeris
                                     error!("{} - failed to redact RINEX header: {}", epoch, e);
-                                    return;
+                                    continue;
                                 },
                             }
-
-                            self.header_released = true;
                         }
 
                         let mut do_release = false;
@@ -125,12 +238,16 @@ impl Collecter {
 
                         if do_release {
                             match self.release_message(epoch, sv, ephemeris) {
-                                Ok(_) => {
+                                Ok(true) => {
                                     self.latest_release.insert(sv, epoch); // update
                                     debug!("{}({}) - published ephemeris message", epoch, sv);
                                 },
+                                Ok(false) => {
+                                    debug!("{}({}) - same ephemeris set: not repeated", epoch, sv);
+                                },
                                 Err(e) => {
-                                    error!("{} - failed to release epoch: {}", epoch, e);
+                                    // this ephemeris is dropped, the collection carries on
+                                    error!("{}({}) - failed to format ephemeris: {}", epoch, sv, e);
                                 },
                             }
                         }
@@ -139,6 +256,7 @@ impl Collecter {
                     },
 
                     Message::Shutdown => {
+                        self.close();
                         return;
                     },
 
@@ -149,7 +267,7 @@ impl Collecter {
         }
     }
 
-    fn build_header(&self) -> Header {
+    fn build_header(&self, constellation: Constellation) -> Header {
         let mut header = Header::default();
 
         // revision
@@ -157,11 +275,7 @@ impl Collecter {
         header.version.major = self.settings.major;
 
         // GNSS
-        if self.ubx_settings.constellations.len() == 1 {
-            header.constellation = Some(self.ubx_settings.constellations[0]);
-        } else {
-            header.constellation = Some(Constellation::Mixed);
-        }
+        header.constellation = Some(constellation);
 
         // real time flow comments
         for comment in self.header_comments.iter() {
@@ -186,27 +300,93 @@ impl Collecter {
         header
     }
 
-    fn release_header(&mut self) -> Result<(), FormattingError> {
-        // obtain a file descriptor
-        let mut fd = BufWriter::new(self.fd());
+    /// Releases the header of the file of this [Constellation]
+    fn release_header(&mut self, constellation: Constellation) -> Result<(), FormattingError> {
+        // in memory: the header is kept as is
+        if let Some(records) = &self.settings.records {
+            let header = self.build_header(constellation);
+            let record = records.open(header, Record::NavRecord(Default::default()));
+
+            self.files.insert(
+                constellation,
+                NavFile {
+                    record: Some(record),
+                    ..Default::default()
+                },
+            );
 
-        let header = self.build_header();
+            return Ok(());
+        }
 
-        header.format(&mut fd)?; // must pass
+        // obtain a file descriptor
+        let (fd, filename, continued) = self.fd(constellation)?;
+        let mut fd = BufWriter::new(fd);
+
+        // header was released by a previous session
+        if !continued {
+            let header = self.build_header(constellation);
+            header.format(&mut fd)?; // must pass
+        }
 
         let _ = fd.flush(); // can fail
-        self.fd = Some(fd);
+
+        self.files.insert(
+            constellation,
+            NavFile {
+                fd: Some(fd),
+                record: None,
+                filename: Some((filename, continued)),
+            },
+        );
 
         Ok(())
     }
 
+    /// Releases this [Ephemeris]. Returns false when it was not released,
+    /// because it matches the last ephemeris set written for this [SV] (--decimate-nav).
+    /// The message is entirely formatted before being written, so a formatting
+    /// error does not leave a partial message in the file.
     fn release_message(
         &mut self,
         epoch: Epoch,
         sv: SV,
         ephemeris: Ephemeris,
-    ) -> Result<(), FormattingError> {
-        let fd = self.fd.as_mut().unwrap();
+    ) -> Result<bool, FormattingError> {
+        let iod = issue_of_data(&ephemeris);
+
+        if self.settings.decimate_nav
+            && iod != (None, None)
+            && self.latest_iod.get(&sv) == Some(&iod)
+        {
+            return Ok(false);
+        }
+
+        let constellation = self.file_constellation(sv);
+
+        let Some(file) = self.files.get_mut(&constellation) else {
+            return Err(std::io::Error::other("no file descriptor").into());
+        };
+
+        if let (Some(records), Some(index)) = (&self.settings.records, file.record) {
+            let key = NavKey {
+                epoch,
+                sv,
+                msgtype: NavMessageType::LNAV,
+                frmtype: NavFrameType::Ephemeris,
+            };
+
+            records.with(index, |rinex| {
+                if let Some(record) = rinex.record.as_mut_nav() {
+                    record.insert(key, NavFrame::EPH(ephemeris));
+                }
+            });
+
+            self.latest_iod.insert(sv, iod);
+            return Ok(true);
+        }
+
+        let mut buf = BufWriter::new(Vec::<u8>::new());
+        let fd = &mut buf;
 
         // write epoch
         let (y, m, d, hh, mm, ss, nanos) = epoch.to_gregorian(epoch.time_scale);
@@ -237,26 +417,19 @@ impl Collecter {
                 )?;
             },
             _ => {
-                if self.ubx_settings.constellations.len() == 1 {
-                    write!(
-                        fd,
-                        "{:02} {:02} {:02} {:02} {:02} {:02} {:2}.{:01}",
-                        sv.prn,
-                        y - 2000,
-                        m,
-                        d,
-                        hh,
-                        mm,
-                        ss,
-                        decis
-                    )?;
-                } else {
-                    write!(
-                        fd,
-                        "{:x} {:04} {:02} {:02} {:02} {:02} {:02}",
-                        sv, y, m, d, hh, mm, ss
-                    )?;
-                }
+                // V2 files describe a single constellation
+                write!(
+                    fd,
+                    "{:02} {:02} {:02} {:02} {:02} {:02} {:2}.{:01}",
+                    sv.prn,
+                    y - 2000,
+                    m,
+                    d,
+                    hh,
+                    mm,
+                    ss,
+                    decis
+                )?;
             },
         }
 
@@ -264,8 +437,114 @@ impl Collecter {
         let version = Version::from_major(self.settings.major);
         ephemeris.format(fd, sv, version, NavMessageType::LNAV)?;
 
+        let message = buf.into_inner().map_err(|e| e.into_error())?;
+
+        let Some(fd) = file.fd.as_mut() else {
+            return Err(std::io::Error::other("no file descriptor").into());
+        };
+
+        fd.write_all(&message)?;
         let _ = fd.flush();
 
-        Ok(())
+        self.latest_iod.insert(sv, iod);
+
+        Ok(true)
+    }
+}
+
+impl Drop for Collecter {
+    /// Safety net when the [Collecter] is dropped without [Message::Shutdown]
+    /// (abnormal exit): the files are flushed.
+    fn drop(&mut self) {
+        for file in self.files.values_mut() {
+            if let Some(fd) = file.fd.as_mut()
+                && let Err(e) = fd.flush()
+            {
+                error!("failed to flush navigation: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Collecter, issue_of_data};
+    use crate::{
+        UbloxSettings,
+        collecter::{memory::Records, settings::Settings},
+    };
+    use rinex::{
+        navigation::{Ephemeris, OrbitItem},
+        prelude::{Constellation, Epoch, SV},
+    };
+    use std::str::FromStr;
+
+    #[test]
+    fn ephemeris_issue_of_data() {
+        let mut ephemeris = Ephemeris::default();
+        assert_eq!(issue_of_data(&ephemeris), (None, None));
+
+        ephemeris
+            .orbits
+            .insert("iode".to_string(), OrbitItem::F64(45.0));
+
+        ephemeris
+            .orbits
+            .insert("iodc".to_string(), OrbitItem::F64(301.0));
+
+        assert_eq!(issue_of_data(&ephemeris), (Some(45), Some(301)));
+    }
+
+    #[test]
+    fn v2_files_per_constellation() {
+        let t0 = Epoch::from_str("2020-01-01T00:00:00 GPST").unwrap();
+
+        for (major, expected) in [
+            (2, vec![Constellation::GPS, Constellation::Glonass]),
+            (3, vec![Constellation::Mixed]),
+        ] {
+            let (_tx, rx) = tokio::sync::mpsc::channel(1);
+            let (_shutdown_tx, shutdown) = tokio::sync::watch::channel(false);
+
+            let records = Records::default();
+
+            let settings = Settings {
+                major,
+                records: Some(records.clone()),
+                ..Default::default()
+            };
+
+            let ublox = UbloxSettings {
+                nav_constellations: vec![Constellation::GPS, Constellation::Glonass],
+                ..Default::default()
+            };
+
+            let mut collecter = Collecter::new(settings, ublox, shutdown, rx);
+            collecter.epoch = Some(t0);
+
+            for sv in ["G01", "R01", "G02"] {
+                let sv = SV::from_str(sv).unwrap();
+                let constellation = collecter.file_constellation(sv);
+
+                if !collecter.files.contains_key(&constellation) {
+                    collecter.release_header(constellation).unwrap();
+                }
+
+                assert!(
+                    collecter
+                        .release_message(t0, sv, Ephemeris::default())
+                        .unwrap()
+                );
+            }
+
+            let rinex = records.rinex();
+
+            let constellations = rinex
+                .iter()
+                .map(|rinex| rinex.header.constellation.unwrap())
+                .collect::<Vec<_>>();
+
+            assert_eq!(constellations, expected, "V{}", major);
+        }
     }
 }
@@ -1,31 +1,58 @@
-use log::{debug, error};
+use log::{debug, error, info, warn};
 
 use std::{
+    collections::{BTreeMap, HashMap},
     io::{BufWriter, Write},
-    str::FromStr,
+    time::Instant,
 };
 
 use rinex::{
     error::FormattingError,
     hardware::{Antenna, Receiver},
     hatanaka::Compressor,
-    observation::{ClockObservation, HeaderFields as ObsHeader, SNR},
+    observation::{ClockObservation, HeaderFields as ObsHeader, LliFlags, SNR},
     prelude::{
-        CRINEX, Constellation, Epoch, Header, Observable, RinexType,
+        CRINEX, Constellation, Epoch, Header, RinexType, SV,
         obs::{EpochFlag, ObsKey, Observations, SignalObservation},
     },
+    record::Record,
 };
 
 use tokio::{sync::mpsc::Receiver as Rx, sync::watch::Receiver as WatchRx};
 
 use crate::{
     UbloxSettings,
-    collecter::{Message, fd::FileDescriptor, settings::Settings},
-    utils::{SignalCarrier, from_constellation},
+    collecter::{
+        Message,
+        fd::{FileDescriptor, compress_on_close},
+        header::{insert_records, record, rewrite_header},
+        rawxm::Rawxm,
+        settings::{FlushPolicy, Settings},
+        summary::Summary,
+    },
+    utils::{ObservableKind, SignalCarrier, sort_observables},
 };
 
 use hifitime::prelude::Duration;
 
+/// Flush period, in [FlushPolicy::Periodic]
+const FLUSH_PERIOD: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Samples closer than this belong to the same epoch. NAV-CLOCK is
+/// timestamped to the millisecond, while RXM-RAWX is not.
+const EPOCH_TOLERANCE_MS: f64 = 1.0;
+
+/// Measurements attached to a single epoch beyond which the receiver
+/// time is considered stuck (firmware issue): the epoch is then released.
+const MAX_EPOCH_MEASUREMENTS: usize = 2048;
+
+/// Maximal number of epochs buffered before the header is redacted,
+/// when some of the declared constellations did not produce data yet
+const HEADER_DEFERRAL_EPOCHS: usize = 10;
+
+/// RXM-RAWX lock time saturation (ms)
+const MAX_LOCK_TIME_MS: u16 = 64_500;
+
 pub struct Collecter {
     /// Latest [Epoch]
     epoch: Option<Epoch>,
@@ -54,11 +81,51 @@ pub struct Collecter {
     /// Current [FileDescriptor] handle
     fd: Option<BufWriter<FileDescriptor>>,
 
+    /// Current in-memory record, in place of [FileDescriptor]
+    record: Option<usize>,
+
     /// List of header comments
     header_comments: Vec<String>,
 
     /// CRINEX compressor
     compressor: Compressor,
+
+    /// Latest flush, in [FlushPolicy::Periodic]
+    last_flush: Instant,
+
+    /// Pending external events
+    events: Vec<Epoch>,
+
+    /// Pending receiver clock reset
+    clock_reset: Option<Epoch>,
+
+    /// Current file name
+    filename: Option<String>,
+
+    /// Current file is continued from a previous session
+    continued: bool,
+
+    /// Number of observations per SV, for each declared observable
+    counts: BTreeMap<SV, Vec<u32>>,
+
+    /// FDMA frequency channel of each GLONASS satellite, as reported by RXM-RAWX
+    glo_channels: BTreeMap<SV, i8>,
+
+    /// Latest observation [Epoch] of each signal, for carrier phase
+    /// continuity. This is preserved across file rotations.
+    locks: HashMap<(SV, u8), Epoch>,
+
+    /// End of run [Summary] (--observables-summary)
+    summary: Option<Summary>,
+
+    /// Epochs buffered until the header is redacted
+    pending: Vec<(ObsKey, Observations, Vec<Epoch>)>,
+
+    /// Number of measurements attached to the current epoch
+    epoch_measurements: usize,
+
+    /// Receiver time is stuck: measurements are dropped until it advances
+    stalled: bool,
 }
 
 impl Collecter {
@@ -72,6 +139,12 @@ impl Collecter {
         let mut compressor = Compressor::default();
         compressor.v3 = settings.major > 2;
 
+        let summary = if settings.observables_summary {
+            Some(Summary::new(ublox.sampling_period))
+        } else {
+            None
+        };
+
         Self {
             rx,
             shutdown,
@@ -79,22 +152,106 @@ impl Collecter {
             compressor,
             ubx_settings: ublox,
             fd: Default::default(),
+            record: Default::default(),
             deploy_epoch: Default::default(),
             epoch: Default::default(),
             header: Default::default(),
             buf: Observations::default(),
             header_comments: Default::default(),
+            last_flush: Instant::now(),
+            events: Default::default(),
+            clock_reset: None,
+            filename: Default::default(),
+            continued: false,
+            counts: Default::default(),
+            glo_channels: Default::default(),
+            locks: Default::default(),
+            summary,
+            pending: Default::default(),
+            epoch_measurements: 0,
+            stalled: false,
+        }
+    }
+
+    /// Obtain a new file descriptor. Returns true when the file is being
+    /// continued (continuous mode), in which case it already has a header.
+    fn fd(&self, t: Epoch, filename: &str) -> (FileDescriptor, bool) {
+        if self.settings.continues(filename) {
+            info!("{} - continuing \"{}\"", t, filename);
+            (FileDescriptor::reopen(&self.settings, filename), true)
+        } else {
+            (FileDescriptor::open(&self.settings, filename), false)
+        }
+    }
+
+    /// Closes the current file. The header is then completed with the
+    /// observation counts and the GLONASS frequency channels, which are only known at this point.
+    /// This does not apply to a continued file, because it only
+    /// gathers part of the observations. In --gzip-on-close mode,
+    /// the working file is compressed last.
+    fn close(&mut self) {
+        let Some(mut fd) = self.fd.take() else {
+            return;
+        };
+
+        let _ = fd.flush();
+
+        // releases the file (and completes the gzip stream)
+        drop(fd);
+
+        let filename = self.filename.take();
+        let counts = std::mem::take(&mut self.counts);
+
+        let Some(filename) = filename else {
+            return;
+        };
+
+        if self.settings.products.is_some() {
+            return;
+        }
+
+        let on_close = self.settings.compresses_on_close();
+
+        let working = if on_close {
+            self.settings.working_filename(&filename)
+        } else {
+            filename.clone()
+        };
+
+        if !self.continued
+            && let Some(header) = &self.header
+        {
+            let mut records = prn_obs_records(header, &counts);
+
+            if self.settings.major > 2 && header.codes.contains_key(&Constellation::Glonass) {
+                records.extend(glonass_slot_records(&self.glo_channels));
+            }
+
+            let gzip = self.settings.gzip && !on_close;
+
+            if let Err(e) = rewrite_header(&working, gzip, &records) {
+                error!("failed to complete \"{}\" header: {}", working, e);
+            }
+        }
+
+        if on_close && let Err(e) = compress_on_close(&working, &filename, self.continued) {
+            error!("failed to compress \"{}\": {}", working, e);
         }
     }
 
-    /// Obtain a new file descriptor
-    fn fd(&self, t: Epoch) -> FileDescriptor {
-        let filename = self.settings.filename(false, t);
-        FileDescriptor::new(self.settings.gzip, &filename)
+    /// Closes the current file, at UTC midnight in continuous mode.
+    /// The next epoch starts a new file.
+    fn rotate(&mut self, epoch: Epoch) {
+        self.close();
+
+        debug!("{} - new day: closing file", epoch);
+
+        self.header = None;
+        self.deploy_epoch = Some(epoch);
     }
 
     pub async fn run(&mut self) {
-        let cfg_precision = Duration::from_seconds(1.0);
+        let cfg_precision = self.ubx_settings.precision;
 
         loop {
             match self.rx.recv().await {
@@ -103,11 +260,24 @@ impl Collecter {
                         self.ubx_settings.firmware = Some(version.to_string());
                     },
 
+                    Message::Country(code) => {
+                        if self.settings.infer_country {
+                            self.settings.country = code;
+                        }
+                    },
+
                     Message::Shutdown => {
                         if self.buf.signals.len() > 0 || self.buf.clock.is_some() {
                             self.release_epoch();
                         }
 
+                        self.release_pending();
+                        self.close();
+
+                        if let Some(summary) = &self.summary {
+                            println!("{} - observables summary\n{}", self.settings.name, summary);
+                        }
+
                         return; // abort
                     },
 
@@ -117,19 +287,48 @@ impl Collecter {
                         }
                     },
 
-                    Message::Clock(clock) => {
+                    Message::Clock((t, clock)) => {
                         debug!(
                             "{} - new clock state: {}",
-                            self.epoch.unwrap_or_default().round(cfg_precision),
+                            t.round(cfg_precision),
                             Duration::from_seconds(clock)
                         );
 
+                        if self.deploy_epoch.is_none() {
+                            self.deploy_epoch = Some(t);
+                        }
+
+                        if new_epoch(self.epoch, t) {
+                            // standalone clock sample: previous epoch is complete
+                            if self.buf.signals.len() > 0 || self.buf.clock.is_some() {
+                                self.release_epoch();
+                            }
+
+                            self.epoch_measurements = 0;
+                            self.stalled = false;
+
+                            self.epoch = Some(t);
+                        } else if self.epoch.is_none() {
+                            self.epoch = Some(t);
+                        }
+
                         let bias = clock * 1.0E-3;
                         let mut clock = ClockObservation::default();
                         clock.set_offset_s(Default::default(), bias);
                         self.buf.clock = Some(clock);
                     },
 
+                    Message::ExternalEvent(event) => {
+                        debug!("{} - external event", event.round(cfg_precision));
+                        self.events.push(event);
+                    },
+
+                    Message::ClockReset(epoch) => {
+                        if self.settings.epoch_flag_on_clk_reset {
+                            self.clock_reset = Some(epoch);
+                        }
+                    },
+
                     Message::Measurement(rawxm) => {
                         debug!(
                             "{} - RXM-RAWX: {}",
@@ -139,23 +338,6 @@ impl Collecter {
 
                         if self.deploy_epoch.is_none() {
                             self.deploy_epoch = Some(rawxm.epoch);
-
-                            match self.release_header() {
-                                Ok(_) => {
-                                    debug!(
-                                        "{} - RINEX header redacted",
-                                        self.epoch.unwrap_or_default().round(cfg_precision)
-                                    );
-                                },
-                                Err(e) => {
-                                    error!(
-                                        "{} - failed to redact RINEX header: {}",
-                                        self.epoch.unwrap_or_default().round(cfg_precision),
-                                        e
-                                    );
-                                    return;
-                                },
-                            }
                         }
 
                         if self.epoch.is_none() {
@@ -164,98 +346,35 @@ impl Collecter {
 
                         let epoch = self.epoch.unwrap();
 
-                        if rawxm.epoch > epoch {
+                        if new_epoch(Some(epoch), rawxm.epoch) {
                             // new epoch
                             debug!("{} - new epoch", rawxm.epoch.round(cfg_precision));
 
                             if self.buf.signals.len() > 0 || self.buf.clock.is_some() {
                                 self.release_epoch();
                             }
-                        }
 
-                        let gnss_id = from_constellation(&rawxm.sv.constellation);
-
-                        let carrier = SignalCarrier::from_ubx(gnss_id, rawxm.freq_id);
-
-                        let v2 = self.settings.major == 2;
-
-                        let pr_observable = carrier.to_pseudo_range_observable(v2);
-                        let cp_observable = carrier.to_phase_range_observable(v2);
-                        let dop_observable = carrier.to_doppler_observable(v2);
-                        let ssi_observable = carrier.to_ssi_observable(v2);
-
-                        match Observable::from_str(&pr_observable) {
-                            Ok(observable) => {
-                                self.buf.signals.push(SignalObservation {
-                                    sv: rawxm.sv,
-                                    lli: None,
-                                    observable,
-                                    value: rawxm.pr,
-                                    snr: Some(SNR::from(rawxm.cno as f64)),
-                                });
-                            },
-                            Err(_) => {
-                                error!(
-                                    "{} - invalid RINEX observable \"{}\"",
-                                    rawxm.epoch, pr_observable
-                                );
-                            },
+                            self.epoch_measurements = 0;
+                            self.stalled = false;
                         }
 
-                        match Observable::from_str(&cp_observable) {
-                            Ok(observable) => {
-                                self.buf.signals.push(SignalObservation {
-                                    sv: rawxm.sv,
-                                    lli: None,
-                                    observable,
-                                    value: rawxm.cp,
-                                    snr: Some(SNR::from(rawxm.cno as f64)),
-                                });
-                            },
-                            Err(_) => {
-                                error!(
-                                    "{} - invalid RINEX observable \"{}\"",
-                                    rawxm.epoch, cp_observable
-                                );
-                            },
+                        if !self.accept_measurement() {
+                            continue;
                         }
 
-                        match Observable::from_str(&dop_observable) {
-                            Ok(observable) => {
-                                self.buf.signals.push(SignalObservation {
-                                    sv: rawxm.sv,
-                                    lli: None,
-                                    observable,
-                                    value: rawxm.dop as f64,
-                                    snr: Some(SNR::from(rawxm.cno as f64)),
-                                });
-                            },
-                            Err(_) => {
-                                error!(
-                                    "{} - invalid RINEX observable \"{}\"",
-                                    rawxm.epoch, dop_observable
-                                );
-                            },
+                        if !self.settings.observed_sv(rawxm.sv) {
+                            self.epoch = Some(rawxm.epoch);
+                            continue;
                         }
 
-                        match Observable::from_str(&ssi_observable) {
-                            Ok(observable) => {
-                                self.buf.signals.push(SignalObservation {
-                                    sv: rawxm.sv,
-                                    lli: None,
-                                    observable,
-                                    snr: None,
-                                    value: rawxm.cno as f64,
-                                });
-                            },
-                            Err(_) => {
-                                error!(
-                                    "{} - invalid RINEX observable \"{}\"",
-                                    rawxm.epoch, ssi_observable
-                                );
-                            },
+                        if let Some(channel) = rawxm.glo_channel {
+                            self.glo_channels.insert(rawxm.sv, channel);
                         }
 
+                        let lli = self.lock_lli(&rawxm);
+                        let signals = rawxm_signals(&self.settings, &rawxm, lli);
+                        push_signals(&mut self.buf.signals, signals);
+
                         self.epoch = Some(rawxm.epoch);
                     },
                     _ => {},
@@ -265,15 +384,86 @@ impl Collecter {
         }
     }
 
+    /// Accounts for a new measurement of the current epoch. Returns false when it must be dropped:
+    /// past [MAX_EPOCH_MEASUREMENTS], the receiver time is stuck and the pending epoch is
+    /// released (once), the following measurements are dropped until the time advances.
+    fn accept_measurement(&mut self) -> bool {
+        if self.stalled {
+            return false;
+        }
+
+        self.epoch_measurements += 1;
+
+        if self.epoch_measurements <= MAX_EPOCH_MEASUREMENTS {
+            return true;
+        }
+
+        warn!(
+            "{} - receiver time is not advancing: releasing pending epoch, dropping measurements until it does",
+            self.epoch.unwrap_or_default()
+        );
+
+        if !self.buf.signals.is_empty() || self.buf.clock.is_some() {
+            self.release_epoch();
+        }
+
+        self.stalled = true;
+        false
+    }
+
+    /// Updates the carrier phase lock state of this signal and returns its LLI.
+    /// The state survives file rotations: a slip spanning the boundary is flagged
+    /// on the first epoch of the new file, while a continuous lock is not.
+    fn lock_lli(&mut self, rawxm: &Rawxm) -> Option<LliFlags> {
+        let lock_time = rawxm.lock_time?;
+        let previous = self.locks.insert((rawxm.sv, rawxm.freq_id), rawxm.epoch);
+        lli_flag(previous, rawxm.epoch, lock_time)
+    }
+
     fn release_header(&mut self) -> Result<(), FormattingError> {
         let deploy_epoch = self.deploy_epoch.unwrap();
 
+        let header = self.build_header();
+
+        // in memory: the header is kept as is
+        if let Some(records) = &self.settings.records {
+            self.record = Some(records.open(header.clone(), Record::ObsRecord(Default::default())));
+            self.header = Some(header.obs.unwrap().clone());
+            return Ok(());
+        }
+
         // obtain new file, release header
-        let mut fd = BufWriter::new(self.fd(deploy_epoch));
+        let constellation = header.constellation.unwrap_or(Constellation::Mixed);
+        let filename = self.settings.filename(false, constellation, deploy_epoch)?;
 
-        let header = self.build_header();
+        let (fd, continued) = self.fd(deploy_epoch, &filename);
+        let mut fd = BufWriter::new(fd);
+
+        self.settings
+            .declare_fragment(false, constellation, deploy_epoch, &filename);
 
-        header.format(&mut fd)?; // must pass
+        self.filename = Some(filename);
+        self.continued = continued;
+
+        // records that are not described by [Header]
+        // aligned epochs report the receiver clock offset as well
+        let rx_clock = self.ubx_settings.rx_clock || self.settings.align_epochs;
+        let mut records = header_records(&self.settings, rx_clock, &header);
+        records.extend(phase_shift_records(&self.settings, &header));
+
+        if continued {
+            // header was released by a previous session
+        } else if records.is_empty() {
+            header.format(&mut fd)?; // must pass
+        } else {
+            let mut buf = BufWriter::new(Vec::new());
+            header.format(&mut buf)?; // must pass
+
+            let content = buf.into_inner().map_err(|e| e.into_error())?;
+            let content = insert_records(&String::from_utf8_lossy(&content), &records);
+
+            fd.write_all(content.as_bytes())?;
+        }
 
         let _ = fd.flush(); // can fail
 
@@ -283,42 +473,225 @@ impl Collecter {
         Ok(())
     }
 
+    /// Returns the [EpochFlag] of this [Epoch]: [EpochFlag::CycleSlip]
+    /// if the receiver clock was reset, [EpochFlag::Ok] otherwise.
+    fn epoch_flag(&mut self, epoch: Epoch) -> EpochFlag {
+        if self.clock_reset.is_some_and(|reset| reset <= epoch) {
+            self.clock_reset = None;
+            EpochFlag::CycleSlip
+        } else {
+            EpochFlag::Ok
+        }
+    }
+
     fn release_epoch(&mut self) {
-        let epoch = self.epoch.unwrap_or_default();
+        let mut epoch = self.epoch.unwrap_or_default();
+
+        if self.settings.align_epochs {
+            let (aligned, offset_s) = align_epoch(epoch, self.ubx_settings.sampling_period);
+
+            let offset_s = offset_s + self.buf.clock.as_ref().map_or(0.0, |clock| clock.offset_s);
+
+            let mut clock = ClockObservation::default();
+            clock.set_offset_s(Default::default(), offset_s);
+            self.buf.clock = Some(clock);
+
+            epoch = aligned;
+        }
+
+        if let Some(deploy_epoch) = self.deploy_epoch
+            && self.header.is_some()
+            && self.settings.rotates(deploy_epoch, epoch)
+        {
+            self.rotate(epoch);
+        }
 
         let key = ObsKey {
             epoch,
-            flag: EpochFlag::Ok, // TODO: manage events correctly
+            flag: self.epoch_flag(epoch), // events are released as dedicated records
         };
 
-        let mut fd = self.fd.as_mut().unwrap();
+        let observations = std::mem::take(&mut self.buf);
+        let events = self.events.drain(..).collect::<Vec<_>>();
 
-        match self.header.as_ref() {
-            Some(header) => {
-                match self
-                    .buf
-                    .format(self.settings.major == 2, &key, header, &mut fd)
-                {
-                    Ok(_) => {
-                        let _ = fd.flush(); // improves interaction
+        if self.header.is_none() {
+            // Header is redacted once the buffered epochs describe all constellations,
+            // so it only describes constellations that actually produced data.
+            self.pending.push((key, observations, events));
+
+            if self.header_ready() {
+                self.release_pending();
+            }
+
+            return;
+        }
+
+        self.format_epoch(key, observations, events);
+    }
+
+    /// True once the header may be redacted: every constellation with declared
+    /// observables produced data, or [HEADER_DEFERRAL_EPOCHS] epochs with signals are pending.
+    /// Clock-only epochs do not count. Never true without declared observables.
+    fn header_ready(&self) -> bool {
+        let mut declared = self.declared_constellations().peekable();
+
+        if declared.peek().is_none() {
+            return false;
+        }
+
+        let signal_epochs = self
+            .pending
+            .iter()
+            .filter(|(_, observations, _)| !observations.signals.is_empty())
+            .count();
+
+        if signal_epochs >= HEADER_DEFERRAL_EPOCHS {
+            return true;
+        }
+
+        declared.all(|constellation| self.pending_data(constellation))
+    }
+
+    /// Constellations with declared observables
+    fn declared_constellations(&self) -> impl Iterator<Item = Constellation> + '_ {
+        self.settings
+            .observables
+            .iter()
+            .filter(|(_, observables)| !observables.is_empty())
+            .map(|(constellation, _)| *constellation)
+    }
+
+    /// True when this [Constellation] produced data in the pending epochs
+    fn pending_data(&self, constellation: Constellation) -> bool {
+        self.pending.iter().any(|(_, observations, _)| {
+            observations
+                .signals
+                .iter()
+                .any(|signal| signal.sv.constellation == constellation)
+        })
+    }
+
+    /// Redacts the header from the pending epochs, then releases them
+    fn release_pending(&mut self) {
+        let Some((key, _, _)) = self.pending.first() else {
+            return;
+        };
+
+        let epoch = key.epoch;
+
+        if self.declared_constellations().next().is_none() {
+            error!(
+                "{} - no observables to declare: dropping {} epoch(s)",
+                epoch,
+                self.pending.len()
+            );
+            self.pending.clear();
+            return;
+        }
+
+        match self.release_header() {
+            Ok(_) => {
+                debug!("{} - RINEX header redacted", epoch);
+            },
+            Err(e) => {
+                error!("{} - failed to redact RINEX header: {}", epoch, e);
+                return;
+            },
+        }
+
+        for (key, observations, events) in std::mem::take(&mut self.pending) {
+            self.format_epoch(key, observations, events);
+        }
+    }
+
+    /// Releases these [Observations] and the external events latched during this epoch
+    fn format_epoch(&mut self, key: ObsKey, mut observations: Observations, events: Vec<Epoch>) {
+        let epoch = key.epoch;
+
+        if let (Some(records), Some(index), Some(header)) =
+            (&self.settings.records, self.record, self.header.as_ref())
+        {
+            retain_declared_signals(header, &mut observations.signals);
+
+            if let Some(summary) = &mut self.summary {
+                summary.add_epoch(epoch, &observations.signals);
+            }
+
+            records.with(index, |rinex| {
+                if let Some(record) = rinex.record.as_mut_obs() {
+                    record.insert(key, observations);
+
+                    // external events latched during this epoch
+                    for event in events {
+                        let key = ObsKey {
+                            epoch: event,
+                            flag: EpochFlag::ExternalEvent,
+                        };
+
+                        record.insert(key, Observations::default());
+                    }
+                }
+            });
+
+            debug!("{} - new epoch released", epoch);
+            return;
+        }
+
+        let (Some(header), Some(mut fd)) = (self.header.as_ref(), self.fd.as_mut()) else {
+            error!(
+                "{} - internal error: failed to release pending epoch: incomplete RINEX header",
+                epoch
+            );
+            return;
+        };
+
+        retain_declared_signals(header, &mut observations.signals);
+        count_observations(header, &observations.signals, &mut self.counts);
+
+        if let Some(summary) = &mut self.summary {
+            summary.add_epoch(epoch, &observations.signals);
+        }
 
-                        self.buf.clock = None;
-                        self.buf.signals.clear();
+        if self.settings.major > 2 {
+            scale_signals(&self.settings, &mut observations.signals);
+        }
+
+        match observations.format(self.settings.major == 2, &key, header, &mut fd) {
+            Ok(_) => {
+                // external events latched during this epoch
+                for event in events {
+                    let key = ObsKey {
+                        epoch: event,
+                        flag: EpochFlag::ExternalEvent,
+                    };
+
+                    if let Err(e) = Observations::default().format(
+                        self.settings.major == 2,
+                        &key,
+                        header,
+                        &mut fd,
+                    ) {
+                        error!("{} - failed to format external event: {}", event, e);
+                    }
+                }
 
-                        debug!("{} - new epoch released", epoch);
+                match self.settings.flush_policy {
+                    FlushPolicy::Epoch => {
+                        let _ = fd.flush(); // improves interaction
                     },
-                    Err(e) => {
-                        error!("{} - failed to format pending epoch: {}", epoch, e);
+                    FlushPolicy::Periodic => {
+                        if self.last_flush.elapsed() >= FLUSH_PERIOD {
+                            let _ = fd.flush();
+                            self.last_flush = Instant::now();
+                        }
                     },
+                    FlushPolicy::Close => {},
                 }
-            },
-            None => {
-                error!(
-                    "{} - internal error: failed to release pending epoch",
-                    epoch
-                );
 
-                error!("{} - internal error: incomplete RINEX header", epoch);
+                debug!("{} - new epoch released", epoch);
+            },
+            Err(e) => {
+                error!("{} - failed to format pending epoch: {}", epoch, e);
             },
         }
     }
@@ -334,10 +707,39 @@ impl Collecter {
         // revision
         header.rinex_type = RinexType::ObservationData;
         header.version.major = self.settings.major;
+        header.sampling_interval = Some(self.settings.sampling);
+
+        // constellations that did not produce data yet (late acquisition) are declared
+        // anyway, otherwise their observations would be dropped for the whole file
+        for constellation in self.declared_constellations() {
+            if !self.pending.is_empty() && !self.pending_data(constellation) {
+                warn!(
+                    "{} - {} did not produce data yet: declared anyway",
+                    self.deploy_epoch.unwrap_or_default(),
+                    constellation
+                );
+            }
+        }
+
+        obs_header.codes = self
+            .settings
+            .observables
+            .iter()
+            .filter(|(_, observables)| !observables.is_empty())
+            .map(|(constellation, observables)| {
+                let mut observables = observables.clone();
+
+                if !self.settings.reference_layout {
+                    sort_observables(&mut observables);
+                }
+
+                (*constellation, observables)
+            })
+            .collect();
 
         // GNSS
-        if self.ubx_settings.constellations.len() == 1 {
-            header.constellation = Some(self.ubx_settings.constellations[0]);
+        if obs_header.codes.len() == 1 {
+            header.constellation = obs_header.codes.keys().next().copied();
         } else {
             header.constellation = Some(Constellation::Mixed);
         }
@@ -346,11 +748,8 @@ impl Collecter {
         if self.settings.crinex {
             let mut crinex = CRINEX::default();
 
-            if self.settings.major == 2 {
-                crinex.version.major = 2;
-            } else {
-                crinex.version.major = 3;
-            }
+            crinex.version.major = self.settings.crinex_major();
+            crinex.version.minor = 0;
 
             obs_header.crinex = Some(crinex);
         }
@@ -401,9 +800,1326 @@ impl Collecter {
 
         header.rcvr_antenna = antenna;
 
-        obs_header.codes = self.settings.observables.clone();
-
         header.obs = Some(obs_header);
         header
     }
 }
+
+impl Drop for Collecter {
+    /// Safety net when the [Collecter] is dropped without [Message::Shutdown]
+    /// (abnormal exit): the gathered epochs are released and the file is flushed.
+    /// It is not completed (header counts, compression on close).
+    fn drop(&mut self) {
+        // unwinding: only flush what was already formatted
+        if !std::thread::panicking() {
+            if self.buf.signals.len() > 0 || self.buf.clock.is_some() {
+                self.release_epoch();
+            }
+
+            self.release_pending();
+        }
+
+        if let Some(fd) = self.fd.as_mut()
+            && let Err(e) = fd.flush()
+        {
+            error!("failed to flush observations: {}", e);
+        }
+    }
+}
+
+/// Header records that [Header] does not describe
+fn header_records(settings: &Settings, rx_clock: bool, header: &Header) -> Vec<String> {
+    let mut records = Vec::new();
+
+    let obs_header = match &header.obs {
+        Some(obs_header) => obs_header,
+        None => return records,
+    };
+
+    if settings.major == 2 {
+        // full cycle ambiguities, on both L1 and L2
+        records.push(record(&format!("{:6}{:6}", 1, 1), "WAVELENGTH FACT L1/2"));
+    }
+
+    if rx_clock {
+        // receiver clock offset is reported (epoch records), not applied to the observations
+        records.push(record(&format!("{:6}", 0), "RCV CLOCK OFFS APPL"));
+    }
+
+    if settings.major > 2 && obs_header.codes.contains_key(&Constellation::Glonass) {
+        // single receiver: no code-phase bias
+        let content = ["C1C", "C1P", "C2C", "C2P"]
+            .iter()
+            .map(|code| format!(" {} {:8.3}", code, 0.0))
+            .collect::<String>();
+
+        records.push(record(&content, "GLONASS COD/PHS/BIS"));
+    }
+
+    if settings.major > 2 {
+        for phase_center in settings.phase_centers.iter() {
+            if obs_header.codes.contains_key(&phase_center.constellation) {
+                let (north, east, up) = phase_center.neu;
+
+                let content = format!(
+                    "{:x} {:<3}{:9.4}{:14.4}{:14.4}",
+                    phase_center.constellation, phase_center.observable, north, east, up
+                );
+
+                records.push(record(&content, "ANTENNA: PHASECENTER"));
+            }
+        }
+    }
+
+    if settings.major > 2 && settings.raw_snr {
+        // SSI observables carry the RXM-RAWX C/N0, in dB-Hz
+        let has_ssi = obs_header
+            .codes
+            .values()
+            .flatten()
+            .any(|observable| observable.to_string().starts_with('S'));
+
+        if has_ssi {
+            records.push(record("DBHZ", "SIGNAL STRENGTH UNIT"));
+        }
+    }
+
+    if settings.major > 2 && !settings.scale_factors.is_empty() {
+        let mut codes = obs_header.codes.iter().collect::<Vec<_>>();
+        codes.sort_by_key(|(constellation, _)| format!("{:x}", constellation));
+
+        for (constellation, observables) in codes {
+            for factor in [10, 100, 1000] {
+                let scaled = observables
+                    .iter()
+                    .filter(|observable| settings.scale_factor(observable) == Some(factor))
+                    .collect::<Vec<_>>();
+
+                // 12 observables per line
+                for chunk in scaled.chunks(12) {
+                    let content = format!(
+                        "{:x} {:4}  {:2}{}",
+                        constellation,
+                        factor,
+                        chunk.len(),
+                        chunk
+                            .iter()
+                            .map(|observable| format!(" {:<3}", observable))
+                            .collect::<String>()
+                    );
+
+                    records.push(record(&content, "SYS / SCALE FACTOR"));
+                }
+            }
+        }
+    }
+
+    records
+}
+
+/// Converts a [Rawxm] measurement to [SignalObservation]s. Units do not depend on the revision:
+/// pseudo range in meters and carrier phase in cycles (as reported by RXM-RAWX), Doppler in Hz.
+/// Only the observable names differ, between V2 and later revisions.
+fn rawxm_signals(
+    settings: &Settings,
+    rawxm: &Rawxm,
+    lli: Option<LliFlags>,
+) -> Vec<SignalObservation> {
+    let mut signals = Vec::with_capacity(4);
+
+    let carrier = SignalCarrier::from_ubx_sv(rawxm.sv, rawxm.freq_id);
+
+    // 1-9 signal strength digit, which accounts for multipath (RXM-MEASX):
+    // each digit spans 6 dB-Hz
+    let snr = Some(SNR::from(rawxm.ssi_code() as f64 * 6.0));
+
+    // same reference as the header, in this revision
+    let values = [
+        (ObservableKind::PseudoRange, rawxm.pr, snr),
+        (ObservableKind::PhaseRange, rawxm.cp, snr),
+        (ObservableKind::Doppler, Some(rawxm.dop as f64), snr),
+        (ObservableKind::SSI, Some(rawxm.cno as f64), None),
+    ];
+
+    for (kind, value, snr) in values {
+        let Some(value) = value else {
+            continue;
+        };
+
+        signals.push(SignalObservation {
+            sv: rawxm.sv,
+            lli: if kind == ObservableKind::PhaseRange {
+                lli
+            } else {
+                None
+            },
+            observable: settings.observable(carrier, kind),
+            value,
+            snr,
+        });
+    }
+
+    signals
+}
+
+/// Buffers the [SignalObservation]s of one measurement. Each measurement
+/// only produces the observables of its own signal (E1-C or E1-B, for example),
+/// but RINEX V2 gathers several signals under a single observable (C1):
+/// the first signal is then retained.
+fn push_signals(buffer: &mut Vec<SignalObservation>, signals: Vec<SignalObservation>) {
+    for signal in signals {
+        if !buffer
+            .iter()
+            .any(|buffered| buffered.sv == signal.sv && buffered.observable == signal.observable)
+        {
+            buffer.push(signal);
+        }
+    }
+}
+
+/// Applies the scaling factors to the [SignalObservation]s about to be written
+fn scale_signals(settings: &Settings, signals: &mut [SignalObservation]) {
+    for signal in signals.iter_mut() {
+        if let Some(factor) = settings.scale_factor(&signal.observable) {
+            signal.value *= factor as f64;
+        }
+    }
+}
+
+/// Counts the observations of each SV, per declared observable
+fn count_observations(
+    header: &ObsHeader,
+    signals: &[SignalObservation],
+    counts: &mut BTreeMap<SV, Vec<u32>>,
+) {
+    for signal in signals.iter() {
+        let Some(observables) = header.codes.get(&signal.sv.constellation) else {
+            continue;
+        };
+
+        if let Some(index) = observables
+            .iter()
+            .position(|observable| *observable == signal.observable)
+        {
+            let sv_counts = counts
+                .entry(signal.sv)
+                .or_insert_with(|| vec![0; observables.len()]);
+
+            sv_counts[index] += 1;
+        }
+    }
+}
+
+/// "# OF SATELLITES" and "PRN / # OF OBS" header records
+fn prn_obs_records(header: &ObsHeader, counts: &BTreeMap<SV, Vec<u32>>) -> Vec<String> {
+    let mut records = vec![record(&format!("{:6}", counts.len()), "# OF SATELLITES")];
+
+    for (sv, sv_counts) in counts.iter() {
+        if !header.codes.contains_key(&sv.constellation) {
+            continue;
+        }
+
+        // 9 observables per line
+        for (nth, chunk) in sv_counts.chunks(9).enumerate() {
+            let prn = if nth == 0 {
+                format!("{:x}", sv)
+            } else {
+                String::new()
+            };
+
+            let content = format!(
+                "   {:<3}{}",
+                prn,
+                chunk
+                    .iter()
+                    .map(|count| format!("{:6}", count))
+                    .collect::<String>()
+            );
+
+            records.push(record(&content, "PRN / # OF OBS"));
+        }
+    }
+
+    records
+}
+
+/// "SYS / PHASE SHIFT" records (RINEX3+), required for each phase observable.
+/// RXM-RAWX phases are not aligned to a reference signal: the shift is null.
+fn phase_shift_records(settings: &Settings, header: &Header) -> Vec<String> {
+    let mut records = Vec::new();
+
+    let obs_header = match &header.obs {
+        Some(obs_header) if settings.major > 2 => obs_header,
+        _ => return records,
+    };
+
+    let mut codes = obs_header.codes.iter().collect::<Vec<_>>();
+    codes.sort_by_key(|(constellation, _)| format!("{:x}", constellation));
+
+    for (constellation, observables) in codes {
+        for observable in observables.iter() {
+            if observable.to_string().starts_with('L') {
+                let content = format!("{:x} {:<3} {:8.5}", constellation, observable, 0.0);
+                records.push(record(&content, "SYS / PHASE SHIFT"));
+            }
+        }
+    }
+
+    records
+}
+
+/// "GLONASS SLOT / FRQ #" records: FDMA frequency channel of each GLONASS satellite,
+/// which defines its carrier frequencies (phase and Doppler are expressed in cycles and Hz).
+fn glonass_slot_records(channels: &BTreeMap<SV, i8>) -> Vec<String> {
+    let slots = channels
+        .iter()
+        .filter(|(sv, _)| sv.constellation == Constellation::Glonass)
+        .map(|(sv, channel)| format!("{:x} {:2} ", sv, channel))
+        .collect::<Vec<_>>();
+
+    // 8 satellites per line
+    slots
+        .chunks(8)
+        .enumerate()
+        .map(|(nth, chunk)| {
+            let count = if nth == 0 {
+                format!("{:3} ", slots.len())
+            } else {
+                "    ".to_string()
+            };
+
+            record(
+                &format!("{}{}", count, chunk.concat()),
+                "GLONASS SLOT / FRQ #",
+            )
+        })
+        .collect()
+}
+
+/// Drops [SignalObservation]s the header does not declare, they would misalign the record.
+/// Declared observables that a SV did not report are blanked by the formatter.
+/// Retained signals are sorted in order of declaration, so an observable that is missing
+/// in this epoch (for example, a Doppler dropout) does not shift the following columns.
+fn retain_declared_signals(header: &ObsHeader, signals: &mut Vec<SignalObservation>) {
+    signals.retain(|signal| {
+        let declared = header
+            .codes
+            .get(&signal.sv.constellation)
+            .is_some_and(|observables| observables.contains(&signal.observable));
+
+        if !declared {
+            debug!(
+                "{}({}) - undeclared observable",
+                signal.sv, signal.observable
+            );
+        }
+
+        declared
+    });
+
+    signals.sort_by_key(|signal| {
+        let index = header
+            .codes
+            .get(&signal.sv.constellation)
+            .and_then(|observables| observables.iter().position(|obs| *obs == signal.observable));
+
+        (signal.sv, index)
+    });
+}
+
+/// Loss of lock, when the lock time (ms) is shorter than the time elapsed
+/// since the `previous` observation of this signal. Unknown for a first observation.
+/// A saturated lock time is a continuous lock, even when sampling slower than its range.
+fn lli_flag(previous: Option<Epoch>, epoch: Epoch, lock_time: u16) -> Option<LliFlags> {
+    let previous = previous?;
+
+    if lock_time >= MAX_LOCK_TIME_MS {
+        return None;
+    }
+
+    let elapsed_ms = (epoch - previous).to_seconds() * 1.0E3;
+
+    if (lock_time as f64) < elapsed_ms {
+        Some(LliFlags::LOCK_LOSS)
+    } else {
+        None
+    }
+}
+
+/// Snaps this receiver [Epoch] to the nominal `sampling` grid.
+/// Returns the aligned [Epoch] and the offset (s) of the receiver [Epoch] to it.
+fn align_epoch(epoch: Epoch, sampling: Duration) -> (Epoch, f64) {
+    let aligned = epoch.round(sampling);
+    (aligned, (epoch - aligned).to_seconds())
+}
+
+/// Returns true when `t` starts a new epoch, past the `current` one.
+/// A sample within [EPOCH_TOLERANCE_MS] is attached to the current epoch.
+fn new_epoch(current: Option<Epoch>, t: Epoch) -> bool {
+    match current {
+        Some(current) => t - current > Duration::from_milliseconds(EPOCH_TOLERANCE_MS),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        Collecter, HEADER_DEFERRAL_EPOCHS, MAX_EPOCH_MEASUREMENTS, align_epoch, count_observations,
+        glonass_slot_records, header_records, lli_flag, new_epoch, phase_shift_records,
+        prn_obs_records, push_signals, rawxm_signals, retain_declared_signals, scale_signals,
+    };
+    use crate::{
+        UbloxSettings,
+        collecter::{
+            Products,
+            memory::Records,
+            rawxm::Rawxm,
+            settings::{FlushPolicy, PhaseCenter, Settings},
+        },
+    };
+
+    use rinex::{
+        observation::{ClockObservation, HeaderFields as ObsHeader, LliFlags, SNR},
+        prelude::{
+            Constellation, Epoch, Header, Observable, SV,
+            obs::{EpochFlag, ObsKey, Observations, SignalObservation},
+        },
+    };
+
+    use hifitime::prelude::Duration;
+
+    use std::{
+        collections::{BTreeMap, HashMap},
+        io::BufWriter,
+        str::FromStr,
+    };
+
+    /// [Collecter] deployed at 2020-01-01T00:00:00 GPST, that is not fed by any channel
+    fn test_collecter(settings: Settings) -> Collecter {
+        let (_tx, rx) = tokio::sync::mpsc::channel(1);
+        let (_shutdown_tx, shutdown) = tokio::sync::watch::channel(false);
+
+        let mut collecter = Collecter::new(settings, UbloxSettings::default(), shutdown, rx);
+        collecter.deploy_epoch = Some(Epoch::from_str("2020-01-01T00:00:00 GPST").unwrap());
+        collecter
+    }
+
+    fn gps_l1_header() -> ObsHeader {
+        let mut header = ObsHeader::default();
+
+        header.codes.insert(
+            Constellation::GPS,
+            ["C1C", "L1C", "D1C", "S1C"]
+                .iter()
+                .map(|code| Observable::from_str(code).unwrap())
+                .collect(),
+        );
+
+        header
+    }
+
+    fn signal(sv: &str, code: &str, value: f64) -> SignalObservation {
+        SignalObservation {
+            sv: SV::from_str(sv).unwrap(),
+            lli: None,
+            snr: None,
+            observable: Observable::from_str(code).unwrap(),
+            value,
+        }
+    }
+
+    #[test]
+    fn prn_obs_counts() {
+        let header = gps_l1_header();
+        let mut counts = Default::default();
+
+        for _ in 0..2 {
+            let signals = vec![
+                signal("G02", "C1C", 20_000_000.0),
+                signal("G02", "S1C", 45.0),
+                signal("G01", "C1C", 20_000_000.0),
+                // not declared: not counted
+                signal("G01", "C2L", 20_000_001.0),
+            ];
+
+            count_observations(&header, &signals, &mut counts);
+        }
+
+        let records = prn_obs_records(&header, &counts);
+
+        assert_eq!(records.len(), 3);
+
+        assert_eq!(
+            records[0],
+            "     2                                                      # OF SATELLITES"
+        );
+
+        assert_eq!(
+            records[1],
+            "   G01     2     0     0     0                              PRN / # OF OBS"
+        );
+
+        assert_eq!(
+            records[2],
+            "   G02     2     0     0     2                              PRN / # OF OBS"
+        );
+    }
+
+    #[test]
+    fn missing_observables_are_blanked() {
+        let header = gps_l1_header();
+
+        let mut observations = Observations::default();
+
+        observations.signals = vec![
+            signal("G01", "C1C", 20_000_000.0),
+            signal("G02", "C1C", 20_000_000.0),
+            signal("G02", "L1C", 105_000_000.0),
+            signal("G02", "D1C", -1000.0),
+            signal("G02", "S1C", 45.0),
+            // not declared: must not shift the G02 columns
+            signal("G02", "C2L", 20_000_001.0),
+        ];
+
+        retain_declared_signals(&header, &mut observations.signals);
+        assert_eq!(observations.signals.len(), 5);
+
+        let key = ObsKey {
+            epoch: Epoch::from_str("2025-01-01T00:00:00 GPST").unwrap(),
+            flag: EpochFlag::Ok,
+        };
+
+        let mut buf = BufWriter::new(Vec::new());
+
+        observations.format(false, &key, &header, &mut buf).unwrap();
+
+        let content = String::from_utf8(buf.into_inner().unwrap()).unwrap();
+
+        let g01 = content
+            .lines()
+            .find(|line| line.starts_with("G01"))
+            .unwrap();
+        let g02 = content
+            .lines()
+            .find(|line| line.starts_with("G02"))
+            .unwrap();
+
+        // C1C is aligned for both SV
+        assert_eq!(g01[3..19], g02[3..19]);
+
+        // G01 other declared observables are blank fields
+        assert!(g01.get(19..).unwrap_or_default().trim().is_empty());
+
+        // G02 has exactly the 4 declared fields
+        assert_eq!(g02[3..].split_whitespace().count(), 4);
+    }
+
+    #[test]
+    fn v2_wavelength_fact() {
+        let header = Header {
+            obs: Some(gps_l1_header()),
+            ..Default::default()
+        };
+
+        let settings = Settings {
+            major: 2,
+            ..Default::default()
+        };
+
+        let records = header_records(&settings, false, &header);
+
+        assert_eq!(
+            records,
+            vec![
+                "     1     1                                                WAVELENGTH FACT L1/2"
+                    .to_string()
+            ]
+        );
+
+        let settings = Settings {
+            major: 3,
+            ..Default::default()
+        };
+
+        let records = header_records(&settings, false, &header);
+        assert!(records.is_empty());
+    }
+
+    #[test]
+    fn rcv_clock_offs_appl() {
+        let header = Header {
+            obs: Some(gps_l1_header()),
+            ..Default::default()
+        };
+
+        for major in [2, 3] {
+            let settings = Settings {
+                major,
+                ..Default::default()
+            };
+
+            let records = header_records(&settings, true, &header);
+
+            assert!(records.contains(
+                &"     0                                                      RCV CLOCK OFFS APPL"
+                    .to_string()
+            ));
+
+            let records = header_records(&settings, false, &header);
+
+            assert!(
+                !records
+                    .iter()
+                    .any(|record| record.ends_with("RCV CLOCK OFFS APPL"))
+            );
+        }
+    }
+
+    #[test]
+    fn phase_shift() {
+        let header = Header {
+            obs: Some(gps_l1_header()),
+            ..Default::default()
+        };
+
+        let settings = Settings {
+            major: 3,
+            ..Default::default()
+        };
+
+        let records = phase_shift_records(&settings, &header);
+
+        assert_eq!(
+            records,
+            vec![
+                "G L1C  0.00000                                              SYS / PHASE SHIFT"
+                    .to_string()
+            ]
+        );
+
+        // not described in V2
+        let settings = Settings {
+            major: 2,
+            ..Default::default()
+        };
+
+        assert!(phase_shift_records(&settings, &header).is_empty());
+    }
+
+    #[test]
+    fn glonass_slots() {
+        let channels = (1..=10)
+            .map(|prn| (SV::new(Constellation::Glonass, prn), prn as i8 - 5))
+            .collect::<BTreeMap<_, _>>();
+
+        let records = glonass_slot_records(&channels);
+
+        assert_eq!(
+            records,
+            vec![
+                " 10 R01 -4 R02 -3 R03 -2 R04 -1 R05  0 R06  1 R07  2 R08  3 GLONASS SLOT / FRQ #",
+                "    R09  4 R10  5                                           GLONASS SLOT / FRQ #",
+            ]
+        );
+
+        assert!(glonass_slot_records(&BTreeMap::new()).is_empty());
+    }
+
+    #[test]
+    fn glonass_code_phase_bias() {
+        let mut obs_header = gps_l1_header();
+
+        obs_header.codes.insert(
+            Constellation::Glonass,
+            vec![Observable::from_str("C1C").unwrap()],
+        );
+
+        let header = Header {
+            obs: Some(obs_header),
+            ..Default::default()
+        };
+
+        let settings = Settings::default();
+
+        let records = header_records(&settings, false, &header);
+
+        assert_eq!(
+            records,
+            vec![
+                " C1C    0.000 C1P    0.000 C2C    0.000 C2P    0.000        GLONASS COD/PHS/BIS"
+                    .to_string()
+            ]
+        );
+
+        // not described when Glonass is not observed
+        let header = Header {
+            obs: Some(gps_l1_header()),
+            ..Default::default()
+        };
+
+        assert!(header_records(&settings, false, &header).is_empty());
+    }
+
+    #[test]
+    fn scale_factor() {
+        let mut header = Header::default();
+        header.obs = Some(gps_l1_header());
+
+        let settings = Settings {
+            major: 3,
+            scale_factors: vec![(Observable::from_str("L1C").unwrap(), 1000)],
+            ..Default::default()
+        };
+
+        let records = header_records(&settings, false, &header);
+
+        assert_eq!(
+            records,
+            vec![
+                "G 1000   1 L1C                                              SYS / SCALE FACTOR"
+                    .to_string()
+            ]
+        );
+
+        let mut signals = vec![signal("G01", "L1C", 1.234), signal("G01", "C1C", 1.234)];
+        scale_signals(&settings, &mut signals);
+
+        assert_eq!(signals[0].value, 1234.0);
+        assert_eq!(signals[1].value, 1.234);
+    }
+
+    #[test]
+    fn antenna_phase_center() {
+        let mut header = Header::default();
+        header.obs = Some(gps_l1_header());
+
+        let mut settings = Settings {
+            major: 3,
+            phase_centers: vec![
+                PhaseCenter {
+                    constellation: Constellation::GPS,
+                    observable: Observable::from_str("L1C").unwrap(),
+                    neu: (0.0012, -0.0005, 0.0871),
+                },
+                // not observed
+                PhaseCenter {
+                    constellation: Constellation::Galileo,
+                    observable: Observable::from_str("L1C").unwrap(),
+                    neu: (0.0, 0.0, 0.0911),
+                },
+            ],
+            ..Default::default()
+        };
+
+        let records = header_records(&settings, false, &header);
+
+        assert_eq!(
+            records,
+            vec![
+                "G L1C   0.0012       -0.0005        0.0871                  ANTENNA: PHASECENTER"
+                    .to_string()
+            ]
+        );
+
+        // not described in V2
+        settings.major = 2;
+
+        let records = header_records(&settings, false, &header);
+        assert!(
+            records
+                .iter()
+                .all(|record| !record.ends_with("ANTENNA: PHASECENTER"))
+        );
+    }
+
+    #[test]
+    fn late_constellation() {
+        let gps_gal = || {
+            let mut observables = HashMap::new();
+            observables.insert(
+                Constellation::GPS,
+                vec![Observable::from_str("C1C").unwrap()],
+            );
+            observables.insert(
+                Constellation::Galileo,
+                vec![Observable::from_str("C1C").unwrap()],
+            );
+            observables
+        };
+
+        let t0 = Epoch::from_str("2020-01-01T00:00:00 GPST").unwrap();
+        let dt = Duration::from_seconds(30.0);
+
+        for galileo_epoch in [1, HEADER_DEFERRAL_EPOCHS + 1] {
+            let records = Records::default();
+
+            let settings = Settings {
+                observables: gps_gal(),
+                records: Some(records.clone()),
+                ..Default::default()
+            };
+
+            let mut collecter = test_collecter(settings);
+
+            for i in 0..=galileo_epoch {
+                collecter.epoch = Some(t0 + dt * i as f64);
+                collecter
+                    .buf
+                    .signals
+                    .push(signal("G01", "C1C", 20_000_000.0));
+
+                if i == galileo_epoch {
+                    collecter
+                        .buf
+                        .signals
+                        .push(signal("E05", "C1C", 23_000_000.0));
+                }
+
+                collecter.release_epoch();
+
+                if i < galileo_epoch.min(HEADER_DEFERRAL_EPOCHS - 1) {
+                    // header is deferred: Galileo did not produce data yet
+                    assert!(collecter.header.is_none(), "epoch #{}", i);
+                }
+            }
+
+            let header = collecter.header.as_ref().unwrap();
+
+            let rinex = records.rinex();
+            let record = rinex[0].record.as_obs().unwrap();
+
+            // all epochs were released
+            assert_eq!(record.len(), galileo_epoch + 1);
+
+            // late constellation is described, even past the deferral
+            assert_eq!(header.codes.len(), 2);
+            assert!(header.codes.contains_key(&Constellation::Galileo));
+
+            let (_, last) = record.iter().last().unwrap();
+            assert_eq!(last.signals.len(), 2);
+        }
+    }
+
+    #[test]
+    fn clock_only_header_deferral() {
+        let mut observables = HashMap::new();
+        observables.insert(
+            Constellation::GPS,
+            vec![Observable::from_str("C1C").unwrap()],
+        );
+
+        let records = Records::default();
+
+        let settings = Settings {
+            observables,
+            records: Some(records.clone()),
+            ..Default::default()
+        };
+
+        let t0 = Epoch::from_str("2020-01-01T00:00:00 GPST").unwrap();
+        let dt = Duration::from_seconds(30.0);
+
+        let mut collecter = test_collecter(settings);
+
+        // clock-only epochs do not count towards the deferral
+        for i in 0..2 * HEADER_DEFERRAL_EPOCHS {
+            collecter.epoch = Some(t0 + dt * i as f64);
+            collecter.buf.clock = Some(ClockObservation::default());
+            collecter.release_epoch();
+        }
+
+        assert!(collecter.header.is_none());
+
+        // first epoch with signals
+        collecter.epoch = Some(t0 + dt * (2 * HEADER_DEFERRAL_EPOCHS) as f64);
+        collecter
+            .buf
+            .signals
+            .push(signal("G01", "C1C", 20_000_000.0));
+        collecter.release_epoch();
+
+        let header = collecter.header.as_ref().unwrap();
+        assert!(header.codes.contains_key(&Constellation::GPS));
+    }
+
+    #[test]
+    fn no_observables_header() {
+        let mut collecter = test_collecter(Settings {
+            records: Some(Records::default()),
+            ..Default::default()
+        });
+
+        let t0 = Epoch::from_str("2020-01-01T00:00:00 GPST").unwrap();
+
+        collecter.epoch = Some(t0);
+        collecter.buf.clock = Some(ClockObservation::default());
+        collecter.release_epoch();
+        collecter.release_pending();
+
+        // never redacted without observables
+        assert!(collecter.header.is_none());
+        assert!(collecter.pending.is_empty());
+    }
+
+    #[test]
+    fn early_drop() {
+        let products = Products::default();
+
+        let mut observables = HashMap::new();
+        observables.insert(
+            Constellation::GPS,
+            vec![Observable::from_str("C1C").unwrap()],
+        );
+
+        let settings = Settings {
+            observables,
+            flush_policy: FlushPolicy::Close,
+            products: Some(products.clone()),
+            ..Default::default()
+        };
+
+        let t0 = Epoch::from_str("2020-01-01T00:00:00 GPST").unwrap();
+        let dt = Duration::from_seconds(30.0);
+
+        let mut collecter = test_collecter(settings);
+
+        for i in 0..3 {
+            collecter.epoch = Some(t0 + dt * i as f64);
+            collecter
+                .buf
+                .signals
+                .push(signal("G01", "C1C", 20_000_000.0));
+
+            // last epoch is still being gathered
+            if i < 2 {
+                collecter.release_epoch();
+            }
+        }
+
+        // no shutdown
+        drop(collecter);
+
+        let files = products.files();
+        assert_eq!(files.len(), 1);
+
+        let content = String::from_utf8(files[0].1.clone()).unwrap();
+
+        let epochs = content
+            .lines()
+            .filter(|line| line.starts_with("> "))
+            .count();
+
+        assert_eq!(epochs, 3, "{}", content);
+    }
+
+    #[test]
+    fn stuck_receiver_time() {
+        let records = Records::default();
+
+        let settings = Settings {
+            records: Some(records.clone()),
+            ..Default::default()
+        };
+
+        let t0 = Epoch::from_str("2020-01-01T00:00:00 GPST").unwrap();
+
+        let mut collecter = test_collecter(settings);
+        collecter.epoch = Some(t0);
+
+        for _ in 0..MAX_EPOCH_MEASUREMENTS {
+            assert!(collecter.accept_measurement());
+            collecter
+                .buf
+                .signals
+                .push(signal("G01", "C1C", 20_000_000.0));
+        }
+
+        // stuck: the pending epoch is released
+        assert!(!collecter.accept_measurement());
+        assert!(collecter.buf.signals.is_empty());
+        assert_eq!(records.rinex()[0].record.as_obs().unwrap().len(), 1);
+
+        // then dropped until the time advances
+        assert!(!collecter.accept_measurement());
+        assert!(collecter.buf.signals.is_empty());
+    }
+
+    #[test]
+    fn clock_reset_epoch_flag() {
+        let settings = Settings {
+            epoch_flag_on_clk_reset: true,
+            ..Default::default()
+        };
+
+        let mut collecter = test_collecter(settings);
+
+        let t0 = Epoch::from_str("2020-01-01T00:00:00 GPST").unwrap();
+        let t1 = Epoch::from_str("2020-01-01T00:00:30 GPST").unwrap();
+
+        assert_eq!(collecter.epoch_flag(t0), EpochFlag::Ok);
+
+        // reset latched at t1, while t0 is being released
+        collecter.clock_reset = Some(t1);
+
+        assert_eq!(collecter.epoch_flag(t0), EpochFlag::Ok);
+        assert_eq!(collecter.epoch_flag(t1), EpochFlag::CycleSlip);
+        assert_eq!(collecter.epoch_flag(t1), EpochFlag::Ok);
+    }
+
+    #[test]
+    fn aligned_epochs() {
+        let sampling = Duration::from_seconds(30.0);
+
+        let t = Epoch::from_str("2020-01-01T00:00:29.999999 GPST").unwrap();
+        let (aligned, offset_s) = align_epoch(t, sampling);
+
+        assert_eq!(
+            aligned,
+            Epoch::from_str("2020-01-01T00:00:30 GPST").unwrap()
+        );
+        assert!((offset_s + 1.0E-6).abs() < 1.0E-9);
+
+        let t = Epoch::from_str("2020-01-01T00:01:00.000002 GPST").unwrap();
+        let (aligned, offset_s) = align_epoch(t, sampling);
+
+        assert_eq!(
+            aligned,
+            Epoch::from_str("2020-01-01T00:01:00 GPST").unwrap()
+        );
+        assert!((offset_s - 2.0E-6).abs() < 1.0E-9);
+    }
+
+    #[test]
+    fn standalone_clock_epoch() {
+        let t0 = Epoch::from_str("2020-01-01T00:00:00 GPST").unwrap();
+
+        // first sample
+        assert!(!new_epoch(None, t0));
+
+        // NAV-CLOCK of the current RXM-RAWX epoch
+        let t_clk = t0 + Duration::from_microseconds(400.0);
+        assert!(!new_epoch(Some(t0), t_clk));
+        assert!(!new_epoch(Some(t_clk), t0));
+
+        // NAV-CLOCK without coincident RXM-RAWX: new epoch
+        let t1 = t0 + Duration::from_seconds(1.0);
+        assert!(new_epoch(Some(t0), t1));
+
+        // following RXM-RAWX, slightly past the clock-only epoch
+        assert!(!new_epoch(Some(t1), t1 + Duration::from_microseconds(10.0)));
+        assert!(new_epoch(Some(t1), t1 + Duration::from_seconds(1.0)));
+    }
+
+    #[test]
+    fn crinex_vers_type() {
+        for (major, crinex) in [(2, "1.0"), (3, "3.0")] {
+            let settings = Settings {
+                major,
+                crinex: true,
+                ..Default::default()
+            };
+
+            let collecter = test_collecter(settings);
+
+            let header = collecter.build_header();
+
+            let mut buf = BufWriter::new(Vec::new());
+            header.format(&mut buf).unwrap();
+
+            let content = String::from_utf8(buf.into_inner().unwrap()).unwrap();
+
+            let line = content
+                .lines()
+                .find(|line| line.ends_with("CRINEX VERS   / TYPE"))
+                .unwrap();
+
+            // crx2rnx identifies the revision from the first characters
+            assert!(line.starts_with(crinex), "{}", line);
+            assert!(line.contains("COMPACT RINEX FORMAT"));
+        }
+    }
+
+    #[test]
+    fn lli_slow_sampling() {
+        let t0 = Epoch::from_str("2020-01-01T00:00:00 GPST").unwrap();
+        let t1 = t0 + Duration::from_seconds(120.0);
+
+        // first observation
+        assert_eq!(lli_flag(None, t0, 64500), None);
+
+        // saturated: continuous lock over the 120 s interval
+        assert_eq!(lli_flag(Some(t0), t1, 64500), None);
+
+        // lock acquired within the interval
+        assert_eq!(lli_flag(Some(t0), t1, 64499), Some(LliFlags::LOCK_LOSS));
+        assert_eq!(lli_flag(Some(t0), t1, 30000), Some(LliFlags::LOCK_LOSS));
+    }
+
+    #[test]
+    fn lock_state_across_rotation() {
+        let mut collecter = test_collecter(Settings::default());
+
+        let rawxm = |t: &str, sv: &str, lock_time: u16| Rawxm {
+            epoch: Epoch::from_str(t).unwrap(),
+            sv: SV::from_str(sv).unwrap(),
+            freq_id: 0,
+            pr: Some(22_000_000.0),
+            cp: Some(115_600_000.0),
+            dop: -1234.5,
+            cno: 45,
+            lock_time: Some(lock_time),
+            glo_channel: None,
+            multipath: None,
+        };
+
+        // first observations: unknown
+        for sv in ["G01", "G02"] {
+            let lli = collecter.lock_lli(&rawxm("2020-01-01T00:59:30 GPST", sv, 64500));
+            assert_eq!(lli, None);
+        }
+
+        // last epoch of the file: G02 slipped
+        let lli = collecter.lock_lli(&rawxm("2020-01-01T00:59:45 GPST", "G02", 2000));
+        assert_eq!(lli, Some(LliFlags::LOCK_LOSS));
+
+        collecter.rotate(Epoch::from_str("2020-01-01T01:00:00 GPST").unwrap());
+
+        // G01 continuous lock: not flagged
+        let lli = collecter.lock_lli(&rawxm("2020-01-01T01:00:00 GPST", "G01", 64500));
+        assert_eq!(lli, None);
+
+        // G02 slip is not repeated on the new file
+        let lli = collecter.lock_lli(&rawxm("2020-01-01T01:00:00 GPST", "G02", 17000));
+        assert_eq!(lli, None);
+
+        // G01 slip spanning the boundary (lock acquired after the previous observation)
+        let lli = collecter.lock_lli(&rawxm("2020-01-01T01:00:15 GPST", "G01", 10000));
+        assert_eq!(lli, Some(LliFlags::LOCK_LOSS));
+
+        let signals = rawxm_signals(
+            &Settings::default(),
+            &rawxm("2020-01-01T01:00:15 GPST", "G01", 10000),
+            lli,
+        );
+
+        for signal in signals.iter() {
+            if signal.observable.to_string() == "L1C" {
+                assert_eq!(signal.lli, Some(LliFlags::LOCK_LOSS));
+            } else {
+                assert_eq!(signal.lli, None);
+            }
+        }
+    }
+
+    #[test]
+    fn signal_strength() {
+        let mut rawxm = Rawxm {
+            epoch: Epoch::from_str("2025-01-01T00:00:00 GPST").unwrap(),
+            sv: SV::from_str("G01").unwrap(),
+            freq_id: 0,
+            pr: Some(22_000_000.0),
+            cp: None,
+            dop: -1234.5,
+            cno: 45,
+            lock_time: None,
+            glo_channel: None,
+            multipath: None,
+        };
+
+        let settings = Settings::default();
+
+        let signals = rawxm_signals(&settings, &rawxm, None);
+
+        // C/N0 in dB-Hz, signal strength digit in the SSI field
+        assert_eq!(signals[0].snr, Some(SNR::from(45.0)));
+        assert_eq!(signals[2].observable.to_string(), "S1C");
+        assert_eq!(signals[2].value, 45.0);
+
+        // high multipath: degraded digit, same C/N0
+        rawxm.multipath = Some(3);
+
+        let signals = rawxm_signals(&settings, &rawxm, None);
+        assert_eq!(signals[0].snr, Some(SNR::from(24.0)));
+        assert_eq!(signals[2].value, 45.0);
+    }
+
+    #[test]
+    fn galileo_e1b_signals() {
+        let rawxm = |freq_id: u8| Rawxm {
+            epoch: Epoch::from_str("2025-01-01T00:00:00 GPST").unwrap(),
+            sv: SV::from_str("E05").unwrap(),
+            freq_id,
+            pr: Some(23_000_000.0),
+            cp: Some(120_000_000.0),
+            dop: 512.0,
+            cno: 40,
+            lock_time: None,
+            glo_channel: None,
+            multipath: None,
+        };
+
+        let codes = |signals: &[SignalObservation]| {
+            signals
+                .iter()
+                .map(|sig| sig.observable.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let settings = Settings::default();
+
+        // E1-B (data) measurement: only the E1-B observables
+        let e1b = rawxm_signals(&settings, &rawxm(1), None);
+        assert_eq!(codes(&e1b), vec!["C1B", "L1B", "D1B", "S1B"]);
+
+        // E1-C (pilot) measurement: only the E1-C observables
+        let e1c = rawxm_signals(&settings, &rawxm(0), None);
+        assert_eq!(codes(&e1c), vec!["C1C", "L1C", "D1C", "S1C"]);
+
+        let mut buffer = Vec::new();
+        push_signals(&mut buffer, e1c);
+        push_signals(&mut buffer, e1b);
+        assert_eq!(buffer.len(), 8);
+
+        // V2: both signals are C1, the first one is retained
+        let settings = Settings {
+            major: 2,
+            ..Default::default()
+        };
+
+        let mut buffer = Vec::new();
+        push_signals(&mut buffer, rawxm_signals(&settings, &rawxm(0), None));
+        push_signals(&mut buffer, rawxm_signals(&settings, &rawxm(1), None));
+
+        assert_eq!(codes(&buffer), vec!["C1", "L1", "D1", "S1"]);
+        assert_eq!(buffer[0].value, 23_000_000.0);
+    }
+
+    #[test]
+    fn v2_v3_units() {
+        let rawxm = Rawxm {
+            epoch: Epoch::from_str("2025-01-01T00:00:00 GPST").unwrap(),
+            sv: SV::from_str("G01").unwrap(),
+            freq_id: 0,
+            pr: Some(22_000_000.123),
+            cp: Some(115_600_000.456),
+            dop: -1234.5,
+            cno: 45,
+            lock_time: Some(64500),
+            glo_channel: None,
+            multipath: None,
+        };
+
+        let key = ObsKey {
+            epoch: rawxm.epoch,
+            flag: EpochFlag::Ok,
+        };
+
+        let mut formatted = Vec::new();
+
+        for (major, codes) in [
+            (2, ["C1", "L1", "D1", "S1"]),
+            (3, ["C1C", "L1C", "D1C", "S1C"]),
+        ] {
+            let settings = Settings {
+                major,
+                ..Default::default()
+            };
+
+            let signals = rawxm_signals(&settings, &rawxm, None);
+
+            // same values (meters, cycles, Hz) whatever the revision
+            let values = signals.iter().map(|sig| sig.value).collect::<Vec<_>>();
+            assert_eq!(values, vec![22_000_000.123, 115_600_000.456, -1234.5, 45.0]);
+
+            let mut header = ObsHeader::default();
+
+            header.codes.insert(
+                Constellation::GPS,
+                codes
+                    .iter()
+                    .map(|code| Observable::from_str(code).unwrap())
+                    .collect(),
+            );
+
+            let observations = Observations {
+                clock: None,
+                signals,
+            };
+
+            let mut buf = BufWriter::new(Vec::new());
+
+            observations
+                .format(major == 2, &key, &header, &mut buf)
+                .unwrap();
+
+            formatted.push(String::from_utf8(buf.into_inner().unwrap()).unwrap());
+        }
+
+        // V2: observations follow the epoch line, V3: observations follow the SV
+        let v2 = formatted[0].lines().nth(1).unwrap();
+        let v3 = formatted[1]
+            .lines()
+            .find(|line| line.starts_with("G01"))
+            .unwrap();
+
+        for i in 0..4 {
+            assert_eq!(
+                v2[i * 16..i * 16 + 14].trim(),
+                v3[3 + i * 16..3 + i * 16 + 14].trim(),
+                "observable #{} differs between V2 and V3",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn doppler_dropout_is_blanked() {
+        let header = gps_l1_header();
+
+        let mut observations = Observations::default();
+
+        observations.signals = vec![
+            // G01: Doppler dropout
+            signal("G01", "S1C", 42.0),
+            signal("G01", "C1C", 20_000_000.0),
+            signal("G01", "L1C", 105_000_000.0),
+            signal("G02", "C1C", 21_000_000.0),
+            signal("G02", "L1C", 110_000_000.0),
+            signal("G02", "D1C", -1000.0),
+            signal("G02", "S1C", 45.0),
+        ];
+
+        retain_declared_signals(&header, &mut observations.signals);
+
+        let key = ObsKey {
+            epoch: Epoch::from_str("2025-01-01T00:00:00 GPST").unwrap(),
+            flag: EpochFlag::Ok,
+        };
+
+        let mut buf = BufWriter::new(Vec::new());
+
+        observations.format(false, &key, &header, &mut buf).unwrap();
+
+        let content = String::from_utf8(buf.into_inner().unwrap()).unwrap();
+
+        let g01 = content
+            .lines()
+            .find(|line| line.starts_with("G01"))
+            .unwrap();
+
+        // C1C, L1C, (blank D1C), S1C
+        let fields = (0..4)
+            .map(|i| {
+                g01.get(3 + i * 16..3 + i * 16 + 14)
+                    .unwrap_or_default()
+                    .trim()
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(fields[0], "20000000.000");
+        assert_eq!(fields[1], "105000000.000");
+        assert_eq!(fields[2], "");
+        assert_eq!(fields[3], "42.000");
+    }
+}
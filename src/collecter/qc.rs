@@ -0,0 +1,293 @@
+//! Quality control report (--qc), computed from the measurements stream:
+//! observation completeness, mean C/N0 and multipath (code minus carrier), per satellite.
+
+use log::error;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use rinex::prelude::{Epoch, SV};
+
+use tokio::sync::mpsc::Receiver as Rx;
+
+use crate::{
+    collecter::{Message, rawxm::Rawxm},
+    utils::SignalCarrier,
+};
+
+/// Speed of light (m/s)
+const SPEED_OF_LIGHT_M_S: f64 = 299_792_458.0;
+
+/// Code minus carrier variations larger than this (m)
+/// are cycle slips, rather than multipath
+const MAX_CMC_VARIATION_M: f64 = 10.0;
+
+/// Statistics of a single satellite
+#[derive(Debug, Default, Clone)]
+struct Statistics {
+    /// Number of epochs with a valid pseudo range
+    epochs: usize,
+
+    /// Latest [Epoch] with a valid pseudo range
+    latest: Option<Epoch>,
+
+    /// Sum of the C/N0 measurements (dB-Hz)
+    cno_sum: f64,
+
+    /// Number of C/N0 measurements
+    cno_count: usize,
+
+    /// Sum of the squared code minus carrier variations (m²)
+    cmc_sum_sq: f64,
+
+    /// Number of code minus carrier variations
+    cmc_count: usize,
+}
+
+impl Statistics {
+    /// Mean C/N0 (dB-Hz)
+    fn mean_cno(&self) -> f64 {
+        if self.cno_count > 0 {
+            self.cno_sum / self.cno_count as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// Multipath (m): RMS of the code minus carrier variations,
+    /// halved in variance because consecutive samples are differenced.
+    fn multipath(&self) -> Option<f64> {
+        if self.cmc_count > 0 {
+            Some((self.cmc_sum_sq / (2.0 * self.cmc_count as f64)).sqrt())
+        } else {
+            None
+        }
+    }
+}
+
+/// Quality control [Report]
+#[derive(Debug, Default, Clone)]
+pub struct Report {
+    /// Number of measurement epochs
+    epochs: usize,
+
+    /// First measurement [Epoch]
+    first: Option<Epoch>,
+
+    /// Latest measurement [Epoch]
+    latest: Option<Epoch>,
+
+    /// [Statistics] per satellite
+    satellites: BTreeMap<SV, Statistics>,
+
+    /// Latest code minus carrier (m), per signal
+    cmc: HashMap<(SV, u8), f64>,
+}
+
+impl Report {
+    /// Accounts for a new measurement
+    pub fn add(&mut self, rawxm: &Rawxm) {
+        if self.latest != Some(rawxm.epoch) {
+            if self.first.is_none() {
+                self.first = Some(rawxm.epoch);
+            }
+
+            self.latest = Some(rawxm.epoch);
+            self.epochs += 1;
+        }
+
+        let stats = self.satellites.entry(rawxm.sv).or_default();
+
+        stats.cno_sum += rawxm.cno as f64;
+        stats.cno_count += 1;
+
+        if rawxm.pr.is_some() && stats.latest != Some(rawxm.epoch) {
+            stats.latest = Some(rawxm.epoch);
+            stats.epochs += 1;
+        }
+
+        let key = (rawxm.sv, rawxm.freq_id);
+
+        match (rawxm.pr, rawxm.cp) {
+            (Some(pr), Some(cp)) => {
+                let carrier = SignalCarrier::from_ubx_sv(rawxm.sv, rawxm.freq_id);
+                let wavelength = SPEED_OF_LIGHT_M_S / carrier.frequency(rawxm.glo_channel);
+                let cmc = pr - cp * wavelength;
+
+                if let Some(previous) = self.cmc.insert(key, cmc) {
+                    let variation = cmc - previous;
+
+                    if variation.abs() < MAX_CMC_VARIATION_M {
+                        stats.cmc_sum_sq += variation.powi(2);
+                        stats.cmc_count += 1;
+                    }
+                }
+            },
+            _ => {
+                // continuity is lost
+                self.cmc.remove(&key);
+            },
+        }
+    }
+
+    /// Formats this [Report]: one line per satellite
+    pub fn format<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        writeln!(w, "# ubx2rinex QC report")?;
+        writeln!(w, "# epochs: {}", self.epochs)?;
+
+        if let (Some(first), Some(latest)) = (self.first, self.latest) {
+            writeln!(w, "# time span: {} - {}", first, latest)?;
+        }
+
+        writeln!(
+            w,
+            "{:3} {:>7} {:>16} {:>16} {:>8}",
+            "SV", "epochs", "completeness(%)", "mean_cno(dB-Hz)", "mp(m)"
+        )?;
+
+        for (sv, stats) in self.satellites.iter() {
+            let completeness = if self.epochs > 0 {
+                stats.epochs as f64 * 100.0 / self.epochs as f64
+            } else {
+                0.0
+            };
+
+            let multipath = match stats.multipath() {
+                Some(multipath) => format!("{:.3}", multipath),
+                None => "-".to_string(),
+            };
+
+            writeln!(
+                w,
+                "{:x} {:7} {:16.1} {:16.1} {:>8}",
+                sv,
+                stats.epochs,
+                completeness,
+                stats.mean_cno(),
+                multipath
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// [Collecter] of the quality control [Report], released at the end of the run
+pub struct Collecter {
+    /// [Message]ing handle
+    rx: Rx<Message>,
+
+    /// Output file
+    fd: BufWriter<File>,
+
+    /// [Report] being gathered
+    report: Report,
+}
+
+impl Collecter {
+    /// Builds new [Collecter]
+    pub fn new(path: &str, rx: Rx<Message>) -> Self {
+        let fd =
+            File::create(path).unwrap_or_else(|e| panic!("Failed to open \"{}\": {}", path, e));
+
+        Self {
+            rx,
+            fd: BufWriter::new(fd),
+            report: Default::default(),
+        }
+    }
+
+    pub async fn run(&mut self) {
+        loop {
+            match self.rx.recv().await {
+                Some(Message::Measurement(rawxm)) => {
+                    self.report.add(&rawxm);
+                },
+                Some(Message::Shutdown) | None => {
+                    if let Err(e) = self.report.format(&mut self.fd) {
+                        error!("failed to release QC report: {}", e);
+                    }
+
+                    let _ = self.fd.flush();
+                    return;
+                },
+                _ => {},
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Report, SPEED_OF_LIGHT_M_S};
+    use crate::{collecter::rawxm::Rawxm, utils::SignalCarrier};
+    use rinex::prelude::{Duration, Epoch, SV};
+    use std::str::FromStr;
+
+    fn rawxm(epoch: Epoch, sv: &str, pr: f64, cmc: f64, cno: u8) -> Rawxm {
+        let wavelength = SPEED_OF_LIGHT_M_S / SignalCarrier::GPS_L1_CA.frequency(None);
+
+        Rawxm {
+            epoch,
+            sv: SV::from_str(sv).unwrap(),
+            freq_id: 0,
+            pr: Some(pr),
+            cp: Some((pr - cmc) / wavelength),
+            dop: 0.0,
+            cno,
+            lock_time: Some(64500),
+            glo_channel: None,
+            multipath: None,
+        }
+    }
+
+    #[test]
+    fn qc_report() {
+        let t0 = Epoch::from_str("2025-01-01T00:00:00 GPST").unwrap();
+        let dt = Duration::from_seconds(30.0);
+
+        let mut report = Report::default();
+
+        report.add(&rawxm(t0, "G01", 20_000_000.0, 5.0, 40));
+        report.add(&rawxm(t0, "G02", 21_000_000.0, 1.0, 30));
+        report.add(&rawxm(t0 + dt, "G01", 20_000_100.0, 5.2, 42));
+        report.add(&rawxm(t0 + dt * 2, "G01", 20_000_200.0, 5.0, 44));
+
+        assert_eq!(report.epochs, 3);
+
+        let g01 = &report.satellites[&SV::from_str("G01").unwrap()];
+        assert_eq!(g01.epochs, 3);
+        assert_eq!(g01.mean_cno(), 42.0);
+
+        let multipath = g01.multipath().unwrap();
+        assert!(
+            (multipath - 0.02_f64.sqrt()).abs() < 1.0E-6,
+            "{}",
+            multipath
+        );
+
+        let g02 = &report.satellites[&SV::from_str("G02").unwrap()];
+        assert_eq!(g02.epochs, 1);
+        assert!(g02.multipath().is_none());
+
+        let mut content = Vec::new();
+        report.format(&mut content).unwrap();
+
+        let content = String::from_utf8(content).unwrap();
+
+        assert!(content.contains("# epochs: 3"), "{}", content);
+        assert!(
+            content.contains("G01       3            100.0             42.0    0.141"),
+            "{}",
+            content
+        );
+        assert!(
+            content.contains("G02       1             33.3             30.0        -"),
+            "{}",
+            content
+        );
+    }
+}
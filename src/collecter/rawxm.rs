@@ -1,5 +1,7 @@
 use rinex::prelude::{Epoch, SV};
 
+use crate::utils::ssi_code;
+
 #[derive(Debug, Clone, Copy)]
 pub struct Rawxm {
     /// [Epoch] of observation
@@ -11,17 +13,40 @@ pub struct Rawxm {
     /// freq_id
     pub freq_id: u8,
 
-    /// PR measurement
-    pub pr: f64,
+    /// PR measurement, when available
+    pub pr: Option<f64>,
 
-    /// CP measurement
-    pub cp: f64,
+    /// CP measurement, when available
+    pub cp: Option<f64>,
 
     /// DOP measurement
     pub dop: f32,
 
     /// CNO
     pub cno: u8,
+
+    /// Carrier phase lock time (ms), saturating at 64500 ms (RXM-RAWX only)
+    pub lock_time: Option<u16>,
+
+    /// GLONASS FDMA frequency channel (-7..=6), from the RXM-RAWX frequency slot
+    pub glo_channel: Option<i8>,
+
+    /// Multipath indicator (RXM-MEASX only):
+    /// 0: not measured, 1: low, 2: medium, 3: high
+    pub multipath: Option<u8>,
+}
+
+impl Rawxm {
+    /// RINEX signal strength code. A medium or high multipath indicator
+    /// keeps it below the "good S/N" threshold (5).
+    pub fn ssi_code(&self) -> u8 {
+        let code = ssi_code(self.cno as f64);
+
+        match self.multipath {
+            Some(2..) => code.min(4),
+            _ => code,
+        }
+    }
 }
 
 impl std::fmt::Display for Rawxm {
@@ -29,7 +54,13 @@ impl std::fmt::Display for Rawxm {
         write!(
             f,
             "{}({}) freq_id={} pr={:.7E} cp={:.7E} dop={:.7E} cno={}",
-            self.epoch, self.sv, self.freq_id, self.pr, self.cp, self.dop, self.cno,
+            self.epoch,
+            self.sv,
+            self.freq_id,
+            self.pr.unwrap_or(f64::NAN),
+            self.cp.unwrap_or(f64::NAN),
+            self.dop,
+            self.cno,
         )
     }
 }
@@ -0,0 +1,85 @@
+use rinex::prelude::{Epoch, SV};
+
+use tokio::sync::mpsc::Receiver as Rx;
+
+use crate::collecter::{Message, csv::Collecter};
+
+/// SBAS L1 message, as received in RXM-SFRBX
+#[derive(Debug, Clone)]
+pub struct SbasMessage {
+    /// [Epoch] of reception
+    pub epoch: Epoch,
+
+    /// [SV]
+    pub sv: SV,
+
+    /// 250 bit message, MSB first, as 8 words
+    pub words: Vec<u32>,
+}
+
+impl SbasMessage {
+    /// Message Type (MT) number: 6 bits following the 8 bit preamble
+    pub fn message_type(&self) -> u8 {
+        self.words
+            .first()
+            .map(|word| ((word >> 18) & 0x3f) as u8)
+            .unwrap_or_default()
+    }
+
+    /// Complete message, as hexadecimal string
+    pub fn payload(&self) -> String {
+        self.words
+            .iter()
+            .map(|word| format!("{:08X}", word))
+            .collect()
+    }
+
+    /// Formats this [SbasMessage] as a CSV line
+    pub fn csv(&self) -> String {
+        format!(
+            "{},{},{},{}",
+            self.epoch,
+            self.sv,
+            self.message_type(),
+            self.payload()
+        )
+    }
+}
+
+/// Builds the [Collecter] of the SBAS message stream, as CSV
+pub fn collecter(path: &str, rx: Rx<Message>) -> Collecter {
+    Collecter::new(
+        path,
+        "epoch,sv,mt,payload",
+        "SBAS message",
+        rx,
+        |message| match message {
+            Message::Sbas(message) => Some(message.csv()),
+            _ => None,
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::SbasMessage;
+    use rinex::prelude::{Epoch, SV};
+    use std::str::FromStr;
+
+    #[test]
+    fn sbas_message_type() {
+        // preamble 0x53, MT9 (GEO navigation message)
+        let mut words = vec![(0x53 << 24) | (9 << 18) | 0x1234];
+        words.extend_from_slice(&[0; 7]);
+
+        let message = SbasMessage {
+            epoch: Epoch::from_str("2025-01-01T00:00:00 GPST").unwrap(),
+            sv: SV::from_str("S23").unwrap(),
+            words,
+        };
+
+        assert_eq!(message.message_type(), 9);
+        assert_eq!(message.payload().len(), 64);
+        assert!(message.payload().starts_with("53241234"));
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{collections::HashMap, path::Path, str::FromStr};
 
 use hifitime::{
     efmt::Format,
@@ -6,8 +6,13 @@ use hifitime::{
 };
 
 use rinex::{
-    prelude::{Constellation, Observable},
-    production::{FFU, PPU},
+    prelude::{Constellation, Observable, SV},
+    production::FFU,
+};
+
+use crate::{
+    collecter::{Fragments, Products, memory::Records},
+    utils::{ObservableKind, SignalCarrier},
 };
 
 #[derive(Debug, Default, Copy, Clone, PartialEq)]
@@ -22,32 +27,94 @@ pub enum HealthMask {
     UnhealthyOnly,
 }
 
+/// Antenna phase center offset of one observable, with respect
+/// to the antenna reference point (ANTENNA: PHASECENTER)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhaseCenter {
+    /// [Constellation] of this observable
+    pub constellation: Constellation,
+
+    /// [Observable] this offset applies to
+    pub observable: Observable,
+
+    /// North, East and Up offsets (m)
+    pub neu: (f64, f64, f64),
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum FlushPolicy {
+    /// Flush after each epoch, for live tailing
+    #[default]
+    Epoch,
+
+    /// Flush periodically
+    Periodic,
+
+    /// Flush when the file is closed, for file conversion throughput
+    Close,
+}
+
+impl FromStr for FlushPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "epoch" => Ok(Self::Epoch),
+            "periodic" => Ok(Self::Periodic),
+            "close" => Ok(Self::Close),
+            _ => Err(format!("invalid flush policy \"{}\"", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Settings {
     /// Release Major version
     pub major: u8,
 
-    /// GZIP compression
+    /// GZIP compression (Observation files)
     pub gzip: bool,
 
+    /// GZIP compression of the Navigation files
+    pub gzip_nav: bool,
+
+    /// GZIP compression is applied once each file is closed:
+    /// the current file is written plain meanwhile, so it can be monitored.
+    pub gzip_on_close: bool,
+
     /// CRINEX compression
     pub crinex: bool,
 
+    /// CRINEX revision (major), rather than the one matching the RINEX revision
+    pub crinex_version: Option<u8>,
+
     /// Custom name
     pub name: String,
 
     /// Custom country code
     pub country: String,
 
+    /// Country code is inferred from the first position fix
+    /// (when it can be), rather than using [Settings::country]
+    pub infer_country: bool,
+
     /// Snapshot period
     pub period: Duration,
 
+    /// Observation sampling interval, as applied by the receiver
+    /// (header INTERVAL and file name sampling field)
+    pub sampling: Duration,
+
     /// Prefers short RINEX file name
     pub short_filename: bool,
 
     /// Custom file prefix
     pub prefix: Option<String>,
 
+    /// Custom file name template, rather than the standard file names.
+    /// Supported tokens: {name}, {yyyy}, {doy}, {hh}, {sys} and {type}.
+    pub filename_template: Option<String>,
+
     /// Custom publishing agency
     pub agency: Option<String>,
 
@@ -63,24 +130,288 @@ pub struct Settings {
     /// Observables per system
     pub observables: HashMap<Constellation, Vec<Observable>>,
 
+    /// [Settings::observables] come from a reference header:
+    /// their order is preserved
+    pub reference_layout: bool,
+
     /// Custom navigation message period
     pub nav_period: Duration,
 
+    /// Do not release an ephemeris that matches (IODE/IODC) the last one written for this SV
+    pub decimate_nav: bool,
+
     /// Satellite Health mask
     pub health_mask: HealthMask,
+
+    /// Declare the unit (dB-Hz) of the SSI observables (RINEX3+)
+    pub raw_snr: bool,
+
+    /// Observations limited to these satellites (when not empty)
+    pub only_sv: Vec<SV>,
+
+    /// Satellites excluded from the observations
+    pub exclude_sv: Vec<SV>,
+
+    /// Observation output [FlushPolicy]
+    pub flush_policy: FlushPolicy,
+
+    /// Epochs where the receiver clock was reset are flagged
+    /// (cycle slip), rather than reported as regular epochs
+    pub epoch_flag_on_clk_reset: bool,
+
+    /// Epochs are snapped to the nominal sampling grid, the residual
+    /// receiver clock offset being reported in the clock field
+    pub align_epochs: bool,
+
+    /// Report a summary of the collected observations at the end of the run
+    pub observables_summary: bool,
+
+    /// Antenna [PhaseCenter]s (RINEX3+)
+    pub phase_centers: Vec<PhaseCenter>,
+
+    /// Observable code overrides (RINEX3+), as the signal code
+    /// (band and attribute, for example "1I") of each [SignalCarrier]
+    pub observable_map: Vec<(SignalCarrier, String)>,
+
+    /// Scaling factor per [Observable] (RINEX3+), applied on write
+    pub scale_factors: Vec<(Observable, u16)>,
+
+    /// Continuous station: a new file at each UTC midnight,
+    /// which is appended to (rather than truncated) when it already exists
+    pub continuous: bool,
+
+    /// Gather [Products] in memory, rather than writing files
+    pub products: Option<Products>,
+
+    /// Files produced during this session, merged into daily files
+    /// once the collection is over (--merge)
+    pub fragments: Option<Fragments>,
+
+    /// Gather [Records] in memory, rather than formatting files
+    pub records: Option<Records>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            major: 3,
+            gzip: false,
+            gzip_nav: false,
+            gzip_on_close: false,
+            crinex: false,
+            crinex_version: None,
+            prefix: None,
+            filename_template: None,
+            agency: None,
+            operator: None,
+            header_comment: None,
+            short_filename: true,
+            timescale: TimeScale::GPST,
+            name: "UBXR".to_string(),
+            country: "FRA".to_string(),
+            infer_country: true,
+            period: Duration::from_hours(1.0),
+            sampling: Duration::from_seconds(30.0),
+            nav_period: Duration::from_hours(2.0),
+            decimate_nav: false,
+            observables: Default::default(),
+            reference_layout: false,
+            health_mask: Default::default(),
+            raw_snr: false,
+            only_sv: Default::default(),
+            exclude_sv: Default::default(),
+            flush_policy: Default::default(),
+            epoch_flag_on_clk_reset: false,
+            align_epochs: false,
+            observables_summary: false,
+            phase_centers: Default::default(),
+            observable_map: Default::default(),
+            scale_factors: Default::default(),
+            continuous: false,
+            products: None,
+            fragments: None,
+            records: None,
+        }
+    }
 }
 
 impl Settings {
-    pub fn filename(&self, is_nav: bool, t: Epoch) -> String {
-        let mut filepath = if let Some(prefix) = &self.prefix {
-            format!("{}/", prefix)
-        } else {
-            "".to_string()
+    /// True if this [SV] should be described in the observations
+    pub fn observed_sv(&self, sv: SV) -> bool {
+        if !self.only_sv.is_empty() && !self.only_sv.contains(&sv) {
+            return false;
+        }
+
+        !self.exclude_sv.contains(&sv)
+    }
+
+    /// Returns the scaling factor of this [Observable], if any
+    pub fn scale_factor(&self, observable: &Observable) -> Option<u16> {
+        self.scale_factors
+            .iter()
+            .find(|(scaled, _)| scaled == observable)
+            .map(|(_, factor)| *factor)
+    }
+
+    /// Returns the [Observable] of this [SignalCarrier], following
+    /// the observable code overrides (RINEX3+), if any
+    pub fn observable(&self, carrier: SignalCarrier, kind: ObservableKind) -> Observable {
+        let observable = carrier.to_observable(kind, self.major);
+
+        if self.major < 3 {
+            return observable;
+        }
+
+        let Some((_, code)) = self
+            .observable_map
+            .iter()
+            .find(|(remapped, _)| *remapped == carrier)
+        else {
+            return observable;
         };
 
-        let filename = if self.short_filename {
+        // observable kind (C, L, D or S), followed by the signal code
+        let code = format!("{}{}", &observable.to_string()[..1], code);
+
+        Observable::from_str(&code).unwrap_or(observable)
+    }
+
+    /// Applies the observable code overrides to the declared observables,
+    /// so the header describes what [Self::observable] produces
+    pub fn remap_observables(&mut self) {
+        if self.major < 3 {
+            return;
+        }
+
+        let kinds = [
+            ObservableKind::PseudoRange,
+            ObservableKind::PhaseRange,
+            ObservableKind::Doppler,
+            ObservableKind::SSI,
+        ];
+
+        let carriers = self
+            .observable_map
+            .iter()
+            .map(|(carrier, _)| *carrier)
+            .collect::<Vec<_>>();
+
+        for carrier in carriers {
+            let Some(observables) = self.observables.get(&carrier.constellation()) else {
+                continue;
+            };
+
+            let mut observables = observables.clone();
+
+            for kind in kinds {
+                let default = carrier.to_observable(kind, self.major);
+                let remapped = self.observable(carrier, kind);
+
+                if remapped == default {
+                    continue;
+                }
+
+                if let Some(index) = observables.iter().position(|obs| *obs == default) {
+                    if observables.contains(&remapped) {
+                        observables.remove(index);
+                    } else {
+                        observables[index] = remapped;
+                    }
+                }
+            }
+
+            self.observables
+                .insert(carrier.constellation(), observables);
+        }
+    }
+
+    /// CRINEX revision (major): 1 for RINEX V2 and 3 for RINEX V3 and V4,
+    /// unless overridden (--crinex-version)
+    pub fn crinex_major(&self) -> u8 {
+        self.crinex_version
+            .unwrap_or(if self.major == 2 { 1 } else { 3 })
+    }
+
+    /// True when a new file should be started at this [Epoch],
+    /// in continuous mode: the file started at `file_epoch` ends at UTC midnight.
+    pub fn rotates(&self, file_epoch: Epoch, t: Epoch) -> bool {
+        if !self.continuous {
+            return false;
+        }
+
+        let (y0, m0, d0, _, _, _, _) = file_epoch.to_gregorian_utc();
+        let (y, m, d, _, _, _, _) = t.to_gregorian_utc();
+
+        (y, m, d) != (y0, m0, d0)
+    }
+
+    /// True when this file should be appended to, rather than created:
+    /// in continuous mode, when restarting during the day.
+    /// In --gzip-on-close mode, the plain working file left by an interrupted
+    /// session is continued as well.
+    pub fn continues(&self, filename: &str) -> bool {
+        if !self.continuous || self.products.is_some() {
+            return false;
+        }
+
+        Path::new(filename).exists()
+            || (self.compresses_on_close() && Path::new(&self.working_filename(filename)).exists())
+    }
+
+    /// [Settings] of the Navigation files, which follow their own compression (--gzip-nav)
+    pub fn navigation(&self) -> Self {
+        Self {
+            gzip: self.gzip_nav,
+            ..self.clone()
+        }
+    }
+
+    /// True when files are written plain, then compressed once closed (--gzip-on-close)
+    pub fn compresses_on_close(&self) -> bool {
+        self.gzip && self.gzip_on_close && self.products.is_none()
+    }
+
+    /// Returns the plain working file of this (gzip) file, in --gzip-on-close mode
+    pub fn working_filename(&self, filename: &str) -> String {
+        filename.strip_suffix(".gz").unwrap_or(filename).to_string()
+    }
+
+    /// Declares this new file as a fragment of its daily file, in --merge mode
+    pub fn declare_fragment(
+        &self,
+        is_nav: bool,
+        constellation: Constellation,
+        t: Epoch,
+        filename: &str,
+    ) {
+        if let (Some(fragments), None) = (&self.fragments, &self.products) {
+            let daily = Self {
+                period: Duration::from_days(1.0),
+                ..self.clone()
+            };
+
+            // the daily file name is valid whenever this one is
+            if let Ok(daily) = daily.filename(is_nav, constellation, t) {
+                fragments.push(&daily, filename);
+            }
+        }
+    }
+
+    /// Returns the file name of the next file to be produced.
+    /// [Constellation] is the header [Constellation], which
+    /// customizes the short navigation file names.
+    /// Fails when this [Constellation] cannot be described by a short navigation file name.
+    pub fn filename(
+        &self,
+        is_nav: bool,
+        constellation: Constellation,
+        t: Epoch,
+    ) -> std::io::Result<String> {
+        let filename = if let Some(template) = &self.filename_template {
+            self.template_filename(template, is_nav, constellation, t)
+        } else if self.short_filename {
             if is_nav {
-                self.nav_v2_filename(t)
+                self.nav_v2_filename(t, constellation)?
             } else {
                 self.obs_v2_filename(t)
             }
@@ -92,8 +423,51 @@ impl Settings {
             }
         };
 
-        filepath.push_str(&filename);
-        filepath
+        Ok(match &self.prefix {
+            Some(prefix) => Path::new(prefix)
+                .join(filename)
+                .to_string_lossy()
+                .to_string(),
+            None => filename,
+        })
+    }
+
+    /// Substitutes the tokens of the file name `template`:
+    /// {sys} is the header [Constellation] and {type} the RINEX
+    /// file type (O, D for CRINEX, or N), like short file names.
+    fn template_filename(
+        &self,
+        template: &str,
+        is_nav: bool,
+        constellation: Constellation,
+        t: Epoch,
+    ) -> String {
+        let (y, _, _, hh, _, _, _) = t.to_gregorian_utc();
+
+        let fmt = Format::from_str("%j").unwrap();
+        let doy = Formatter::new(t, fmt).to_string();
+
+        let file_type = if is_nav {
+            "N"
+        } else if self.crinex {
+            "D"
+        } else {
+            "O"
+        };
+
+        let mut formatted = template
+            .replace("{name}", &self.name)
+            .replace("{yyyy}", &format!("{:04}", y))
+            .replace("{doy}", &doy)
+            .replace("{hh}", &format!("{:02}", hh))
+            .replace("{sys}", &format!("{:x}", constellation))
+            .replace("{type}", file_type);
+
+        if self.gzip {
+            formatted.push_str(".gz");
+        }
+
+        formatted
     }
 
     fn obs_v2_filename(&self, t: Epoch) -> String {
@@ -122,9 +496,38 @@ impl Settings {
         formatted
     }
 
+    /// Returns the period token (PPU) of the V3 file names: two digits and
+    /// a unit (M, H or D). None when this period cannot be represented:
+    /// it must be a whole number of minutes, hours or days, up to 99.
+    pub fn period_token(&self) -> Option<String> {
+        let minutes = self.period.to_seconds() / 60.0;
+
+        if minutes.fract() != 0.0 || minutes < 1.0 {
+            return None;
+        }
+
+        let minutes = minutes as u64;
+
+        [(1440, 'D'), (60, 'H'), (1, 'M')]
+            .iter()
+            .filter(|(unit, _)| minutes % unit == 0)
+            .map(|(unit, token)| (minutes / unit, token))
+            .find(|(value, _)| *value <= 99)
+            .map(|(value, token)| format!("{:02}{}", value, token))
+    }
+
+    fn ppu(&self) -> String {
+        self.period_token().unwrap_or_else(|| {
+            panic!(
+                "{} period cannot be represented in a V3 file name",
+                self.period
+            )
+        })
+    }
+
     fn obs_v3_filename(&self, t: Epoch) -> String {
-        let ppu: PPU = self.period.into();
-        let ffu: FFU = Duration::from_seconds(30.0).into();
+        let ppu = self.ppu();
+        let ffu: FFU = self.sampling.into();
 
         let mut formatted = format!("{}{}_R_", self.name, self.country);
 
@@ -134,7 +537,7 @@ impl Settings {
         formatted.push_str(&formatter.to_string());
         formatted.push_str("0000_");
 
-        formatted.push_str(&ppu.to_string());
+        formatted.push_str(&ppu);
         formatted.push('_');
 
         formatted.push_str(&ffu.to_string());
@@ -153,9 +556,36 @@ impl Settings {
         formatted
     }
 
-    fn nav_v2_filename(&self, t: Epoch) -> String {
+    /// Returns the short navigation file name suffix for this [Constellation],
+    /// or None if it cannot be represented in this revision.
+    /// Strict V2 only describes one system per file.
+    pub fn nav_v2_suffix(&self, constellation: Constellation) -> Option<char> {
+        match constellation {
+            Constellation::GPS => Some('N'),
+            Constellation::Glonass => Some('G'),
+            Constellation::SBAS => Some('H'),
+            Constellation::Galileo => Some('L'),
+            Constellation::QZSS if self.major > 2 => Some('Q'),
+            Constellation::BeiDou if self.major > 2 => Some('F'),
+            Constellation::IRNSS if self.major > 2 => Some('I'),
+            Constellation::Mixed if self.major > 2 => Some('P'),
+            _ => None,
+        }
+    }
+
+    fn nav_v2_filename(&self, t: Epoch, constellation: Constellation) -> std::io::Result<String> {
         let (y, _, _, _, _, _, _) = t.to_gregorian_utc();
 
+        let suffix = self.nav_v2_suffix(constellation).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "{} navigation cannot be described by a RINEX V{} short file name",
+                    constellation, self.major
+                ),
+            )
+        })?;
+
         let fmt = Format::from_str("%j").unwrap();
         let formatter = Formatter::new(t, fmt);
 
@@ -165,18 +595,18 @@ impl Settings {
         formatted.push('.');
 
         formatted.push_str(&format!("{:02}", y - 2000));
-        formatted.push('N');
+        formatted.push(suffix);
 
         if self.gzip {
             formatted.push_str(".gz")
         }
 
-        formatted
+        Ok(formatted)
     }
 
+    /// Navigation long file names do not have a sampling (FFU) field
     fn nav_v3_filename(&self, t: Epoch) -> String {
-        let ppu: PPU = self.period.into();
-        let ffu: FFU = Duration::from_seconds(30.0).into();
+        let ppu = self.ppu();
 
         let mut formatted = format!("{}{}_R_", self.name, self.country);
 
@@ -186,10 +616,7 @@ impl Settings {
         formatted.push_str(&formatter.to_string());
         formatted.push_str("0000_");
 
-        formatted.push_str(&ppu.to_string());
-        formatted.push('_');
-
-        formatted.push_str(&ffu.to_string());
+        formatted.push_str(&ppu);
         formatted.push_str("_MN.rnx");
 
         if self.gzip {
@@ -203,8 +630,94 @@ impl Settings {
 #[cfg(test)]
 mod test {
     use super::Settings;
+    use crate::utils::{ObservableKind, SignalCarrier};
     use hifitime::prelude::{Duration, Epoch, TimeScale};
-    use std::str::FromStr;
+    use rinex::prelude::{Constellation, Observable, SV};
+    use std::{collections::HashMap, str::FromStr};
+
+    #[test]
+    fn observable_map() {
+        let observables = |codes: &[&str]| {
+            codes
+                .iter()
+                .map(|code| Observable::from_str(code).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let mut settings = Settings {
+            observables: HashMap::from([
+                (
+                    Constellation::BeiDou,
+                    observables(&["C2I", "L2I", "C2D", "L2D"]),
+                ),
+                (Constellation::GPS, observables(&["C1C", "L1C"])),
+            ]),
+            observable_map: vec![(SignalCarrier::BDS_B1I_D1, "1I".to_string())],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            settings.observable(SignalCarrier::BDS_B1I_D1, ObservableKind::PhaseRange),
+            Observable::from_str("L1I").unwrap()
+        );
+
+        assert_eq!(
+            settings.observable(SignalCarrier::BDS_B1I_D2, ObservableKind::PhaseRange),
+            Observable::from_str("L2D").unwrap()
+        );
+
+        settings.remap_observables();
+
+        assert_eq!(
+            settings.observables[&Constellation::BeiDou],
+            observables(&["C1I", "L1I", "C2D", "L2D"])
+        );
+
+        assert_eq!(
+            settings.observables[&Constellation::GPS],
+            observables(&["C1C", "L1C"])
+        );
+
+        // V2 observables are never remapped
+        settings.major = 2;
+
+        assert_eq!(
+            settings.observable(SignalCarrier::BDS_B1I_D1, ObservableKind::PseudoRange),
+            SignalCarrier::BDS_B1I_D1.to_observable(ObservableKind::PseudoRange, 2)
+        );
+    }
+
+    #[test]
+    fn filename_template() {
+        let mut settings = Settings {
+            name: "STAT".to_string(),
+            filename_template: Some("{name}/{yyyy}/{doy}/{name}{doy}{hh}_{sys}.{type}".to_string()),
+            ..Default::default()
+        };
+
+        let t0 = Epoch::from_str("2020-02-01T13:00:00 UTC").unwrap();
+
+        assert_eq!(
+            settings.filename(false, Constellation::Mixed, t0).unwrap(),
+            "STAT/2020/032/STAT03213_M.O"
+        );
+
+        assert_eq!(
+            settings.filename(true, Constellation::GPS, t0).unwrap(),
+            "STAT/2020/032/STAT03213_G.N"
+        );
+
+        settings.crinex = true;
+        settings.gzip = true;
+        settings.prefix = Some("/tmp".to_string());
+
+        assert_eq!(
+            settings
+                .filename(false, Constellation::Galileo, t0)
+                .unwrap(),
+            "/tmp/STAT/2020/032/STAT03213_E.D.gz"
+        );
+    }
 
     #[test]
     fn test_v2_filename() {
@@ -221,6 +734,7 @@ mod test {
             country: "FRA".to_string(),
             period: Duration::from_days(1.0),
             observables: Default::default(),
+            ..Default::default()
         };
 
         let t0 = Epoch::from_str("2020-01-01T00:00:00 UTC").unwrap();
@@ -234,6 +748,31 @@ mod test {
         assert_eq!(settings.obs_v2_filename(t0), "UBX001.20D.gz");
     }
 
+    #[test]
+    fn granular_gzip() {
+        let settings = Settings {
+            gzip: true,
+            gzip_nav: false,
+            name: "UBX".to_string(),
+            ..Default::default()
+        };
+
+        let t0 = Epoch::from_str("2020-01-01T00:00:00 UTC").unwrap();
+
+        assert_eq!(
+            settings.filename(false, Constellation::Mixed, t0).unwrap(),
+            "UBX001.20O.gz"
+        );
+
+        let navigation = settings.navigation();
+
+        assert!(!navigation.gzip);
+        assert_eq!(
+            navigation.filename(true, Constellation::GPS, t0).unwrap(),
+            "UBX001.20N"
+        );
+    }
+
     #[test]
     fn test_v3_filename() {
         let mut settings = Settings {
@@ -249,6 +788,7 @@ mod test {
             country: "FRA".to_string(),
             period: Duration::from_days(1.0),
             observables: Default::default(),
+            ..Default::default()
         };
 
         let t0 = Epoch::from_str("2020-01-01T00:00:00 UTC").unwrap();
@@ -271,5 +811,235 @@ mod test {
             settings.obs_v3_filename(t0),
             "UBXFRA_R_20200010000_01D_30S_MO.crx.gz"
         );
+
+        settings.sampling = Duration::from_seconds(1.0);
+
+        assert_eq!(
+            settings.obs_v3_filename(t0),
+            "UBXFRA_R_20200010000_01D_01S_MO.crx.gz"
+        );
+    }
+
+    #[test]
+    fn test_nav_v2_filename() {
+        let mut settings = Settings {
+            major: 2,
+            name: "UBX".to_string(),
+            ..Default::default()
+        };
+
+        let t0 = Epoch::from_str("2020-01-01T00:00:00 UTC").unwrap();
+
+        assert_eq!(
+            settings.nav_v2_filename(t0, Constellation::GPS).unwrap(),
+            "UBX001.20N"
+        );
+
+        assert_eq!(
+            settings
+                .nav_v2_filename(t0, Constellation::Glonass)
+                .unwrap(),
+            "UBX001.20G"
+        );
+
+        assert_eq!(
+            settings
+                .nav_v2_filename(t0, Constellation::Galileo)
+                .unwrap(),
+            "UBX001.20L"
+        );
+
+        assert_eq!(
+            settings.nav_v2_filename(t0, Constellation::SBAS).unwrap(),
+            "UBX001.20H"
+        );
+
+        // mixed navigation is not representable in V2
+        assert!(settings.nav_v2_suffix(Constellation::Mixed).is_none());
+        assert!(settings.nav_v2_suffix(Constellation::BeiDou).is_none());
+        assert!(settings.nav_v2_filename(t0, Constellation::BeiDou).is_err());
+        assert!(settings.filename(true, Constellation::Mixed, t0).is_err());
+
+        // but it is in V3 short file names
+        settings.major = 3;
+
+        assert_eq!(
+            settings.nav_v2_filename(t0, Constellation::Mixed).unwrap(),
+            "UBX001.20P"
+        );
+
+        settings.gzip = true;
+
+        assert_eq!(
+            settings
+                .nav_v2_filename(t0, Constellation::Glonass)
+                .unwrap(),
+            "UBX001.20G.gz"
+        );
+    }
+
+    #[test]
+    fn test_nav_v3_filename() {
+        let mut settings = Settings {
+            short_filename: false,
+            name: "UBX".to_string(),
+            country: "FRA".to_string(),
+            infer_country: true,
+            period: Duration::from_hours(1.0),
+            ..Default::default()
+        };
+
+        let t0 = Epoch::from_str("2020-01-01T00:00:00 UTC").unwrap();
+
+        assert_eq!(
+            settings.nav_v3_filename(t0),
+            "UBXFRA_R_20200010000_01H_MN.rnx"
+        );
+
+        assert_eq!(
+            settings.filename(true, Constellation::Mixed, t0).unwrap(),
+            "UBXFRA_R_20200010000_01H_MN.rnx"
+        );
+
+        settings.period = Duration::from_days(1.0);
+
+        assert_eq!(
+            settings.nav_v3_filename(t0),
+            "UBXFRA_R_20200010000_01D_MN.rnx"
+        );
+
+        settings.gzip = true;
+
+        assert_eq!(
+            settings.nav_v3_filename(t0),
+            "UBXFRA_R_20200010000_01D_MN.rnx.gz"
+        );
+    }
+
+    #[test]
+    fn period_token() {
+        let mut settings = Settings::default();
+
+        for (period, token) in [
+            (Duration::from_minutes(15.0), Some("15M")),
+            (Duration::from_minutes(45.0), Some("45M")),
+            (Duration::from_minutes(90.0), Some("90M")),
+            (Duration::from_hours(1.0), Some("01H")),
+            (Duration::from_hours(12.0), Some("12H")),
+            (Duration::from_days(1.0), Some("01D")),
+            (Duration::from_days(30.0), Some("30D")),
+            (Duration::from_seconds(90.0), None),
+            (Duration::from_seconds(30.0), None),
+            (Duration::from_minutes(100.0), None),
+            (Duration::from_days(120.0), None),
+        ] {
+            settings.period = period;
+            assert_eq!(settings.period_token().as_deref(), token, "{}", period);
+        }
+    }
+
+    #[test]
+    fn crinex_version() {
+        let mut settings = Settings {
+            major: 2,
+            ..Default::default()
+        };
+
+        assert_eq!(settings.crinex_major(), 1);
+
+        for major in [3, 4] {
+            settings.major = major;
+            assert_eq!(settings.crinex_major(), 3);
+        }
+
+        settings.crinex_version = Some(1);
+        assert_eq!(settings.crinex_major(), 1);
+    }
+
+    #[test]
+    fn test_continuous_rotation() {
+        let mut settings = Settings::default();
+
+        let t0 = Epoch::from_str("2020-01-01T12:00:00 UTC").unwrap();
+        let t1 = Epoch::from_str("2020-01-01T23:59:30 UTC").unwrap();
+        let t2 = Epoch::from_str("2020-01-02T00:00:00 UTC").unwrap();
+
+        assert!(!settings.rotates(t0, t2));
+
+        settings.continuous = true;
+
+        assert!(!settings.rotates(t0, t1));
+        assert!(settings.rotates(t0, t2));
+        assert!(settings.rotates(t1, t2));
+    }
+
+    #[test]
+    fn test_continued_working_file() {
+        let root = std::env::temp_dir().join("ubx2rinex-continued-working-file");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+
+        let filename = root.join("TEST.obs.gz");
+        let filename = filename.to_str().unwrap();
+
+        let mut settings = Settings {
+            continuous: true,
+            gzip: true,
+            ..Default::default()
+        };
+
+        assert!(!settings.continues(filename));
+
+        // interrupted session: only the working file exists
+        std::fs::write(settings.working_filename(filename), b"header\n").unwrap();
+        assert!(!settings.continues(filename));
+
+        settings.gzip_on_close = true;
+        assert!(settings.continues(filename));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_sv_filtering() {
+        let g04 = SV::from_str("G04").unwrap();
+        let g05 = SV::from_str("G05").unwrap();
+        let e14 = SV::from_str("E14").unwrap();
+
+        let mut settings = Settings::default();
+        assert!(settings.observed_sv(g04));
+        assert!(settings.observed_sv(e14));
+
+        settings.exclude_sv = vec![g04, e14];
+        assert!(!settings.observed_sv(g04));
+        assert!(!settings.observed_sv(e14));
+        assert!(settings.observed_sv(g05));
+
+        settings.exclude_sv = vec![g04];
+        settings.only_sv = vec![g04, e14];
+        assert!(!settings.observed_sv(g04));
+        assert!(settings.observed_sv(e14));
+        assert!(!settings.observed_sv(g05));
+    }
+
+    #[test]
+    fn test_prefix_trailing_slash() {
+        let t = Epoch::from_str("2020-01-01T00:00:00 GPST").unwrap();
+
+        for prefix in ["/tmp/rinex", "/tmp/rinex/"] {
+            let settings = Settings {
+                prefix: Some(prefix.to_string()),
+                ..Default::default()
+            };
+
+            let filename = settings.filename(false, Constellation::GPS, t).unwrap();
+
+            assert_eq!(
+                filename,
+                format!("/tmp/rinex/{}", settings.obs_v2_filename(t)),
+                "failed for prefix \"{}\"",
+                prefix
+            );
+        }
     }
 }
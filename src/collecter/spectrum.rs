@@ -0,0 +1,92 @@
+use rinex::prelude::Epoch;
+
+use tokio::sync::mpsc::Receiver as Rx;
+
+use crate::collecter::{Message, csv::Collecter};
+
+/// RF telemetry of one RF block, as reported by MON-RF
+#[derive(Debug, Clone)]
+pub struct RfBlock {
+    /// [Epoch] of reception
+    pub epoch: Epoch,
+
+    /// RF block ID (0: L1, 1: L2/L5)
+    pub block_id: u8,
+
+    /// Noise level, as measured by the GPS core
+    pub noise_per_ms: u16,
+
+    /// AGC monitor (0 - 8191)
+    pub agc_cnt: u16,
+
+    /// CW jamming indicator (0: none, 255: strong)
+    pub jam_ind: u8,
+
+    /// Imbalance of I-part of complex signal (-128..127)
+    pub ofs_i: i8,
+
+    /// Magnitude of I-part of complex signal (0..255)
+    pub mag_i: u8,
+
+    /// Imbalance of Q-part of complex signal (-128..127)
+    pub ofs_q: i8,
+
+    /// Magnitude of Q-part of complex signal (0..255)
+    pub mag_q: u8,
+}
+
+impl RfBlock {
+    /// Formats this [RfBlock] as a CSV line
+    pub fn csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{}",
+            self.epoch,
+            self.block_id,
+            self.noise_per_ms,
+            self.agc_cnt,
+            self.jam_ind,
+            self.ofs_i,
+            self.mag_i,
+            self.ofs_q,
+            self.mag_q
+        )
+    }
+}
+
+/// Builds the [Collecter] of the RF telemetry, as CSV
+pub fn collecter(path: &str, rx: Rx<Message>) -> Collecter {
+    Collecter::new(
+        path,
+        "epoch,block,noise_per_ms,agc_cnt,jam_ind,ofs_i,mag_i,ofs_q,mag_q",
+        "RF telemetry",
+        rx,
+        |message| match message {
+            Message::Spectrum(block) => Some(block.csv()),
+            _ => None,
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::RfBlock;
+    use rinex::prelude::Epoch;
+    use std::str::FromStr;
+
+    #[test]
+    fn rf_block_csv() {
+        let block = RfBlock {
+            epoch: Epoch::from_str("2025-01-01T00:00:00 GPST").unwrap(),
+            block_id: 1,
+            noise_per_ms: 85,
+            agc_cnt: 4200,
+            jam_ind: 12,
+            ofs_i: -3,
+            mag_i: 140,
+            ofs_q: 2,
+            mag_q: 138,
+        };
+
+        assert!(block.csv().ends_with(",1,85,4200,12,-3,140,2,138"));
+    }
+}
@@ -0,0 +1,188 @@
+//! End of run summary of the collected observations (--observables-summary)
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+
+use rinex::prelude::{Constellation, Duration, Epoch, Observable, SV, obs::SignalObservation};
+
+use crate::utils::sort_observables;
+
+/// Epochs further apart than this many sampling periods define a data gap
+const GAP_TOLERANCE: f64 = 1.5;
+
+/// [Summary] of the observations released during this session
+#[derive(Debug, Clone)]
+pub struct Summary {
+    /// Nominal sampling period
+    sampling_period: Duration,
+
+    /// Number of released epochs
+    epochs: usize,
+
+    /// First released [Epoch]
+    first: Option<Epoch>,
+
+    /// Latest released [Epoch]
+    last: Option<Epoch>,
+
+    /// Data gaps, as (last epoch before the gap, first epoch after the gap)
+    gaps: Vec<(Epoch, Epoch)>,
+
+    /// Satellites that were observed, per [Constellation]
+    satellites: BTreeMap<Constellation, BTreeSet<SV>>,
+
+    /// Observables that were released, per [Constellation]
+    observables: BTreeMap<Constellation, Vec<Observable>>,
+}
+
+impl Summary {
+    /// Builds new [Summary]
+    pub fn new(sampling_period: Duration) -> Self {
+        Self {
+            sampling_period,
+            epochs: 0,
+            first: None,
+            last: None,
+            gaps: Default::default(),
+            satellites: Default::default(),
+            observables: Default::default(),
+        }
+    }
+
+    /// Accounts for a released epoch
+    pub fn add_epoch(&mut self, epoch: Epoch, signals: &[SignalObservation]) {
+        if let Some(last) = self.last
+            && epoch - last > self.sampling_period * GAP_TOLERANCE
+        {
+            self.gaps.push((last, epoch));
+        }
+
+        if self.first.is_none() {
+            self.first = Some(epoch);
+        }
+
+        self.last = Some(epoch);
+        self.epochs += 1;
+
+        for signal in signals.iter() {
+            let constellation = signal.sv.constellation;
+
+            self.satellites
+                .entry(constellation)
+                .or_default()
+                .insert(signal.sv);
+
+            let observables = self.observables.entry(constellation).or_default();
+
+            if !observables.contains(&signal.observable) {
+                observables.push(signal.observable.clone());
+            }
+        }
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let (Some(first), Some(last)) = (self.first, self.last) else {
+            return write!(f, "no epoch was released");
+        };
+
+        writeln!(f, "epochs: {}", self.epochs)?;
+        writeln!(f, "time span: {} - {} ({})", first, last, last - first)?;
+
+        for (constellation, satellites) in self.satellites.iter() {
+            let mut observables = self
+                .observables
+                .get(constellation)
+                .cloned()
+                .unwrap_or_default();
+
+            sort_observables(&mut observables);
+
+            let observables = observables
+                .iter()
+                .map(|observable| observable.to_string())
+                .collect::<Vec<_>>();
+
+            writeln!(
+                f,
+                "{}: {} SV, observables: {}",
+                constellation,
+                satellites.len(),
+                observables.join(" ")
+            )?;
+        }
+
+        if self.gaps.is_empty() {
+            write!(f, "no data gap")
+        } else {
+            write!(f, "{} data gap(s)", self.gaps.len())?;
+
+            for (before, after) in self.gaps.iter() {
+                write!(f, "\n  {} - {} ({})", before, after, *after - *before)?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Summary;
+    use rinex::prelude::{Constellation, Duration, Epoch, Observable, SV, obs::SignalObservation};
+    use std::str::FromStr;
+
+    fn signal(sv: &str, code: &str) -> SignalObservation {
+        SignalObservation {
+            sv: SV::from_str(sv).unwrap(),
+            lli: None,
+            snr: None,
+            value: 1.0,
+            observable: Observable::from_str(code).unwrap(),
+        }
+    }
+
+    #[test]
+    fn observables_summary() {
+        let mut summary = Summary::new(Duration::from_seconds(30.0));
+
+        let t0 = Epoch::from_str("2025-01-01T00:00:00 GPST").unwrap();
+        let signals = [
+            signal("G01", "S1C"),
+            signal("G01", "C1C"),
+            signal("G02", "C1C"),
+            signal("E05", "C1C"),
+        ];
+
+        summary.add_epoch(t0, &signals);
+        summary.add_epoch(t0 + Duration::from_seconds(30.0), &signals[..2]);
+
+        // 3 epochs are missing
+        summary.add_epoch(t0 + Duration::from_seconds(150.0), &signals[3..]);
+
+        assert_eq!(summary.epochs, 3);
+        assert_eq!(summary.satellites[&Constellation::GPS].len(), 2);
+        assert_eq!(summary.satellites[&Constellation::Galileo].len(), 1);
+
+        assert_eq!(summary.gaps.len(), 1);
+        assert_eq!(summary.gaps[0].0, t0 + Duration::from_seconds(30.0));
+
+        let report = summary.to_string();
+        assert!(report.contains("epochs: 3"), "{}", report);
+        assert!(
+            report.contains("GPS: 2 SV, observables: C1C S1C"),
+            "{}",
+            report
+        );
+        assert!(report.contains("1 data gap(s)"), "{}", report);
+    }
+
+    #[test]
+    fn empty_summary() {
+        let summary = Summary::new(Duration::from_seconds(30.0));
+        assert_eq!(summary.to_string(), "no epoch was released");
+    }
+}
@@ -0,0 +1,53 @@
+use rinex::prelude::Epoch;
+
+use tokio::sync::mpsc::Receiver as Rx;
+
+use crate::collecter::{Message, csv::Collecter};
+
+/// Time pulse, as reported by TIM-TP
+#[derive(Debug, Clone)]
+pub struct TimePulse {
+    /// [Epoch] of the next time pulse
+    pub epoch: Epoch,
+
+    /// Quantization error of the time pulse (ps)
+    pub q_err: i32,
+}
+
+impl TimePulse {
+    /// Formats this [TimePulse] as a CSV line
+    pub fn csv(&self) -> String {
+        format!("{},{}", self.epoch, self.q_err)
+    }
+}
+
+/// Builds the [Collecter] of the time pulse quantization error, as CSV
+pub fn collecter(path: &str, rx: Rx<Message>) -> Collecter {
+    Collecter::new(
+        path,
+        "epoch,qerr_ps",
+        "time pulse",
+        rx,
+        |message| match message {
+            Message::TimePulse(pulse) => Some(pulse.csv()),
+            _ => None,
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::TimePulse;
+    use rinex::prelude::Epoch;
+    use std::str::FromStr;
+
+    #[test]
+    fn time_pulse_csv() {
+        let pulse = TimePulse {
+            epoch: Epoch::from_str("2025-01-01T00:00:01 GPST").unwrap(),
+            q_err: -1234,
+        };
+
+        assert!(pulse.csv().ends_with(",-1234"));
+    }
+}
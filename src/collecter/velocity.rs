@@ -0,0 +1,76 @@
+use rinex::prelude::Epoch;
+
+use tokio::sync::mpsc::Receiver as Rx;
+
+use crate::collecter::{Message, csv::Collecter};
+
+/// Receiver velocity, as reported by NAV-PVT
+#[derive(Debug, Clone)]
+pub struct Velocity {
+    /// [Epoch] of the navigation solution
+    pub epoch: Epoch,
+
+    /// North velocity (m/s)
+    pub north: f64,
+
+    /// East velocity (m/s)
+    pub east: f64,
+
+    /// Down velocity (m/s)
+    pub down: f64,
+
+    /// Ground speed (2D) (m/s)
+    pub speed: f64,
+
+    /// Heading of motion (2D) (degrees)
+    pub heading: f64,
+}
+
+impl Velocity {
+    /// Formats this [Velocity] as a CSV line
+    pub fn csv(&self) -> String {
+        format!(
+            "{},{:.3},{:.3},{:.3},{:.3},{:.5}",
+            self.epoch, self.north, self.east, self.down, self.speed, self.heading
+        )
+    }
+}
+
+/// Builds the [Collecter] of the receiver velocity, as CSV
+pub fn collecter(path: &str, rx: Rx<Message>) -> Collecter {
+    Collecter::new(
+        path,
+        "epoch,vel_n,vel_e,vel_d,speed,heading",
+        "velocity",
+        rx,
+        |message| match message {
+            Message::Velocity(velocity) => Some(velocity.csv()),
+            _ => None,
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::Velocity;
+    use rinex::prelude::Epoch;
+    use std::str::FromStr;
+
+    #[test]
+    fn velocity_csv() {
+        let velocity = Velocity {
+            epoch: Epoch::from_str("2025-01-01T00:00:00 GPST").unwrap(),
+            north: 1.5,
+            east: -2.25,
+            down: 0.012,
+            speed: 2.704,
+            heading: 303.69007,
+        };
+
+        assert!(
+            velocity
+                .csv()
+                .ends_with(",1.500,-2.250,0.012,2.704,303.69007")
+        );
+    }
+}
@@ -0,0 +1,381 @@
+use flate2::read::MultiGzDecoder;
+use log::{error, info, warn};
+
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Write},
+};
+
+use tokio::sync::{mpsc, watch};
+
+use rinex::{
+    merge::Merge,
+    prelude::{Constellation, Epoch, Rinex, RinexType},
+};
+
+use crate::{
+    Proto,
+    collecter::{
+        Products, fd::FileDescriptor, memory::Records, navigation::Collecter as NavCollecter,
+        observation::Collecter as ObsCollecter, settings::Settings as RinexSettings,
+    },
+    device::Device,
+    packet::{
+        Channels, PassiveOptions, consume_passive, send_header_comments, shutdown_collecters,
+        source_comment,
+    },
+    runtime::Runtime,
+    ubx::Settings as UbloxSettings,
+};
+
+/// Collecters channel capacity
+const CHANNEL_CAPACITY: usize = 128;
+
+/// [UbxToRinex] converts a UBX stream to RINEX, like the command line
+/// does in passive mode. It must be awaited within a tokio runtime.
+///
+/// ```no_run
+/// use std::fs::File;
+/// use ubx2rinex::{RinexSettings, UbloxSettings, UbxToRinex};
+///
+/// # async fn convert() {
+/// let fd = File::open("capture.ubx").unwrap();
+///
+/// let products = UbxToRinex::new(fd)
+///     .with_rinex_settings(RinexSettings::default())
+///     .with_ublox_settings(UbloxSettings::default())
+///     .convert_to_memory()
+///     .await;
+///
+/// for (filename, content) in products.files() {
+///     println!("{}: {} bytes", filename, content.len());
+/// }
+/// # }
+/// ```
+pub struct UbxToRinex {
+    /// UBX source
+    source: Box<dyn Read>,
+
+    /// [RinexSettings]
+    settings: RinexSettings,
+
+    /// [UbloxSettings]
+    ubx_settings: UbloxSettings,
+
+    /// Header comments
+    comments: Vec<String>,
+}
+
+impl UbxToRinex {
+    /// Builds a new [UbxToRinex] from any [Read]able UBX source,
+    /// with default settings.
+    pub fn new<R: Read + 'static>(source: R) -> Self {
+        Self {
+            source: Box::new(source),
+            settings: Default::default(),
+            ubx_settings: Default::default(),
+            comments: Default::default(),
+        }
+    }
+
+    /// Copies and returns [UbxToRinex] with desired [RinexSettings]
+    pub fn with_rinex_settings(mut self, settings: RinexSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Copies and returns [UbxToRinex] with desired [UbloxSettings]
+    pub fn with_ublox_settings(mut self, settings: UbloxSettings) -> Self {
+        self.ubx_settings = settings;
+        self
+    }
+
+    /// Copies and returns [UbxToRinex] with this comment added to the RINEX headers,
+    /// for example to describe the UBX source.
+    pub fn with_header_comment(mut self, comment: &str) -> Self {
+        self.comments.push(comment.to_string());
+        self
+    }
+
+    /// Converts the UBX source to RINEX files, according to [RinexSettings].
+    pub async fn convert(self) {
+        self.run().await;
+    }
+
+    /// Converts the UBX source and returns the RINEX [Products], in memory.
+    pub async fn convert_to_memory(mut self) -> Products {
+        let products = Products::default();
+        self.settings.products = Some(products.clone());
+        self.run().await;
+        products
+    }
+
+    /// Converts the UBX source and returns the [Rinex] structures,
+    /// rather than formatting them.
+    pub async fn convert_to_rinex(mut self) -> Vec<Rinex> {
+        let records = Records::default();
+        self.settings.records = Some(records.clone());
+        self.run().await;
+        records.rinex()
+    }
+
+    /// Converts the UBX source and writes all RINEX [Products] to this writer,
+    /// in order of production.
+    pub async fn convert_to_writer<W: Write>(self, writer: &mut W) -> std::io::Result<()> {
+        for (_, content) in self.convert_to_memory().await.files() {
+            writer.write_all(&content)?;
+        }
+
+        writer.flush()
+    }
+
+    async fn run(self) {
+        let mut buffer = [0; 8192];
+
+        let cfg_precision = self.ubx_settings.precision;
+
+        let mut device = Device::<Proto>::from_reader(self.source);
+
+        // graceful exit is driven by Message::Shutdown
+        let (_shutdown_tx, shutdown_rx) = watch::channel(true);
+
+        let (obs_tx, obs_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let (nav_tx, nav_rx) = mpsc::channel(CHANNEL_CAPACITY);
+
+        let mut obs_collecter = ObsCollecter::new(
+            self.settings.clone(),
+            self.ubx_settings.clone(),
+            shutdown_rx.clone(),
+            obs_rx,
+        );
+
+        let mut nav_collecter = NavCollecter::new(
+            self.settings.clone(),
+            self.ubx_settings.clone(),
+            shutdown_rx.clone(),
+            nav_rx,
+        );
+
+        let channels = Channels {
+            obs: obs_tx,
+            nav: nav_tx,
+            sbas: None,
+            spectrum: None,
+            velocity: None,
+            timepulse: None,
+            qc: None,
+        };
+
+        send_header_comments(&channels, &self.ubx_settings, &self.comments);
+
+        let mut tasks = Vec::new();
+
+        if self.ubx_settings.rawxm {
+            tasks.push(tokio::spawn(async move {
+                obs_collecter.run().await;
+            }));
+        }
+
+        if self.ubx_settings.ephemeris {
+            tasks.push(tokio::spawn(async move {
+                nav_collecter.run().await;
+            }));
+        }
+
+        let mut rtm = Runtime::new();
+        rtm.nav_eoe_fallback = !self.ubx_settings.nav_eoe;
+
+        consume_passive(
+            &mut rtm,
+            &channels,
+            &mut device,
+            &mut buffer,
+            cfg_precision,
+            &self.ubx_settings,
+            &PassiveOptions::default(),
+        )
+        .await;
+
+        shutdown_collecters(&rtm, &channels, &self.ubx_settings).await;
+
+        for task in tasks {
+            let _ = task.await;
+        }
+    }
+}
+
+/// Converts these UBX files concurrently (one task per file), into per-file [Rinex],
+/// which are then merged in the order of the files and formatted according to [RinexSettings]:
+/// one file per kind. The files should not overlap in time.
+pub async fn convert_parallel(
+    filepaths: &[String],
+    settings: RinexSettings,
+    ubx_settings: UbloxSettings,
+) {
+    let handle = tokio::runtime::Handle::current();
+
+    let tasks = filepaths
+        .iter()
+        .map(|path| {
+            let path = path.clone();
+            let handle = handle.clone();
+            let settings = settings.clone();
+            let ubx_settings = ubx_settings.clone();
+
+            tokio::task::spawn_blocking(move || {
+                let fd =
+                    File::open(&path).unwrap_or_else(|e| panic!("failed to open {}: {}", path, e));
+
+                let converter = if path.ends_with(".gz") {
+                    UbxToRinex::new(MultiGzDecoder::new(fd))
+                } else {
+                    UbxToRinex::new(fd)
+                };
+
+                let rinex = handle.block_on(
+                    converter
+                        .with_header_comment(&source_comment(&path))
+                        .with_rinex_settings(settings)
+                        .with_ublox_settings(ubx_settings)
+                        .convert_to_rinex(),
+                );
+
+                info!("\"{}\": decoded", path);
+                rinex
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut observations = Option::<Rinex>::None;
+    let mut navigation = Option::<Rinex>::None;
+
+    let mut previous_end = Option::<Epoch>::None;
+
+    for (index, task) in tasks.into_iter().enumerate() {
+        let fragments = task
+            .await
+            .unwrap_or_else(|e| panic!("failed to decode {}: {}", filepaths[index], e));
+
+        for rinex in fragments {
+            let merged = match rinex.header.rinex_type {
+                RinexType::NavigationData => &mut navigation,
+                _ => &mut observations,
+            };
+
+            if rinex.header.rinex_type == RinexType::ObservationData {
+                if let (Some(start), Some(previous_end)) = (rinex.first_epoch(), previous_end)
+                    && start < previous_end
+                {
+                    warn!(
+                        "{} - input file #{} overlaps previous file (ending {}): duplicate epochs are to be expected",
+                        start,
+                        index + 1,
+                        previous_end
+                    );
+                }
+
+                previous_end = rinex.last_epoch().or(previous_end);
+            }
+
+            match merged {
+                Some(merged) => {
+                    if let Err(e) = merged.merge_mut(&rinex) {
+                        error!("failed to merge {}: {}", filepaths[index], e);
+                    }
+                },
+                None => {
+                    *merged = Some(rinex);
+                },
+            }
+        }
+    }
+
+    for (is_nav, rinex) in [(false, observations), (true, navigation)] {
+        let Some(rinex) = rinex else {
+            continue;
+        };
+
+        let Some(t) = rinex.first_epoch() else {
+            continue;
+        };
+
+        let settings = if is_nav {
+            settings.navigation()
+        } else {
+            settings.clone()
+        };
+
+        let constellation = rinex.header.constellation.unwrap_or(Constellation::Mixed);
+        let filename = match settings.filename(is_nav, constellation, t) {
+            Ok(filename) => filename,
+            Err(e) => {
+                error!("failed to release {}: {}", constellation, e);
+                continue;
+            },
+        };
+
+        let mut fd = BufWriter::new(FileDescriptor::new(settings.gzip, &filename));
+
+        match rinex.format(&mut fd) {
+            Ok(_) => {
+                let _ = fd.flush();
+                info!("\"{}\": released", filename);
+            },
+            Err(e) => {
+                error!("failed to format \"{}\": {}", filename, e);
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{UbxToRinex, convert_parallel};
+    use std::io::Cursor;
+
+    #[tokio::test]
+    async fn empty_source() {
+        let products = UbxToRinex::new(Cursor::new(Vec::<u8>::new()))
+            .convert_to_memory()
+            .await;
+
+        assert!(products.files().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn parallel_empty_files() {
+        let root = std::env::temp_dir().join("ubx2rinex-parallel");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+
+        let filepaths = ["A.ubx", "B.ubx"]
+            .iter()
+            .map(|name| {
+                let path = root.join(name);
+                std::fs::write(&path, []).unwrap();
+                path.to_string_lossy().to_string()
+            })
+            .collect::<Vec<_>>();
+
+        let settings = crate::RinexSettings {
+            prefix: Some(root.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        convert_parallel(&filepaths, settings, Default::default()).await;
+
+        // nothing to release
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 2);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn empty_source_to_rinex() {
+        let rinex = UbxToRinex::new(Cursor::new(Vec::<u8>::new()))
+            .convert_to_rinex()
+            .await;
+
+        assert!(rinex.is_empty());
+    }
+}
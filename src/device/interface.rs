@@ -1,3 +1,4 @@
+use hifitime::prelude::Epoch;
 use serialport::SerialPort;
 use std::{fs::File, io::Read};
 
@@ -13,6 +14,25 @@ pub struct ReadOnlyPool {
 
     /// Stack
     readers: Vec<Box<dyn Read>>,
+
+    /// Time span of each descriptor, in terms of U-Blox epochs
+    spans: Vec<Option<(Epoch, Epoch)>>,
+
+    /// Descriptor that produced the latest bytes
+    source: Option<usize>,
+}
+
+/// Time [Overlap] between two consecutive input files
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Overlap {
+    /// Index of the file that overlaps its predecessor
+    pub index: usize,
+
+    /// Last [Epoch] of the previous file
+    pub previous_end: Epoch,
+
+    /// First [Epoch] of this file
+    pub start: Epoch,
 }
 
 impl std::io::Read for ReadOnlyPool {
@@ -31,8 +51,11 @@ impl std::io::Read for ReadOnlyPool {
                 self.ptr += 1;
                 self.read(buf)
             },
-            Ok(size) => Ok(size), // pass through
-            Err(e) => Err(e),     // pass through
+            Ok(size) => {
+                self.source = Some(self.ptr);
+                Ok(size)
+            },
+            Err(e) => Err(e), // pass through
         }
     }
 }
@@ -43,13 +66,58 @@ impl ReadOnlyPool {
             ptr: 0,
             size: 1,
             readers: vec![handle],
+            spans: vec![None],
+            source: None,
         }
     }
 
     pub fn stack_handle(&mut self, handle: Box<dyn Read>) {
         self.readers.push(handle);
+        self.spans.push(None);
         self.size += 1;
     }
+
+    /// Gives up on the descriptor being consumed (unreadable or corrupt file)
+    /// and moves on to the next one. Returns the index of the skipped descriptor.
+    pub fn skip_current(&mut self) -> Option<usize> {
+        if self.ptr == self.size {
+            return None;
+        }
+
+        self.ptr += 1;
+        Some(self.ptr - 1)
+    }
+
+    /// Latch a new [Epoch], decoded from the latest bytes, for the descriptor that produced them.
+    /// Returns an [Overlap] on the first [Epoch] of a file, when it precedes the
+    /// end of the previous file (typically, the same capture was loaded twice).
+    pub fn latch_epoch(&mut self, epoch: Epoch) -> Option<Overlap> {
+        let index = self.source?;
+
+        match &mut self.spans[index] {
+            Some((_, end)) => {
+                if epoch > *end {
+                    *end = epoch;
+                }
+                None
+            },
+            None => {
+                self.spans[index] = Some((epoch, epoch));
+
+                let (_, previous_end) = self.spans[..index].iter().rev().flatten().next()?;
+
+                if epoch <= *previous_end {
+                    Some(Overlap {
+                        index,
+                        previous_end: *previous_end,
+                        start: epoch,
+                    })
+                } else {
+                    None
+                }
+            },
+        }
+    }
 }
 
 /// [Interface] to the U-Blox stream
@@ -79,6 +147,11 @@ impl Interface {
         Self::ReadOnlyPool(ReadOnlyPool::new(Box::new(handle)))
     }
 
+    /// Creates a new read-only interface, from any [Read]able source
+    pub fn from_reader(handle: Box<dyn Read>) -> Self {
+        Self::ReadOnlyPool(ReadOnlyPool::new(handle))
+    }
+
     /// Creates a new gzip read-only interface
     pub fn from_gzip_file_handle(handle: File) -> Self {
         Self::ReadOnlyPool(ReadOnlyPool::new(Box::new(GzDecoder::new(handle))))
@@ -102,6 +175,25 @@ impl Interface {
     }
 }
 
+impl Interface {
+    /// Skips the file being consumed, only applies to [Self::ReadOnlyPool].
+    /// Returns the index of the skipped file.
+    pub fn skip_current(&mut self) -> Option<usize> {
+        match self {
+            Self::Port(_) => None,
+            Self::ReadOnlyPool(pool) => pool.skip_current(),
+        }
+    }
+
+    /// Latch a new [Epoch], only applies to [Self::ReadOnlyPool]
+    pub fn latch_epoch(&mut self, epoch: Epoch) -> Option<Overlap> {
+        match self {
+            Self::Port(_) => None,
+            Self::ReadOnlyPool(pool) => pool.latch_epoch(epoch),
+        }
+    }
+}
+
 impl std::io::Read for Interface {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         match self {
@@ -126,3 +218,78 @@ impl std::io::Write for Interface {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Overlap, ReadOnlyPool};
+    use hifitime::prelude::{Duration, Epoch};
+    use std::{
+        io::{Cursor, Read},
+        str::FromStr,
+    };
+
+    /// Reader that always fails, like a corrupt gzip stream
+    struct Corrupt;
+
+    impl Read for Corrupt {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("corrupt"))
+        }
+    }
+
+    #[test]
+    fn skip_corrupt_file() {
+        let mut pool = ReadOnlyPool::new(Box::new(Corrupt));
+        pool.stack_handle(Box::new(Cursor::new(vec![1; 4])));
+
+        let mut buf = [0; 4];
+
+        assert!(pool.read(&mut buf).is_err());
+        assert_eq!(pool.skip_current(), Some(0));
+
+        assert_eq!(pool.read(&mut buf).unwrap(), 4);
+        assert_eq!(buf, [1; 4]);
+
+        assert_eq!(pool.read(&mut buf).unwrap(), 0);
+        assert_eq!(pool.skip_current(), None);
+    }
+
+    #[test]
+    fn overlapping_files() {
+        let t0 = Epoch::from_str("2025-01-01T00:00:00 GPST").unwrap();
+        let dt = Duration::from_seconds(30.0);
+
+        for (second_start, overlaps) in [(t0 + dt, true), (t0 + dt * 3, false)] {
+            let mut pool = ReadOnlyPool::new(Box::new(Cursor::new(vec![0; 4])));
+            pool.stack_handle(Box::new(Cursor::new(vec![0; 4])));
+
+            let mut buf = [0; 4];
+
+            // first file
+            assert_eq!(pool.read(&mut buf).unwrap(), 4);
+            assert!(pool.latch_epoch(t0).is_none());
+            assert!(pool.latch_epoch(t0 + dt * 2).is_none());
+
+            // second file
+            assert_eq!(pool.read(&mut buf).unwrap(), 4);
+
+            let overlap = pool.latch_epoch(second_start);
+
+            if overlaps {
+                assert_eq!(
+                    overlap,
+                    Some(Overlap {
+                        index: 1,
+                        previous_end: t0 + dt * 2,
+                        start: second_start,
+                    })
+                );
+            } else {
+                assert!(overlap.is_none());
+            }
+
+            // reported once
+            assert!(pool.latch_epoch(second_start + dt).is_none());
+        }
+    }
+}
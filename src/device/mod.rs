@@ -1,4 +1,4 @@
-use log::{debug, error};
+use log::{debug, error, info, warn};
 
 use ublox::{
     Parser, UbxPacket, UbxPacketMeta, UbxPacketRequest, UbxProtocol,
@@ -8,12 +8,19 @@ use ublox::{
         UartMode, UartPortId,
     },
     cfg_rate::{AlignmentToReferenceTime, CfgRate, CfgRateBuilder},
+    cfg_val::{CfgLayerSet, CfgValSet},
+    mga_glo_eph::MgaGloEph,
+    mga_gps_eph::MgaGpsEph,
+    mon_gnss::MonGnss,
+    mon_rf::MonRf,
     mon_ver::MonVer,
     nav_clock::NavClock,
     nav_other::NavEoe,
     nav_sat::NavSat,
     rxm_rawx::RxmRawx,
     rxm_sfrbx::RxmSfrbx,
+    tim_tm2::TimTm2,
+    tim_tp::TimTp,
 };
 
 #[cfg(feature = "ubx14")]
@@ -25,6 +32,9 @@ use ublox::packetref_proto27::PacketRef;
 #[cfg(feature = "ubx31")]
 use ublox::packetref_proto31::PacketRef;
 
+#[cfg(any(feature = "ubx27", feature = "ubx31"))]
+use ublox::nav_sig::NavSig;
+
 #[cfg(feature = "ubx14")]
 use ublox::nav_pvt::proto14::NavPvt;
 #[cfg(feature = "ubx23")]
@@ -32,50 +42,260 @@ use ublox::nav_pvt::proto23::NavPvt;
 #[cfg(any(feature = "ubx27", feature = "ubx31"))]
 use ublox::nav_pvt::proto27_31::NavPvt;
 
-mod interface;
+pub mod interface;
 
 use std::{
     fs::File,
     io::{ErrorKind, Read, Write},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use crate::{UbloxSettings, collecter::Message, utils::from_timescale};
+use crate::{
+    PROTOCOL, UbloxSettings,
+    collecter::Message,
+    ubx,
+    utils::{
+        constellations_to_mask, from_timescale, mask_constellations, protocol_version,
+        receiver_timescale, suited_protocol,
+    },
+};
 use interface::Interface;
 
 use tokio::sync::mpsc::Sender;
 
+/// Default acknowledgment timeout
+pub const ACK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Default number of retries of an unacknowledged request
+pub const ACK_RETRIES: u8 = 2;
+
+/// Serial port framing and flow control, applied to both
+/// the host port and the receiver UART
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SerialSettings {
+    /// 7 or 8 data bits
+    pub data_bits: serialport::DataBits,
+
+    /// [serialport::Parity]
+    pub parity: serialport::Parity,
+
+    /// 1 or 2 stop bits
+    pub stop_bits: serialport::StopBits,
+
+    /// Host side [serialport::FlowControl]
+    pub flow_control: serialport::FlowControl,
+}
+
+impl Default for SerialSettings {
+    /// 8N1 without flow control
+    fn default() -> Self {
+        Self {
+            data_bits: serialport::DataBits::Eight,
+            parity: serialport::Parity::None,
+            stop_bits: serialport::StopBits::One,
+            flow_control: serialport::FlowControl::None,
+        }
+    }
+}
+
+impl SerialSettings {
+    /// Receiver [UartMode]
+    fn uart_mode(&self) -> UartMode {
+        let data_bits = match self.data_bits {
+            serialport::DataBits::Seven => DataBits::Seven,
+            serialport::DataBits::Eight => DataBits::Eight,
+            bits => panic!("{:?} data bits are not supported by U-Blox receivers", bits),
+        };
+
+        let parity = match self.parity {
+            serialport::Parity::None => Parity::None,
+            serialport::Parity::Odd => Parity::Odd,
+            serialport::Parity::Even => Parity::Even,
+        };
+
+        let stop_bits = match self.stop_bits {
+            serialport::StopBits::One => StopBits::One,
+            serialport::StopBits::Two => StopBits::Two,
+        };
+
+        UartMode::new(data_bits, parity, stop_bits)
+    }
+}
+
 pub struct Device<P: UbxProtocol> {
     pub interface: Interface,
     pub parser: Parser<Vec<u8>, P>,
+
+    /// Raw UBX copy of the serial stream, when requested
+    raw_output: Option<File>,
+
+    /// Maximal [Duration] we wait for the device to respond to a request
+    ack_timeout: Duration,
+
+    /// Number of times a configuration request is sent again,
+    /// when the device does not acknowledge it in time
+    ack_retries: u8,
 }
 
 impl<P: UbxProtocol> Device<P> {
-    pub fn configure(&mut self, settings: &UbloxSettings, buf: &mut [u8], tx: Sender<Message>) {
-        let mut vec = Vec::with_capacity(1024);
-
-        self.read_version(buf, tx).unwrap();
+    pub fn configure(
+        &mut self,
+        settings: &mut UbloxSettings,
+        buf: &mut [u8],
+        tx: Sender<Message>,
+    ) -> std::io::Result<()> {
+        let detected_model = self.read_version(buf, tx).map_err(|e| {
+            std::io::Error::new(e.kind(), format!("Failed to read firmware version: {}", e))
+        })?;
+        let model = settings.model.clone().or(detected_model);
 
         if settings.rx_clock {
             self.enable_nav_clock(buf);
         }
 
-        self.enable_nav_eoe(buf);
-        self.enable_nav_pvt(buf);
-        self.enable_nav_sat(buf);
+        if settings.nav_eoe {
+            self.enable_nav_eoe(buf);
+        }
+
+        self.enable_nav_pvt(settings.nav_pvt, buf);
+        self.enable_nav_sat(settings.nav_sat, buf);
+
+        #[cfg(any(feature = "ubx27", feature = "ubx31"))]
+        if settings.rawxm {
+            // signal identification, refines the RXM-RAWX mapping
+            self.enable_nav_sig(buf);
+        }
 
         self.enable_obs_rinex(settings.rawxm, buf);
-        self.enable_rxm_sfrbx(settings.ephemeris, buf);
+
+        if settings.rawxm {
+            // external events, emphasized in the observations
+            self.enable_tim_tm2(buf);
+        }
+
+        if settings.time_pulse {
+            self.enable_tim_tp(buf);
+        }
+
+        self.enable_rxm_sfrbx(settings.ephemeris || settings.sbas_log, buf);
 
         let time_ref = from_timescale(settings.timescale);
 
         let measure_rate_ms = (settings.sampling_period.total_nanoseconds() / 1_000_000) as u16;
-        self.apply_cfg_rate(buf, measure_rate_ms, settings.solutions_ratio, time_ref);
 
-        settings.to_ram_volatile_cfg(&mut vec);
+        let measure_rate_ms = self.apply_cfg_rate(
+            buf,
+            measure_rate_ms,
+            settings.solutions_ratio,
+            time_ref,
+            model.as_deref(),
+        );
+
+        // the receiver may not sample as fast as requested
+        settings.sampling_period = hifitime::Duration::from_milliseconds(measure_rate_ms as f64);
+
+        if model.as_deref().is_some_and(|model| model.contains("M8")) {
+            // M8 series do not support the configuration interface
+            warn!("M8 series: constellation and signal settings are not applied");
+        } else {
+            self.apply_ram_volatile_cfg(settings, buf)?;
+        }
+
+        // once configured: not fatal, the receiver may not reply to MON-GNSS
+        if let Err(e) = self.read_gnss(settings, buf) {
+            warn!("Failed to read the receiver constellations: {}", e);
+        }
+
+        Ok(())
+    }
+
+    /// Applies the constellation and signal settings (CFG-VALSET),
+    /// to RAM and also to the BBR and Flash layers with --persist.
+    /// The receiver does not tell which key was rejected: when so,
+    /// each key is applied individually, to report the faulty ones.
+    /// Keys may also be rejected as a combination only, which is reported as such.
+    fn apply_ram_volatile_cfg(
+        &mut self,
+        settings: &UbloxSettings,
+        buf: &mut [u8],
+    ) -> std::io::Result<()> {
+        let mut vec = Vec::with_capacity(1024);
+
+        settings.to_cfg(&mut vec);
+
+        if self.request_with_ack::<CfgValSet>(&vec, buf).is_ok() {
+            return Ok(());
+        }
+
+        // not all receivers have a Flash memory: apply each layer on its own,
+        // the configuration then only persists to the accepting layers.
+        if settings.persist && self.apply_cfg_layers(settings, buf)? {
+            return Ok(());
+        }
+
+        let mut rejected = Vec::new();
+
+        for cfg_val in settings.ram_volatile_cfg() {
+            let key = format!("{:?}", cfg_val);
+
+            vec.clear();
+            ubx::cfg_valset(&[cfg_val], &mut vec);
+
+            if self.request_with_ack::<CfgValSet>(&vec, buf).is_err() {
+                rejected.push(key);
+            }
+        }
+
+        if rejected.is_empty() {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "UBX-CFG-VALSET NACK: each key is accepted on its own, but not their combination",
+            ));
+        }
+
+        Err(std::io::Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "UBX-CFG-VALSET NACK: rejected key(s): {}",
+                rejected.join(", ")
+            ),
+        ))
+    }
+
+    /// Applies the constellation and signal settings to each layer individually.
+    /// Returns true when at least the RAM layer accepted them.
+    fn apply_cfg_layers(
+        &mut self,
+        settings: &UbloxSettings,
+        buf: &mut [u8],
+    ) -> std::io::Result<bool> {
+        let cfg_data = settings.ram_volatile_cfg();
+        let mut vec = Vec::with_capacity(1024);
+        let mut ram = false;
+
+        for (layer, name) in [
+            (CfgLayerSet::RAM, "RAM"),
+            (CfgLayerSet::BBR, "BBR"),
+            (CfgLayerSet::FLASH, "Flash"),
+        ] {
+            vec.clear();
+            ubx::cfg_valset_layers(&cfg_data, layer, &mut vec);
+
+            match self.request_with_ack::<CfgValSet>(&vec, buf) {
+                Ok(_) => {
+                    debug!("UBX-CFG-VALSET: {} layer configured", name);
+                    ram |= layer == CfgLayerSet::RAM;
+                },
+                Err(_) => {
+                    warn!(
+                        "UBX-CFG-VALSET: {} layer rejected the configuration, it will not persist there",
+                        name
+                    );
+                },
+            }
+        }
 
-        self.write_all(&vec)
-            .unwrap_or_else(|e| panic!("Failed to apply RAM config: {}", e));
+        Ok(ram)
     }
 
     pub fn open_file(fullpath: &str) -> Self {
@@ -84,6 +304,9 @@ impl<P: UbxProtocol> Device<P> {
         });
 
         Self {
+            raw_output: None,
+            ack_timeout: ACK_TIMEOUT,
+            ack_retries: ACK_RETRIES,
             parser: Parser::<_, P>::new(vec![]),
             interface: if fullpath.ends_with(".gz") {
                 Interface::from_gzip_file_handle(handle)
@@ -93,14 +316,39 @@ impl<P: UbxProtocol> Device<P> {
         }
     }
 
-    pub fn open_serial_port(port_str: &str, baud: u32, buffer: &mut [u8]) -> Self {
+    /// Creates a read-only [Device] from any [Read]able UBX source
+    pub fn from_reader(handle: Box<dyn Read>) -> Self {
+        Self {
+            raw_output: None,
+            ack_timeout: ACK_TIMEOUT,
+            ack_retries: ACK_RETRIES,
+            parser: Parser::<_, P>::new(vec![]),
+            interface: Interface::from_reader(handle),
+        }
+    }
+
+    pub fn open_serial_port(
+        port_str: &str,
+        baud: u32,
+        serial: SerialSettings,
+        ack_timeout: Duration,
+        ack_retries: u8,
+        buffer: &mut [u8],
+    ) -> Self {
         // open port
         let port = serialport::new(port_str, baud)
+            .data_bits(serial.data_bits)
+            .parity(serial.parity)
+            .stop_bits(serial.stop_bits)
+            .flow_control(serial.flow_control)
             .timeout(Duration::from_millis(250))
             .open()
             .unwrap_or_else(|e| panic!("Failed to open {} port: {}", port_str, e));
 
         let mut device = Self {
+            raw_output: None,
+            ack_timeout,
+            ack_retries,
             parser: Parser::<_, P>::new(vec![]),
             interface: Interface::from_serial_port(port),
         };
@@ -108,7 +356,7 @@ impl<P: UbxProtocol> Device<P> {
         for portid in [UartPortId::Uart1, UartPortId::Uart2] {
             // Enable UBX protocol on selected UART port
             device
-            .write_all(
+                .request_with_ack::<CfgPrtUart>(
                     &CfgPrtUartBuilder {
                         portid,
                         flags: 0,
@@ -118,9 +366,10 @@ impl<P: UbxProtocol> Device<P> {
                         baud_rate: baud,
                         in_proto_mask: InProtoMask::all(),
                         out_proto_mask: OutProtoMask::UBLOX,
-                        mode: UartMode::new(DataBits::Eight, Parity::None, StopBits::One),
+                        mode: serial.uart_mode(),
                     }
                     .into_packet_bytes(),
+                    buffer,
                 )
                 .unwrap_or_else(|e| {
                     panic!(
@@ -128,17 +377,27 @@ impl<P: UbxProtocol> Device<P> {
                         e
                     )
                 });
-
-            device
-                .wait_for_ack::<CfgPrtUart>(buffer)
-                .unwrap_or_else(|e| {
-                    panic!("CFG-MSG-UART NACK: {}", e);
-                });
         }
 
         device
     }
 
+    /// Copies every byte read from the serial port into a raw UBX file,
+    /// so the capture can be reprocessed later with different options.
+    /// Does not apply to read-only (passive) interfaces.
+    pub fn tee_raw_output(&mut self, fullpath: &str) {
+        if self.interface.is_read_only() {
+            warn!("raw UBX output does not apply to passive mode");
+            return;
+        }
+
+        let fd = File::create(fullpath).unwrap_or_else(|e| {
+            panic!("Failed to create {}: {}", fullpath, e);
+        });
+
+        self.raw_output = Some(fd);
+    }
+
     pub fn write_all(&mut self, data: &[u8]) -> std::io::Result<()> {
         self.interface.write_all(data)
     }
@@ -164,6 +423,17 @@ impl<P: UbxProtocol> Device<P> {
         loop {
             let nbytes = self.read_interface(buffer)?;
             if nbytes == 0 {
+                if self.interface.is_read_only() && !self.parser.is_buffer_empty() {
+                    // Stream ended in the middle of a packet (typically, a killed logger).
+                    // This is a clean end of stream, not a corruption: drop the partial packet.
+                    debug!(
+                        "discarding truncated UBX packet at end of stream ({} bytes)",
+                        self.parser.buffer_len()
+                    );
+
+                    self.parser = Parser::<_, P>::new(vec![]);
+                }
+
                 return Ok(0);
             }
 
@@ -189,71 +459,175 @@ impl<P: UbxProtocol> Device<P> {
         }
     }
 
+    /// Sends this configuration request and waits for its acknowledgment.
+    /// The request is sent again when the device does not acknowledge it in time,
+    /// up to [Device::ack_retries] times. A rejection (ACK-NAK) is not retried.
+    pub fn request_with_ack<T: UbxPacketMeta>(
+        &mut self,
+        request: &[u8],
+        buffer: &mut [u8],
+    ) -> std::io::Result<()> {
+        let mut retries = self.ack_retries;
+
+        loop {
+            self.write_all(request)?;
+
+            match self.wait_for_ack::<T>(buffer) {
+                Err(e) if e.kind() == ErrorKind::TimedOut && retries > 0 => {
+                    retries -= 1;
+                    warn!("{}: sending the request again", e);
+                },
+                result => return result,
+            }
+        }
+    }
+
+    /// Waits for the receiver to acknowledge the last `T` request.
+    /// Returns an error when the request is rejected (ACK-NAK),
+    /// or not acknowledged in timely manner.
     pub fn wait_for_ack<T: UbxPacketMeta>(&mut self, buffer: &mut [u8]) -> std::io::Result<()> {
         let mut found_packet = false;
-        while !found_packet {
-            self.consume_all_cb(buffer, |packet| {
+        let mut rejected = false;
+
+        let t0 = Instant::now();
+
+        while !found_packet && !rejected {
+            if t0.elapsed() > self.ack_timeout {
+                return Err(std::io::Error::new(
+                    ErrorKind::TimedOut,
+                    format!(
+                        "device did not acknowledge (class=0x{:02x}, id=0x{:02x}) within {:?}",
+                        T::CLASS,
+                        T::ID,
+                        self.ack_timeout
+                    ),
+                ));
+            }
+
+            self.consume_all_cb(buffer, |packet| match packet {
                 #[cfg(feature = "ubx14")]
-                if let ublox::UbxPacket::Proto14(PacketRef::AckAck(ack)) = packet {
+                ublox::UbxPacket::Proto14(PacketRef::AckAck(ack)) => {
                     if ack.class() == T::CLASS && ack.msg_id() == T::ID {
                         found_packet = true;
                     }
-                }
-
+                },
+                #[cfg(feature = "ubx14")]
+                ublox::UbxPacket::Proto14(PacketRef::AckNak(nak)) => {
+                    if nak.class() == T::CLASS && nak.msg_id() == T::ID {
+                        rejected = true;
+                    }
+                },
                 #[cfg(feature = "ubx23")]
-                if let ublox::UbxPacket::Proto23(PacketRef::AckAck(ack)) = packet {
+                ublox::UbxPacket::Proto23(PacketRef::AckAck(ack)) => {
                     if ack.class() == T::CLASS && ack.msg_id() == T::ID {
                         found_packet = true;
                     }
-                }
-
+                },
+                #[cfg(feature = "ubx23")]
+                ublox::UbxPacket::Proto23(PacketRef::AckNak(nak)) => {
+                    if nak.class() == T::CLASS && nak.msg_id() == T::ID {
+                        rejected = true;
+                    }
+                },
                 #[cfg(feature = "ubx27")]
-                if let ublox::UbxPacket::Proto27(PacketRef::AckAck(ack)) = packet {
+                ublox::UbxPacket::Proto27(PacketRef::AckAck(ack)) => {
                     if ack.class() == T::CLASS && ack.msg_id() == T::ID {
                         found_packet = true;
                     }
-                }
-
+                },
+                #[cfg(feature = "ubx27")]
+                ublox::UbxPacket::Proto27(PacketRef::AckNak(nak)) => {
+                    if nak.class() == T::CLASS && nak.msg_id() == T::ID {
+                        rejected = true;
+                    }
+                },
                 #[cfg(feature = "ubx31")]
-                if let ublox::UbxPacket::Proto31(PacketRef::AckAck(ack)) = packet {
+                ublox::UbxPacket::Proto31(PacketRef::AckAck(ack)) => {
                     if ack.class() == T::CLASS && ack.msg_id() == T::ID {
                         found_packet = true;
                     }
-                }
+                },
+                #[cfg(feature = "ubx31")]
+                ublox::UbxPacket::Proto31(PacketRef::AckNak(nak)) => {
+                    if nak.class() == T::CLASS && nak.msg_id() == T::ID {
+                        rejected = true;
+                    }
+                },
+                _ => {},
             })?;
         }
-        Ok(())
+
+        if rejected {
+            Err(std::io::Error::other("rejected by the receiver (ACK-NAK)"))
+        } else {
+            Ok(())
+        }
     }
 
-    // pub fn request_mga_gps_eph(&mut self) {
-    //     match self.write_all(&UbxPacketRequest::request_for::<MgaGpsEph>().into_packet_bytes()) {
-    //         Ok(_) => {
-    //             debug!("MGA-GPS-EPH");
-    //         },
-    //         Err(e) => {
-    //             error!("Failed to request MGA-GPS-EPH: {}", e);
-    //         },
-    //     }
-    // }
+    /// Polls MGA-GPS-EPH, the receiver replies with one message per known satellite.
+    /// MGA messages are assistance (input) messages: most firmwares do not answer this poll.
+    pub fn request_mga_gps_eph(&mut self) {
+        match self.write_all(&UbxPacketRequest::request_for::<MgaGpsEph>().into_packet_bytes()) {
+            Ok(_) => {
+                debug!("MGA-GPS-EPH");
+            },
+            Err(e) => {
+                error!("Failed to request MGA-GPS-EPH: {}", e);
+            },
+        }
+    }
 
-    // pub fn request_mga_glonass_eph(&mut self) {
-    //     match self.write_all(&UbxPacketRequest::request_for::<MgaGloEph>().into_packet_bytes()) {
-    //         Ok(_) => {
-    //             debug!("MGA-GLO-EPH");
-    //         },
-    //         Err(e) => {
-    //             error!("Failed to request MGA-GLO-EPH: {}", e);
-    //         },
-    //     }
-    // }
+    /// Polls MGA-GLO-EPH, the receiver replies with one message per known satellite.
+    /// MGA messages are assistance (input) messages: most firmwares do not answer this poll.
+    pub fn request_mga_glonass_eph(&mut self) {
+        match self.write_all(&UbxPacketRequest::request_for::<MgaGloEph>().into_packet_bytes()) {
+            Ok(_) => {
+                debug!("MGA-GLO-EPH");
+            },
+            Err(e) => {
+                error!("Failed to request MGA-GLO-EPH: {}", e);
+            },
+        }
+    }
+
+    /// Polls MON-RF, the receiver replies with the RF telemetry of each RF block
+    pub fn request_mon_rf(&mut self) {
+        match self.write_all(&UbxPacketRequest::request_for::<MonRf>().into_packet_bytes()) {
+            Ok(_) => {
+                debug!("MON-RF");
+            },
+            Err(e) => {
+                error!("Failed to request MON-RF: {}", e);
+            },
+        }
+    }
 
-    pub fn read_version(&mut self, buffer: &mut [u8], tx: Sender<Message>) -> std::io::Result<()> {
+    /// Reads the firmware version, returns the receiver model when the firmware describes it.
+    pub fn read_version(
+        &mut self,
+        buffer: &mut [u8],
+        tx: Sender<Message>,
+    ) -> std::io::Result<Option<String>> {
         self.write_all(&UbxPacketRequest::request_for::<MonVer>().into_packet_bytes())
             .unwrap_or_else(|e| panic!("Failed to request firmware version: {}", e));
 
         let mut packet_found = false;
+        let mut model = Option::<String>::None;
+        let mut protocol = Option::<(u8, u8)>::None;
+
+        let t0 = Instant::now();
 
         while !packet_found {
+            if t0.elapsed() > self.ack_timeout {
+                return Err(std::io::Error::new(
+                    ErrorKind::TimedOut,
+                    format!(
+                        "device did not reply to MON-VER within {:?}",
+                        self.ack_timeout
+                    ),
+                ));
+            }
+
             self.consume_all_cb(buffer, |packet| {
                 #[cfg(feature = "ubx14")]
                 if let ublox::UbxPacket::Proto14(PacketRef::MonVer(pkt)) = packet {
@@ -266,6 +640,13 @@ impl<P: UbxProtocol> Device<P> {
                             panic!("internal error reading firmware version: {}", e)
                         });
 
+                    model = pkt
+                        .extension()
+                        .find_map(|ext| ext.strip_prefix("MOD="))
+                        .map(|model| model.to_string());
+
+                    protocol = pkt.extension().find_map(protocol_version);
+
                     packet_found = true;
                 }
 
@@ -280,6 +661,13 @@ impl<P: UbxProtocol> Device<P> {
                             panic!("internal error reading firmware version: {}", e)
                         });
 
+                    model = pkt
+                        .extension()
+                        .find_map(|ext| ext.strip_prefix("MOD="))
+                        .map(|model| model.to_string());
+
+                    protocol = pkt.extension().find_map(protocol_version);
+
                     packet_found = true;
                 }
 
@@ -294,6 +682,13 @@ impl<P: UbxProtocol> Device<P> {
                             panic!("internal error reading firmware version: {}", e)
                         });
 
+                    model = pkt
+                        .extension()
+                        .find_map(|ext| ext.strip_prefix("MOD="))
+                        .map(|model| model.to_string());
+
+                    protocol = pkt.extension().find_map(protocol_version);
+
                     packet_found = true;
                 }
 
@@ -308,34 +703,67 @@ impl<P: UbxProtocol> Device<P> {
                             panic!("internal error reading firmware version: {}", e)
                         });
 
+                    model = pkt
+                        .extension()
+                        .find_map(|ext| ext.strip_prefix("MOD="))
+                        .map(|model| model.to_string());
+
+                    protocol = pkt.extension().find_map(protocol_version);
+
                     packet_found = true;
                 }
             })?;
         }
 
-        Ok(())
+        match protocol {
+            Some((major, minor)) => {
+                debug!("U-Blox protocol version: {}.{:02}", major, minor);
+                check_protocol(major, minor)?;
+            },
+            None => {
+                warn!("U-Blox protocol version is unknown");
+            },
+        }
+
+        Ok(model)
     }
 
+    /// Applies the measurement rate, clamped to the receiver capability (when model is known)
     pub fn apply_cfg_rate(
         &mut self,
         buffer: &mut [u8],
         measure_rate_ms: u16,
         nav_solutions_ratio: u16,
         time_ref: AlignmentToReferenceTime,
-    ) {
-        self.write_all(
+        model: Option<&str>,
+    ) -> u16 {
+        let measure_rate_ms = match model.and_then(min_measure_rate_ms) {
+            Some(min_rate_ms) if measure_rate_ms < min_rate_ms => {
+                warn!(
+                    "{} cannot sample faster than {}ms ({}Hz): requested {}ms, using {}ms instead",
+                    model.unwrap_or_default(),
+                    min_rate_ms,
+                    1000 / min_rate_ms,
+                    measure_rate_ms,
+                    min_rate_ms
+                );
+                min_rate_ms
+            },
+            _ => measure_rate_ms,
+        };
+
+        self.request_with_ack::<CfgRate>(
             &CfgRateBuilder {
                 measure_rate_ms,
                 nav_rate: nav_solutions_ratio,
                 time_ref,
             }
             .into_packet_bytes(),
+            buffer,
         )
         .unwrap_or_else(|e| panic!("UBX-CFG-RATE: {}", e));
 
-        self.wait_for_ack::<CfgRate>(buffer).unwrap_or_else(|e| {
-            panic!("UBX-CFG-RATE NACK: {}", e);
-        });
+        measure_rate_ms
     }
 
     fn enable_rxm_sfrbx(&mut self, enable: bool, buffer: &mut [u8]) {
@@ -347,10 +775,7 @@ impl<P: UbxProtocol> Device<P> {
             CfgMsgAllPortsBuilder::set_rate_for::<RxmSfrbx>([0, 0, 0, 0, 0, 0])
         };
 
-        self.write_all(&msg.into_packet_bytes())
-            .unwrap_or_else(|e| panic!("UBX-RXM-SFRBX error: {}", e));
-
-        self.wait_for_ack::<CfgMsgAllPorts>(buffer)
+        self.request_with_ack::<CfgMsgAllPorts>(&msg.into_packet_bytes(), buffer)
             .unwrap_or_else(|e| panic!("UBX-RXM-SFRBX error: {}", e));
     }
 
@@ -363,10 +788,7 @@ impl<P: UbxProtocol> Device<P> {
             CfgMsgAllPortsBuilder::set_rate_for::<RxmRawx>([0, 0, 0, 0, 0, 0])
         };
 
-        self.write_all(&msg.into_packet_bytes())
-            .unwrap_or_else(|e| panic!("UBX-RXM-RAWX error: {}", e));
-
-        self.wait_for_ack::<CfgMsgAllPorts>(buffer)
+        self.request_with_ack::<CfgMsgAllPorts>(&msg.into_packet_bytes(), buffer)
             .unwrap_or_else(|e| panic!("UBX-RXM-RAWX error: {}", e));
     }
 
@@ -374,86 +796,181 @@ impl<P: UbxProtocol> Device<P> {
         // By setting 1 in the array below, we enable the NavPvt message for Uart1, Uart2 and USB
         // The other positions are for I2C, SPI, etc. Consult your device manual.
 
-        self.write_all(
+        self.request_with_ack::<CfgMsgAllPorts>(
             &CfgMsgAllPortsBuilder::set_rate_for::<NavEoe>([1, 1, 1, 1, 1, 1]).into_packet_bytes(),
+            buffer,
         )
         .unwrap_or_else(|e| panic!("UBX-NAV-EOE error: {}", e));
 
-        self.wait_for_ack::<CfgMsgAllPorts>(buffer)
-            .unwrap_or_else(|e| panic!("UBX-RXM-EOE error: {}", e));
-
         debug!("UBX-NAV-EOE enabled");
     }
 
+    fn enable_tim_tm2(&mut self, buffer: &mut [u8]) {
+        self.request_with_ack::<CfgMsgAllPorts>(
+            &CfgMsgAllPortsBuilder::set_rate_for::<TimTm2>([1, 1, 1, 1, 1, 1]).into_packet_bytes(),
+            buffer,
+        )
+        .unwrap_or_else(|e| panic!("UBX-TIM-TM2 error: {}", e));
+    }
+
+    fn enable_tim_tp(&mut self, buffer: &mut [u8]) {
+        self.request_with_ack::<CfgMsgAllPorts>(
+            &CfgMsgAllPortsBuilder::set_rate_for::<TimTp>([1, 1, 1, 1, 1, 1]).into_packet_bytes(),
+            buffer,
+        )
+        .unwrap_or_else(|e| panic!("UBX-TIM-TP error: {}", e));
+
+        debug!("UBX-TIM-TP enabled");
+    }
+
     fn enable_nav_clock(&mut self, buffer: &mut [u8]) {
-        self.write_all(
+        self.request_with_ack::<CfgMsgAllPorts>(
             &CfgMsgAllPortsBuilder::set_rate_for::<NavClock>([1, 1, 1, 1, 1, 1])
                 .into_packet_bytes(),
+            buffer,
         )
         .unwrap_or_else(|e| panic!("UBX-NAV-CLK error: {}", e));
-
-        self.wait_for_ack::<CfgMsgAllPorts>(buffer)
-            .unwrap_or_else(|e| panic!("UBX-RXM-CLK error: {}", e));
     }
 
-    pub fn enable_nav_sat(&mut self, buffer: &mut [u8]) {
-        // By setting 1 in the array below, we enable the NavPvt message for Uart1, Uart2 and USB
-        // The other positions are for I2C, SPI, etc. Consult your device manual.
+    pub fn enable_nav_sat(&mut self, enable: bool, buffer: &mut [u8]) {
+        let msg = if enable {
+            // By setting 1 in the array below, we enable the NavSat message for Uart1, Uart2 and USB
+            // The other positions are for I2C, SPI, etc. Consult your device manual.
+            CfgMsgAllPortsBuilder::set_rate_for::<NavSat>([1, 1, 1, 1, 1, 1])
+        } else {
+            CfgMsgAllPortsBuilder::set_rate_for::<NavSat>([0, 0, 0, 0, 0, 0])
+        };
+
+        self.request_with_ack::<CfgMsgAllPorts>(&msg.into_packet_bytes(), buffer)
+            .unwrap_or_else(|e| panic!("UBX-NAV-SAT error: {}", e));
+
+        debug!(
+            "UBX-NAV-SAT {}",
+            if enable { "enabled" } else { "disabled" }
+        );
+    }
 
-        self.write_all(
-            &CfgMsgAllPortsBuilder::set_rate_for::<NavSat>([1, 1, 1, 1, 1, 1]).into_packet_bytes(),
+    #[cfg(any(feature = "ubx27", feature = "ubx31"))]
+    fn enable_nav_sig(&mut self, buffer: &mut [u8]) {
+        self.request_with_ack::<CfgMsgAllPorts>(
+            &CfgMsgAllPortsBuilder::set_rate_for::<NavSig>([1, 1, 1, 1, 1, 1]).into_packet_bytes(),
+            buffer,
         )
-        .unwrap_or_else(|e| panic!("UBX-NAV-SAT error: {}", e));
+        .unwrap_or_else(|e| panic!("UBX-NAV-SIG error: {}", e));
+
+        debug!("UBX-NAV-SIG enabled");
+    }
+
+    pub fn enable_nav_pvt(&mut self, enable: bool, buffer: &mut [u8]) {
+        let msg = if enable {
+            // By setting 1 in the array below, we enable the NavPvt message for Uart1, Uart2 and USB
+            // The other positions are for I2C, SPI, etc. Consult your device manual.
+            CfgMsgAllPortsBuilder::set_rate_for::<NavPvt>([1, 1, 1, 1, 1, 1])
+        } else {
+            CfgMsgAllPortsBuilder::set_rate_for::<NavPvt>([0, 0, 0, 0, 0, 0])
+        };
 
-        self.wait_for_ack::<CfgMsgAllPorts>(buffer)
-            .unwrap_or_else(|e| panic!("UBX-RXM-SAT error: {}", e));
+        self.request_with_ack::<CfgMsgAllPorts>(&msg.into_packet_bytes(), buffer)
+            .unwrap_or_else(|e| panic!("UBX-NAV-PVT error: {}", e));
 
-        debug!("UBX-NAV-SAT enabled");
+        debug!(
+            "UBX-NAV-PVT {}",
+            if enable { "enabled" } else { "disabled" }
+        );
     }
 
-    pub fn enable_nav_pvt(&mut self, buffer: &mut [u8]) {
-        // By setting 1 in the array below, we enable the NavPvt message for Uart1, Uart2 and USB
-        // The other positions are for I2C, SPI, etc. Consult your device manual.
+    /// Reads the constellations the receiver supports and currently enables (MON-GNSS),
+    /// and warns about the requested constellations it does not support,
+    /// which would otherwise result in silently empty observations.
+    /// The enabled constellations define the RXM-RAWX time reference, unless specified.
+    pub fn read_gnss(
+        &mut self,
+        settings: &mut UbloxSettings,
+        buffer: &mut [u8],
+    ) -> std::io::Result<()> {
+        self.write_all(&UbxPacketRequest::request_for::<MonGnss>().into_packet_bytes())?;
+
+        let mut masks = None;
+
+        let t0 = Instant::now();
+
+        while masks.is_none() {
+            if t0.elapsed() > self.ack_timeout {
+                return Err(std::io::Error::new(
+                    ErrorKind::TimedOut,
+                    format!(
+                        "device did not reply to MON-GNSS within {:?}",
+                        self.ack_timeout
+                    ),
+                ));
+            }
 
-        self.write_all(
-            &CfgMsgAllPortsBuilder::set_rate_for::<NavPvt>([1, 1, 1, 1, 1, 1]).into_packet_bytes(),
-        )
-        .unwrap_or_else(|e| panic!("UBX-NAV-PVT error: {}", e));
-
-        self.wait_for_ack::<CfgMsgAllPorts>(buffer)
-            .unwrap_or_else(|e| panic!("UBX-RXM-PVT error: {}", e));
-
-        debug!("UBX-NAV-PVT enabled");
-    }
-
-    // pub fn read_gnss(&mut self, buffer: &mut [u8]) -> std::io::Result<()> {
-    //     self.write_all(&UbxPacketRequest::request_for::<MonGnss>().into_packet_bytes())
-    //         .unwrap_or_else(|e| panic!("Failed to request firmware version: {}", e));
-
-    //     let mut packet_found = false;
-    //     while !packet_found {
-    //         self.consume_all_cb(buffer, |packet| {
-    //             if let PacketRef::MonGnss(pkt) = packet {
-    //                 info!(
-    //                     "Enabled constellations: {}",
-    //                     constell_mask_to_string(pkt.enabled())
-    //                 );
-    //                 info!(
-    //                     "Supported constellations: {}",
-    //                     constell_mask_to_string(pkt.supported())
-    //                 );
-    //                 packet_found = true;
-    //             }
-    //         })?;
-    //     }
-    //     Ok(())
-    // }
+            self.consume_all_cb(buffer, |packet| {
+                #[cfg(feature = "ubx14")]
+                if let ublox::UbxPacket::Proto14(PacketRef::MonGnss(pkt)) = packet {
+                    masks = Some((pkt.supported(), pkt.enabled()));
+                }
+
+                #[cfg(feature = "ubx23")]
+                if let ublox::UbxPacket::Proto23(PacketRef::MonGnss(pkt)) = packet {
+                    masks = Some((pkt.supported(), pkt.enabled()));
+                }
+
+                #[cfg(feature = "ubx27")]
+                if let ublox::UbxPacket::Proto27(PacketRef::MonGnss(pkt)) = packet {
+                    masks = Some((pkt.supported(), pkt.enabled()));
+                }
+
+                #[cfg(feature = "ubx31")]
+                if let ublox::UbxPacket::Proto31(PacketRef::MonGnss(pkt)) = packet {
+                    masks = Some((pkt.supported(), pkt.enabled()));
+                }
+            })?;
+        }
+
+        let (supported, enabled) = masks.unwrap();
+
+        let names = |mask| {
+            mask_constellations(mask)
+                .iter()
+                .map(|constellation| constellation.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        info!("Supported constellations: {}", names(supported));
+        info!("Enabled constellations: {}", names(enabled));
+
+        let unsupported = constellations_to_mask(&settings.constellations).difference(supported);
+
+        for constellation in mask_constellations(unsupported) {
+            warn!(
+                "{} is not supported by this receiver: it will not be observed",
+                constellation
+            );
+        }
+
+        if settings.rawx_timescale.is_none() {
+            let timescale = receiver_timescale(&mask_constellations(enabled));
+            debug!("RXM-RAWX time reference: {}", timescale);
+            settings.rawx_timescale = Some(timescale);
+        }
+
+        Ok(())
+    }
 
     /// Reads internal [Interface], converting timeouts into "No Data Received",
     /// which is most convenient for real-time perpertual hardware application like this one.
     fn read_interface(&mut self, output: &mut [u8]) -> std::io::Result<usize> {
         match self.interface.read(output) {
-            Ok(b) => Ok(b),
+            Ok(b) => {
+                if let Some(raw) = &mut self.raw_output {
+                    if let Err(e) = raw.write_all(&output[..b]) {
+                        error!("Failed to write raw UBX output: {}", e);
+                    }
+                }
+                Ok(b)
+            },
             Err(e) => {
                 if e.kind() == ErrorKind::TimedOut {
                     Ok(0)
@@ -464,3 +981,178 @@ impl<P: UbxProtocol> Device<P> {
         }
     }
 }
+
+/// Returns the shortest RXM-RAWX measurement period (ms) of this receiver model, when known.
+fn min_measure_rate_ms(model: &str) -> Option<u16> {
+    let model = model.to_uppercase();
+
+    if model.contains("F9") {
+        Some(50) // 20 Hz
+    } else if model.contains("M8") || model.contains("M10") {
+        Some(100) // 10 Hz
+    } else {
+        None
+    }
+}
+
+/// Verifies that the device protocol version can be handled by this build.
+/// The parser is selected at build time: a device that uses a more recent protocol
+/// requires another build, because its messages would not be interpreted correctly.
+fn check_protocol(major: u8, minor: u8) -> std::io::Result<()> {
+    let suited = suited_protocol(major);
+
+    if suited > PROTOCOL {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!(
+                "device uses UBX protocol {}.{:02} but ubx2rinex was built for protocol {}: rebuild with --no-default-features --features ubx{}",
+                major, minor, PROTOCOL, suited
+            ),
+        ));
+    }
+
+    if suited < PROTOCOL {
+        warn!(
+            "device uses UBX protocol {}.{:02}, older than protocol {} this build is meant for: some messages may not be supported",
+            major, minor, PROTOCOL
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::{Device, check_protocol, min_measure_rate_ms};
+    use crate::{PROTOCOL, Proto};
+    use std::{
+        io::{Cursor, ErrorKind},
+        time::{Duration, Instant},
+    };
+    use ublox::cfg_rate::CfgRate;
+
+    /// Frames a UBX packet, with its checksum
+    pub(crate) fn ubx_frame(class: u8, id: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0xb5, 0x62, class, id];
+        frame.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        frame.extend_from_slice(payload);
+
+        let (mut ck_a, mut ck_b) = (0u8, 0u8);
+
+        for byte in frame[2..].iter() {
+            ck_a = ck_a.wrapping_add(*byte);
+            ck_b = ck_b.wrapping_add(ck_a);
+        }
+
+        frame.push(ck_a);
+        frame.push(ck_b);
+        frame
+    }
+
+    #[test]
+    fn truncated_capture() {
+        let mut capture = Vec::new();
+
+        for itow in [1000u32, 2000u32] {
+            capture.extend_from_slice(&ubx_frame(0x01, 0x61, &itow.to_le_bytes()));
+        }
+
+        // killed logger: last packet is incomplete
+        let last = ubx_frame(0x01, 0x61, &3000u32.to_le_bytes());
+        capture.extend_from_slice(&last[..last.len() - 3]);
+
+        let path = std::env::temp_dir().join("ubx2rinex-truncated.ubx");
+        std::fs::write(&path, &capture).unwrap();
+
+        let mut device = Device::<Proto>::open_file(path.to_str().unwrap());
+
+        let mut buffer = [0; 8192];
+        let mut total = 0;
+
+        loop {
+            match device.consume_all_cb(&mut buffer, |_| {}) {
+                Ok(0) => break,
+                Ok(n) => total += n,
+                Err(e) => panic!("unexpected I/O error: {}", e),
+            }
+        }
+
+        assert_eq!(total, 2, "both complete packets should be decoded");
+        assert!(
+            device.parser.is_buffer_empty(),
+            "partial packet should be discarded"
+        );
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn measure_rate_limits() {
+        assert_eq!(min_measure_rate_ms("ZED-F9P"), Some(50));
+        assert_eq!(min_measure_rate_ms("f9t"), Some(50));
+        assert_eq!(min_measure_rate_ms("M8T u-Blox"), Some(100));
+        assert_eq!(min_measure_rate_ms("NEO-M8T"), Some(100));
+        assert_eq!(min_measure_rate_ms("custom"), None);
+    }
+
+    #[test]
+    fn protocol_check() {
+        // older protocols are tolerated
+        assert!(check_protocol(14, 0).is_ok());
+        assert!(check_protocol(PROTOCOL, 0).is_ok());
+
+        // more recent protocols require another build
+        let newer = check_protocol(50, 0);
+
+        if PROTOCOL < 31 {
+            let e = newer.unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::Unsupported);
+            assert!(e.to_string().contains("--features ubx31"));
+        } else {
+            assert!(newer.is_ok());
+        }
+    }
+
+    #[test]
+    fn acknowledgment() {
+        let mut buffer = [0; 256];
+
+        // ACK-ACK (CFG-RATE)
+        let ack = ubx_frame(0x05, 0x01, &[0x06, 0x08]);
+        let mut device = Device::<Proto>::from_reader(Box::new(Cursor::new(ack)));
+        assert!(device.wait_for_ack::<CfgRate>(&mut buffer).is_ok());
+
+        // ACK-NAK (CFG-RATE)
+        let nak = ubx_frame(0x05, 0x00, &[0x06, 0x08]);
+        let mut device = Device::<Proto>::from_reader(Box::new(Cursor::new(nak)));
+        assert!(device.wait_for_ack::<CfgRate>(&mut buffer).is_err());
+
+        // silent device
+        let mut device = Device::<Proto>::from_reader(Box::new(Cursor::new(Vec::new())));
+        device.ack_timeout = Duration::from_millis(10);
+
+        let e = device.wait_for_ack::<CfgRate>(&mut buffer).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::TimedOut);
+
+        // silent device: the request is sent again
+        device.ack_retries = 2;
+
+        let t0 = Instant::now();
+        let e = device
+            .request_with_ack::<CfgRate>(&[], &mut buffer)
+            .unwrap_err();
+
+        assert_eq!(e.kind(), ErrorKind::TimedOut);
+        assert!(t0.elapsed() >= Duration::from_millis(30));
+
+        // rejection: not retried
+        let nak = ubx_frame(0x05, 0x00, &[0x06, 0x08]);
+        let mut device = Device::<Proto>::from_reader(Box::new(Cursor::new(nak)));
+        device.ack_timeout = Duration::from_millis(10);
+
+        let e = device
+            .request_with_ack::<CfgRate>(&[], &mut buffer)
+            .unwrap_err();
+        assert_ne!(e.kind(), ErrorKind::TimedOut);
+    }
+}
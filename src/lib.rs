@@ -0,0 +1,62 @@
+#![doc(
+    html_logo_url = "https://raw.githubusercontent.com/nav-solutions/.github/master/logos/logo2.jpg"
+)]
+#![doc = include_str!("../README.md")]
+#![cfg_attr(docsrs, feature(doc_cfg))]
+#![allow(clippy::type_complexity)]
+
+/*
+ * UBX2RINEX is part of the nav-solutions framework.
+ * Authors: Guillaume W. Bres <guillaume.bressaix@gmail.com> et al,
+ * (cf. https://github.com/nav-solutions/rinex/graphs/contributors)
+ * (cf. https://github.com/nav-solutions/ubx2rinex/graphs/contributors)
+ * This framework is shipped under Mozilla Public V2 license.
+ *
+ * Documentation: https://github.com/nav-solutions/ubx2rinex
+ */
+
+extern crate gnss_rs as gnss;
+extern crate ublox;
+
+#[cfg(feature = "ubx14")]
+pub type Proto = ublox::proto14::Proto14;
+
+#[cfg(feature = "ubx23")]
+pub type Proto = ublox::proto23::Proto23;
+
+#[cfg(feature = "ubx27")]
+pub type Proto = ublox::proto27::Proto27;
+
+#[cfg(feature = "ubx31")]
+pub type Proto = ublox::proto31::Proto31;
+
+/// UBX protocol this crate was built for
+#[cfg(feature = "ubx14")]
+pub const PROTOCOL: u8 = 14;
+
+/// UBX protocol this crate was built for
+#[cfg(feature = "ubx23")]
+pub const PROTOCOL: u8 = 23;
+
+/// UBX protocol this crate was built for
+#[cfg(feature = "ubx27")]
+pub const PROTOCOL: u8 = 27;
+
+/// UBX protocol this crate was built for
+#[cfg(feature = "ubx31")]
+pub const PROTOCOL: u8 = 31;
+
+pub mod collecter;
+pub mod device;
+pub mod packet;
+pub mod runtime;
+pub mod ubx;
+pub mod utils;
+
+mod converter;
+
+pub use crate::{
+    collecter::{Products, memory::Records, settings::Settings as RinexSettings},
+    converter::{UbxToRinex, convert_parallel},
+    ubx::Settings as UbloxSettings,
+};
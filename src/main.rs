@@ -1,8 +1,3 @@
-#![doc(
-    html_logo_url = "https://raw.githubusercontent.com/nav-solutions/.github/master/logos/logo2.jpg"
-)]
-#![doc = include_str!("../README.md")]
-#![cfg_attr(docsrs, feature(doc_cfg))]
 #![allow(clippy::type_complexity)]
 
 /*
@@ -15,1702 +10,611 @@
  * Documentation: https://github.com/nav-solutions/ubx2rinex
  */
 
-extern crate gnss_rs as gnss;
-extern crate ublox;
+use env_logger::{Builder, Target, fmt::Formatter};
 
-#[cfg(feature = "ubx14")]
-pub(crate) type Proto = ublox::proto14::Proto14;
-
-#[cfg(feature = "ubx23")]
-pub(crate) type Proto = ublox::proto23::Proto23;
-
-#[cfg(feature = "ubx27")]
-pub(crate) type Proto = ublox::proto27::Proto27;
-
-#[cfg(feature = "ubx31")]
-pub(crate) type Proto = ublox::proto31::Proto31;
-
-use itertools::Itertools;
-
-use env_logger::{Builder, Target};
-
-use log::{debug, error, info, trace, warn};
+use log::{Record, error, info, warn};
 
 use tokio::{
     signal,
     sync::{mpsc, watch},
 };
 
-use std::fs::File;
+use std::{fs::File, io::Write, str::FromStr};
 
-use rinex::prelude::{Constellation, Duration, Epoch, SV, TimeScale};
+use rinex::prelude::{Constellation, Epoch, SV, TimeScale};
 
-use ublox::{
-    UbxPacket, nav_pvt::common::NavPvtValidFlags, nav_time_utc::NavTimeUtcFlags,
-    rxm_rawx::RecStatFlags,
-};
+mod cli;
 
-#[cfg(feature = "ubx14")]
-use ublox::packetref_proto14::PacketRef;
-#[cfg(feature = "ubx23")]
-use ublox::packetref_proto23::PacketRef;
-#[cfg(feature = "ubx27")]
-use ublox::packetref_proto27::PacketRef;
-#[cfg(feature = "ubx31")]
-use ublox::packetref_proto31::PacketRef;
+use crate::cli::Cli;
 
-mod cli;
-mod collecter;
-mod device;
-mod runtime;
-mod ubx;
-mod utils;
-
-use crate::{
-    cli::Cli,
+use ubx2rinex::{
+    Proto, RinexSettings, UbloxSettings,
     collecter::{
-        Message, navigation::Collecter as NavCollecter, observation::Collecter as ObsCollecter,
-        rawxm::Rawxm,
+        navigation::Collecter as NavCollecter, observation::Collecter as ObsCollecter,
+        qc::Collecter as QcCollecter, sbas, spectrum, timepulse, velocity,
     },
+    convert_parallel,
     device::Device,
+    packet::{
+        Channels, PassiveOptions, consume_device, consume_passive, release_ephemeris,
+        send_header_comments, shutdown_collecters, source_comment,
+    },
     runtime::Runtime,
-    ubx::Settings as UbloxSettings,
-    utils::to_constellation,
 };
 
-const SBAS_PRN_OFFSET: u8 = 100;
-
-fn consume_device(
-    runtime: &mut Runtime,
-    obs_tx: &mut mpsc::Sender<Message>,
-    nav_tx: &mut mpsc::Sender<Message>,
-    device: &mut Device<Proto>,
-    buffer: &mut [u8],
-    cfg_precision: Duration,
-    ubx_settings: &UbloxSettings,
-) -> std::io::Result<usize> {
-    let mut end_of_nav_epoch = false;
-
-    device.consume_all_cb(buffer, |packet| {
-        match packet {
-            #[cfg(feature = "ubx14")]
-            UbxPacket::Proto14(PacketRef::CfgNav5(_)) => {
-                // TODO: Dynamic model ?
-                // let _dyn_model = pkt.dyn_model();
-            },
-
-            #[cfg(feature = "ubx23")]
-            UbxPacket::Proto23(PacketRef::CfgNav5(_)) => {
-                // TODO: Dynamic model ?
-                // let _dyn_model = pkt.dyn_model();
-            },
-
-            #[cfg(feature = "ubx27")]
-            UbxPacket::Proto27(PacketRef::CfgNav5(_)) => {
-                // TODO: Dynamic model ?
-                // let _dyn_model = pkt.dyn_model();
-            },
-
-            #[cfg(feature = "ubx31")]
-            UbxPacket::Proto31(PacketRef::CfgNav5(_)) => {
-                // TODO: Dynamic model ?
-                // let _dyn_model = pkt.dyn_model();
-            },
-
-            #[cfg(feature = "ubx14")]
-            UbxPacket::Proto14(PacketRef::RxmSfrbx(sfrbx)) => {
-                // Do not process if user is not interested in this channel.
-                // When attached to hardware this naturally never happens.
-                // But this may arise in passive mode.
-                if ubx_settings.ephemeris {
-                    let gnss_id = sfrbx.gnss_id();
-
-                    match to_constellation(gnss_id) {
-                        Some(constellation) => {
-                            // does not proceeed if we're not interested by this system
-                            if ubx_settings.constellations.contains(&constellation) {
-                                let mut prn = sfrbx.sv_id();
-
-                                if constellation.is_sbas() && prn >= SBAS_PRN_OFFSET {
-                                    prn -= SBAS_PRN_OFFSET;
-                                }
-
-                                let sv = SV::new(constellation, prn);
-
-                                match constellation {
-                                    Constellation::GPS | Constellation::QZSS => {
-                                        // decode
-                                        if let Some(interpretation) = sfrbx.interpret() {
-                                            debug!(
-                                                "{} - decoded {:?}",
-                                                runtime.utc_time().round(cfg_precision),
-                                                interpretation
-                                            );
-
-                                            runtime.latch_sfrbx(sv, interpretation, cfg_precision);
-                                        } else {
-                                            error!(
-                                                "{} - SFRBX interpretation issue",
-                                                runtime.utc_time().round(cfg_precision)
-                                            );
-                                        }
-                                    },
-                                    c => {
-                                        error!(
-                                            "{} - {} constellation not handled yet",
-                                            runtime.utc_time().round(cfg_precision),
-                                            c
-                                        );
-                                    },
-                                }
-                            }
-                        },
-                        None => {
-                            error!(
-                                "{} - constellation id error #{}",
-                                runtime.utc_time().round(cfg_precision),
-                                gnss_id
-                            );
-                        },
-                    }
-                }
-            },
-
-            #[cfg(feature = "ubx23")]
-            UbxPacket::Proto23(PacketRef::RxmSfrbx(sfrbx)) => {
-                // Do not process if user is not interested in this channel.
-                // When attached to hardware this naturally never happens.
-                // But this may arise in passive mode.
-                if ubx_settings.ephemeris {
-                    let gnss_id = sfrbx.gnss_id();
-
-                    match to_constellation(gnss_id) {
-                        Some(constellation) => {
-                            // does not proceeed if we're not interested by this system
-                            if ubx_settings.constellations.contains(&constellation) {
-                                let mut prn = sfrbx.sv_id();
-
-                                if constellation.is_sbas() && prn >= SBAS_PRN_OFFSET {
-                                    prn -= SBAS_PRN_OFFSET;
-                                }
-
-                                let sv = SV::new(constellation, prn);
-
-                                match constellation {
-                                    Constellation::GPS | Constellation::QZSS => {
-                                        // decode
-                                        if let Some(interpretation) = sfrbx.interpret() {
-                                            debug!(
-                                                "{} - decoded {:?}",
-                                                runtime.utc_time().round(cfg_precision),
-                                                interpretation
-                                            );
-
-                                            runtime.latch_sfrbx(sv, interpretation, cfg_precision);
-                                        } else {
-                                            error!(
-                                                "{} - SFRBX interpretation issue",
-                                                runtime.utc_time().round(cfg_precision)
-                                            );
-                                        }
-                                    },
-                                    c => {
-                                        error!(
-                                            "{} - {} constellation not handled yet",
-                                            runtime.utc_time().round(cfg_precision),
-                                            c
-                                        );
-                                    },
-                                }
-                            }
-                        },
-                        None => {
-                            error!(
-                                "{} - constellation id error #{}",
-                                runtime.utc_time().round(cfg_precision),
-                                gnss_id
-                            );
-                        },
-                    }
-                }
-            },
-
-            #[cfg(feature = "ubx27")]
-            UbxPacket::Proto27(PacketRef::RxmSfrbx(sfrbx)) => {
-                // Do not process if user is not interested in this channel.
-                // When attached to hardware this naturally never happens.
-                // But this may arise in passive mode.
-                if ubx_settings.ephemeris {
-                    let gnss_id = sfrbx.gnss_id();
-
-                    match to_constellation(gnss_id) {
-                        Some(constellation) => {
-                            // does not proceeed if we're not interested by this system
-                            if ubx_settings.constellations.contains(&constellation) {
-                                let mut prn = sfrbx.sv_id();
-
-                                if constellation.is_sbas() && prn >= SBAS_PRN_OFFSET {
-                                    prn -= SBAS_PRN_OFFSET;
-                                }
-
-                                let sv = SV::new(constellation, prn);
-
-                                match constellation {
-                                    Constellation::GPS | Constellation::QZSS => {
-                                        // decode
-                                        if let Some(interpretation) = sfrbx.interpret() {
-                                            debug!(
-                                                "{} - decoded {:?}",
-                                                runtime.utc_time().round(cfg_precision),
-                                                interpretation
-                                            );
-
-                                            runtime.latch_sfrbx(sv, interpretation, cfg_precision);
-                                        } else {
-                                            error!(
-                                                "{} - SFRBX interpretation issue",
-                                                runtime.utc_time().round(cfg_precision)
-                                            );
-                                        }
-                                    },
-                                    c => {
-                                        error!(
-                                            "{} - {} constellation not handled yet",
-                                            runtime.utc_time().round(cfg_precision),
-                                            c
-                                        );
-                                    },
-                                }
-                            }
-                        },
-                        None => {
-                            error!(
-                                "{} - constellation id error #{}",
-                                runtime.utc_time().round(cfg_precision),
-                                gnss_id
-                            );
-                        },
-                    }
-                }
-            },
-
-            #[cfg(feature = "ubx31")]
-            UbxPacket::Proto31(PacketRef::RxmSfrbx(sfrbx)) => {
-                // Do not process if user is not interested in this channel.
-                // When attached to hardware this naturally never happens.
-                // But this may arise in passive mode.
-                if ubx_settings.ephemeris {
-                    let gnss_id = sfrbx.gnss_id();
-
-                    match to_constellation(gnss_id) {
-                        Some(constellation) => {
-                            // does not proceeed if we're not interested by this system
-                            if ubx_settings.constellations.contains(&constellation) {
-                                let mut prn = sfrbx.sv_id();
-
-                                if constellation.is_sbas() && prn >= SBAS_PRN_OFFSET {
-                                    prn -= SBAS_PRN_OFFSET;
-                                }
-
-                                let sv = SV::new(constellation, prn);
-
-                                match constellation {
-                                    Constellation::GPS | Constellation::QZSS => {
-                                        // decode
-                                        if let Some(interpretation) = sfrbx.interpret() {
-                                            debug!(
-                                                "{} - decoded {:?}",
-                                                runtime.utc_time().round(cfg_precision),
-                                                interpretation
-                                            );
-
-                                            runtime.latch_sfrbx(sv, interpretation, cfg_precision);
-                                        } else {
-                                            error!(
-                                                "{} - SFRBX interpretation issue",
-                                                runtime.utc_time().round(cfg_precision)
-                                            );
-                                        }
-                                    },
-                                    c => {
-                                        error!(
-                                            "{} - {} constellation not handled yet",
-                                            runtime.utc_time().round(cfg_precision),
-                                            c
-                                        );
-                                    },
-                                }
-                            }
-                        },
-                        None => {
-                            error!(
-                                "{} - constellation id error #{}",
-                                runtime.utc_time().round(cfg_precision),
-                                gnss_id
-                            );
-                        },
-                    }
-                }
-            },
-
-            #[cfg(feature = "ubx14")]
-            UbxPacket::Proto14(PacketRef::RxmRawx(pkt)) => {
-                // Do not process if user is not interested in this channel.
-                // When attached to hardware this naturally never happens.
-                // But this may arise in passive mode.
-                if ubx_settings.rawxm {
-                    let gpst_tow_nanos = (pkt.rcv_tow() * 1.0E9).round() as u64;
-
-                    let t_gpst = Epoch::from_time_of_week(
-                        pkt.week() as u32,
-                        gpst_tow_nanos,
-                        TimeScale::GPST,
-                    );
-
-                    runtime.new_epoch(t_gpst, ubx_settings.timescale);
-
-                    let stat = pkt.rec_stat();
-
-                    if stat.intersects(RecStatFlags::CLK_RESET) {
-                        error!("{} - clock reset!", t_gpst.round(cfg_precision));
-
-                        warn!(
-                            "{} - declaring phase cycle slip! - !!case is not handled!!",
-                            t_gpst.round(cfg_precision)
-                        );
-
-                        error!(
-                            "{} - phase cycle slip not correctly managed in current version",
-                            t_gpst.round(cfg_precision)
-                        );
-                    }
-
-                    for meas in pkt.measurements() {
-                        let pr = meas.pr_mes();
-                        let cp = meas.cp_mes();
-                        let dop = meas.do_mes();
-
-                        let _ = meas.pr_stdev(); // CXX deviation
-                        let _ = meas.cp_stdev(); // LXX deviation
-                        let _ = meas.do_stdev(); // DXX deviation
-
-                        let gnss_id = meas.gnss_id();
-                        let cno = meas.cno();
-
-                        let constell = to_constellation(gnss_id);
-
-                        if constell.is_none() {
-                            error!(
-                                "{} - unknown constellation: #{}",
-                                runtime.utc_time().round(cfg_precision),
-                                gnss_id
-                            );
-                            continue;
-                        }
-
-                        let constell = constell.unwrap();
-
-                        // does not proceed if we're not interested by this system
-                        if ubx_settings.constellations.contains(&constell) {
-                            let mut prn = meas.sv_id();
-
-                            if constell.is_sbas() && prn >= SBAS_PRN_OFFSET {
-                                prn -= SBAS_PRN_OFFSET;
-                            };
-
-                            let sv = SV::new(constell, prn);
-                            let t_meas = t_gpst.to_time_scale(ubx_settings.timescale);
-
-                            let rawxm = Rawxm {
-                                epoch: t_meas,
-                                sv,
-                                pr,
-                                cp,
-                                cno,
-                                dop,
-                                freq_id: meas.freq_id(),
-                            };
-
-                            match obs_tx.try_send(Message::Measurement(rawxm)) {
-                                Ok(_) => {},
-                                Err(e) => {
-                                    error!(
-                                        "{}({}) failed to send measurement: {}",
-                                        t_meas.round(cfg_precision),
-                                        sv,
-                                        e
-                                    );
-                                },
-                            }
-                        }
-                    }
-                }
-            },
-
-            #[cfg(feature = "ubx23")]
-            UbxPacket::Proto23(PacketRef::RxmRawx(pkt)) => {
-                // Do not process if user is not interested in this channel.
-                // When attached to hardware this naturally never happens.
-                // But this may arise in passive mode.
-                if ubx_settings.rawxm {
-                    let gpst_tow_nanos = (pkt.rcv_tow() * 1.0E9).round() as u64;
-
-                    let t_gpst = Epoch::from_time_of_week(
-                        pkt.week() as u32,
-                        gpst_tow_nanos,
-                        TimeScale::GPST,
-                    );
-
-                    runtime.new_epoch(t_gpst, ubx_settings.timescale);
-
-                    let stat = pkt.rec_stat();
-
-                    if stat.intersects(RecStatFlags::CLK_RESET) {
-                        error!("{} - clock reset!", t_gpst.round(cfg_precision));
-
-                        warn!(
-                            "{} - declaring phase cycle slip! - !!case is not handled!!",
-                            t_gpst.round(cfg_precision)
-                        );
-
-                        error!(
-                            "{} - phase cycle slip not correctly managed in current version",
-                            t_gpst.round(cfg_precision)
-                        );
-                    }
-
-                    for meas in pkt.measurements() {
-                        let pr = meas.pr_mes();
-                        let cp = meas.cp_mes();
-                        let dop = meas.do_mes();
-
-                        let _ = meas.pr_stdev(); // CXX deviation
-                        let _ = meas.cp_stdev(); // LXX deviation
-                        let _ = meas.do_stdev(); // DXX deviation
-
-                        let gnss_id = meas.gnss_id();
-                        let cno = meas.cno();
-
-                        let constell = to_constellation(gnss_id);
-
-                        if constell.is_none() {
-                            error!(
-                                "{} - unknown constellation: #{}",
-                                runtime.utc_time().round(cfg_precision),
-                                gnss_id
-                            );
-                            continue;
-                        }
-
-                        let constell = constell.unwrap();
-
-                        // does not proceed if we're not interested by this system
-                        if ubx_settings.constellations.contains(&constell) {
-                            let mut prn = meas.sv_id();
-
-                            if constell.is_sbas() && prn >= SBAS_PRN_OFFSET {
-                                prn -= SBAS_PRN_OFFSET;
-                            };
-
-                            let sv = SV::new(constell, prn);
-                            let t_meas = t_gpst.to_time_scale(ubx_settings.timescale);
-
-                            let rawxm = Rawxm {
-                                epoch: t_meas,
-                                sv,
-                                pr,
-                                cp,
-                                cno,
-                                dop,
-                                freq_id: meas.freq_id(),
-                            };
-
-                            match obs_tx.try_send(Message::Measurement(rawxm)) {
-                                Ok(_) => {},
-                                Err(e) => {
-                                    error!(
-                                        "{}({}) failed to send measurement: {}",
-                                        t_meas.round(cfg_precision),
-                                        sv,
-                                        e
-                                    );
-                                },
-                            }
-                        }
-                    }
-                }
-            },
-
-            #[cfg(feature = "ubx27")]
-            UbxPacket::Proto27(PacketRef::RxmRawx(pkt)) => {
-                // Do not process if user is not interested in this channel.
-                // When attached to hardware this naturally never happens.
-                // But this may arise in passive mode.
-                if ubx_settings.rawxm {
-                    let gpst_tow_nanos = (pkt.rcv_tow() * 1.0E9).round() as u64;
-
-                    let t_gpst = Epoch::from_time_of_week(
-                        pkt.week() as u32,
-                        gpst_tow_nanos,
-                        TimeScale::GPST,
-                    );
-
-                    runtime.new_epoch(t_gpst, ubx_settings.timescale);
-
-                    let stat = pkt.rec_stat();
-
-                    if stat.intersects(RecStatFlags::CLK_RESET) {
-                        error!("{} - clock reset!", t_gpst.round(cfg_precision));
-
-                        warn!(
-                            "{} - declaring phase cycle slip! - !!case is not handled!!",
-                            t_gpst.round(cfg_precision)
-                        );
-
-                        error!(
-                            "{} - phase cycle slip not correctly managed in current version",
-                            t_gpst.round(cfg_precision)
-                        );
-                    }
+/// MGA ephemeris polling period, in --poll-eph mode
+const MGA_EPH_POLLING_PERIOD: std::time::Duration = std::time::Duration::from_secs(60);
 
-                    for meas in pkt.measurements() {
-                        let pr = meas.pr_mes();
-                        let cp = meas.cp_mes();
-                        let dop = meas.do_mes();
+/// MON-RF polling period, in --spectrum mode
+const MON_RF_POLLING_PERIOD: std::time::Duration = std::time::Duration::from_secs(10);
 
-                        let _ = meas.pr_stdev(); // CXX deviation
-                        let _ = meas.cp_stdev(); // LXX deviation
-                        let _ = meas.do_stdev(); // DXX deviation
+/// One receiver, producing its own RINEX set
+struct Station {
+    /// Station name, used in the file names
+    name: String,
 
-                        let gnss_id = meas.gnss_id();
-                        let cno = meas.cno();
+    /// Input [Device]
+    device: Device<Proto>,
 
-                        let constell = to_constellation(gnss_id);
+    /// [Runtime] of this receiver
+    runtime: Runtime,
 
-                        if constell.is_none() {
-                            error!(
-                                "{} - unknown constellation: #{}",
-                                runtime.utc_time().round(cfg_precision),
-                                gnss_id
-                            );
-                            continue;
-                        }
+    /// [UbloxSettings] of this receiver, refined by its configuration
+    ubx_settings: UbloxSettings,
 
-                        let constell = constell.unwrap();
-
-                        // does not proceed if we're not interested by this system
-                        if ubx_settings.constellations.contains(&constell) {
-                            let mut prn = meas.sv_id();
-
-                            if constell.is_sbas() && prn >= SBAS_PRN_OFFSET {
-                                prn -= SBAS_PRN_OFFSET;
-                            };
-
-                            let sv = SV::new(constell, prn);
-                            let t_meas = t_gpst.to_time_scale(ubx_settings.timescale);
-
-                            let rawxm = Rawxm {
-                                epoch: t_meas,
-                                sv,
-                                pr,
-                                cp,
-                                cno,
-                                dop,
-                                freq_id: meas.freq_id(),
-                            };
-
-                            match obs_tx.try_send(Message::Measurement(rawxm)) {
-                                Ok(_) => {},
-                                Err(e) => {
-                                    error!(
-                                        "{}({}) failed to send measurement: {}",
-                                        t_meas.round(cfg_precision),
-                                        sv,
-                                        e
-                                    );
-                                },
-                            }
-                        }
-                    }
-                }
-            },
-
-            #[cfg(feature = "ubx31")]
-            UbxPacket::Proto31(PacketRef::RxmRawx(pkt)) => {
-                // Do not process if user is not interested in this channel.
-                // When attached to hardware this naturally never happens.
-                // But this may arise in passive mode.
-                if ubx_settings.rawxm {
-                    let gpst_tow_nanos = (pkt.rcv_tow() * 1.0E9).round() as u64;
-
-                    let t_gpst = Epoch::from_time_of_week(
-                        pkt.week() as u32,
-                        gpst_tow_nanos,
-                        TimeScale::GPST,
-                    );
+    /// [Channels] to the collecters of this station
+    channels: Channels,
 
-                    runtime.new_epoch(t_gpst, ubx_settings.timescale);
+    /// Latest MGA ephemeris polling, in --poll-eph mode
+    last_eph_poll: Option<std::time::Instant>,
 
-                    let stat = pkt.rec_stat();
+    /// Latest MON-RF polling, in --spectrum mode
+    last_rf_poll: Option<std::time::Instant>,
 
-                    if stat.intersects(RecStatFlags::CLK_RESET) {
-                        error!("{} - clock reset!", t_gpst.round(cfg_precision));
+    /// Data feed is currently silent
+    silent: bool,
+}
 
-                        warn!(
-                            "{} - declaring phase cycle slip! - !!case is not handled!!",
-                            t_gpst.round(cfg_precision)
-                        );
+/// Formats a log [Record] as a JSON line. Epoch and SV are extracted as structured fields,
+/// when the message follows our "epoch(sv) - message" convention.
+fn json_log_format(buf: &mut Formatter, record: &Record) -> std::io::Result<()> {
+    let content = record.args().to_string();
 
-                        error!(
-                            "{} - phase cycle slip not correctly managed in current version",
-                            t_gpst.round(cfg_precision)
-                        );
-                    }
+    let mut fields = serde_json::Map::new();
 
-                    for meas in pkt.measurements() {
-                        let pr = meas.pr_mes();
-                        let cp = meas.cp_mes();
-                        let dop = meas.do_mes();
+    fields.insert(
+        "timestamp".to_string(),
+        buf.timestamp_secs().to_string().into(),
+    );
+    fields.insert("level".to_string(), record.level().to_string().into());
+    fields.insert("module".to_string(), record.target().into());
 
-                        let _ = meas.pr_stdev(); // CXX deviation
-                        let _ = meas.cp_stdev(); // LXX deviation
-                        let _ = meas.do_stdev(); // DXX deviation
+    let mut message = content.as_str();
 
-                        let gnss_id = meas.gnss_id();
-                        let cno = meas.cno();
+    if let Some((prefix, remainder)) = content.split_once(" - ") {
+        let (epoch, sv) = match prefix.strip_suffix(')').and_then(|p| p.split_once('(')) {
+            Some((epoch, sv)) => (epoch, SV::from_str(sv).ok()),
+            None => (prefix, None),
+        };
 
-                        let constell = to_constellation(gnss_id);
+        if let Ok(epoch) = Epoch::from_str(epoch) {
+            fields.insert("epoch".to_string(), epoch.to_string().into());
 
-                        if constell.is_none() {
-                            error!(
-                                "{} - unknown constellation: #{}",
-                                runtime.utc_time().round(cfg_precision),
-                                gnss_id
-                            );
-                            continue;
-                        }
+            if let Some(sv) = sv {
+                fields.insert("sv".to_string(), sv.to_string().into());
+            }
 
-                        let constell = constell.unwrap();
-
-                        // does not proceed if we're not interested by this system
-                        if ubx_settings.constellations.contains(&constell) {
-                            let mut prn = meas.sv_id();
-
-                            if constell.is_sbas() && prn >= SBAS_PRN_OFFSET {
-                                prn -= SBAS_PRN_OFFSET;
-                            };
-
-                            let sv = SV::new(constell, prn);
-                            let t_meas = t_gpst.to_time_scale(ubx_settings.timescale);
-
-                            let rawxm = Rawxm {
-                                epoch: t_meas,
-                                sv,
-                                pr,
-                                cp,
-                                cno,
-                                dop,
-                                freq_id: meas.freq_id(),
-                            };
-
-                            match obs_tx.try_send(Message::Measurement(rawxm)) {
-                                Ok(_) => {},
-                                Err(e) => {
-                                    error!(
-                                        "{}({}) failed to send measurement: {}",
-                                        t_meas.round(cfg_precision),
-                                        sv,
-                                        e
-                                    );
-                                },
-                            }
-                        }
-                    }
-                }
-            },
+            message = remainder;
+        }
+    }
 
-            #[cfg(feature = "ubx14")]
-            UbxPacket::Proto14(PacketRef::MonVer(mon_version)) => {
-                let software_version = mon_version.software_version().to_string();
+    fields.insert("message".to_string(), message.into());
 
-                match obs_tx.try_send(Message::FirmwareVersion(software_version)) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        error!(
-                            "{} - failed to send firmware version: {}",
-                            runtime.utc_time().round(cfg_precision),
-                            e
-                        );
-                    },
-                }
+    writeln!(buf, "{}", serde_json::Value::Object(fields))
+}
 
-                let comment = format!("UBlox hardware version: {}", mon_version.hardware_version());
+#[tokio::main]
+pub async fn main() {
+    // cli: invalid options are reported without a backtrace
+    let cli = Cli::new().unwrap_or_else(|e| {
+        eprintln!("ubx2rinex: {}", e);
+        std::process::exit(2);
+    });
 
-                match obs_tx.try_send(Message::HeaderComment(comment)) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        error!(
-                            "{} - failed to send hardware version: {}",
-                            runtime.utc_time().round(cfg_precision),
-                            e
-                        );
-                    },
-                }
+    // pretty_env_logger::init();
+    let mut builder = Builder::from_default_env();
 
-                let comment = format!("UBlox protocol: {}", mon_version.extension().join(","));
+    builder
+        .target(Target::Stdout)
+        .format_timestamp_secs()
+        .format_module_path(false);
 
-                match obs_tx.try_send(Message::HeaderComment(comment)) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        error!(
-                            "{} - failed to send ublox proto version: {}",
-                            runtime.utc_time().round(cfg_precision),
-                            e
-                        );
-                    },
-                }
-            },
+    if cli.json_logs() {
+        builder.format(json_log_format);
+    }
 
-            #[cfg(feature = "ubx23")]
-            UbxPacket::Proto23(PacketRef::MonVer(mon_version)) => {
-                let software_version = mon_version.software_version().to_string();
+    // $RUST_LOG takes precedence
+    if std::env::var_os("RUST_LOG").is_none()
+        && let Some(level) = cli.log_level()
+    {
+        builder.filter_level(level);
+    }
 
-                match obs_tx.try_send(Message::FirmwareVersion(software_version)) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        error!(
-                            "{} - failed to send firmware version: {}",
-                            runtime.utc_time().round(cfg_precision),
-                            e
-                        );
-                    },
-                }
+    builder.init();
 
-                let comment = format!("UBlox hardware version: {}", mon_version.hardware_version());
+    for warning in cli.warnings() {
+        warn!("{}", warning);
+    }
 
-                match obs_tx.try_send(Message::HeaderComment(comment)) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        error!(
-                            "{} - failed to send hardware version: {}",
-                            runtime.utc_time().round(cfg_precision),
-                            e
-                        );
-                    },
-                }
+    // init
+    let mut buffer = [0; 8192];
 
-                let comment = format!("UBlox protocol: {}", mon_version.extension().join(","));
+    let t_utc = Epoch::now()
+        .unwrap_or_else(|e| panic!("Failed to determine system time: {}", e))
+        .to_time_scale(TimeScale::UTC);
 
-                match obs_tx.try_send(Message::HeaderComment(comment)) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        error!(
-                            "{} - failed to send ublox proto version: {}",
-                            runtime.utc_time().round(cfg_precision),
-                            e
-                        );
-                    },
-                }
-            },
+    // RINEX settings
+    let settings = cli.rinex_settings();
 
-            #[cfg(feature = "ubx27")]
-            UbxPacket::Proto27(PacketRef::MonVer(mon_version)) => {
-                let software_version = mon_version.software_version().to_string();
+    // U-Blox settings
+    let ubx_settings = cli.ublox_settings();
 
-                match obs_tx.try_send(Message::FirmwareVersion(software_version)) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        error!(
-                            "{} - failed to send firmware version: {}",
-                            runtime.utc_time().round(cfg_precision),
-                            e
-                        );
-                    },
-                }
+    let cfg_precision = ubx_settings.precision;
 
-                let comment = format!("UBlox hardware version: {}", mon_version.hardware_version());
+    // shutdown channel
+    let (shutdown_tx, shutdown_rx) = watch::channel(true);
 
-                match obs_tx.try_send(Message::HeaderComment(comment)) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        error!(
-                            "{} - failed to send hardware version: {}",
-                            runtime.utc_time().round(cfg_precision),
-                            e
-                        );
-                    },
-                }
+    let mut tasks = Vec::new();
 
-                let comment = format!("UBlox protocol: {}", mon_version.extension().join(","));
+    // One station per serial port (active mode), or one station for all input files
+    let serial_ports = cli.serial_ports();
 
-                match obs_tx.try_send(Message::HeaderComment(comment)) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        error!(
-                            "{} - failed to send ublox proto version: {}",
-                            runtime.utc_time().round(cfg_precision),
-                            e
-                        );
-                    },
-                }
-            },
+    let names = cli.station_names();
 
-            #[cfg(feature = "ubx31")]
-            UbxPacket::Proto31(PacketRef::MonVer(mon_version)) => {
-                let software_version = mon_version.software_version().to_string();
+    // passive mode: one task per input file
+    if serial_ports.is_empty() && cli.parallel() {
+        let filepaths = cli.filepaths().into_iter().cloned().collect::<Vec<_>>();
 
-                match obs_tx.try_send(Message::FirmwareVersion(software_version)) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        error!(
-                            "{} - failed to send firmware version: {}",
-                            runtime.utc_time().round(cfg_precision),
-                            e
-                        );
-                    },
-                }
+        let settings = RinexSettings {
+            name: names[0].clone(),
+            ..settings.clone()
+        };
 
-                let comment = format!("UBlox hardware version: {}", mon_version.hardware_version());
+        convert_parallel(&filepaths, settings, ubx_settings.clone()).await;
+        return;
+    }
 
-                match obs_tx.try_send(Message::HeaderComment(comment)) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        error!(
-                            "{} - failed to send hardware version: {}",
-                            runtime.utc_time().round(cfg_precision),
-                            e
-                        );
-                    },
-                }
+    let mut stations = Vec::<Station>::new();
+
+    for (index, name) in names.into_iter().enumerate() {
+        // Source files, in passive mode
+        let mut sources = Vec::<String>::new();
+
+        // Input interface
+        let mut device = if let Some(serial) = serial_ports.get(index) {
+            // active mode (GNSS module)
+            let baud_rate = cli.baud_rate().unwrap_or(115_200);
+            let mut device = Device::<Proto>::open_serial_port(
+                serial,
+                baud_rate,
+                cli.serial_settings(),
+                cli.ack_timeout(),
+                cli.ack_retries(),
+                &mut buffer,
+            );
+
+            if let Some(raw_output) = cli.raw_output() {
+                device.tee_raw_output(raw_output);
+            }
 
-                let comment = format!("UBlox protocol: {}", mon_version.extension().join(","));
+            device
+        } else {
+            // passive mode (input files)
+            let mut user_files = cli.filepaths();
 
-                match obs_tx.try_send(Message::HeaderComment(comment)) {
-                    Ok(_) => {},
+            if cli.keep_going() {
+                user_files.retain(|path| match File::open(path) {
+                    Ok(_) => true,
                     Err(e) => {
-                        error!(
-                            "{} - failed to send ublox proto version: {}",
-                            runtime.utc_time().round(cfg_precision),
-                            e
-                        );
+                        warn!("failed to open {}: {} - skipped", path, e);
+                        false
                     },
-                }
-            },
-
-            #[cfg(feature = "ubx14")]
-            UbxPacket::Proto14(PacketRef::MonHw(mon_hardware)) => {
-                // TODO: should contribute to hardware events
-                let _ = mon_hardware.a_status();
-                let _ = mon_hardware.a_power();
-            },
-
-            #[cfg(feature = "ubx23")]
-            UbxPacket::Proto23(PacketRef::MonHw(mon_hardware)) => {
-                // TODO: should contribute to hardware events
-                let _ = mon_hardware.a_status();
-                let _ = mon_hardware.a_power();
-            },
-
-            #[cfg(feature = "ubx27")]
-            UbxPacket::Proto27(PacketRef::MonHw(mon_hardware)) => {
-                // TODO: should contribute to hardware events
-                let _ = mon_hardware.a_status();
-                let _ = mon_hardware.a_power();
-            },
-
-            #[cfg(feature = "ubx31")]
-            UbxPacket::Proto31(PacketRef::MonHw(mon_hardware)) => {
-                // TODO: should contribute to hardware events
-                let _ = mon_hardware.a_status();
-                let _ = mon_hardware.a_power();
-            },
-
-            #[cfg(feature = "ubx14")]
-            UbxPacket::Proto14(PacketRef::NavSat(pkt)) => {
-                for sv in pkt.svs() {
-                    let constellation = to_constellation(sv.gnss_id());
-
-                    if constellation.is_none() {
-                        continue;
-                    }
-
-                    let constellation = constellation.unwrap();
-
-                    let _elev = sv.elev();
-                    let _azim = sv.azim();
-                    let _pr_res = sv.pr_res();
-                    let _flags = sv.flags();
-
-                    let mut prn = sv.sv_id();
-
-                    if constellation.is_sbas() && prn >= SBAS_PRN_OFFSET {
-                        prn -= SBAS_PRN_OFFSET;
-                    }
-
-                    // let sv = SV::new(constellation, prn);
-                    // flags.sv_used()
-                    //flags.health();
-                    //flags.quality_ind();
-                    //flags.differential_correction_available();
-                    //flags.ephemeris_available();
-                }
-            },
-
-            #[cfg(feature = "ubx23")]
-            UbxPacket::Proto23(PacketRef::NavSat(pkt)) => {
-                for sv in pkt.svs() {
-                    let constellation = to_constellation(sv.gnss_id());
-
-                    if constellation.is_none() {
-                        continue;
-                    }
-
-                    let constellation = constellation.unwrap();
-
-                    let _elev = sv.elev();
-                    let _azim = sv.azim();
-                    let _pr_res = sv.pr_res();
-                    let _flags = sv.flags();
-
-                    let mut prn = sv.sv_id();
-
-                    if constellation.is_sbas() && prn >= SBAS_PRN_OFFSET {
-                        prn -= SBAS_PRN_OFFSET;
-                    }
-
-                    // let sv = SV::new(constellation, prn);
-                    // flags.sv_used()
-                    //flags.health();
-                    //flags.quality_ind();
-                    //flags.differential_correction_available();
-                    //flags.ephemeris_available();
-                }
-            },
-
-            #[cfg(feature = "ubx27")]
-            UbxPacket::Proto27(PacketRef::NavSat(pkt)) => {
-                for sv in pkt.svs() {
-                    let constellation = to_constellation(sv.gnss_id());
+                });
+            }
 
-                    if constellation.is_none() {
-                        continue;
-                    }
+            let total = user_files.len();
 
-                    let constellation = constellation.unwrap();
+            assert!(
+                total > 0,
+                "invalid command line: requires either serial port or at least, one input file"
+            );
 
-                    let _elev = sv.elev();
-                    let _azim = sv.azim();
-                    let _pr_res = sv.pr_res();
-                    let _flags = sv.flags();
+            // provenance: file names & sizes are recorded in the header
+            sources.extend(user_files.iter().map(|path| source_comment(path)));
 
-                    let mut prn = sv.sv_id();
+            let mut device = Device::open_file(user_files[0]);
 
-                    if constellation.is_sbas() && prn >= SBAS_PRN_OFFSET {
-                        prn -= SBAS_PRN_OFFSET;
-                    }
+            for i in 1..total {
+                let fd = File::open(user_files[i]).unwrap_or_else(|e| {
+                    panic!("failed to open {}: {}", user_files[i], e);
+                });
 
-                    // let sv = SV::new(constellation, prn);
-                    // flags.sv_used()
-                    //flags.health();
-                    //flags.quality_ind();
-                    //flags.differential_correction_available();
-                    //flags.ephemeris_available();
+                if user_files[i].ends_with(".gz") {
+                    device.interface.stack_gzip_file_handle(fd);
+                } else {
+                    device.interface.stack_file_handle(fd);
                 }
-            },
-
-            #[cfg(feature = "ubx31")]
-            UbxPacket::Proto31(PacketRef::NavSat(pkt)) => {
-                for sv in pkt.svs() {
-                    let constellation = to_constellation(sv.gnss_id());
+            }
 
-                    if constellation.is_none() {
-                        continue;
-                    }
+            device
+        };
 
-                    let constellation = constellation.unwrap();
+        // each station produces its own RINEX set
+        let settings = RinexSettings {
+            name: name.clone(),
+            ..settings.clone()
+        };
 
-                    let _elev = sv.elev();
-                    let _azim = sv.azim();
-                    let _pr_res = sv.pr_res();
-                    let _flags = sv.flags();
+        // Observation RINEX
+        let (obs_tx, obs_rx) = mpsc::channel(cli.channel_capacity());
 
-                    let mut prn = sv.sv_id();
+        // Device configuration, which refines the settings of this receiver
+        let mut ubx_settings = ubx_settings.clone();
 
-                    if constellation.is_sbas() && prn >= SBAS_PRN_OFFSET {
-                        prn -= SBAS_PRN_OFFSET;
-                    }
+        if !device.interface.is_read_only() {
+            if let Err(e) = device.configure(&mut ubx_settings, &mut buffer, obs_tx.clone()) {
+                error!("{}: device configuration failed: {}", name, e);
+                std::process::exit(1);
+            }
+        }
 
-                    // let sv = SV::new(constellation, prn);
-                    // flags.sv_used()
-                    //flags.health();
-                    //flags.quality_ind();
-                    //flags.differential_correction_available();
-                    //flags.ephemeris_available();
-                }
-            },
+        // sampling interval, as applied by the receiver
+        let settings = RinexSettings {
+            sampling: ubx_settings.sampling_period,
+            ..settings
+        };
+
+        let mut obs_collecter = ObsCollecter::new(
+            settings.clone(),
+            ubx_settings.clone(),
+            shutdown_rx.clone(),
+            obs_rx,
+        );
 
-            #[cfg(feature = "ubx14")]
-            UbxPacket::Proto14(PacketRef::NavTimeUTC(pkt)) => {
-                if pkt.valid().intersects(NavTimeUtcFlags::VALID_UTC) {}
-            },
+        // Navigation RINEX
+        let (nav_tx, nav_rx) = mpsc::channel(cli.channel_capacity());
 
-            #[cfg(feature = "ubx23")]
-            UbxPacket::Proto23(PacketRef::NavTimeUTC(pkt)) => {
-                if pkt.valid().intersects(NavTimeUtcFlags::VALID_UTC) {}
-            },
+        let mut nav_collecter = NavCollecter::new(
+            settings.clone(),
+            ubx_settings.clone(),
+            shutdown_rx.clone(),
+            nav_rx,
+        );
 
-            #[cfg(feature = "ubx27")]
-            UbxPacket::Proto27(PacketRef::NavTimeUTC(pkt)) => {
-                if pkt.valid().intersects(NavTimeUtcFlags::VALID_UTC) {}
-            },
+        // auxiliary logs describe the first station only
+        let first = index == 0;
 
-            #[cfg(feature = "ubx31")]
-            UbxPacket::Proto31(PacketRef::NavTimeUTC(pkt)) => {
-                if pkt.valid().intersects(NavTimeUtcFlags::VALID_UTC) {}
+        // SBAS message log
+        let (sbas_tx, mut sbas_collecter) = match cli.sbas_log() {
+            Some(path) if first => {
+                let (sbas_tx, sbas_rx) = mpsc::channel(cli.channel_capacity());
+                (Some(sbas_tx), Some(sbas::collecter(path, sbas_rx)))
             },
+            _ => (None, None),
+        };
 
-            #[cfg(feature = "ubx14")]
-            UbxPacket::Proto14(PacketRef::NavStatus(pkt)) => {
-                //itow = pkt.itow();
-                runtime.uptime = Duration::from_milliseconds(pkt.uptime_ms() as f64);
+        // RF telemetry log: only available in active mode
+        let (spectrum_tx, mut spectrum_collecter) = match cli.spectrum() {
+            Some(path) if first => {
+                if device.interface.is_read_only() {
+                    warn!("--spectrum: RF telemetry is not available in passive mode");
+                    (None, None)
+                } else {
+                    let (spectrum_tx, spectrum_rx) = mpsc::channel(cli.channel_capacity());
+                    (
+                        Some(spectrum_tx),
+                        Some(spectrum::collecter(path, spectrum_rx)),
+                    )
+                }
+            },
+            _ => (None, None),
+        };
+
+        // Velocity log
+        let (velocity_tx, mut velocity_collecter) = match cli.velocity_csv() {
+            Some(path) if first => {
+                let (velocity_tx, velocity_rx) = mpsc::channel(cli.channel_capacity());
+                (
+                    Some(velocity_tx),
+                    Some(velocity::collecter(path, velocity_rx)),
+                )
+            },
+            _ => (None, None),
+        };
+
+        // Time pulse log
+        let (timepulse_tx, mut timepulse_collecter) = match cli.time_pulse_csv() {
+            Some(path) if first => {
+                let (timepulse_tx, timepulse_rx) = mpsc::channel(cli.channel_capacity());
+                (
+                    Some(timepulse_tx),
+                    Some(timepulse::collecter(path, timepulse_rx)),
+                )
+            },
+            _ => (None, None),
+        };
+
+        // QC report
+        let (qc_tx, mut qc_collecter) = match cli.qc() {
+            Some(path) if first => {
+                let (qc_tx, qc_rx) = mpsc::channel(cli.channel_capacity());
+                (Some(qc_tx), Some(QcCollecter::new(path, qc_rx)))
+            },
+            _ => (None, None),
+        };
+
+        let channels = Channels {
+            obs: obs_tx,
+            nav: nav_tx,
+            sbas: sbas_tx,
+            spectrum: spectrum_tx,
+            velocity: velocity_tx,
+            timepulse: timepulse_tx,
+            qc: qc_tx,
+        };
+
+        send_header_comments(&channels, &ubx_settings, &sources);
+
+        // spawns SBAS logger
+        if let Some(mut sbas_collecter) = sbas_collecter.take() {
+            tasks.push(tokio::spawn(async move {
+                info!("{} - SBAS logger deployed", t_utc.round(cfg_precision));
+                sbas_collecter.run().await;
+            }));
+        }
 
-                trace!(
-                    "Fix status: {:?} | {:?} | {:?}",
-                    pkt.fix_stat(),
-                    pkt.flags(),
-                    pkt.flags2()
+        // spawns RF telemetry logger
+        if let Some(mut spectrum_collecter) = spectrum_collecter.take() {
+            tasks.push(tokio::spawn(async move {
+                info!(
+                    "{} - RF telemetry logger deployed",
+                    t_utc.round(cfg_precision)
                 );
+                spectrum_collecter.run().await;
+            }));
+        }
 
-                trace!("Uptime: {}", runtime.uptime);
-            },
-
-            #[cfg(feature = "ubx23")]
-            UbxPacket::Proto23(PacketRef::NavStatus(pkt)) => {
-                //itow = pkt.itow();
-                runtime.uptime = Duration::from_milliseconds(pkt.uptime_ms() as f64);
+        // spawns velocity logger
+        if let Some(mut velocity_collecter) = velocity_collecter.take() {
+            tasks.push(tokio::spawn(async move {
+                info!("{} - velocity logger deployed", t_utc.round(cfg_precision));
+                velocity_collecter.run().await;
+            }));
+        }
 
-                trace!(
-                    "Fix status: {:?} | {:?} | {:?}",
-                    pkt.fix_stat(),
-                    pkt.flags(),
-                    pkt.flags2()
+        // spawns time pulse logger
+        if let Some(mut timepulse_collecter) = timepulse_collecter.take() {
+            tasks.push(tokio::spawn(async move {
+                info!(
+                    "{} - time pulse logger deployed",
+                    t_utc.round(cfg_precision)
                 );
+                timepulse_collecter.run().await;
+            }));
+        }
 
-                trace!("Uptime: {}", runtime.uptime);
-            },
-
-            #[cfg(feature = "ubx27")]
-            UbxPacket::Proto27(PacketRef::NavStatus(pkt)) => {
-                //itow = pkt.itow();
-                runtime.uptime = Duration::from_milliseconds(pkt.uptime_ms() as f64);
+        // spawns QC reporter
+        if let Some(mut qc_collecter) = qc_collecter.take() {
+            tasks.push(tokio::spawn(async move {
+                info!("{} - QC reporter deployed", t_utc.round(cfg_precision));
+                qc_collecter.run().await;
+            }));
+        }
 
-                trace!(
-                    "Fix status: {:?} | {:?} | {:?}",
-                    pkt.fix_stat(),
-                    pkt.flags(),
-                    pkt.flags2()
+        // spawns OBS collector
+        if ubx_settings.rawxm {
+            let name = name.clone();
+            tasks.push(tokio::spawn(async move {
+                info!(
+                    "{} - {} observation mode deployed",
+                    t_utc.round(cfg_precision),
+                    name
                 );
+                obs_collecter.run().await;
+            }));
+        }
 
-                trace!("Uptime: {}", runtime.uptime);
-            },
-
-            #[cfg(feature = "ubx31")]
-            UbxPacket::Proto31(PacketRef::NavStatus(pkt)) => {
-                //itow = pkt.itow();
-                runtime.uptime = Duration::from_milliseconds(pkt.uptime_ms() as f64);
-
-                trace!(
-                    "Fix status: {:?} | {:?} | {:?}",
-                    pkt.fix_stat(),
-                    pkt.flags(),
-                    pkt.flags2()
+        // spawns NAV collector
+        if ubx_settings.ephemeris {
+            let name = name.clone();
+            tasks.push(tokio::spawn(async move {
+                info!(
+                    "{} - {} navigation mode deployed",
+                    t_utc.round(cfg_precision),
+                    name
                 );
+                nav_collecter.run().await;
+            }));
+        }
 
-                trace!("Uptime: {}", runtime.uptime);
-            },
-
-            #[cfg(feature = "ubx14")]
-            UbxPacket::Proto14(PacketRef::NavEoe(pkt)) => {
-                let gpst_itow_nanos = pkt.itow() as u64 * 1_000_000;
-                let t_gpst =
-                    Epoch::from_time_of_week(runtime.gpst_week(), gpst_itow_nanos, TimeScale::GPST);
-                end_of_nav_epoch = true;
-                trace!("{} - End of Epoch", t_gpst.round(cfg_precision));
-                let _ = nav_tx.try_send(Message::EndofEpoch());
-            },
-
-            #[cfg(feature = "ubx23")]
-            UbxPacket::Proto23(PacketRef::NavEoe(pkt)) => {
-                let gpst_itow_nanos = pkt.itow() as u64 * 1_000_000;
-                let t_gpst =
-                    Epoch::from_time_of_week(runtime.gpst_week(), gpst_itow_nanos, TimeScale::GPST);
-                end_of_nav_epoch = true;
-                trace!("{} - End of Epoch", t_gpst.round(cfg_precision));
-                let _ = nav_tx.try_send(Message::EndofEpoch());
-            },
-
-            #[cfg(feature = "ubx27")]
-            UbxPacket::Proto27(PacketRef::NavEoe(pkt)) => {
-                let gpst_itow_nanos = pkt.itow() as u64 * 1_000_000;
-                let t_gpst =
-                    Epoch::from_time_of_week(runtime.gpst_week(), gpst_itow_nanos, TimeScale::GPST);
-                end_of_nav_epoch = true;
-                trace!("{} - End of Epoch", t_gpst.round(cfg_precision));
-                let _ = nav_tx.try_send(Message::EndofEpoch());
-            },
-
-            #[cfg(feature = "ubx31")]
-            UbxPacket::Proto31(PacketRef::NavEoe(pkt)) => {
-                let gpst_itow_nanos = pkt.itow() as u64 * 1_000_000;
-                let t_gpst =
-                    Epoch::from_time_of_week(runtime.gpst_week(), gpst_itow_nanos, TimeScale::GPST);
-                end_of_nav_epoch = true;
-                trace!("{} - End of Epoch", t_gpst.round(cfg_precision));
-                let _ = nav_tx.try_send(Message::EndofEpoch());
-            },
-
-            #[cfg(feature = "ubx14")]
-            UbxPacket::Proto14(PacketRef::NavPvt(pkt)) => {
-                let (y, m, d) = (pkt.year() as i32, pkt.month(), pkt.day());
-                let (hh, mm, ss) = (pkt.hour(), pkt.min(), pkt.sec());
+        let mut runtime = Runtime::new();
+        runtime.nav_eoe_fallback = !ubx_settings.nav_eoe;
+
+        stations.push(Station {
+            name,
+            device,
+            runtime,
+            ubx_settings,
+            channels,
+            last_eph_poll: None,
+            last_rf_poll: None,
+            silent: false,
+        });
+    }
 
-                if pkt.valid().intersects(NavPvtValidFlags::FULLY_RESOLVED) {
-                    let t_solution = Epoch::from_gregorian(y, m, d, hh, mm, ss, 0, TimeScale::UTC)
-                        .to_time_scale(ubx_settings.timescale);
+    // tokio::spawn(async move {
+    //     signal::ctrl_c()
+    //         .await
+    //         .unwrap_or_else(|e| panic!("Tokio signal handling error: {}", e));
 
-                    trace!(
-                        "{} - PVT SOLUTION: lat={:.5E}° long={:.5E}°",
-                        t_solution.round(cfg_precision),
-                        pkt.latitude(),
-                        pkt.longitude()
-                    );
-                }
-            },
+    //     shutdown_tx
+    //         .send(true)
+    //         .unwrap_or_else(|e| panic!("Tokio: signaling error: {}", e));
+    // });
 
-            #[cfg(feature = "ubx23")]
-            UbxPacket::Proto23(PacketRef::NavPvt(pkt)) => {
-                let (y, m, d) = (pkt.year() as i32, pkt.month(), pkt.day());
-                let (hh, mm, ss) = (pkt.hour(), pkt.min(), pkt.sec());
+    // main task
+    info!("{} - application deployed", t_utc.round(cfg_precision));
 
-                if pkt.valid().intersects(NavPvtValidFlags::FULLY_RESOLVED) {
-                    let t_solution = Epoch::from_gregorian(y, m, d, hh, mm, ss, 0, TimeScale::UTC)
-                        .to_time_scale(ubx_settings.timescale);
+    // passive mode: a single station, consuming all input files
+    let passive = stations
+        .iter()
+        .any(|station| station.device.interface.is_read_only());
 
-                    trace!(
-                        "{} - PVT SOLUTION: lat={:.5E}° long={:.5E}°",
-                        t_solution.round(cfg_precision),
-                        pkt.latitude(),
-                        pkt.longitude()
-                    );
-                }
-            },
+    // MGA polling, only possible on active interface
+    let poll_ephemeris = ubx_settings.poll_ephemeris && !passive;
 
-            #[cfg(feature = "ubx27")]
-            UbxPacket::Proto27(PacketRef::NavPvt(pkt)) => {
-                let (y, m, d) = (pkt.year() as i32, pkt.month(), pkt.day());
-                let (hh, mm, ss) = (pkt.hour(), pkt.min(), pkt.sec());
+    if poll_ephemeris {
+        warn!("--poll-eph: MGA messages are input messages, your firmware may not answer the poll");
+    }
 
-                if pkt.valid().intersects(NavPvtValidFlags::FULLY_RESOLVED) {
-                    let t_solution = Epoch::from_gregorian(y, m, d, hh, mm, ss, 0, TimeScale::UTC)
-                        .to_time_scale(ubx_settings.timescale);
+    // data feed watchdog, only applies to active interface
+    let data_timeout = cli.data_timeout();
+    let t_deploy = std::time::Instant::now();
 
-                    trace!(
-                        "{} - PVT SOLUTION: lat={:.5E}° long={:.5E}°",
-                        t_solution.round(cfg_precision),
-                        pkt.latitude(),
-                        pkt.longitude()
-                    );
-                }
-            },
+    // non zero when the collection is aborted (--strict)
+    let mut exit_code = 0;
 
-            #[cfg(feature = "ubx31")]
-            UbxPacket::Proto31(PacketRef::NavPvt(pkt)) => {
-                let (y, m, d) = (pkt.year() as i32, pkt.month(), pkt.day());
-                let (hh, mm, ss) = (pkt.hour(), pkt.min(), pkt.sec());
+    if passive {
+        let station = &mut stations[0];
 
-                if pkt.valid().intersects(NavPvtValidFlags::FULLY_RESOLVED) {
-                    let t_solution = Epoch::from_gregorian(y, m, d, hh, mm, ss, 0, TimeScale::UTC)
-                        .to_time_scale(ubx_settings.timescale);
+        let options = PassiveOptions {
+            replay_rate: cli.replay_rate(),
+            keep_going: cli.keep_going(),
+            strict: cli.strict(),
+        };
 
-                    trace!(
-                        "{} - PVT SOLUTION: lat={:.5E}° long={:.5E}°",
-                        t_solution.round(cfg_precision),
-                        pkt.latitude(),
-                        pkt.longitude()
+        if !consume_passive(
+            &mut station.runtime,
+            &station.channels,
+            &mut station.device,
+            &mut buffer,
+            cfg_precision,
+            &station.ubx_settings,
+            &options,
+        )
+        .await
+        {
+            exit_code = 1;
+        }
+    } else {
+        'main: loop {
+            for station in stations.iter_mut() {
+                let rtm = &mut station.runtime;
+                let device = &mut station.device;
+                let channels = &station.channels;
+                let ubx_settings = &station.ubx_settings;
+
+                if let Err(e) = consume_device(
+                    rtm,
+                    channels,
+                    device,
+                    &mut buffer,
+                    cfg_precision,
+                    ubx_settings,
+                )
+                .await
+                {
+                    error!(
+                        "{} - {}: I/O error: {}",
+                        rtm.utc_time().round(cfg_precision),
+                        station.name,
+                        e
                     );
                 }
-            },
 
-            #[cfg(feature = "ubx14")]
-            UbxPacket::Proto14(PacketRef::NavClock(pkt)) => {
-                // Do not process if user is not interested in this channel.
-                if ubx_settings.rawxm && ubx_settings.rx_clock {
-                    let clock = pkt.clk_bias();
-                    match obs_tx.try_send(Message::Clock(clock)) {
-                        Ok(_) => {},
-                        Err(e) => {
-                            error!(
-                                "{} - failed to send clock state: {}",
-                                runtime.utc_time().round(cfg_precision),
-                                e
-                            );
-                        },
-                    }
-                }
-            },
+                let silence = rtm.last_packet.unwrap_or(t_deploy).elapsed();
 
-            #[cfg(feature = "ubx23")]
-            UbxPacket::Proto23(PacketRef::NavClock(pkt)) => {
-                // Do not process if user is not interested in this channel.
-                if ubx_settings.rawxm && ubx_settings.rx_clock {
-                    let clock = pkt.clk_bias();
-                    match obs_tx.try_send(Message::Clock(clock)) {
-                        Ok(_) => {},
-                        Err(e) => {
-                            error!(
-                                "{} - failed to send clock state: {}",
-                                runtime.utc_time().round(cfg_precision),
-                                e
-                            );
-                        },
-                    }
-                }
-            },
-
-            #[cfg(feature = "ubx27")]
-            UbxPacket::Proto27(PacketRef::NavClock(pkt)) => {
-                // Do not process if user is not interested in this channel.
-                if ubx_settings.rawxm && ubx_settings.rx_clock {
-                    let clock = pkt.clk_bias();
-                    match obs_tx.try_send(Message::Clock(clock)) {
-                        Ok(_) => {},
-                        Err(e) => {
-                            error!(
-                                "{} - failed to send clock state: {}",
-                                runtime.utc_time().round(cfg_precision),
-                                e
-                            );
-                        },
+                if silence >= data_timeout {
+                    if !station.silent {
+                        warn!(
+                            "{} - {}: no data received for {:?}: check the antenna and the device",
+                            rtm.utc_time().round(cfg_precision),
+                            station.name,
+                            silence
+                        );
+                        station.silent = true;
                     }
-                }
-            },
 
-            #[cfg(feature = "ubx31")]
-            UbxPacket::Proto31(PacketRef::NavClock(pkt)) => {
-                // Do not process if user is not interested in this channel.
-                if ubx_settings.rawxm && ubx_settings.rx_clock {
-                    let clock = pkt.clk_bias();
-                    match obs_tx.try_send(Message::Clock(clock)) {
-                        Ok(_) => {},
-                        Err(e) => {
-                            error!(
-                                "{} - failed to send clock state: {}",
-                                runtime.utc_time().round(cfg_precision),
-                                e
-                            );
-                        },
+                    if cli.exit_on_timeout() {
+                        error!(
+                            "{} - {}: data feed timed out: exiting",
+                            rtm.utc_time().round(cfg_precision),
+                            station.name
+                        );
+                        break 'main;
                     }
-                }
-            },
-
-            #[cfg(feature = "ubx14")]
-            UbxPacket::Proto14(PacketRef::InfTest(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    trace!(
-                        "{} - received test message {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
-                }
-            },
-
-            #[cfg(feature = "ubx23")]
-            UbxPacket::Proto23(PacketRef::InfTest(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    trace!(
-                        "{} - received test message {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
-                }
-            },
-
-            #[cfg(feature = "ubx27")]
-            UbxPacket::Proto27(PacketRef::InfTest(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    trace!(
-                        "{} - received test message {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
-                }
-            },
-
-            #[cfg(feature = "ubx31")]
-            UbxPacket::Proto31(PacketRef::InfTest(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    trace!(
-                        "{} - received test message {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
-                }
-            },
-
-            #[cfg(feature = "ubx14")]
-            UbxPacket::Proto14(PacketRef::InfDebug(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    debug!(
-                        "{} - received debug message {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
-                }
-            },
-
-            #[cfg(feature = "ubx23")]
-            UbxPacket::Proto23(PacketRef::InfDebug(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    debug!(
-                        "{} - received debug message {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
-                }
-            },
-
-            #[cfg(feature = "ubx27")]
-            UbxPacket::Proto27(PacketRef::InfDebug(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    debug!(
-                        "{} - received debug message {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
-                }
-            },
-
-            #[cfg(feature = "ubx31")]
-            UbxPacket::Proto31(PacketRef::InfDebug(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    debug!(
-                        "{} - received debug message {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
-                }
-            },
-
-            #[cfg(feature = "ubx14")]
-            UbxPacket::Proto14(PacketRef::InfNotice(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    info!(
-                        "{} - received notification {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
-                }
-            },
-
-            #[cfg(feature = "ubx23")]
-            UbxPacket::Proto23(PacketRef::InfNotice(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    info!(
-                        "{} - received notification {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
-                }
-            },
-
-            #[cfg(feature = "ubx27")]
-            UbxPacket::Proto27(PacketRef::InfNotice(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    info!(
-                        "{} - received notification {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
-                }
-            },
-
-            #[cfg(feature = "ubx31")]
-            UbxPacket::Proto31(PacketRef::InfNotice(pkt)) => {
-                if let Some(msg) = pkt.message() {
+                } else if station.silent {
                     info!(
-                        "{} - received notification {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
-                }
-            },
-
-            #[cfg(feature = "ubx14")]
-            UbxPacket::Proto14(PacketRef::InfError(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    error!(
-                        "{} - received error notification {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
-                }
-            },
-
-            #[cfg(feature = "ubx23")]
-            UbxPacket::Proto23(PacketRef::InfError(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    error!(
-                        "{} - received error notification {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
-                }
-            },
-
-            #[cfg(feature = "ubx27")]
-            UbxPacket::Proto27(PacketRef::InfError(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    error!(
-                        "{} - received error notification {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
-                }
-            },
-
-            #[cfg(feature = "ubx31")]
-            UbxPacket::Proto31(PacketRef::InfError(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    error!(
-                        "{} - received error notification {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
-                }
-            },
-
-            #[cfg(feature = "ubx14")]
-            UbxPacket::Proto14(PacketRef::InfWarning(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    warn!(
-                        "{} - received warning message {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
+                        "{} - {}: data feed resumed",
+                        rtm.utc_time().round(cfg_precision),
+                        station.name
                     );
+                    station.silent = false;
+                }
+
+                if poll_ephemeris {
+                    let poll = match station.last_eph_poll {
+                        Some(instant) => instant.elapsed() >= MGA_EPH_POLLING_PERIOD,
+                        None => true,
+                    };
+
+                    if poll {
+                        // MGA-GLO-EPH is not converted to Glonass ephemeris yet
+                        if ubx_settings
+                            .nav_constellations
+                            .contains(&Constellation::GPS)
+                        {
+                            device.request_mga_gps_eph();
+                        }
+                        station.last_eph_poll = Some(std::time::Instant::now());
+                    }
                 }
-            },
 
-            #[cfg(feature = "ubx23")]
-            UbxPacket::Proto23(PacketRef::InfWarning(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    warn!(
-                        "{} - received warning message {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
-                }
-            },
+                if channels.spectrum.is_some() {
+                    let poll = match station.last_rf_poll {
+                        Some(instant) => instant.elapsed() >= MON_RF_POLLING_PERIOD,
+                        None => true,
+                    };
 
-            #[cfg(feature = "ubx27")]
-            UbxPacket::Proto27(PacketRef::InfWarning(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    warn!(
-                        "{} - received warning message {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
+                    if poll {
+                        device.request_mon_rf();
+                        station.last_rf_poll = Some(std::time::Instant::now());
+                    }
                 }
-            },
 
-            #[cfg(feature = "ubx31")]
-            UbxPacket::Proto31(PacketRef::InfWarning(pkt)) => {
-                if let Some(msg) = pkt.message() {
-                    warn!(
-                        "{} - received warning message {}",
-                        runtime.utc_time().round(cfg_precision),
-                        msg
-                    );
+                // handle all pending NAV-EPH messages
+                if ubx_settings.ephemeris {
+                    release_ephemeris(rtm, channels, cfg_precision);
                 }
-            },
-            _ => {},
-        } //packet
-    })
-}
-
-#[tokio::main]
-pub async fn main() {
-    // pretty_env_logger::init();
-    let mut builder = Builder::from_default_env();
-
-    builder
-        .target(Target::Stdout)
-        .format_timestamp_secs()
-        .format_module_path(false)
-        .init();
-
-    // init
-    let mut buffer = [0; 8192];
-
-    let cfg_precision = Duration::from_seconds(1.0);
-
-    let t_utc = Epoch::now()
-        .unwrap_or_else(|e| panic!("Failed to determine system time: {}", e))
-        .to_time_scale(TimeScale::UTC);
-
-    // cli
-    let cli = Cli::new();
-
-    // Input interface
-    let mut device = if let Some(serial) = cli.serial_port() {
-        // active mode (GNSS module)
-        let baud_rate = cli.baud_rate().unwrap_or(115_200);
-        Device::<Proto>::open_serial_port(serial, baud_rate, &mut buffer)
-    } else {
-        // passive mode (input files)
-        let user_files = cli.filepaths();
-        let total = user_files.len();
-
-        assert!(
-            total > 0,
-            "invalid command line: requires either serial port or at least, one input file"
-        );
-
-        let mut device = Device::open_file(user_files[0]);
-
-        for i in 1..total {
-            let fd = File::open(user_files[i]).unwrap_or_else(|e| {
-                panic!("failed to open {}: {}", user_files[i], e);
-            });
-
-            if user_files[i].ends_with(".gz") {
-                device.interface.stack_gzip_file_handle(fd);
-            } else {
-                device.interface.stack_file_handle(fd);
             }
         }
-
-        device
-    };
-
-    // RINEX settings
-    let settings = cli.rinex_settings();
-
-    // U-Blox settings
-    let ubx_settings = cli.ublox_settings();
-
-    // shutdown channel
-    let (shutdown_tx, shutdown_rx) = watch::channel(true);
-
-    // Observation RINEX
-    let (mut obs_tx, obs_rx) = mpsc::channel(128);
-
-    let mut obs_collecter = ObsCollecter::new(
-        settings.clone(),
-        ubx_settings.clone(),
-        shutdown_rx.clone(),
-        obs_rx,
-    );
-
-    // Navigation RINEX
-    let (mut nav_tx, nav_rx) = mpsc::channel(128);
-
-    let mut nav_collecter = NavCollecter::new(
-        settings.clone(),
-        ubx_settings.clone(),
-        shutdown_rx.clone(),
-        nav_rx,
-    );
-
-    // Device configuration
-    if !device.interface.is_read_only() {
-        device.configure(&ubx_settings, &mut buffer, obs_tx.clone());
     }
 
-    // spawns OBS collector
-    if ubx_settings.rawxm {
-        tokio::spawn(async move {
-            info!("{} - Observation mode deployed", t_utc.round(cfg_precision));
-            obs_collecter.run().await;
-        });
+    // collecters release pending content & flush their output
+    for station in stations.iter() {
+        shutdown_collecters(&station.runtime, &station.channels, &station.ubx_settings).await;
     }
 
-    // spawns NAV collector
-    if ubx_settings.ephemeris {
-        tokio::spawn(async move {
-            info!("{} - Navigation  mode deployed", t_utc.round(cfg_precision));
-            nav_collecter.run().await;
-        });
+    for task in tasks {
+        let _ = task.await;
     }
 
-    // tokio::spawn(async move {
-    //     signal::ctrl_c()
-    //         .await
-    //         .unwrap_or_else(|e| panic!("Tokio signal handling error: {}", e));
-
-    //     shutdown_tx
-    //         .send(true)
-    //         .unwrap_or_else(|e| panic!("Tokio: signaling error: {}", e));
-    // });
-
-    // main task
-    let mut rtm = Runtime::new();
-    info!("{} - application deployed", t_utc.round(cfg_precision));
-
-    loop {
-        match consume_device(
-            &mut rtm,
-            &mut obs_tx,
-            &mut nav_tx,
-            &mut device,
-            &mut buffer,
-            cfg_precision,
-            &ubx_settings,
-        ) {
-            Ok(0) => {
-                // in standard mode, this may happen,
-                // in passive mode, we have consumed all content: we should exit.
-                if device.interface.is_read_only() {
-                    info!(
-                        "{} - consumed all content",
-                        rtm.utc_time().round(cfg_precision)
-                    );
-
-                    break;
-                }
-            },
-            Ok(_) => {}, // nominal
-            Err(e) => {
-                error!("{} - I/O error: {}", rtm.utc_time().round(cfg_precision), e);
-            },
-        }
-
-        // handle all pending NAV-EPH messages
-        if ubx_settings.ephemeris {
-            for (sv, pending) in rtm.pending_frames.iter() {
-                if let Some(validated) = pending.validate() {
-                    let (epoch, rinex) = validated.to_rinex(rtm.utc_time());
-
-                    // redact message
-                    match nav_tx.try_send(Message::Ephemeris((epoch, *sv, rinex))) {
-                        Ok(_) => {},
-                        Err(e) => {
-                            error!(
-                                "{}({}) failed to send collected ephemeris: {}",
-                                epoch.round(cfg_precision),
-                                sv,
-                                e
-                            );
-                        },
-                    }
-                }
-            }
-        }
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
 
-        if device.interface.is_read_only() {
-            // In passive mode, there is not hardware acting as a throttle,
-            // the channel capacity becomes the limit.
-            // Adds a little bit of dead-time to reduce pressure on the data channel.
-            std::thread::sleep(std::time::Duration::from_millis(50));
-        }
+    // all files are complete: merge them into daily files
+    if let Some(fragments) = &settings.fragments {
+        fragments.merge();
     }
 }
@@ -0,0 +1,3752 @@
+use itertools::Itertools;
+
+use log::{debug, error, info, trace, warn};
+
+use std::time::Instant;
+
+use tokio::sync::mpsc;
+
+use rinex::prelude::{Constellation, Duration, Epoch, SV, TimeScale};
+
+use ublox::{
+    UbxPacket,
+    nav_pvt::common::{NavPvtFlags, NavPvtValidFlags},
+    nav_time_utc::NavTimeUtcFlags,
+    rxm_rawx::RecStatFlags,
+    tim_tm2::TimTm2Flags,
+};
+
+#[cfg(feature = "ubx14")]
+use ublox::packetref_proto14::PacketRef;
+#[cfg(feature = "ubx23")]
+use ublox::packetref_proto23::PacketRef;
+#[cfg(feature = "ubx27")]
+use ublox::packetref_proto27::PacketRef;
+#[cfg(feature = "ubx31")]
+use ublox::packetref_proto31::PacketRef;
+
+use crate::{
+    PROTOCOL, Proto,
+    collecter::{
+        Message, ephemeris::GpsQzssEphemeris, rawxm::Rawxm, sbas::SbasMessage, spectrum::RfBlock,
+        timepulse::TimePulse, velocity::Velocity,
+    },
+    device::Device,
+    runtime::Runtime,
+    ubx::{InfLevel, Settings as UbloxSettings},
+    utils::{country_code, protocol_version, suited_protocol, to_constellation},
+};
+
+const SBAS_PRN_OFFSET: u8 = 100;
+
+/// Read size when replaying input files (--replay-rate): a few packets at a time
+const REPLAY_CHUNK_SIZE: usize = 512;
+
+/// TIM-TM2 time base (flags bits 3-4): receiver time, GNSS time or UTC
+const TIM_TM2_TIME_BASE_MASK: u8 = 0x18;
+const TIM_TM2_TIME_BASE_UTC: u8 = 0x10;
+
+/// TIM-TM2 time is valid (flags bit 6)
+const TIM_TM2_TIME_VALID: u8 = 0x40;
+
+/// Dead-time between two reads of a passive [Device]
+const PASSIVE_DEAD_TIME: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// UBX-RXM-MEASX is not decoded by the parser: it is identified
+/// by class and ID, and interpreted here.
+const RXM_MEASX_CLASS: u8 = 0x02;
+const RXM_MEASX_ID: u8 = 0x14;
+
+/// RXM-MEASX header length, and length of each satellite block
+const RXM_MEASX_HEADER_LEN: usize = 44;
+const RXM_MEASX_BLOCK_LEN: usize = 24;
+
+/// [Message] senders, to each collecter
+pub struct Channels {
+    /// Observation RINEX
+    pub obs: mpsc::Sender<Message>,
+
+    /// Navigation RINEX
+    pub nav: mpsc::Sender<Message>,
+
+    /// SBAS message log, when requested
+    pub sbas: Option<mpsc::Sender<Message>>,
+
+    /// RF telemetry log, when requested
+    pub spectrum: Option<mpsc::Sender<Message>>,
+
+    /// Velocity log, when requested
+    pub velocity: Option<mpsc::Sender<Message>>,
+
+    /// Time pulse log, when requested
+    pub timepulse: Option<mpsc::Sender<Message>>,
+
+    /// Quality control report, when requested
+    pub qc: Option<mpsc::Sender<Message>>,
+}
+
+/// Consumes all available bytes from the [Device] and dispatches
+/// the resulting [Message]s to the collecters.
+pub async fn consume_device(
+    runtime: &mut Runtime,
+    channels: &Channels,
+    device: &mut Device<Proto>,
+    buffer: &mut [u8],
+    cfg_precision: Duration,
+    ubx_settings: &UbloxSettings,
+) -> std::io::Result<usize> {
+    // Measurements & clock states are the hot path: they are gathered here
+    // and then awaited, so they are never dropped under load.
+    let mut obs_pending = Vec::<Message>::new();
+
+    // epochs decoded from these bytes only
+    let mut epochs = Vec::<Epoch>::new();
+
+    let size = device.consume_all_cb(buffer, |packet| {
+        runtime.last_packet = Some(Instant::now());
+
+        let latest = runtime.latest_epoch();
+
+        handle_packet(
+            packet,
+            runtime,
+            &mut obs_pending,
+            channels,
+            cfg_precision,
+            ubx_settings,
+        );
+
+        if let Some(epoch) = runtime.latest_epoch()
+            && Some(epoch) != latest
+        {
+            epochs.push(epoch);
+        }
+    })?;
+
+    // stacked input files should not overlap in time
+    for epoch in epochs {
+        if let Some(overlap) = device.interface.latch_epoch(epoch) {
+            runtime.overlap.get_or_insert(overlap);
+        }
+    }
+
+    for message in obs_pending {
+        if let (Some(qc), Message::Measurement(rawxm)) = (&channels.qc, &message) {
+            let _ = qc.try_send(Message::Measurement(*rawxm));
+        }
+
+        if let Err(e) = channels.obs.send(message).await {
+            error!(
+                "{} - failed to send observation: {}",
+                runtime.utc_time().round(cfg_precision),
+                e
+            );
+        }
+    }
+
+    Ok(size)
+}
+
+/// Handles a single [UbxPacket]: observations are gathered in `obs_pending`,
+/// other [Message]s are directly forwarded to the collecters.
+fn handle_packet(
+    packet: UbxPacket,
+    runtime: &mut Runtime,
+    obs_pending: &mut Vec<Message>,
+    channels: &Channels,
+    cfg_precision: Duration,
+    ubx_settings: &UbloxSettings,
+) {
+    if ubx_settings.dump_packets {
+        println!("{}", dump_packet(&packet));
+    }
+
+    match packet {
+        #[cfg(feature = "ubx14")]
+        UbxPacket::Proto14(PacketRef::CfgNav5(_)) => {
+            // TODO: Dynamic model ?
+            // let _dyn_model = pkt.dyn_model();
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::CfgNav5(_)) => {
+            // TODO: Dynamic model ?
+            // let _dyn_model = pkt.dyn_model();
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::CfgNav5(_)) => {
+            // TODO: Dynamic model ?
+            // let _dyn_model = pkt.dyn_model();
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::CfgNav5(_)) => {
+            // TODO: Dynamic model ?
+            // let _dyn_model = pkt.dyn_model();
+        },
+
+        #[cfg(feature = "ubx14")]
+        UbxPacket::Proto14(PacketRef::RxmSfrbx(sfrbx)) => {
+            if let Some(sbas) = &channels.sbas
+                && to_constellation(sfrbx.gnss_id()) == Some(Constellation::SBAS)
+            {
+                let mut prn = sfrbx.sv_id();
+
+                if prn >= SBAS_PRN_OFFSET {
+                    prn -= SBAS_PRN_OFFSET;
+                }
+
+                let message = SbasMessage {
+                    epoch: runtime.epoch(),
+                    sv: SV::new(Constellation::SBAS, prn),
+                    words: sfrbx.dwrd().collect(),
+                };
+
+                if let Err(e) = sbas.try_send(Message::Sbas(message)) {
+                    error!(
+                        "{} - failed to send SBAS message: {}",
+                        runtime.utc_time().round(cfg_precision),
+                        e
+                    );
+                }
+            }
+
+            // Do not process if user is not interested in this channel.
+            // When attached to hardware this naturally never happens.
+            // But this may arise in passive mode.
+            if ubx_settings.ephemeris {
+                let gnss_id = sfrbx.gnss_id();
+
+                match to_constellation(gnss_id) {
+                    Some(constellation) => {
+                        // does not proceeed if we're not interested by this system
+                        if ubx_settings.nav_constellations.contains(&constellation) {
+                            let mut prn = sfrbx.sv_id();
+
+                            if constellation.is_sbas() && prn >= SBAS_PRN_OFFSET {
+                                prn -= SBAS_PRN_OFFSET;
+                            }
+
+                            let sv = SV::new(constellation, prn);
+
+                            match constellation {
+                                Constellation::GPS | Constellation::QZSS => {
+                                    // decode
+                                    if let Some(interpretation) = sfrbx.interpret() {
+                                        debug!(
+                                            "{} - decoded {:?}",
+                                            runtime.utc_time().round(cfg_precision),
+                                            interpretation
+                                        );
+
+                                        runtime.latch_sfrbx(sv, interpretation, cfg_precision);
+                                    } else {
+                                        error!(
+                                            "{} - SFRBX interpretation issue",
+                                            runtime.utc_time().round(cfg_precision)
+                                        );
+                                    }
+                                },
+                                c => {
+                                    error!(
+                                        "{} - {} constellation not handled yet",
+                                        runtime.utc_time().round(cfg_precision),
+                                        c
+                                    );
+                                },
+                            }
+                        }
+                    },
+                    None => {
+                        error!(
+                            "{} - constellation id error #{}",
+                            runtime.utc_time().round(cfg_precision),
+                            gnss_id
+                        );
+                    },
+                }
+            }
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::RxmSfrbx(sfrbx)) => {
+            if let Some(sbas) = &channels.sbas
+                && to_constellation(sfrbx.gnss_id()) == Some(Constellation::SBAS)
+            {
+                let mut prn = sfrbx.sv_id();
+
+                if prn >= SBAS_PRN_OFFSET {
+                    prn -= SBAS_PRN_OFFSET;
+                }
+
+                let message = SbasMessage {
+                    epoch: runtime.epoch(),
+                    sv: SV::new(Constellation::SBAS, prn),
+                    words: sfrbx.dwrd().collect(),
+                };
+
+                if let Err(e) = sbas.try_send(Message::Sbas(message)) {
+                    error!(
+                        "{} - failed to send SBAS message: {}",
+                        runtime.utc_time().round(cfg_precision),
+                        e
+                    );
+                }
+            }
+
+            // Do not process if user is not interested in this channel.
+            // When attached to hardware this naturally never happens.
+            // But this may arise in passive mode.
+            if ubx_settings.ephemeris {
+                let gnss_id = sfrbx.gnss_id();
+
+                match to_constellation(gnss_id) {
+                    Some(constellation) => {
+                        // does not proceeed if we're not interested by this system
+                        if ubx_settings.nav_constellations.contains(&constellation) {
+                            let mut prn = sfrbx.sv_id();
+
+                            if constellation.is_sbas() && prn >= SBAS_PRN_OFFSET {
+                                prn -= SBAS_PRN_OFFSET;
+                            }
+
+                            let sv = SV::new(constellation, prn);
+
+                            match constellation {
+                                Constellation::GPS | Constellation::QZSS => {
+                                    // decode
+                                    if let Some(interpretation) = sfrbx.interpret() {
+                                        debug!(
+                                            "{} - decoded {:?}",
+                                            runtime.utc_time().round(cfg_precision),
+                                            interpretation
+                                        );
+
+                                        runtime.latch_sfrbx(sv, interpretation, cfg_precision);
+                                    } else {
+                                        error!(
+                                            "{} - SFRBX interpretation issue",
+                                            runtime.utc_time().round(cfg_precision)
+                                        );
+                                    }
+                                },
+                                c => {
+                                    error!(
+                                        "{} - {} constellation not handled yet",
+                                        runtime.utc_time().round(cfg_precision),
+                                        c
+                                    );
+                                },
+                            }
+                        }
+                    },
+                    None => {
+                        error!(
+                            "{} - constellation id error #{}",
+                            runtime.utc_time().round(cfg_precision),
+                            gnss_id
+                        );
+                    },
+                }
+            }
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::RxmSfrbx(sfrbx)) => {
+            if let Some(sbas) = &channels.sbas
+                && to_constellation(sfrbx.gnss_id()) == Some(Constellation::SBAS)
+            {
+                let mut prn = sfrbx.sv_id();
+
+                if prn >= SBAS_PRN_OFFSET {
+                    prn -= SBAS_PRN_OFFSET;
+                }
+
+                let message = SbasMessage {
+                    epoch: runtime.epoch(),
+                    sv: SV::new(Constellation::SBAS, prn),
+                    words: sfrbx.dwrd().collect(),
+                };
+
+                if let Err(e) = sbas.try_send(Message::Sbas(message)) {
+                    error!(
+                        "{} - failed to send SBAS message: {}",
+                        runtime.utc_time().round(cfg_precision),
+                        e
+                    );
+                }
+            }
+
+            // Do not process if user is not interested in this channel.
+            // When attached to hardware this naturally never happens.
+            // But this may arise in passive mode.
+            if ubx_settings.ephemeris {
+                let gnss_id = sfrbx.gnss_id();
+
+                match to_constellation(gnss_id) {
+                    Some(constellation) => {
+                        // does not proceeed if we're not interested by this system
+                        if ubx_settings.nav_constellations.contains(&constellation) {
+                            let mut prn = sfrbx.sv_id();
+
+                            if constellation.is_sbas() && prn >= SBAS_PRN_OFFSET {
+                                prn -= SBAS_PRN_OFFSET;
+                            }
+
+                            let sv = SV::new(constellation, prn);
+
+                            match constellation {
+                                Constellation::GPS | Constellation::QZSS => {
+                                    // decode
+                                    if let Some(interpretation) = sfrbx.interpret() {
+                                        debug!(
+                                            "{} - decoded {:?}",
+                                            runtime.utc_time().round(cfg_precision),
+                                            interpretation
+                                        );
+
+                                        runtime.latch_sfrbx(sv, interpretation, cfg_precision);
+                                    } else {
+                                        error!(
+                                            "{} - SFRBX interpretation issue",
+                                            runtime.utc_time().round(cfg_precision)
+                                        );
+                                    }
+                                },
+                                c => {
+                                    error!(
+                                        "{} - {} constellation not handled yet",
+                                        runtime.utc_time().round(cfg_precision),
+                                        c
+                                    );
+                                },
+                            }
+                        }
+                    },
+                    None => {
+                        error!(
+                            "{} - constellation id error #{}",
+                            runtime.utc_time().round(cfg_precision),
+                            gnss_id
+                        );
+                    },
+                }
+            }
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::RxmSfrbx(sfrbx)) => {
+            if let Some(sbas) = &channels.sbas
+                && to_constellation(sfrbx.gnss_id()) == Some(Constellation::SBAS)
+            {
+                let mut prn = sfrbx.sv_id();
+
+                if prn >= SBAS_PRN_OFFSET {
+                    prn -= SBAS_PRN_OFFSET;
+                }
+
+                let message = SbasMessage {
+                    epoch: runtime.epoch(),
+                    sv: SV::new(Constellation::SBAS, prn),
+                    words: sfrbx.dwrd().collect(),
+                };
+
+                if let Err(e) = sbas.try_send(Message::Sbas(message)) {
+                    error!(
+                        "{} - failed to send SBAS message: {}",
+                        runtime.utc_time().round(cfg_precision),
+                        e
+                    );
+                }
+            }
+
+            // Do not process if user is not interested in this channel.
+            // When attached to hardware this naturally never happens.
+            // But this may arise in passive mode.
+            if ubx_settings.ephemeris {
+                let gnss_id = sfrbx.gnss_id();
+
+                match to_constellation(gnss_id) {
+                    Some(constellation) => {
+                        // does not proceeed if we're not interested by this system
+                        if ubx_settings.nav_constellations.contains(&constellation) {
+                            let mut prn = sfrbx.sv_id();
+
+                            if constellation.is_sbas() && prn >= SBAS_PRN_OFFSET {
+                                prn -= SBAS_PRN_OFFSET;
+                            }
+
+                            let sv = SV::new(constellation, prn);
+
+                            match constellation {
+                                Constellation::GPS | Constellation::QZSS => {
+                                    // decode
+                                    if let Some(interpretation) = sfrbx.interpret() {
+                                        debug!(
+                                            "{} - decoded {:?}",
+                                            runtime.utc_time().round(cfg_precision),
+                                            interpretation
+                                        );
+
+                                        runtime.latch_sfrbx(sv, interpretation, cfg_precision);
+                                    } else {
+                                        error!(
+                                            "{} - SFRBX interpretation issue",
+                                            runtime.utc_time().round(cfg_precision)
+                                        );
+                                    }
+                                },
+                                c => {
+                                    error!(
+                                        "{} - {} constellation not handled yet",
+                                        runtime.utc_time().round(cfg_precision),
+                                        c
+                                    );
+                                },
+                            }
+                        }
+                    },
+                    None => {
+                        error!(
+                            "{} - constellation id error #{}",
+                            runtime.utc_time().round(cfg_precision),
+                            gnss_id
+                        );
+                    },
+                }
+            }
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::MgaGpsEph(mga)) => {
+            if ubx_settings.ephemeris
+                && ubx_settings
+                    .nav_constellations
+                    .contains(&Constellation::GPS)
+            {
+                let sv = SV::new(Constellation::GPS, mga.sv_id());
+
+                // the week of the ephemeris comes from the epoch stream
+                let Some(toe) = runtime.gpst_time_of_week((mga.toe() * 1.0E3) as u32) else {
+                    debug!("{} - MGA-GPS-EPH: week number is not known yet", sv);
+                    return;
+                };
+
+                let week = (toe.to_time_of_week().0 % 1024) as u16;
+                let ephemeris = GpsQzssEphemeris::from_mga_gps_eph(week, &mga);
+
+                debug!(
+                    "{}({}) - MGA-GPS-EPH {:?}",
+                    runtime.utc_time().round(cfg_precision),
+                    sv,
+                    ephemeris
+                );
+
+                runtime.latch_gps_qzss_ephemeris(sv, ephemeris);
+            }
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::MgaGloEph(mga)) => {
+            trace!(
+                "{}(R{:02}) - MGA-GLO-EPH: Glonass ephemeris not supported yet",
+                runtime.utc_time().round(cfg_precision),
+                mga.sv_id()
+            );
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::MgaGpsEph(mga)) => {
+            if ubx_settings.ephemeris
+                && ubx_settings
+                    .nav_constellations
+                    .contains(&Constellation::GPS)
+            {
+                let sv = SV::new(Constellation::GPS, mga.sv_id());
+
+                // the week of the ephemeris comes from the epoch stream
+                let Some(toe) = runtime.gpst_time_of_week((mga.toe() * 1.0E3) as u32) else {
+                    debug!("{} - MGA-GPS-EPH: week number is not known yet", sv);
+                    return;
+                };
+
+                let week = (toe.to_time_of_week().0 % 1024) as u16;
+                let ephemeris = GpsQzssEphemeris::from_mga_gps_eph(week, &mga);
+
+                debug!(
+                    "{}({}) - MGA-GPS-EPH {:?}",
+                    runtime.utc_time().round(cfg_precision),
+                    sv,
+                    ephemeris
+                );
+
+                runtime.latch_gps_qzss_ephemeris(sv, ephemeris);
+            }
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::MgaGloEph(mga)) => {
+            trace!(
+                "{}(R{:02}) - MGA-GLO-EPH: Glonass ephemeris not supported yet",
+                runtime.utc_time().round(cfg_precision),
+                mga.sv_id()
+            );
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::MgaGpsEph(mga)) => {
+            if ubx_settings.ephemeris
+                && ubx_settings
+                    .nav_constellations
+                    .contains(&Constellation::GPS)
+            {
+                let sv = SV::new(Constellation::GPS, mga.sv_id());
+
+                // the week of the ephemeris comes from the epoch stream
+                let Some(toe) = runtime.gpst_time_of_week((mga.toe() * 1.0E3) as u32) else {
+                    debug!("{} - MGA-GPS-EPH: week number is not known yet", sv);
+                    return;
+                };
+
+                let week = (toe.to_time_of_week().0 % 1024) as u16;
+                let ephemeris = GpsQzssEphemeris::from_mga_gps_eph(week, &mga);
+
+                debug!(
+                    "{}({}) - MGA-GPS-EPH {:?}",
+                    runtime.utc_time().round(cfg_precision),
+                    sv,
+                    ephemeris
+                );
+
+                runtime.latch_gps_qzss_ephemeris(sv, ephemeris);
+            }
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::MgaGloEph(mga)) => {
+            trace!(
+                "{}(R{:02}) - MGA-GLO-EPH: Glonass ephemeris not supported yet",
+                runtime.utc_time().round(cfg_precision),
+                mga.sv_id()
+            );
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::TimTm2(pkt)) => {
+            if ubx_settings.rawxm && pkt.flags().contains(TimTm2Flags::NEW_RISING_EDGE) {
+                let nanos = pkt.tow_ms_r() as u64 * 1_000_000 + pkt.tow_sub_ms_r() as u64;
+
+                match tim_tm2_epoch(pkt.flags().bits(), pkt.wnr() as u32, nanos) {
+                    Some(t_event) => {
+                        let t_event = t_event.to_time_scale(ubx_settings.timescale);
+
+                        debug!("{} - TIM-TM2 external event #{}", t_event, pkt.count());
+
+                        obs_pending.push(Message::ExternalEvent(t_event));
+                    },
+                    None => {
+                        warn!(
+                            "{} - TIM-TM2 external event #{}: time is not valid",
+                            runtime.utc_time().round(cfg_precision),
+                            pkt.count()
+                        );
+                    },
+                }
+            }
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::TimTp(pkt)) => {
+            if channels.timepulse.is_some() {
+                let nanos = pkt.tow_ms() as u64 * 1_000_000;
+
+                let epoch = Epoch::from_time_of_week(pkt.week() as u32, nanos, TimeScale::GPST)
+                    .to_time_scale(ubx_settings.timescale);
+
+                let pulse = TimePulse {
+                    epoch,
+                    q_err: pkt.q_err(),
+                };
+
+                send_time_pulse(channels, pulse, cfg_precision);
+            }
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::TimTm2(pkt)) => {
+            if ubx_settings.rawxm && pkt.flags().contains(TimTm2Flags::NEW_RISING_EDGE) {
+                let nanos = pkt.tow_ms_r() as u64 * 1_000_000 + pkt.tow_sub_ms_r() as u64;
+
+                match tim_tm2_epoch(pkt.flags().bits(), pkt.wnr() as u32, nanos) {
+                    Some(t_event) => {
+                        let t_event = t_event.to_time_scale(ubx_settings.timescale);
+
+                        debug!("{} - TIM-TM2 external event #{}", t_event, pkt.count());
+
+                        obs_pending.push(Message::ExternalEvent(t_event));
+                    },
+                    None => {
+                        warn!(
+                            "{} - TIM-TM2 external event #{}: time is not valid",
+                            runtime.utc_time().round(cfg_precision),
+                            pkt.count()
+                        );
+                    },
+                }
+            }
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::TimTp(pkt)) => {
+            if channels.timepulse.is_some() {
+                let nanos = pkt.tow_ms() as u64 * 1_000_000;
+
+                let epoch = Epoch::from_time_of_week(pkt.week() as u32, nanos, TimeScale::GPST)
+                    .to_time_scale(ubx_settings.timescale);
+
+                let pulse = TimePulse {
+                    epoch,
+                    q_err: pkt.q_err(),
+                };
+
+                send_time_pulse(channels, pulse, cfg_precision);
+            }
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::TimTm2(pkt)) => {
+            if ubx_settings.rawxm && pkt.flags().contains(TimTm2Flags::NEW_RISING_EDGE) {
+                let nanos = pkt.tow_ms_r() as u64 * 1_000_000 + pkt.tow_sub_ms_r() as u64;
+
+                match tim_tm2_epoch(pkt.flags().bits(), pkt.wnr() as u32, nanos) {
+                    Some(t_event) => {
+                        let t_event = t_event.to_time_scale(ubx_settings.timescale);
+
+                        debug!("{} - TIM-TM2 external event #{}", t_event, pkt.count());
+
+                        obs_pending.push(Message::ExternalEvent(t_event));
+                    },
+                    None => {
+                        warn!(
+                            "{} - TIM-TM2 external event #{}: time is not valid",
+                            runtime.utc_time().round(cfg_precision),
+                            pkt.count()
+                        );
+                    },
+                }
+            }
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::TimTp(pkt)) => {
+            if channels.timepulse.is_some() {
+                let nanos = pkt.tow_ms() as u64 * 1_000_000;
+
+                let epoch = Epoch::from_time_of_week(pkt.week() as u32, nanos, TimeScale::GPST)
+                    .to_time_scale(ubx_settings.timescale);
+
+                let pulse = TimePulse {
+                    epoch,
+                    q_err: pkt.q_err(),
+                };
+
+                send_time_pulse(channels, pulse, cfg_precision);
+            }
+        },
+
+        #[cfg(feature = "ubx14")]
+        UbxPacket::Proto14(PacketRef::RxmRawx(pkt)) => {
+            // Do not process if user is not interested in this channel.
+            // When attached to hardware this naturally never happens.
+            // But this may arise in passive mode.
+            if ubx_settings.rawxm {
+                let tow_nanos = rawx_tow_nanos(pkt.rcv_tow(), ubx_settings.sampling_period);
+
+                // week and time of week are expressed in the receiver timescale
+                let t_gpst = Epoch::from_time_of_week(
+                    pkt.week() as u32,
+                    tow_nanos,
+                    ubx_settings.rawx_timescale.unwrap_or(TimeScale::GPST),
+                )
+                .to_time_scale(TimeScale::GPST);
+
+                runtime.new_epoch(t_gpst, ubx_settings.timescale);
+                runtime.rawx = true;
+
+                if runtime.nav_eoe_fallback(ubx_settings.nav_eoe_timeout()) {
+                    trace!("{} - End of Epoch (fallback)", t_gpst.round(cfg_precision));
+                    let _ = channels.nav.try_send(Message::EndofEpoch());
+                }
+
+                let stat = pkt.rec_stat();
+
+                if stat.intersects(RecStatFlags::CLK_RESET) {
+                    error!("{} - clock reset!", t_gpst.round(cfg_precision));
+
+                    obs_pending.push(Message::ClockReset(
+                        t_gpst.to_time_scale(ubx_settings.timescale),
+                    ));
+
+                    warn!(
+                        "{} - declaring phase cycle slip! - !!case is not handled!!",
+                        t_gpst.round(cfg_precision)
+                    );
+
+                    error!(
+                        "{} - phase cycle slip not correctly managed in current version",
+                        t_gpst.round(cfg_precision)
+                    );
+                }
+
+                let mut svs = Vec::new();
+
+                for meas in pkt.measurements() {
+                    // zero when invalid
+                    let pr = meas.pr_mes();
+                    let cp = meas.cp_mes();
+                    let dop = meas.do_mes();
+
+                    let _ = meas.pr_stdev(); // CXX deviation
+                    let _ = meas.cp_stdev(); // LXX deviation
+                    let _ = meas.do_stdev(); // DXX deviation
+
+                    let gnss_id = meas.gnss_id();
+                    let cno = meas.cno();
+
+                    let constell = to_constellation(gnss_id);
+
+                    if constell.is_none() {
+                        error!(
+                            "{} - unknown constellation: #{}",
+                            runtime.utc_time().round(cfg_precision),
+                            gnss_id
+                        );
+                        continue;
+                    }
+
+                    let constell = constell.unwrap();
+
+                    // does not proceed if we're not interested by this system
+                    if ubx_settings.constellations.contains(&constell) {
+                        let mut prn = meas.sv_id();
+
+                        if constell.is_sbas() && prn >= SBAS_PRN_OFFSET {
+                            prn -= SBAS_PRN_OFFSET;
+                        };
+
+                        let sv = SV::new(constell, prn);
+                        let t_meas = t_gpst.to_time_scale(ubx_settings.timescale);
+
+                        svs.push(sv);
+
+                        let rawxm = Rawxm {
+                            epoch: t_meas,
+                            sv,
+                            pr: valid_pseudo_range(pr),
+                            cp: valid_phase(cp),
+                            cno,
+                            dop,
+                            // GLONASS: frequency slot rather than signal identifier (L1 only)
+                            freq_id: if constell == Constellation::Glonass {
+                                0
+                            } else {
+                                meas.freq_id()
+                            },
+                            lock_time: Some(meas.lock_time()),
+                            glo_channel: glonass_channel(constell, meas.freq_id()),
+                            multipath: None,
+                        };
+
+                        obs_pending.push(Message::Measurement(rawxm));
+                    }
+                }
+
+                runtime.latch_rawx_svs(&svs);
+            }
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::RxmRawx(pkt)) => {
+            // Do not process if user is not interested in this channel.
+            // When attached to hardware this naturally never happens.
+            // But this may arise in passive mode.
+            if ubx_settings.rawxm {
+                let tow_nanos = rawx_tow_nanos(pkt.rcv_tow(), ubx_settings.sampling_period);
+
+                // week and time of week are expressed in the receiver timescale
+                let t_gpst = Epoch::from_time_of_week(
+                    pkt.week() as u32,
+                    tow_nanos,
+                    ubx_settings.rawx_timescale.unwrap_or(TimeScale::GPST),
+                )
+                .to_time_scale(TimeScale::GPST);
+
+                runtime.new_epoch(t_gpst, ubx_settings.timescale);
+                runtime.rawx = true;
+
+                if runtime.nav_eoe_fallback(ubx_settings.nav_eoe_timeout()) {
+                    trace!("{} - End of Epoch (fallback)", t_gpst.round(cfg_precision));
+                    let _ = channels.nav.try_send(Message::EndofEpoch());
+                }
+
+                let stat = pkt.rec_stat();
+
+                if stat.intersects(RecStatFlags::CLK_RESET) {
+                    error!("{} - clock reset!", t_gpst.round(cfg_precision));
+
+                    obs_pending.push(Message::ClockReset(
+                        t_gpst.to_time_scale(ubx_settings.timescale),
+                    ));
+
+                    warn!(
+                        "{} - declaring phase cycle slip! - !!case is not handled!!",
+                        t_gpst.round(cfg_precision)
+                    );
+
+                    error!(
+                        "{} - phase cycle slip not correctly managed in current version",
+                        t_gpst.round(cfg_precision)
+                    );
+                }
+
+                let mut svs = Vec::new();
+
+                for meas in pkt.measurements() {
+                    // zero when invalid
+                    let pr = meas.pr_mes();
+                    let cp = meas.cp_mes();
+                    let dop = meas.do_mes();
+
+                    let _ = meas.pr_stdev(); // CXX deviation
+                    let _ = meas.cp_stdev(); // LXX deviation
+                    let _ = meas.do_stdev(); // DXX deviation
+
+                    let gnss_id = meas.gnss_id();
+                    let cno = meas.cno();
+
+                    let constell = to_constellation(gnss_id);
+
+                    if constell.is_none() {
+                        error!(
+                            "{} - unknown constellation: #{}",
+                            runtime.utc_time().round(cfg_precision),
+                            gnss_id
+                        );
+                        continue;
+                    }
+
+                    let constell = constell.unwrap();
+
+                    // does not proceed if we're not interested by this system
+                    if ubx_settings.constellations.contains(&constell) {
+                        let mut prn = meas.sv_id();
+
+                        if constell.is_sbas() && prn >= SBAS_PRN_OFFSET {
+                            prn -= SBAS_PRN_OFFSET;
+                        };
+
+                        let sv = SV::new(constell, prn);
+                        let t_meas = t_gpst.to_time_scale(ubx_settings.timescale);
+
+                        svs.push(sv);
+
+                        let rawxm = Rawxm {
+                            epoch: t_meas,
+                            sv,
+                            pr: valid_pseudo_range(pr),
+                            cp: valid_phase(cp),
+                            cno,
+                            dop,
+                            // GLONASS: frequency slot rather than signal identifier (L1 only)
+                            freq_id: if constell == Constellation::Glonass {
+                                0
+                            } else {
+                                meas.freq_id()
+                            },
+                            lock_time: Some(meas.lock_time()),
+                            glo_channel: glonass_channel(constell, meas.freq_id()),
+                            multipath: None,
+                        };
+
+                        obs_pending.push(Message::Measurement(rawxm));
+                    }
+                }
+
+                runtime.latch_rawx_svs(&svs);
+            }
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::RxmRawx(pkt)) => {
+            // Do not process if user is not interested in this channel.
+            // When attached to hardware this naturally never happens.
+            // But this may arise in passive mode.
+            if ubx_settings.rawxm {
+                let tow_nanos = rawx_tow_nanos(pkt.rcv_tow(), ubx_settings.sampling_period);
+
+                // week and time of week are expressed in the receiver timescale
+                let t_gpst = Epoch::from_time_of_week(
+                    pkt.week() as u32,
+                    tow_nanos,
+                    ubx_settings.rawx_timescale.unwrap_or(TimeScale::GPST),
+                )
+                .to_time_scale(TimeScale::GPST);
+
+                runtime.new_epoch(t_gpst, ubx_settings.timescale);
+                runtime.rawx = true;
+
+                if runtime.nav_eoe_fallback(ubx_settings.nav_eoe_timeout()) {
+                    trace!("{} - End of Epoch (fallback)", t_gpst.round(cfg_precision));
+                    let _ = channels.nav.try_send(Message::EndofEpoch());
+                }
+
+                let stat = pkt.rec_stat();
+
+                if stat.intersects(RecStatFlags::CLK_RESET) {
+                    error!("{} - clock reset!", t_gpst.round(cfg_precision));
+
+                    obs_pending.push(Message::ClockReset(
+                        t_gpst.to_time_scale(ubx_settings.timescale),
+                    ));
+
+                    warn!(
+                        "{} - declaring phase cycle slip! - !!case is not handled!!",
+                        t_gpst.round(cfg_precision)
+                    );
+
+                    error!(
+                        "{} - phase cycle slip not correctly managed in current version",
+                        t_gpst.round(cfg_precision)
+                    );
+                }
+
+                let mut svs = Vec::new();
+
+                for meas in pkt.measurements() {
+                    // zero when invalid
+                    let pr = meas.pr_mes();
+                    let cp = meas.cp_mes();
+                    let dop = meas.do_mes();
+
+                    let _ = meas.pr_stdev(); // CXX deviation
+                    let _ = meas.cp_stdev(); // LXX deviation
+                    let _ = meas.do_stdev(); // DXX deviation
+
+                    let gnss_id = meas.gnss_id();
+                    let cno = meas.cno();
+
+                    let constell = to_constellation(gnss_id);
+
+                    if constell.is_none() {
+                        error!(
+                            "{} - unknown constellation: #{}",
+                            runtime.utc_time().round(cfg_precision),
+                            gnss_id
+                        );
+                        continue;
+                    }
+
+                    let constell = constell.unwrap();
+
+                    // does not proceed if we're not interested by this system
+                    if ubx_settings.constellations.contains(&constell) {
+                        let mut prn = meas.sv_id();
+
+                        if constell.is_sbas() && prn >= SBAS_PRN_OFFSET {
+                            prn -= SBAS_PRN_OFFSET;
+                        };
+
+                        let sv = SV::new(constell, prn);
+                        let t_meas = t_gpst.to_time_scale(ubx_settings.timescale);
+
+                        svs.push(sv);
+
+                        let sig_id = meas.sig_id();
+
+                        // NAV-SIG of this very epoch, as a cross-check
+                        if runtime.signal_agrees(t_gpst, sv, sig_id, cno) == Some(false) {
+                            warn!(
+                                "{} - {}: signal #{} not confirmed by NAV-SIG",
+                                t_gpst.round(cfg_precision),
+                                sv,
+                                sig_id
+                            );
+                        }
+
+                        let rawxm = Rawxm {
+                            epoch: t_meas,
+                            sv,
+                            pr: valid_pseudo_range(pr),
+                            cp: valid_phase(cp),
+                            cno,
+                            dop,
+                            freq_id: sig_id,
+                            lock_time: Some(meas.lock_time()),
+                            glo_channel: glonass_channel(constell, meas.freq_id()),
+                            multipath: None,
+                        };
+
+                        obs_pending.push(Message::Measurement(rawxm));
+                    }
+                }
+
+                runtime.latch_rawx_svs(&svs);
+            }
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::RxmRawx(pkt)) => {
+            // Do not process if user is not interested in this channel.
+            // When attached to hardware this naturally never happens.
+            // But this may arise in passive mode.
+            if ubx_settings.rawxm {
+                let tow_nanos = rawx_tow_nanos(pkt.rcv_tow(), ubx_settings.sampling_period);
+
+                // week and time of week are expressed in the receiver timescale
+                let t_gpst = Epoch::from_time_of_week(
+                    pkt.week() as u32,
+                    tow_nanos,
+                    ubx_settings.rawx_timescale.unwrap_or(TimeScale::GPST),
+                )
+                .to_time_scale(TimeScale::GPST);
+
+                runtime.new_epoch(t_gpst, ubx_settings.timescale);
+                runtime.rawx = true;
+
+                if runtime.nav_eoe_fallback(ubx_settings.nav_eoe_timeout()) {
+                    trace!("{} - End of Epoch (fallback)", t_gpst.round(cfg_precision));
+                    let _ = channels.nav.try_send(Message::EndofEpoch());
+                }
+
+                let stat = pkt.rec_stat();
+
+                if stat.intersects(RecStatFlags::CLK_RESET) {
+                    error!("{} - clock reset!", t_gpst.round(cfg_precision));
+
+                    obs_pending.push(Message::ClockReset(
+                        t_gpst.to_time_scale(ubx_settings.timescale),
+                    ));
+
+                    warn!(
+                        "{} - declaring phase cycle slip! - !!case is not handled!!",
+                        t_gpst.round(cfg_precision)
+                    );
+
+                    error!(
+                        "{} - phase cycle slip not correctly managed in current version",
+                        t_gpst.round(cfg_precision)
+                    );
+                }
+
+                let mut svs = Vec::new();
+
+                for meas in pkt.measurements() {
+                    // zero when invalid
+                    let pr = meas.pr_mes();
+                    let cp = meas.cp_mes();
+                    let dop = meas.do_mes();
+
+                    let _ = meas.pr_stdev(); // CXX deviation
+                    let _ = meas.cp_stdev(); // LXX deviation
+                    let _ = meas.do_stdev(); // DXX deviation
+
+                    let gnss_id = meas.gnss_id();
+                    let cno = meas.cno();
+
+                    let constell = to_constellation(gnss_id);
+
+                    if constell.is_none() {
+                        error!(
+                            "{} - unknown constellation: #{}",
+                            runtime.utc_time().round(cfg_precision),
+                            gnss_id
+                        );
+                        continue;
+                    }
+
+                    let constell = constell.unwrap();
+
+                    // does not proceed if we're not interested by this system
+                    if ubx_settings.constellations.contains(&constell) {
+                        let mut prn = meas.sv_id();
+
+                        if constell.is_sbas() && prn >= SBAS_PRN_OFFSET {
+                            prn -= SBAS_PRN_OFFSET;
+                        };
+
+                        let sv = SV::new(constell, prn);
+                        let t_meas = t_gpst.to_time_scale(ubx_settings.timescale);
+
+                        svs.push(sv);
+
+                        let sig_id = meas.sig_id();
+
+                        // NAV-SIG of this very epoch, as a cross-check
+                        if runtime.signal_agrees(t_gpst, sv, sig_id, cno) == Some(false) {
+                            warn!(
+                                "{} - {}: signal #{} not confirmed by NAV-SIG",
+                                t_gpst.round(cfg_precision),
+                                sv,
+                                sig_id
+                            );
+                        }
+
+                        let rawxm = Rawxm {
+                            epoch: t_meas,
+                            sv,
+                            pr: valid_pseudo_range(pr),
+                            cp: valid_phase(cp),
+                            cno,
+                            dop,
+                            freq_id: sig_id,
+                            lock_time: Some(meas.lock_time()),
+                            glo_channel: glonass_channel(constell, meas.freq_id()),
+                            multipath: None,
+                        };
+
+                        obs_pending.push(Message::Measurement(rawxm));
+                    }
+                }
+
+                runtime.latch_rawx_svs(&svs);
+            }
+        },
+
+        #[cfg(feature = "ubx14")]
+        UbxPacket::Proto14(PacketRef::Unknown(pkt))
+            if pkt.class == RXM_MEASX_CLASS && pkt.msg_id == RXM_MEASX_ID =>
+        {
+            handle_rxm_measx(
+                pkt.payload,
+                runtime,
+                obs_pending,
+                channels,
+                cfg_precision,
+                ubx_settings,
+            );
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::Unknown(pkt))
+            if pkt.class == RXM_MEASX_CLASS && pkt.msg_id == RXM_MEASX_ID =>
+        {
+            handle_rxm_measx(
+                pkt.payload,
+                runtime,
+                obs_pending,
+                channels,
+                cfg_precision,
+                ubx_settings,
+            );
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::Unknown(pkt))
+            if pkt.class == RXM_MEASX_CLASS && pkt.msg_id == RXM_MEASX_ID =>
+        {
+            handle_rxm_measx(
+                pkt.payload,
+                runtime,
+                obs_pending,
+                channels,
+                cfg_precision,
+                ubx_settings,
+            );
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::Unknown(pkt))
+            if pkt.class == RXM_MEASX_CLASS && pkt.msg_id == RXM_MEASX_ID =>
+        {
+            handle_rxm_measx(
+                pkt.payload,
+                runtime,
+                obs_pending,
+                channels,
+                cfg_precision,
+                ubx_settings,
+            );
+        },
+
+        #[cfg(feature = "ubx14")]
+        UbxPacket::Proto14(PacketRef::MonVer(mon_version)) => {
+            let software_version = mon_version.software_version().to_string();
+
+            match channels
+                .obs
+                .try_send(Message::FirmwareVersion(software_version))
+            {
+                Ok(_) => {},
+                Err(e) => {
+                    error!(
+                        "{} - failed to send firmware version: {}",
+                        runtime.utc_time().round(cfg_precision),
+                        e
+                    );
+                },
+            }
+
+            let comment = format!("UBlox hardware version: {}", mon_version.hardware_version());
+
+            match channels.obs.try_send(Message::HeaderComment(comment)) {
+                Ok(_) => {},
+                Err(e) => {
+                    error!(
+                        "{} - failed to send hardware version: {}",
+                        runtime.utc_time().round(cfg_precision),
+                        e
+                    );
+                },
+            }
+
+            let comment = format!("UBlox protocol: {}", mon_version.extension().join(","));
+
+            match channels.obs.try_send(Message::HeaderComment(comment)) {
+                Ok(_) => {},
+                Err(e) => {
+                    error!(
+                        "{} - failed to send ublox proto version: {}",
+                        runtime.utc_time().round(cfg_precision),
+                        e
+                    );
+                },
+            }
+
+            if let Some((major, minor)) = mon_version.extension().find_map(protocol_version)
+                && suited_protocol(major) > PROTOCOL
+            {
+                error!(
+                    "UBX protocol {}.{:02} stream, but ubx2rinex was built for protocol {}: rebuild with --features ubx{}",
+                    major,
+                    minor,
+                    PROTOCOL,
+                    suited_protocol(major)
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::MonVer(mon_version)) => {
+            let software_version = mon_version.software_version().to_string();
+
+            match channels
+                .obs
+                .try_send(Message::FirmwareVersion(software_version))
+            {
+                Ok(_) => {},
+                Err(e) => {
+                    error!(
+                        "{} - failed to send firmware version: {}",
+                        runtime.utc_time().round(cfg_precision),
+                        e
+                    );
+                },
+            }
+
+            let comment = format!("UBlox hardware version: {}", mon_version.hardware_version());
+
+            match channels.obs.try_send(Message::HeaderComment(comment)) {
+                Ok(_) => {},
+                Err(e) => {
+                    error!(
+                        "{} - failed to send hardware version: {}",
+                        runtime.utc_time().round(cfg_precision),
+                        e
+                    );
+                },
+            }
+
+            let comment = format!("UBlox protocol: {}", mon_version.extension().join(","));
+
+            match channels.obs.try_send(Message::HeaderComment(comment)) {
+                Ok(_) => {},
+                Err(e) => {
+                    error!(
+                        "{} - failed to send ublox proto version: {}",
+                        runtime.utc_time().round(cfg_precision),
+                        e
+                    );
+                },
+            }
+
+            if let Some((major, minor)) = mon_version.extension().find_map(protocol_version)
+                && suited_protocol(major) > PROTOCOL
+            {
+                error!(
+                    "UBX protocol {}.{:02} stream, but ubx2rinex was built for protocol {}: rebuild with --features ubx{}",
+                    major,
+                    minor,
+                    PROTOCOL,
+                    suited_protocol(major)
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::MonVer(mon_version)) => {
+            let software_version = mon_version.software_version().to_string();
+
+            match channels
+                .obs
+                .try_send(Message::FirmwareVersion(software_version))
+            {
+                Ok(_) => {},
+                Err(e) => {
+                    error!(
+                        "{} - failed to send firmware version: {}",
+                        runtime.utc_time().round(cfg_precision),
+                        e
+                    );
+                },
+            }
+
+            let comment = format!("UBlox hardware version: {}", mon_version.hardware_version());
+
+            match channels.obs.try_send(Message::HeaderComment(comment)) {
+                Ok(_) => {},
+                Err(e) => {
+                    error!(
+                        "{} - failed to send hardware version: {}",
+                        runtime.utc_time().round(cfg_precision),
+                        e
+                    );
+                },
+            }
+
+            let comment = format!("UBlox protocol: {}", mon_version.extension().join(","));
+
+            match channels.obs.try_send(Message::HeaderComment(comment)) {
+                Ok(_) => {},
+                Err(e) => {
+                    error!(
+                        "{} - failed to send ublox proto version: {}",
+                        runtime.utc_time().round(cfg_precision),
+                        e
+                    );
+                },
+            }
+
+            if let Some((major, minor)) = mon_version.extension().find_map(protocol_version)
+                && suited_protocol(major) > PROTOCOL
+            {
+                error!(
+                    "UBX protocol {}.{:02} stream, but ubx2rinex was built for protocol {}: rebuild with --features ubx{}",
+                    major,
+                    minor,
+                    PROTOCOL,
+                    suited_protocol(major)
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::MonVer(mon_version)) => {
+            let software_version = mon_version.software_version().to_string();
+
+            match channels
+                .obs
+                .try_send(Message::FirmwareVersion(software_version))
+            {
+                Ok(_) => {},
+                Err(e) => {
+                    error!(
+                        "{} - failed to send firmware version: {}",
+                        runtime.utc_time().round(cfg_precision),
+                        e
+                    );
+                },
+            }
+
+            let comment = format!("UBlox hardware version: {}", mon_version.hardware_version());
+
+            match channels.obs.try_send(Message::HeaderComment(comment)) {
+                Ok(_) => {},
+                Err(e) => {
+                    error!(
+                        "{} - failed to send hardware version: {}",
+                        runtime.utc_time().round(cfg_precision),
+                        e
+                    );
+                },
+            }
+
+            let comment = format!("UBlox protocol: {}", mon_version.extension().join(","));
+
+            match channels.obs.try_send(Message::HeaderComment(comment)) {
+                Ok(_) => {},
+                Err(e) => {
+                    error!(
+                        "{} - failed to send ublox proto version: {}",
+                        runtime.utc_time().round(cfg_precision),
+                        e
+                    );
+                },
+            }
+
+            if let Some((major, minor)) = mon_version.extension().find_map(protocol_version)
+                && suited_protocol(major) > PROTOCOL
+            {
+                error!(
+                    "UBX protocol {}.{:02} stream, but ubx2rinex was built for protocol {}: rebuild with --features ubx{}",
+                    major,
+                    minor,
+                    PROTOCOL,
+                    suited_protocol(major)
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx14")]
+        UbxPacket::Proto14(PacketRef::MonHw(mon_hardware)) => {
+            // TODO: should contribute to hardware events
+            let _ = mon_hardware.a_status();
+            let _ = mon_hardware.a_power();
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::MonHw(mon_hardware)) => {
+            // TODO: should contribute to hardware events
+            let _ = mon_hardware.a_status();
+            let _ = mon_hardware.a_power();
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::MonHw(mon_hardware)) => {
+            // TODO: should contribute to hardware events
+            let _ = mon_hardware.a_status();
+            let _ = mon_hardware.a_power();
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::MonHw(mon_hardware)) => {
+            // TODO: should contribute to hardware events
+            let _ = mon_hardware.a_status();
+            let _ = mon_hardware.a_power();
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::MonRf(mon_rf)) => {
+            if let Some(spectrum) = &channels.spectrum {
+                let epoch = runtime.epoch();
+
+                for block in mon_rf.blocks() {
+                    let block = RfBlock {
+                        epoch,
+                        block_id: block.block_id as _,
+                        noise_per_ms: block.noise_per_ms as _,
+                        agc_cnt: block.agc_cnt as _,
+                        jam_ind: block.jam_ind as _,
+                        ofs_i: block.ofs_i as _,
+                        mag_i: block.mag_i as _,
+                        ofs_q: block.ofs_q as _,
+                        mag_q: block.mag_q as _,
+                    };
+
+                    if let Err(e) = spectrum.try_send(Message::Spectrum(block)) {
+                        error!(
+                            "{} - failed to send RF telemetry: {}",
+                            runtime.utc_time().round(cfg_precision),
+                            e
+                        );
+                    }
+                }
+            }
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::MonRf(mon_rf)) => {
+            if let Some(spectrum) = &channels.spectrum {
+                let epoch = runtime.epoch();
+
+                for block in mon_rf.blocks() {
+                    let block = RfBlock {
+                        epoch,
+                        block_id: block.block_id as _,
+                        noise_per_ms: block.noise_per_ms as _,
+                        agc_cnt: block.agc_cnt as _,
+                        jam_ind: block.jam_ind as _,
+                        ofs_i: block.ofs_i as _,
+                        mag_i: block.mag_i as _,
+                        ofs_q: block.ofs_q as _,
+                        mag_q: block.mag_q as _,
+                    };
+
+                    if let Err(e) = spectrum.try_send(Message::Spectrum(block)) {
+                        error!(
+                            "{} - failed to send RF telemetry: {}",
+                            runtime.utc_time().round(cfg_precision),
+                            e
+                        );
+                    }
+                }
+            }
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::MonRf(mon_rf)) => {
+            if let Some(spectrum) = &channels.spectrum {
+                let epoch = runtime.epoch();
+
+                for block in mon_rf.blocks() {
+                    let block = RfBlock {
+                        epoch,
+                        block_id: block.block_id as _,
+                        noise_per_ms: block.noise_per_ms as _,
+                        agc_cnt: block.agc_cnt as _,
+                        jam_ind: block.jam_ind as _,
+                        ofs_i: block.ofs_i as _,
+                        mag_i: block.mag_i as _,
+                        ofs_q: block.ofs_q as _,
+                        mag_q: block.mag_q as _,
+                    };
+
+                    if let Err(e) = spectrum.try_send(Message::Spectrum(block)) {
+                        error!(
+                            "{} - failed to send RF telemetry: {}",
+                            runtime.utc_time().round(cfg_precision),
+                            e
+                        );
+                    }
+                }
+            }
+        },
+
+        #[cfg(feature = "ubx14")]
+        UbxPacket::Proto14(PacketRef::NavSat(pkt)) => {
+            let mut used = 0;
+
+            for sv in pkt.svs() {
+                let constellation = to_constellation(sv.gnss_id());
+
+                if constellation.is_none() {
+                    continue;
+                }
+
+                let constellation = constellation.unwrap();
+
+                let _elev = sv.elev();
+                let _azim = sv.azim();
+                let _pr_res = sv.pr_res();
+                let flags = sv.flags();
+
+                if flags.sv_used() && ubx_settings.constellations.contains(&constellation) {
+                    used += 1;
+                }
+
+                let mut prn = sv.sv_id();
+
+                if constellation.is_sbas() && prn >= SBAS_PRN_OFFSET {
+                    prn -= SBAS_PRN_OFFSET;
+                }
+
+                // let sv = SV::new(constellation, prn);
+                //flags.health();
+                //flags.quality_ind();
+                //flags.differential_correction_available();
+                //flags.ephemeris_available();
+            }
+
+            check_sv_used(runtime, used, cfg_precision, ubx_settings);
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::NavSat(pkt)) => {
+            let mut used = 0;
+
+            for sv in pkt.svs() {
+                let constellation = to_constellation(sv.gnss_id());
+
+                if constellation.is_none() {
+                    continue;
+                }
+
+                let constellation = constellation.unwrap();
+
+                let _elev = sv.elev();
+                let _azim = sv.azim();
+                let _pr_res = sv.pr_res();
+                let flags = sv.flags();
+
+                if flags.sv_used() && ubx_settings.constellations.contains(&constellation) {
+                    used += 1;
+                }
+
+                let mut prn = sv.sv_id();
+
+                if constellation.is_sbas() && prn >= SBAS_PRN_OFFSET {
+                    prn -= SBAS_PRN_OFFSET;
+                }
+
+                // let sv = SV::new(constellation, prn);
+                //flags.health();
+                //flags.quality_ind();
+                //flags.differential_correction_available();
+                //flags.ephemeris_available();
+            }
+
+            check_sv_used(runtime, used, cfg_precision, ubx_settings);
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::NavSat(pkt)) => {
+            let mut used = 0;
+
+            for sv in pkt.svs() {
+                let constellation = to_constellation(sv.gnss_id());
+
+                if constellation.is_none() {
+                    continue;
+                }
+
+                let constellation = constellation.unwrap();
+
+                let _elev = sv.elev();
+                let _azim = sv.azim();
+                let _pr_res = sv.pr_res();
+                let flags = sv.flags();
+
+                if flags.sv_used() && ubx_settings.constellations.contains(&constellation) {
+                    used += 1;
+                }
+
+                let mut prn = sv.sv_id();
+
+                if constellation.is_sbas() && prn >= SBAS_PRN_OFFSET {
+                    prn -= SBAS_PRN_OFFSET;
+                }
+
+                // let sv = SV::new(constellation, prn);
+                //flags.health();
+                //flags.quality_ind();
+                //flags.differential_correction_available();
+                //flags.ephemeris_available();
+            }
+
+            check_sv_used(runtime, used, cfg_precision, ubx_settings);
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::NavSat(pkt)) => {
+            let mut used = 0;
+
+            for sv in pkt.svs() {
+                let constellation = to_constellation(sv.gnss_id());
+
+                if constellation.is_none() {
+                    continue;
+                }
+
+                let constellation = constellation.unwrap();
+
+                let _elev = sv.elev();
+                let _azim = sv.azim();
+                let _pr_res = sv.pr_res();
+                let flags = sv.flags();
+
+                if flags.sv_used() && ubx_settings.constellations.contains(&constellation) {
+                    used += 1;
+                }
+
+                let mut prn = sv.sv_id();
+
+                if constellation.is_sbas() && prn >= SBAS_PRN_OFFSET {
+                    prn -= SBAS_PRN_OFFSET;
+                }
+
+                // let sv = SV::new(constellation, prn);
+                //flags.health();
+                //flags.quality_ind();
+                //flags.differential_correction_available();
+                //flags.ephemeris_available();
+            }
+
+            check_sv_used(runtime, used, cfg_precision, ubx_settings);
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::NavSig(pkt)) => {
+            latch_nav_sig(
+                runtime,
+                pkt.itow(),
+                pkt.sigs()
+                    .map(|sig| (sig.gnss_id(), sig.sv_id(), sig.sig_id(), sig.cno())),
+            );
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::NavSig(pkt)) => {
+            latch_nav_sig(
+                runtime,
+                pkt.itow(),
+                pkt.sigs()
+                    .map(|sig| (sig.gnss_id(), sig.sv_id(), sig.sig_id(), sig.cno())),
+            );
+        },
+
+        #[cfg(feature = "ubx14")]
+        UbxPacket::Proto14(PacketRef::NavTimeUTC(pkt)) => {
+            if pkt.valid().intersects(NavTimeUtcFlags::VALID_UTC) {
+                let (y, m, d) = (pkt.year() as i32, pkt.month(), pkt.day());
+                let (hh, mm, ss) = (pkt.hour(), pkt.min(), pkt.sec());
+
+                let t_utc = Epoch::from_gregorian(y, m, d, hh, mm, ss, 0, TimeScale::UTC);
+                runtime.latch_time_reference(t_utc);
+            }
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::NavTimeUTC(pkt)) => {
+            if pkt.valid().intersects(NavTimeUtcFlags::VALID_UTC) {
+                let (y, m, d) = (pkt.year() as i32, pkt.month(), pkt.day());
+                let (hh, mm, ss) = (pkt.hour(), pkt.min(), pkt.sec());
+
+                let t_utc = Epoch::from_gregorian(y, m, d, hh, mm, ss, 0, TimeScale::UTC);
+                runtime.latch_time_reference(t_utc);
+            }
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::NavTimeUTC(pkt)) => {
+            if pkt.valid().intersects(NavTimeUtcFlags::VALID_UTC) {
+                let (y, m, d) = (pkt.year() as i32, pkt.month(), pkt.day());
+                let (hh, mm, ss) = (pkt.hour(), pkt.min(), pkt.sec());
+
+                let t_utc = Epoch::from_gregorian(y, m, d, hh, mm, ss, 0, TimeScale::UTC);
+                runtime.latch_time_reference(t_utc);
+            }
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::NavTimeUTC(pkt)) => {
+            if pkt.valid().intersects(NavTimeUtcFlags::VALID_UTC) {
+                let (y, m, d) = (pkt.year() as i32, pkt.month(), pkt.day());
+                let (hh, mm, ss) = (pkt.hour(), pkt.min(), pkt.sec());
+
+                let t_utc = Epoch::from_gregorian(y, m, d, hh, mm, ss, 0, TimeScale::UTC);
+                runtime.latch_time_reference(t_utc);
+            }
+        },
+
+        #[cfg(feature = "ubx14")]
+        UbxPacket::Proto14(PacketRef::NavStatus(pkt)) => {
+            //itow = pkt.itow();
+            runtime.uptime = Duration::from_milliseconds(pkt.uptime_ms() as f64);
+
+            trace!(
+                "Fix status: {:?} | {:?} | {:?}",
+                pkt.fix_stat(),
+                pkt.flags(),
+                pkt.flags2()
+            );
+
+            trace!("Uptime: {}", runtime.uptime);
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::NavStatus(pkt)) => {
+            //itow = pkt.itow();
+            runtime.uptime = Duration::from_milliseconds(pkt.uptime_ms() as f64);
+
+            trace!(
+                "Fix status: {:?} | {:?} | {:?}",
+                pkt.fix_stat(),
+                pkt.flags(),
+                pkt.flags2()
+            );
+
+            trace!("Uptime: {}", runtime.uptime);
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::NavStatus(pkt)) => {
+            //itow = pkt.itow();
+            runtime.uptime = Duration::from_milliseconds(pkt.uptime_ms() as f64);
+
+            trace!(
+                "Fix status: {:?} | {:?} | {:?}",
+                pkt.fix_stat(),
+                pkt.flags(),
+                pkt.flags2()
+            );
+
+            trace!("Uptime: {}", runtime.uptime);
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::NavStatus(pkt)) => {
+            //itow = pkt.itow();
+            runtime.uptime = Duration::from_milliseconds(pkt.uptime_ms() as f64);
+
+            trace!(
+                "Fix status: {:?} | {:?} | {:?}",
+                pkt.fix_stat(),
+                pkt.flags(),
+                pkt.flags2()
+            );
+
+            trace!("Uptime: {}", runtime.uptime);
+        },
+
+        #[cfg(feature = "ubx14")]
+        UbxPacket::Proto14(PacketRef::NavEoe(pkt)) => {
+            // week is unknown until the first measurement or navigation solution
+            match runtime.gpst_time_of_week(pkt.itow()) {
+                Some(t_gpst) => {
+                    runtime.latch_nav_eoe(t_gpst);
+                    trace!("{} - End of Epoch", t_gpst.round(cfg_precision));
+                },
+                None => {
+                    trace!("End of Epoch (unresolved week)");
+                },
+            }
+
+            let _ = channels.nav.try_send(Message::EndofEpoch());
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::NavEoe(pkt)) => {
+            // week is unknown until the first measurement or navigation solution
+            match runtime.gpst_time_of_week(pkt.itow()) {
+                Some(t_gpst) => {
+                    runtime.latch_nav_eoe(t_gpst);
+                    trace!("{} - End of Epoch", t_gpst.round(cfg_precision));
+                },
+                None => {
+                    trace!("End of Epoch (unresolved week)");
+                },
+            }
+
+            let _ = channels.nav.try_send(Message::EndofEpoch());
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::NavEoe(pkt)) => {
+            // week is unknown until the first measurement or navigation solution
+            match runtime.gpst_time_of_week(pkt.itow()) {
+                Some(t_gpst) => {
+                    runtime.latch_nav_eoe(t_gpst);
+                    trace!("{} - End of Epoch", t_gpst.round(cfg_precision));
+                },
+                None => {
+                    trace!("End of Epoch (unresolved week)");
+                },
+            }
+
+            let _ = channels.nav.try_send(Message::EndofEpoch());
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::NavEoe(pkt)) => {
+            // week is unknown until the first measurement or navigation solution
+            match runtime.gpst_time_of_week(pkt.itow()) {
+                Some(t_gpst) => {
+                    runtime.latch_nav_eoe(t_gpst);
+                    trace!("{} - End of Epoch", t_gpst.round(cfg_precision));
+                },
+                None => {
+                    trace!("End of Epoch (unresolved week)");
+                },
+            }
+
+            let _ = channels.nav.try_send(Message::EndofEpoch());
+        },
+
+        #[cfg(feature = "ubx14")]
+        UbxPacket::Proto14(PacketRef::NavPvt(pkt)) => {
+            let (y, m, d) = (pkt.year() as i32, pkt.month(), pkt.day());
+            let (hh, mm, ss) = (pkt.hour(), pkt.min(), pkt.sec());
+
+            if pkt.valid().intersects(NavPvtValidFlags::FULLY_RESOLVED) {
+                let t_solution = Epoch::from_gregorian(y, m, d, hh, mm, ss, 0, TimeScale::UTC)
+                    .to_time_scale(ubx_settings.timescale);
+
+                runtime.latch_time_reference(t_solution);
+
+                trace!(
+                    "{} - PVT SOLUTION: lat={:.5E}° long={:.5E}°",
+                    t_solution.round(cfg_precision),
+                    pkt.latitude(),
+                    pkt.longitude()
+                );
+
+                if pkt.flags().intersects(NavPvtFlags::GPS_FIX_OK) {
+                    infer_country(
+                        runtime,
+                        obs_pending,
+                        channels,
+                        pkt.latitude(),
+                        pkt.longitude(),
+                    );
+
+                    let velocity = Velocity {
+                        epoch: t_solution,
+                        north: pkt.vel_north(),
+                        east: pkt.vel_east(),
+                        down: pkt.vel_down(),
+                        speed: pkt.ground_speed_2d(),
+                        heading: pkt.heading_motion(),
+                    };
+
+                    send_velocity(channels, velocity, cfg_precision);
+                }
+
+                // RXM-RAWX is not streamed: the epoch stream is defined by NAV-PVT
+                if !ubx_settings.rawxm {
+                    runtime.new_epoch(t_solution, ubx_settings.timescale);
+
+                    if runtime.nav_eoe_fallback(ubx_settings.nav_eoe_timeout()) {
+                        trace!(
+                            "{} - End of Epoch (fallback)",
+                            t_solution.round(cfg_precision)
+                        );
+                        let _ = channels.nav.try_send(Message::EndofEpoch());
+                    }
+                }
+            }
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::NavPvt(pkt)) => {
+            let (y, m, d) = (pkt.year() as i32, pkt.month(), pkt.day());
+            let (hh, mm, ss) = (pkt.hour(), pkt.min(), pkt.sec());
+
+            if pkt.valid().intersects(NavPvtValidFlags::FULLY_RESOLVED) {
+                let t_solution = Epoch::from_gregorian(y, m, d, hh, mm, ss, 0, TimeScale::UTC)
+                    .to_time_scale(ubx_settings.timescale);
+
+                runtime.latch_time_reference(t_solution);
+
+                trace!(
+                    "{} - PVT SOLUTION: lat={:.5E}° long={:.5E}°",
+                    t_solution.round(cfg_precision),
+                    pkt.latitude(),
+                    pkt.longitude()
+                );
+
+                if pkt.flags().intersects(NavPvtFlags::GPS_FIX_OK) {
+                    infer_country(
+                        runtime,
+                        obs_pending,
+                        channels,
+                        pkt.latitude(),
+                        pkt.longitude(),
+                    );
+
+                    let velocity = Velocity {
+                        epoch: t_solution,
+                        north: pkt.vel_north(),
+                        east: pkt.vel_east(),
+                        down: pkt.vel_down(),
+                        speed: pkt.ground_speed_2d(),
+                        heading: pkt.heading_motion(),
+                    };
+
+                    send_velocity(channels, velocity, cfg_precision);
+                }
+
+                // RXM-RAWX is not streamed: the epoch stream is defined by NAV-PVT
+                if !ubx_settings.rawxm {
+                    runtime.new_epoch(t_solution, ubx_settings.timescale);
+
+                    if runtime.nav_eoe_fallback(ubx_settings.nav_eoe_timeout()) {
+                        trace!(
+                            "{} - End of Epoch (fallback)",
+                            t_solution.round(cfg_precision)
+                        );
+                        let _ = channels.nav.try_send(Message::EndofEpoch());
+                    }
+                }
+            }
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::NavPvt(pkt)) => {
+            let (y, m, d) = (pkt.year() as i32, pkt.month(), pkt.day());
+            let (hh, mm, ss) = (pkt.hour(), pkt.min(), pkt.sec());
+
+            if pkt.valid().intersects(NavPvtValidFlags::FULLY_RESOLVED) {
+                let t_solution = Epoch::from_gregorian(y, m, d, hh, mm, ss, 0, TimeScale::UTC)
+                    .to_time_scale(ubx_settings.timescale);
+
+                runtime.latch_time_reference(t_solution);
+
+                trace!(
+                    "{} - PVT SOLUTION: lat={:.5E}° long={:.5E}°",
+                    t_solution.round(cfg_precision),
+                    pkt.latitude(),
+                    pkt.longitude()
+                );
+
+                if pkt.flags().intersects(NavPvtFlags::GPS_FIX_OK) {
+                    infer_country(
+                        runtime,
+                        obs_pending,
+                        channels,
+                        pkt.latitude(),
+                        pkt.longitude(),
+                    );
+
+                    let velocity = Velocity {
+                        epoch: t_solution,
+                        north: pkt.vel_north(),
+                        east: pkt.vel_east(),
+                        down: pkt.vel_down(),
+                        speed: pkt.ground_speed_2d(),
+                        heading: pkt.heading_motion(),
+                    };
+
+                    send_velocity(channels, velocity, cfg_precision);
+                }
+
+                // RXM-RAWX is not streamed: the epoch stream is defined by NAV-PVT
+                if !ubx_settings.rawxm {
+                    runtime.new_epoch(t_solution, ubx_settings.timescale);
+
+                    if runtime.nav_eoe_fallback(ubx_settings.nav_eoe_timeout()) {
+                        trace!(
+                            "{} - End of Epoch (fallback)",
+                            t_solution.round(cfg_precision)
+                        );
+                        let _ = channels.nav.try_send(Message::EndofEpoch());
+                    }
+                }
+            }
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::NavPvt(pkt)) => {
+            let (y, m, d) = (pkt.year() as i32, pkt.month(), pkt.day());
+            let (hh, mm, ss) = (pkt.hour(), pkt.min(), pkt.sec());
+
+            if pkt.valid().intersects(NavPvtValidFlags::FULLY_RESOLVED) {
+                let t_solution = Epoch::from_gregorian(y, m, d, hh, mm, ss, 0, TimeScale::UTC)
+                    .to_time_scale(ubx_settings.timescale);
+
+                runtime.latch_time_reference(t_solution);
+
+                trace!(
+                    "{} - PVT SOLUTION: lat={:.5E}° long={:.5E}°",
+                    t_solution.round(cfg_precision),
+                    pkt.latitude(),
+                    pkt.longitude()
+                );
+
+                if pkt.flags().intersects(NavPvtFlags::GPS_FIX_OK) {
+                    infer_country(
+                        runtime,
+                        obs_pending,
+                        channels,
+                        pkt.latitude(),
+                        pkt.longitude(),
+                    );
+
+                    let velocity = Velocity {
+                        epoch: t_solution,
+                        north: pkt.vel_north(),
+                        east: pkt.vel_east(),
+                        down: pkt.vel_down(),
+                        speed: pkt.ground_speed_2d(),
+                        heading: pkt.heading_motion(),
+                    };
+
+                    send_velocity(channels, velocity, cfg_precision);
+                }
+
+                // RXM-RAWX is not streamed: the epoch stream is defined by NAV-PVT
+                if !ubx_settings.rawxm {
+                    runtime.new_epoch(t_solution, ubx_settings.timescale);
+
+                    if runtime.nav_eoe_fallback(ubx_settings.nav_eoe_timeout()) {
+                        trace!(
+                            "{} - End of Epoch (fallback)",
+                            t_solution.round(cfg_precision)
+                        );
+                        let _ = channels.nav.try_send(Message::EndofEpoch());
+                    }
+                }
+            }
+        },
+
+        #[cfg(feature = "ubx14")]
+        UbxPacket::Proto14(PacketRef::NavClock(pkt)) => {
+            // Do not process if user is not interested in this channel,
+            // nor while the week is unknown.
+            if ubx_settings.rawxm
+                && ubx_settings.rx_clock
+                && let Some(t) = runtime.gpst_time_of_week(pkt.itow())
+            {
+                let t = t.to_time_scale(ubx_settings.timescale);
+
+                let clock = pkt.clk_bias();
+                obs_pending.push(Message::Clock((t, clock)));
+            }
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::NavClock(pkt)) => {
+            // Do not process if user is not interested in this channel,
+            // nor while the week is unknown.
+            if ubx_settings.rawxm
+                && ubx_settings.rx_clock
+                && let Some(t) = runtime.gpst_time_of_week(pkt.itow())
+            {
+                let t = t.to_time_scale(ubx_settings.timescale);
+
+                let clock = pkt.clk_bias();
+                obs_pending.push(Message::Clock((t, clock)));
+            }
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::NavClock(pkt)) => {
+            // Do not process if user is not interested in this channel,
+            // nor while the week is unknown.
+            if ubx_settings.rawxm
+                && ubx_settings.rx_clock
+                && let Some(t) = runtime.gpst_time_of_week(pkt.itow())
+            {
+                let t = t.to_time_scale(ubx_settings.timescale);
+
+                let clock = pkt.clk_bias();
+                obs_pending.push(Message::Clock((t, clock)));
+            }
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::NavClock(pkt)) => {
+            // Do not process if user is not interested in this channel,
+            // nor while the week is unknown.
+            if ubx_settings.rawxm
+                && ubx_settings.rx_clock
+                && let Some(t) = runtime.gpst_time_of_week(pkt.itow())
+            {
+                let t = t.to_time_scale(ubx_settings.timescale);
+
+                let clock = pkt.clk_bias();
+                obs_pending.push(Message::Clock((t, clock)));
+            }
+        },
+
+        #[cfg(feature = "ubx14")]
+        UbxPacket::Proto14(PacketRef::InfTest(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                trace!(
+                    "{} - received test message {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::InfTest(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                trace!(
+                    "{} - received test message {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::InfTest(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                trace!(
+                    "{} - received test message {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::InfTest(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                trace!(
+                    "{} - received test message {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx14")]
+        UbxPacket::Proto14(PacketRef::InfDebug(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                debug!(
+                    "{} - received debug message {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::InfDebug(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                debug!(
+                    "{} - received debug message {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::InfDebug(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                debug!(
+                    "{} - received debug message {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::InfDebug(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                debug!(
+                    "{} - received debug message {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx14")]
+        UbxPacket::Proto14(PacketRef::InfNotice(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                info!(
+                    "{} - received notification {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+
+                capture_inf(
+                    runtime,
+                    channels,
+                    InfLevel::Notice,
+                    msg,
+                    ubx_settings,
+                    cfg_precision,
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::InfNotice(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                info!(
+                    "{} - received notification {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+
+                capture_inf(
+                    runtime,
+                    channels,
+                    InfLevel::Notice,
+                    msg,
+                    ubx_settings,
+                    cfg_precision,
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::InfNotice(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                info!(
+                    "{} - received notification {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+
+                capture_inf(
+                    runtime,
+                    channels,
+                    InfLevel::Notice,
+                    msg,
+                    ubx_settings,
+                    cfg_precision,
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::InfNotice(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                info!(
+                    "{} - received notification {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+
+                capture_inf(
+                    runtime,
+                    channels,
+                    InfLevel::Notice,
+                    msg,
+                    ubx_settings,
+                    cfg_precision,
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx14")]
+        UbxPacket::Proto14(PacketRef::InfError(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                error!(
+                    "{} - received error notification {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+
+                capture_inf(
+                    runtime,
+                    channels,
+                    InfLevel::Error,
+                    msg,
+                    ubx_settings,
+                    cfg_precision,
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::InfError(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                error!(
+                    "{} - received error notification {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+
+                capture_inf(
+                    runtime,
+                    channels,
+                    InfLevel::Error,
+                    msg,
+                    ubx_settings,
+                    cfg_precision,
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::InfError(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                error!(
+                    "{} - received error notification {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+
+                capture_inf(
+                    runtime,
+                    channels,
+                    InfLevel::Error,
+                    msg,
+                    ubx_settings,
+                    cfg_precision,
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::InfError(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                error!(
+                    "{} - received error notification {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+
+                capture_inf(
+                    runtime,
+                    channels,
+                    InfLevel::Error,
+                    msg,
+                    ubx_settings,
+                    cfg_precision,
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx14")]
+        UbxPacket::Proto14(PacketRef::InfWarning(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                warn!(
+                    "{} - received warning message {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+
+                capture_inf(
+                    runtime,
+                    channels,
+                    InfLevel::Warning,
+                    msg,
+                    ubx_settings,
+                    cfg_precision,
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(PacketRef::InfWarning(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                warn!(
+                    "{} - received warning message {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+
+                capture_inf(
+                    runtime,
+                    channels,
+                    InfLevel::Warning,
+                    msg,
+                    ubx_settings,
+                    cfg_precision,
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(PacketRef::InfWarning(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                warn!(
+                    "{} - received warning message {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+
+                capture_inf(
+                    runtime,
+                    channels,
+                    InfLevel::Warning,
+                    msg,
+                    ubx_settings,
+                    cfg_precision,
+                );
+            }
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(PacketRef::InfWarning(pkt)) => {
+            if let Some(msg) = pkt.message() {
+                warn!(
+                    "{} - received warning message {}",
+                    runtime.utc_time().round(cfg_precision),
+                    msg
+                );
+
+                capture_inf(
+                    runtime,
+                    channels,
+                    InfLevel::Warning,
+                    msg,
+                    ubx_settings,
+                    cfg_precision,
+                );
+            }
+        },
+        _ => {},
+    } //packet
+}
+
+/// Formats a decoded [UbxPacket] as text (--dump-packets): class, ID and decoded fields.
+fn dump_packet(packet: &UbxPacket) -> String {
+    match packet {
+        #[cfg(feature = "ubx14")]
+        UbxPacket::Proto14(pkt) => {
+            let (class, id) = pkt.class_and_msg_id();
+            format!("UBX-{:02X}-{:02X} {:?}", class, id, pkt)
+        },
+
+        #[cfg(feature = "ubx23")]
+        UbxPacket::Proto23(pkt) => {
+            let (class, id) = pkt.class_and_msg_id();
+            format!("UBX-{:02X}-{:02X} {:?}", class, id, pkt)
+        },
+
+        #[cfg(feature = "ubx27")]
+        UbxPacket::Proto27(pkt) => {
+            let (class, id) = pkt.class_and_msg_id();
+            format!("UBX-{:02X}-{:02X} {:?}", class, id, pkt)
+        },
+
+        #[cfg(feature = "ubx31")]
+        UbxPacket::Proto31(pkt) => {
+            let (class, id) = pkt.class_and_msg_id();
+            format!("UBX-{:02X}-{:02X} {:?}", class, id, pkt)
+        },
+
+        #[allow(unreachable_patterns)]
+        _ => "UBX (unsupported protocol)".to_string(),
+    }
+}
+
+/// Width of a RINEX header comment
+const COMMENT_WIDTH: usize = 60;
+
+/// Formats this UBX-INF message as header comments, wrapped to the comment width
+fn inf_comments(level: InfLevel, msg: &str) -> Vec<String> {
+    let text = format!("UBX-INF-{}: {}", level, msg.trim())
+        .chars()
+        .collect::<Vec<_>>();
+
+    text.chunks(COMMENT_WIDTH)
+        .map(|chunk| chunk.iter().collect::<String>())
+        .collect()
+}
+
+/// Forwards this UBX-INF message to the Observation RINEX header (--capture-inf),
+/// when its severity was selected.
+fn capture_inf(
+    runtime: &Runtime,
+    channels: &Channels,
+    level: InfLevel,
+    msg: &str,
+    ubx_settings: &UbloxSettings,
+    cfg_precision: Duration,
+) {
+    if !ubx_settings.capture_inf.contains(&level) {
+        return;
+    }
+
+    for comment in inf_comments(level, msg) {
+        if let Err(e) = channels.obs.try_send(Message::HeaderComment(comment)) {
+            error!(
+                "{} - failed to capture INF message: {}",
+                runtime.utc_time().round(cfg_precision),
+                e
+            );
+        }
+    }
+}
+
+/// Receiver time of week (s) closer than this (ns) to the nominal sampling grid
+/// is snapped to it. This is the resolution of the RINEX epochs.
+const RAWX_TOW_TOLERANCE_NANOS: i128 = 100;
+
+/// Converts the RXM-RAWX receiver time of week (s) to nanoseconds.
+/// Its floating point representation is not exact (for example 9.999999998 s
+/// rather than 10 s), which would break the epoch grouping: values within
+/// [RAWX_TOW_TOLERANCE_NANOS] of the sampling grid are snapped to it.
+/// Larger offsets (receiver clock) are preserved, see --align-epochs.
+fn rawx_tow_nanos(rcv_tow: f64, sampling_period: Duration) -> u64 {
+    let nanos = (rcv_tow * 1.0E9).round() as i128;
+    let period = sampling_period.total_nanoseconds();
+
+    if period <= 0 {
+        return nanos as u64;
+    }
+
+    let nominal = ((nanos + period / 2) / period) * period;
+
+    if (nanos - nominal).abs() <= RAWX_TOW_TOLERANCE_NANOS {
+        nominal as u64
+    } else {
+        nanos as u64
+    }
+}
+
+/// RXM-RAWX reports a null (or negative) pseudo range when it is not valid:
+/// it is then omitted, and blanked in the RINEX.
+fn valid_pseudo_range(pr: f64) -> Option<f64> {
+    if pr > 0.0 { Some(pr) } else { None }
+}
+
+/// RXM-RAWX reports a null carrier phase when it is not valid:
+/// it is then omitted, and blanked in the RINEX.
+fn valid_phase(cp: f64) -> Option<f64> {
+    if cp != 0.0 { Some(cp) } else { None }
+}
+
+/// Warns when RXM-RAWX lacks satellites that the receiver uses in its navigation
+/// solution (NAV-SAT), which indicates signals are silently dropped (configuration issue).
+fn check_sv_used(
+    runtime: &mut Runtime,
+    used: usize,
+    cfg_precision: Duration,
+    ubx_settings: &UbloxSettings,
+) {
+    if !ubx_settings.rawxm {
+        return;
+    }
+
+    if let Some((rawx_svs, used)) = runtime.check_sv_used(used) {
+        warn!(
+            "{} - RXM-RAWX reports {} satellites, while {} are used in the navigation solution (NAV-SAT): check the receiver configuration",
+            runtime.utc_time().round(cfg_precision),
+            rawx_svs,
+            used
+        );
+    }
+}
+
+/// GLONASS FDMA frequency channel, from the RXM-RAWX frequency slot (offset by 7)
+fn glonass_channel(constellation: Constellation, freq_slot: u8) -> Option<i8> {
+    if constellation == Constellation::Glonass {
+        Some(freq_slot as i8 - 7)
+    } else {
+        None
+    }
+}
+
+/// Handles an RXM-MEASX frame: Doppler and signal strength (with the multipath
+/// indicator) for each satellite. This message does not carry a complete pseudo range
+/// nor a carrier phase. It is dropped whenever RXM-RAWX is available.
+fn handle_rxm_measx(
+    payload: &[u8],
+    runtime: &mut Runtime,
+    obs_pending: &mut Vec<Message>,
+    channels: &Channels,
+    cfg_precision: Duration,
+    ubx_settings: &UbloxSettings,
+) {
+    if !ubx_settings.rawxm || runtime.rawx {
+        return;
+    }
+
+    if payload.len() < RXM_MEASX_HEADER_LEN {
+        error!(
+            "{} - invalid RXM-MEASX frame ({} bytes)",
+            runtime.utc_time().round(cfg_precision),
+            payload.len()
+        );
+        return;
+    }
+
+    let gps_tow_ms = u32::from_le_bytes([payload[4], payload[5], payload[6], payload[7]]);
+    let num_sv = payload[34] as usize;
+
+    // MEASX only describes the time of week: the week counter comes from
+    // the epoch stream, or the latest navigation solution.
+    let Some(t_gpst) = runtime.gpst_time_of_week(gps_tow_ms) else {
+        debug!("RXM-MEASX: week number is not known yet");
+        return;
+    };
+
+    runtime.new_epoch(t_gpst, ubx_settings.timescale);
+
+    if runtime.nav_eoe_fallback(ubx_settings.nav_eoe_timeout()) {
+        trace!("{} - End of Epoch (fallback)", t_gpst.round(cfg_precision));
+        let _ = channels.nav.try_send(Message::EndofEpoch());
+    }
+
+    let t_meas = t_gpst.to_time_scale(ubx_settings.timescale);
+
+    for block in payload[RXM_MEASX_HEADER_LEN..]
+        .chunks_exact(RXM_MEASX_BLOCK_LEN)
+        .take(num_sv)
+    {
+        let gnss_id = block[0];
+        let cno = block[2];
+        let multipath = block[3];
+        let doppler_hz = i32::from_le_bytes([block[8], block[9], block[10], block[11]]);
+
+        let Some(constell) = to_constellation(gnss_id) else {
+            error!(
+                "{} - unknown constellation: #{}",
+                runtime.utc_time().round(cfg_precision),
+                gnss_id
+            );
+            continue;
+        };
+
+        // does not proceed if we're not interested by this system
+        if !ubx_settings.constellations.contains(&constell) {
+            continue;
+        }
+
+        let mut prn = block[1];
+
+        if constell.is_sbas() && prn >= SBAS_PRN_OFFSET {
+            prn -= SBAS_PRN_OFFSET;
+        };
+
+        let rawxm = Rawxm {
+            epoch: t_meas,
+            sv: SV::new(constell, prn),
+            pr: None,
+            cp: None,
+            cno,
+            dop: doppler_hz as f32 * 0.2,
+            freq_id: 0,
+            lock_time: None,
+            glo_channel: None,
+            multipath: Some(multipath),
+        };
+
+        obs_pending.push(Message::Measurement(rawxm));
+    }
+}
+
+/// Latches the signals tracked by each satellite, reported by NAV-SIG as
+/// (gnss_id, sv_id, sig_id, C/N0). Signals that are not tracked (null C/N0) are dropped.
+/// Dropped while the week is unknown.
+#[cfg(any(feature = "ubx27", feature = "ubx31"))]
+fn latch_nav_sig(runtime: &mut Runtime, itow: u32, sigs: impl Iterator<Item = (u8, u8, u8, u8)>) {
+    let Some(epoch) = runtime.gpst_time_of_week(itow) else {
+        return;
+    };
+
+    let mut signals = std::collections::HashMap::<SV, Vec<(u8, u8)>>::new();
+
+    for (gnss_id, sv_id, sig_id, cno) in sigs {
+        let Some(constell) = to_constellation(gnss_id) else {
+            continue;
+        };
+
+        if cno == 0 {
+            continue;
+        }
+
+        let mut prn = sv_id;
+
+        if constell.is_sbas() && prn >= SBAS_PRN_OFFSET {
+            prn -= SBAS_PRN_OFFSET;
+        }
+
+        signals
+            .entry(SV::new(constell, prn))
+            .or_default()
+            .push((sig_id, cno));
+    }
+
+    runtime.latch_signals(epoch, signals);
+}
+
+/// Forwards this [TimePulse] to the time pulse log, when requested
+fn send_time_pulse(channels: &Channels, pulse: TimePulse, cfg_precision: Duration) {
+    if let Some(tx) = &channels.timepulse {
+        let epoch = pulse.epoch;
+
+        if let Err(e) = tx.try_send(Message::TimePulse(pulse)) {
+            error!(
+                "{} - failed to send time pulse: {}",
+                epoch.round(cfg_precision),
+                e
+            );
+        }
+    }
+}
+
+/// Forwards this [Velocity] to the velocity log, when requested
+fn send_velocity(channels: &Channels, velocity: Velocity, cfg_precision: Duration) {
+    if let Some(tx) = &channels.velocity {
+        let epoch = velocity.epoch;
+
+        if let Err(e) = tx.try_send(Message::Velocity(velocity)) {
+            error!(
+                "{} - failed to send velocity: {}",
+                epoch.round(cfg_precision),
+                e
+            );
+        }
+    }
+}
+
+/// Infers the country code from the first position fix, for the V3 file names.
+fn infer_country(
+    runtime: &mut Runtime,
+    obs_pending: &mut Vec<Message>,
+    channels: &Channels,
+    latitude: f64,
+    longitude: f64,
+) {
+    if runtime.country_inferred {
+        return;
+    }
+
+    runtime.country_inferred = true;
+
+    match country_code(latitude, longitude) {
+        Some(code) => {
+            debug!("country code inferred from position fix: {}", code);
+            obs_pending.push(Message::Country(code.to_string()));
+            let _ = channels.nav.try_send(Message::Country(code.to_string()));
+        },
+        None => {
+            debug!(
+                "no country code for lat={:.3}° long={:.3}°",
+                latitude, longitude
+            );
+        },
+    }
+}
+
+/// Forwards all pending ephemeris that are now complete, to the navigation collecter.
+pub fn release_ephemeris(runtime: &Runtime, channels: &Channels, cfg_precision: Duration) {
+    for (sv, pending) in runtime.pending_frames.iter() {
+        let validated = match pending.validate() {
+            Ok(validated) => validated,
+            Err(e) => {
+                // regular situation, while the subframes are being collected
+                trace!(
+                    "{}({}) ephemeris not ready: {}",
+                    runtime.utc_time().round(cfg_precision),
+                    sv,
+                    e
+                );
+                continue;
+            },
+        };
+
+        let (epoch, rinex) = validated.to_rinex(runtime.utc_time());
+
+        // redact message
+        match channels
+            .nav
+            .try_send(Message::Ephemeris((epoch, *sv, rinex)))
+        {
+            Ok(_) => {},
+            Err(e) => {
+                error!(
+                    "{}({}) failed to send collected ephemeris: {}",
+                    epoch.round(cfg_precision),
+                    sv,
+                    e
+                );
+            },
+        }
+    }
+}
+
+/// Releases all pending NAV-EPH, waiting for the collecter to accept them.
+/// This is the final flush, once collection is over: incomplete ephemeris
+/// are reported, because they will not make it to the Navigation RINEX.
+pub async fn flush_ephemeris(runtime: &Runtime, channels: &Channels) {
+    for (sv, pending) in runtime.pending_frames.iter() {
+        match pending.validate() {
+            Ok(validated) => {
+                let (epoch, rinex) = validated.to_rinex(runtime.utc_time());
+
+                let _ = channels
+                    .nav
+                    .send(Message::Ephemeris((epoch, *sv, rinex)))
+                    .await;
+            },
+            Err(e) => {
+                warn!("{} - ephemeris not released: {}", sv, e);
+            },
+        }
+    }
+}
+
+/// Resolves the [Epoch] of a TIM-TM2 event from its week and time of week,
+/// expressed in the time base described by the `flags`. Receiver and GNSS time are
+/// aligned to GPST, UTC weeks are counted from the GPST origin as well.
+/// Returns None when the event time is not valid.
+fn tim_tm2_epoch(flags: u8, week: u32, nanos: u64) -> Option<Epoch> {
+    if flags & TIM_TM2_TIME_VALID == 0 {
+        return None;
+    }
+
+    let t = Epoch::from_time_of_week(week, nanos, TimeScale::GPST);
+
+    if flags & TIM_TM2_TIME_BASE_MASK == TIM_TM2_TIME_BASE_UTC {
+        let (y, m, d, hh, mm, ss, ns) = t.to_gregorian(TimeScale::GPST);
+        let t_utc = Epoch::from_gregorian(y, m, d, hh, mm, ss, ns, TimeScale::UTC);
+        Some(t_utc)
+    } else {
+        Some(t)
+    }
+}
+
+/// Options of the [consume_passive] loop
+#[derive(Debug, Clone, Default)]
+pub struct PassiveOptions {
+    /// Input files paced to real time, at this rate (--replay-rate)
+    pub replay_rate: Option<f64>,
+
+    /// Corrupt input files are skipped (--keep-going)
+    pub keep_going: bool,
+
+    /// Overlapping input files abort the collection (--strict)
+    pub strict: bool,
+}
+
+/// Consumes a passive (read-only) [Device] until all content has been consumed.
+/// This is the conversion loop of both the command line and [UbxToRinex](crate::UbxToRinex).
+/// Returns false when the collection was aborted (--strict).
+pub async fn consume_passive(
+    runtime: &mut Runtime,
+    channels: &Channels,
+    device: &mut Device<Proto>,
+    buffer: &mut [u8],
+    cfg_precision: Duration,
+    ubx_settings: &UbloxSettings,
+    options: &PassiveOptions,
+) -> bool {
+    // small reads when replaying, so the epochs are paced individually
+    let buffer = if options.replay_rate.is_some() {
+        &mut buffer[..REPLAY_CHUNK_SIZE]
+    } else {
+        buffer
+    };
+
+    loop {
+        match consume_device(
+            runtime,
+            channels,
+            device,
+            buffer,
+            cfg_precision,
+            ubx_settings,
+        )
+        .await
+        {
+            Ok(0) => {
+                info!(
+                    "{} - consumed all content",
+                    runtime.utc_time().round(cfg_precision)
+                );
+
+                // exit right away (no dead-time):
+                // pending content is flushed on shutdown.
+                return true;
+            },
+            Ok(_) => {}, // nominal
+            Err(e) => {
+                error!(
+                    "{} - I/O error: {}",
+                    runtime.utc_time().round(cfg_precision),
+                    e
+                );
+
+                // corrupt input file: move on to the next one
+                if options.keep_going
+                    && let Some(index) = device.interface.skip_current()
+                {
+                    warn!("input file #{} is corrupt - skipped", index + 1);
+                } else {
+                    return true;
+                }
+            },
+        }
+
+        // stacked input files should not overlap in time
+        if let Some(overlap) = runtime.overlap.take() {
+            if options.strict {
+                error!(
+                    "input file #{} starts at {}, before the end of previous file ({}): aborting",
+                    overlap.index + 1,
+                    overlap.start.round(cfg_precision),
+                    overlap.previous_end.round(cfg_precision)
+                );
+
+                return false;
+            } else {
+                warn!(
+                    "{} - input file #{} overlaps previous file (ending {}): duplicate epochs are to be expected",
+                    overlap.start.round(cfg_precision),
+                    overlap.index + 1,
+                    overlap.previous_end.round(cfg_precision)
+                );
+            }
+        }
+
+        // handle all pending NAV-EPH messages
+        if ubx_settings.ephemeris {
+            release_ephemeris(runtime, channels, cfg_precision);
+        }
+
+        match options.replay_rate {
+            Some(rate) => {
+                // wait for the stream to catch up with the wall clock
+                if let Some(delay) = runtime.replay_delay(rate) {
+                    tokio::time::sleep(delay).await;
+                }
+            },
+            None => {
+                // There is no hardware acting as a throttle,
+                // the channel capacity becomes the limit.
+                // Adds a little bit of dead-time to reduce pressure on the data channel,
+                // and let the collecters catch up.
+                tokio::time::sleep(PASSIVE_DEAD_TIME).await;
+            },
+        }
+    }
+}
+
+/// Shuts all collecters down, once the collection is over:
+/// they release their pending content and flush their output.
+pub async fn shutdown_collecters(
+    runtime: &Runtime,
+    channels: &Channels,
+    ubx_settings: &UbloxSettings,
+) {
+    if ubx_settings.rawxm {
+        let _ = channels.obs.send(Message::Shutdown).await;
+    }
+
+    if ubx_settings.ephemeris {
+        // final NAV-EPH flush, which may not have happened on EOF
+        flush_ephemeris(runtime, channels).await;
+        let _ = channels.nav.send(Message::Shutdown).await;
+    }
+
+    for logger in [
+        &channels.sbas,
+        &channels.spectrum,
+        &channels.velocity,
+        &channels.timepulse,
+        &channels.qc,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        let _ = logger.send(Message::Shutdown).await;
+    }
+}
+
+/// Returns the header comment that describes this UBX source file (provenance)
+pub fn source_comment(path: &str) -> String {
+    let name = std::path::Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string());
+
+    let size = std::fs::metadata(path)
+        .map(|metadata| metadata.len())
+        .unwrap_or_default();
+
+    // header comments are limited to 60 characters
+    let size = format!(" ({} bytes)", size);
+    let max_len = 60 - "UBX source: ".len() - size.len();
+    let name = name.chars().take(max_len).collect::<String>();
+
+    format!("UBX source: {}{}", name, size)
+}
+
+/// Forwards these header comments to the RINEX collecters
+pub fn send_header_comments(
+    channels: &Channels,
+    ubx_settings: &UbloxSettings,
+    comments: &[String],
+) {
+    for comment in comments {
+        if ubx_settings.rawxm {
+            let _ = channels
+                .obs
+                .try_send(Message::HeaderComment(comment.clone()));
+        }
+
+        if ubx_settings.ephemeris {
+            let _ = channels
+                .nav
+                .try_send(Message::HeaderComment(comment.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        Channels, consume_device, dump_packet, handle_packet, inf_comments, rawx_tow_nanos,
+        tim_tm2_epoch,
+    };
+
+    use crate::{
+        Proto,
+        collecter::Message,
+        device::{Device, interface::Interface, test::ubx_frame},
+        runtime::Runtime,
+        ubx::{InfLevel, Settings as UbloxSettings},
+    };
+
+    use rinex::prelude::{Constellation, Duration, Epoch, SV, TimeScale};
+    use std::io::Cursor;
+    use tokio::sync::mpsc;
+    use ublox::Parser;
+
+    /// Feeds canned UBX bytes through [handle_packet], returns
+    /// the gathered observations and the forwarded navigation messages.
+    fn feed(
+        bytes: &[u8],
+        runtime: &mut Runtime,
+        ubx_settings: &UbloxSettings,
+    ) -> (Vec<Message>, Vec<Message>) {
+        let (obs_tx, mut obs_rx) = mpsc::channel(16);
+        let (nav_tx, mut nav_rx) = mpsc::channel(16);
+
+        let channels = Channels {
+            obs: obs_tx,
+            nav: nav_tx,
+            sbas: None,
+            spectrum: None,
+            velocity: None,
+            timepulse: None,
+            qc: None,
+        };
+
+        let mut obs_pending = Vec::new();
+
+        let mut parser = Parser::<_, Proto>::new(vec![]);
+        let mut it = parser.consume_ubx(bytes);
+
+        while let Some(packet) = it.next() {
+            let packet = packet.unwrap_or_else(|e| panic!("UBX parsing error: {}", e));
+
+            handle_packet(
+                packet,
+                runtime,
+                &mut obs_pending,
+                &channels,
+                Duration::from_seconds(1.0),
+                ubx_settings,
+            );
+        }
+
+        while let Ok(message) = obs_rx.try_recv() {
+            obs_pending.push(message);
+        }
+
+        let mut nav = Vec::new();
+
+        while let Ok(message) = nav_rx.try_recv() {
+            nav.push(message);
+        }
+
+        (obs_pending, nav)
+    }
+
+    /// RXM-RAWX payload, with a single measurement
+    fn rxm_rawx(week: u16, tow: f64, gnss_id: u8, sv_id: u8, pr: f64, cno: u8) -> Vec<u8> {
+        rxm_rawx_slot(week, tow, gnss_id, sv_id, pr, cno, 0)
+    }
+
+    /// RXM-RAWX payload, with a single measurement on this frequency slot (GLONASS)
+    fn rxm_rawx_slot(
+        week: u16,
+        tow: f64,
+        gnss_id: u8,
+        sv_id: u8,
+        pr: f64,
+        cno: u8,
+        freq_slot: u8,
+    ) -> Vec<u8> {
+        let mut payload = Vec::new();
+
+        payload.extend_from_slice(&tow.to_le_bytes());
+        payload.extend_from_slice(&week.to_le_bytes());
+        payload.extend_from_slice(&[18, 1, 0x01, 0x01, 0, 0]); // leap, numMeas, recStat, version, reserved
+
+        payload.extend_from_slice(&pr.to_le_bytes());
+        payload.extend_from_slice(&(pr * 5.25).to_le_bytes()); // cp
+        payload.extend_from_slice(&(-1000.0f32).to_le_bytes()); // doppler
+        payload.extend_from_slice(&[gnss_id, sv_id, 0, freq_slot]); // gnss, sv, sig, freq
+        payload.extend_from_slice(&1000u16.to_le_bytes()); // locktime
+        payload.extend_from_slice(&[cno, 0, 0, 0, 0x07, 0]); // cno, stdevs, trkStat, reserved
+
+        ubx_frame(0x02, 0x15, &payload)
+    }
+
+    #[test]
+    fn rxm_rawx_receiver_timescale() {
+        // GPS + Galileo stream, Galileo filter: the week is still counted in GPST
+        let ubx_settings = UbloxSettings {
+            constellations: vec![Constellation::Galileo],
+            ..Default::default()
+        };
+
+        let mut runtime = Runtime::new();
+
+        let mut bytes = rxm_rawx(2300, 100.0, 0, 5, 22_000_000.0, 45);
+        bytes.extend_from_slice(&rxm_rawx(2300, 100.0, 2, 14, 23_000_000.0, 40));
+
+        let (obs, _) = feed(&bytes, &mut runtime, &ubx_settings);
+        assert_eq!(obs.len(), 1);
+
+        match &obs[0] {
+            Message::Measurement(rawxm) => {
+                assert_eq!(rawxm.sv, SV::new(Constellation::Galileo, 14));
+                assert_eq!(
+                    rawxm.epoch,
+                    Epoch::from_time_of_week(2300, 100_000_000_000, TimeScale::GPST)
+                );
+            },
+            _ => panic!("expecting a measurement"),
+        }
+
+        // Galileo only receiver (declared): week counted in GST
+        let ubx_settings = UbloxSettings {
+            constellations: vec![Constellation::Galileo],
+            rawx_timescale: Some(TimeScale::GST),
+            ..Default::default()
+        };
+
+        let mut runtime = Runtime::new();
+
+        let bytes = rxm_rawx(1300, 100.0, 2, 14, 23_000_000.0, 40);
+        let (obs, _) = feed(&bytes, &mut runtime, &ubx_settings);
+
+        match &obs[0] {
+            Message::Measurement(rawxm) => {
+                assert_eq!(
+                    rawxm.epoch,
+                    Epoch::from_time_of_week(1300, 100_000_000_000, TimeScale::GST)
+                        .to_time_scale(ubx_settings.timescale)
+                );
+            },
+            _ => panic!("expecting a measurement"),
+        }
+    }
+
+    #[test]
+    fn rxm_rawx_glonass_channel() {
+        let ubx_settings = UbloxSettings {
+            constellations: vec![Constellation::GPS, Constellation::Glonass],
+            ..Default::default()
+        };
+
+        let mut runtime = Runtime::new();
+
+        let mut bytes = rxm_rawx_slot(2300, 100.0, 6, 3, 21_000_000.0, 40, 12);
+        bytes.extend_from_slice(&rxm_rawx_slot(2300, 100.0, 6, 4, 21_500_000.0, 40, 0));
+        bytes.extend_from_slice(&rxm_rawx(2300, 100.0, 0, 5, 22_000_000.0, 45));
+
+        let (obs, _) = feed(&bytes, &mut runtime, &ubx_settings);
+        assert_eq!(obs.len(), 3);
+
+        let channels = obs
+            .iter()
+            .map(|message| match message {
+                Message::Measurement(rawxm) => (rawxm.sv, rawxm.freq_id, rawxm.glo_channel),
+                _ => panic!("expecting measurements"),
+            })
+            .collect::<Vec<_>>();
+
+        // whatever the protocol, the frequency slot is not a signal identifier (GLO L1OF)
+        assert_eq!(
+            channels,
+            vec![
+                (SV::new(Constellation::Glonass, 3), 0, Some(5)),
+                (SV::new(Constellation::Glonass, 4), 0, Some(-7)),
+                (SV::new(Constellation::GPS, 5), 0, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn dump_nav_eoe() {
+        let bytes = ubx_frame(0x01, 0x61, &1000u32.to_le_bytes());
+
+        let mut parser = Parser::<_, Proto>::new(vec![]);
+        let mut it = parser.consume_ubx(&bytes);
+
+        let packet = it
+            .next()
+            .expect("missing packet")
+            .unwrap_or_else(|e| panic!("UBX parsing error: {}", e));
+
+        let dump = dump_packet(&packet);
+
+        assert!(dump.starts_with("UBX-01-61 "), "{}", dump);
+        assert!(dump.contains("1000"), "{}", dump);
+    }
+
+    #[test]
+    fn rxm_rawx_measurement() {
+        let ubx_settings = UbloxSettings::default();
+        let mut runtime = Runtime::new();
+
+        let mut bytes = rxm_rawx(2300, 100.0, 0, 5, 22_000_000.0, 45);
+
+        // not selected: dropped
+        bytes.extend_from_slice(&rxm_rawx(2300, 101.0, 2, 14, 23_000_000.0, 40));
+
+        let (obs, nav) = feed(&bytes, &mut runtime, &ubx_settings);
+
+        assert!(nav.is_empty());
+        assert_eq!(obs.len(), 1);
+
+        match &obs[0] {
+            Message::Measurement(rawxm) => {
+                assert_eq!(rawxm.sv, SV::new(Constellation::GPS, 5));
+                assert_eq!(
+                    rawxm.epoch,
+                    Epoch::from_time_of_week(2300, 100_000_000_000, TimeScale::GPST)
+                );
+                assert_eq!(rawxm.pr, Some(22_000_000.0));
+                assert_eq!(rawxm.dop, -1000.0);
+                assert_eq!(rawxm.cno, 45);
+            },
+            _ => panic!("expecting a measurement"),
+        }
+
+        assert_eq!(runtime.gpst_week(), 2300);
+    }
+
+    #[test]
+    fn rxm_rawx_jittery_tow() {
+        let sampling = Duration::from_seconds(1.0);
+
+        for (rcv_tow, expected) in [
+            (10.0, 10_000_000_000),
+            (10.000000001, 10_000_000_000),
+            (9.999999998, 10_000_000_000),
+            (604_799.999_999_999_9, 604_800_000_000_000),
+            // receiver clock offset is preserved
+            (10.0000004, 10_000_000_400),
+            (9.9995, 9_999_500_000),
+        ] {
+            assert_eq!(rawx_tow_nanos(rcv_tow, sampling), expected, "{}", rcv_tow);
+        }
+
+        let sampling = Duration::from_milliseconds(100.0);
+        assert_eq!(rawx_tow_nanos(0.299_999_999, sampling), 300_000_000);
+
+        // through the RXM-RAWX handler: a single epoch
+        let ubx_settings = UbloxSettings {
+            sampling_period: sampling,
+            ..Default::default()
+        };
+
+        let mut runtime = Runtime::new();
+
+        let mut bytes = rxm_rawx(2300, 100.000_000_001, 0, 5, 22_000_000.0, 45);
+        bytes.extend_from_slice(&rxm_rawx(2300, 99.999_999_998, 0, 7, 21_000_000.0, 40));
+
+        let (obs, _) = feed(&bytes, &mut runtime, &ubx_settings);
+        assert_eq!(obs.len(), 2);
+
+        for message in obs.iter() {
+            match message {
+                Message::Measurement(rawxm) => {
+                    assert_eq!(
+                        rawxm.epoch,
+                        Epoch::from_time_of_week(2300, 100_000_000_000, TimeScale::GPST)
+                    );
+                },
+                _ => panic!("expecting a measurement"),
+            }
+        }
+    }
+
+    #[test]
+    fn rxm_rawx_invalid_measurement() {
+        let ubx_settings = UbloxSettings::default();
+        let mut runtime = Runtime::new();
+
+        let mut bytes = rxm_rawx(2300, 100.0, 0, 5, 22_000_000.0, 45);
+        bytes.extend_from_slice(&rxm_rawx(2300, 100.0, 0, 7, 0.0, 30));
+
+        let (obs, _) = feed(&bytes, &mut runtime, &ubx_settings);
+        assert_eq!(obs.len(), 2);
+
+        match (&obs[0], &obs[1]) {
+            (Message::Measurement(valid), Message::Measurement(invalid)) => {
+                assert_eq!(valid.sv, SV::new(Constellation::GPS, 5));
+                assert_eq!(valid.pr, Some(22_000_000.0));
+                assert_eq!(valid.cp, Some(22_000_000.0 * 5.25));
+
+                // still reported: Doppler and signal strength are valid
+                assert_eq!(invalid.sv, SV::new(Constellation::GPS, 7));
+                assert!(invalid.pr.is_none());
+                assert!(invalid.cp.is_none());
+                assert_eq!(invalid.dop, -1000.0);
+            },
+            _ => panic!("expecting measurements"),
+        }
+    }
+
+    #[test]
+    fn rxm_rawx_disabled() {
+        let ubx_settings = UbloxSettings {
+            rawxm: false,
+            ..Default::default()
+        };
+
+        let mut runtime = Runtime::new();
+        let bytes = rxm_rawx(2300, 100.0, 0, 5, 22_000_000.0, 45);
+
+        let (obs, nav) = feed(&bytes, &mut runtime, &ubx_settings);
+
+        assert!(obs.is_empty());
+        assert!(nav.is_empty());
+    }
+
+    /// NAV-PVT payload, fully resolved UTC date and time, without fix
+    fn nav_pvt(itow_ms: u32, year: u16, month: u8, day: u8, hh: u8, mm: u8, ss: u8) -> Vec<u8> {
+        let mut payload = vec![0; 92];
+
+        payload[0..4].copy_from_slice(&itow_ms.to_le_bytes());
+        payload[4..6].copy_from_slice(&year.to_le_bytes());
+        payload[6..11].copy_from_slice(&[month, day, hh, mm, ss]);
+        payload[11] = 0x07; // validDate, validTime, fullyResolved
+
+        ubx_frame(0x01, 0x07, &payload)
+    }
+
+    /// RXM-MEASX payload, with a single satellite
+    fn rxm_measx(tow_ms: u32, gnss_id: u8, sv_id: u8, cno: u8, multipath: u8) -> Vec<u8> {
+        let mut payload = vec![0; 44];
+
+        payload[4..8].copy_from_slice(&tow_ms.to_le_bytes());
+        payload[34] = 1; // numSV
+
+        payload.extend_from_slice(&[gnss_id, sv_id, cno, multipath]);
+        payload.extend_from_slice(&(-50i32).to_le_bytes()); // dopplerMS
+        payload.extend_from_slice(&(-5000i32).to_le_bytes()); // dopplerHz
+        payload.extend_from_slice(&[0; 12]);
+
+        ubx_frame(0x02, 0x14, &payload)
+    }
+
+    #[test]
+    fn rxm_measx_measurement() {
+        let ubx_settings = UbloxSettings::default();
+        let mut runtime = Runtime::new();
+
+        // week is not known yet: dropped
+        let bytes = rxm_measx(100_000, 0, 5, 45, 3);
+        let (obs, _) = feed(&bytes, &mut runtime, &ubx_settings);
+        assert!(obs.is_empty());
+
+        // week 2300 starts on 2024-02-04
+        let mut bytes = nav_pvt(78_000, 2024, 2, 4, 0, 1, 0);
+        bytes.extend_from_slice(&rxm_measx(100_000, 0, 5, 45, 3));
+
+        let (obs, _) = feed(&bytes, &mut runtime, &ubx_settings);
+        assert_eq!(obs.len(), 1);
+
+        match &obs[0] {
+            Message::Measurement(rawxm) => {
+                assert_eq!(rawxm.sv, SV::new(Constellation::GPS, 5));
+                assert_eq!(
+                    rawxm.epoch,
+                    Epoch::from_time_of_week(2300, 100_000_000_000, TimeScale::GPST)
+                );
+                assert!(rawxm.pr.is_none());
+                assert!(rawxm.cp.is_none());
+                assert_eq!(rawxm.dop, -1000.0);
+                assert_eq!(rawxm.multipath, Some(3));
+                assert_eq!(rawxm.ssi_code(), 4);
+            },
+            _ => panic!("expecting a measurement"),
+        }
+
+        // RAWX is preferred
+        let mut bytes = rxm_rawx(2300, 101.0, 0, 5, 22_000_000.0, 45);
+        bytes.extend_from_slice(&rxm_measx(101_000, 0, 5, 45, 1));
+
+        let (obs, _) = feed(&bytes, &mut runtime, &ubx_settings);
+        assert_eq!(obs.len(), 1);
+
+        match &obs[0] {
+            Message::Measurement(rawxm) => assert!(rawxm.pr.is_some()),
+            _ => panic!("expecting a measurement"),
+        }
+    }
+
+    #[test]
+    fn nav_eoe() {
+        let ubx_settings = UbloxSettings::default();
+        let mut runtime = Runtime::new();
+
+        let mut bytes = rxm_rawx(2300, 100.0, 0, 5, 22_000_000.0, 45);
+        bytes.extend_from_slice(&ubx_frame(0x01, 0x61, &100_000u32.to_le_bytes()));
+
+        let (obs, nav) = feed(&bytes, &mut runtime, &ubx_settings);
+
+        assert_eq!(obs.len(), 1);
+        assert_eq!(nav.len(), 1);
+        assert!(matches!(nav[0], Message::EndofEpoch()));
+    }
+
+    #[test]
+    fn rxm_sfrbx_filtering() {
+        // GPS subframe, 10 words
+        let mut payload = vec![0, 5, 0, 0, 10, 1, 2, 0];
+
+        for _ in 0..10 {
+            payload.extend_from_slice(&0u32.to_le_bytes());
+        }
+
+        let bytes = ubx_frame(0x02, 0x13, &payload);
+
+        // navigation not requested
+        let ubx_settings = UbloxSettings::default();
+        let mut runtime = Runtime::new();
+
+        let (obs, nav) = feed(&bytes, &mut runtime, &ubx_settings);
+
+        assert!(obs.is_empty());
+        assert!(nav.is_empty());
+        assert!(runtime.pending_frames.is_empty());
+
+        // constellation not selected
+        let ubx_settings = UbloxSettings {
+            ephemeris: true,
+            constellations: vec![Constellation::Galileo],
+            ..Default::default()
+        };
+
+        let (obs, nav) = feed(&bytes, &mut runtime, &ubx_settings);
+
+        assert!(obs.is_empty());
+        assert!(nav.is_empty());
+        assert!(runtime.pending_frames.is_empty());
+    }
+
+    #[test]
+    fn inf_capture() {
+        let bytes = ubx_frame(0x04, 0x01, b"ANTSTATUS=SHORT");
+
+        // not requested
+        let ubx_settings = UbloxSettings::default();
+        let mut runtime = Runtime::new();
+
+        let (obs, _) = feed(&bytes, &mut runtime, &ubx_settings);
+        assert!(obs.is_empty());
+
+        // other severity
+        let ubx_settings = UbloxSettings {
+            capture_inf: vec![InfLevel::Error],
+            ..Default::default()
+        };
+
+        let (obs, _) = feed(&bytes, &mut runtime, &ubx_settings);
+        assert!(obs.is_empty());
+
+        let ubx_settings = UbloxSettings {
+            capture_inf: vec![InfLevel::Error, InfLevel::Warning],
+            ..Default::default()
+        };
+
+        let (obs, _) = feed(&bytes, &mut runtime, &ubx_settings);
+        assert_eq!(obs.len(), 1);
+
+        match &obs[0] {
+            Message::HeaderComment(comment) => {
+                assert_eq!(comment, "UBX-INF-WARNING: ANTSTATUS=SHORT");
+            },
+            _ => panic!("expecting a header comment"),
+        }
+    }
+
+    #[test]
+    fn inf_comments_wrapping() {
+        let msg = "x".repeat(100);
+        let comments = inf_comments(InfLevel::Notice, &msg);
+
+        assert_eq!(comments.len(), 2);
+        assert_eq!(comments[0].len(), 60);
+        assert_eq!(comments[1].len(), 116 - 60);
+        assert!(comments[0].starts_with("UBX-INF-NOTICE: xxx"));
+    }
+
+    #[test]
+    fn tim_tm2_time_base() {
+        let nanos = 100_000_000_000;
+        let gpst = Epoch::from_time_of_week(2300, nanos, TimeScale::GPST);
+
+        // time is not valid
+        assert_eq!(tim_tm2_epoch(0x80 | 0x08, 2300, nanos), None);
+
+        // receiver time, GNSS time
+        for time_base in [0x00, 0x08] {
+            assert_eq!(
+                tim_tm2_epoch(0x80 | 0x40 | time_base, 2300, nanos),
+                Some(gpst)
+            );
+        }
+
+        // UTC: same week and time of week, in UTC
+        let utc = tim_tm2_epoch(0x80 | 0x40 | 0x10, 2300, nanos).unwrap();
+
+        assert_eq!(utc.time_scale, TimeScale::UTC);
+        assert_eq!(
+            utc.to_gregorian(TimeScale::UTC),
+            gpst.to_gregorian(TimeScale::GPST)
+        );
+    }
+
+    #[tokio::test]
+    async fn input_files_overlap() {
+        let ubx_settings = UbloxSettings::default();
+
+        // first file: 100 s and 130 s
+        let mut first = rxm_rawx(2300, 100.0, 0, 5, 22_000_000.0, 45);
+        first.extend_from_slice(&rxm_rawx(2300, 130.0, 0, 5, 22_000_000.0, 45));
+
+        for (second_tow, overlaps) in [(160.0, false), (100.0, true)] {
+            // second file starts on non RXM-RAWX frames,
+            // that fill the first reads on their own
+            let mut second = Vec::new();
+
+            for _ in 0..4 {
+                second.extend_from_slice(&ubx_frame(0x04, 0x02, b"NOTICE"));
+            }
+
+            second.extend_from_slice(&rxm_rawx(2300, second_tow, 0, 5, 22_000_000.0, 45));
+
+            let mut device = Device::<Proto>::from_reader(Box::new(Cursor::new(first.clone())));
+
+            if let Interface::ReadOnlyPool(pool) = &mut device.interface {
+                pool.stack_handle(Box::new(Cursor::new(second)));
+            }
+
+            let (obs_tx, _obs_rx) = mpsc::channel(16);
+            let (nav_tx, _nav_rx) = mpsc::channel(16);
+
+            let channels = Channels {
+                obs: obs_tx,
+                nav: nav_tx,
+                sbas: None,
+                spectrum: None,
+                velocity: None,
+                timepulse: None,
+                qc: None,
+            };
+
+            let mut runtime = Runtime::new();
+            let mut overlap = None;
+
+            // small reads, like --replay-rate
+            let mut buffer = [0; 16];
+
+            for _ in 0..64 {
+                consume_device(
+                    &mut runtime,
+                    &channels,
+                    &mut device,
+                    &mut buffer,
+                    Duration::from_seconds(1.0),
+                    &ubx_settings,
+                )
+                .await
+                .unwrap();
+
+                if let Some(latched) = runtime.overlap.take() {
+                    assert!(overlap.is_none(), "reported once");
+                    overlap = Some(latched);
+                }
+            }
+
+            if overlaps {
+                let overlap = overlap.expect("overlap not detected");
+
+                assert_eq!(overlap.index, 1);
+                assert_eq!(
+                    overlap.start,
+                    Epoch::from_time_of_week(2300, 100_000_000_000, TimeScale::GPST)
+                );
+                assert_eq!(
+                    overlap.previous_end,
+                    Epoch::from_time_of_week(2300, 130_000_000_000, TimeScale::GPST)
+                );
+            } else {
+                assert!(overlap.is_none(), "{:?}", overlap);
+            }
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use hifitime::prelude::{Duration, Epoch, TimeScale};
-use log::trace;
+use log::{trace, warn};
 
 use ublox::{
     // NavStatusFlags,
@@ -9,9 +9,16 @@ use ublox::{
 
 use rinex::prelude::{Constellation, SV};
 
-use crate::collecter::ephemeris::{PendingFrame, PendingGpsQzssFrame};
+use crate::{
+    collecter::ephemeris::{GpsQzssEphemeris, PendingFrame, PendingGpsQzssFrame},
+    device::interface::Overlap,
+};
+
+use std::{collections::HashMap, time::Instant};
 
-use std::collections::HashMap;
+/// Satellites used in the navigation solution (NAV-SAT) may exceed
+/// the RXM-RAWX satellites by this many, before this is reported.
+const SV_COUNT_TOLERANCE: usize = 2;
 
 #[derive(Debug)]
 pub struct Runtime {
@@ -26,6 +33,49 @@ pub struct Runtime {
 
     /// [PendingFrame]s
     pub pending_frames: HashMap<SV, PendingFrame>,
+
+    /// Latest NAV-EOE [Epoch]
+    last_nav_eoe: Option<Epoch>,
+
+    /// End of epochs are derived from the epoch stream, rather than NAV-EOE
+    pub nav_eoe_fallback: bool,
+
+    /// RXM-RAWX is being received: RXM-MEASX is then ignored
+    pub rawx: bool,
+
+    /// Country code was inferred from the first position fix
+    pub country_inferred: bool,
+
+    /// Reception of the latest valid UBX packet
+    pub last_packet: Option<Instant>,
+
+    /// Signals tracked per [SV], as (sig_id, C/N0) reported by the latest NAV-SIG
+    signals: HashMap<SV, Vec<(u8, u8)>>,
+
+    /// [Epoch] of the latest NAV-SIG
+    signals_epoch: Option<Epoch>,
+
+    /// Number of satellites in the latest RXM-RAWX epoch
+    rawx_svs: Option<usize>,
+
+    /// RXM-RAWX lacks satellites that NAV-SAT reports as used
+    sv_count_mismatch: bool,
+
+    /// Start of the replay (--replay-rate)
+    replay_start: Option<Instant>,
+
+    /// Latest [Epoch] of the navigation solution (NAV-PVT, NAV-TIMEUTC),
+    /// which resolves the week before the first measurement
+    time_reference: Option<Epoch>,
+
+    /// Input files [Overlap], to be reported (passive mode)
+    pub overlap: Option<Overlap>,
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Runtime {
@@ -41,18 +91,100 @@ impl Runtime {
             // fix_flag: NavStatusFlags::empty(),
             // nav_status: NavStatusFlags2::Inactive,
             pending_frames: Default::default(),
+            last_nav_eoe: Default::default(),
+            nav_eoe_fallback: false,
+            rawx: false,
+            country_inferred: false,
+            last_packet: None,
+            signals: Default::default(),
+            signals_epoch: Default::default(),
+            rawx_svs: None,
+            sv_count_mismatch: false,
+            replay_start: None,
+            time_reference: None,
+            overlap: None,
         }
     }
 
+    /// Returns latest [Epoch], if any
+    pub fn latest_epoch(&self) -> Option<Epoch> {
+        self.epoch
+    }
+
     /// Update latest epoch
     pub fn new_epoch(&mut self, epoch: Epoch, cfg_timescale: TimeScale) {
         self.epoch = Some(epoch.to_time_scale(cfg_timescale));
 
         if let Some(first_epoch) = self.first_epoch {
             self.uptime = epoch - first_epoch;
+        } else {
+            self.first_epoch = Some(epoch);
         }
     }
 
+    /// Latch the [Epoch] of the latest navigation solution (NAV-PVT, NAV-TIMEUTC)
+    pub fn latch_time_reference(&mut self, epoch: Epoch) {
+        self.time_reference = Some(epoch);
+    }
+
+    /// Resolves a GPST time of week (ms), as reported by the navigation messages.
+    /// The week follows the latest [Epoch], or the latest navigation solution
+    /// before the first measurement. None while the week is unknown.
+    pub fn gpst_time_of_week(&self, itow_ms: u32) -> Option<Epoch> {
+        let reference = self.epoch.or(self.time_reference)?;
+        Some(resolve_time_of_week(reference, itow_ms as u64 * 1_000_000))
+    }
+
+    /// Latch new NAV-EOE
+    pub fn latch_nav_eoe(&mut self, epoch: Epoch) {
+        self.last_nav_eoe = Some(epoch);
+    }
+
+    /// Returns true when end of epochs should be derived from the epoch stream:
+    /// either NAV-EOE is not expected, or it was not received within the timeout.
+    pub fn nav_eoe_fallback(&mut self, timeout: Duration) -> bool {
+        if self.nav_eoe_fallback {
+            return true;
+        }
+
+        let (Some(epoch), Some(reference)) = (self.epoch, self.last_nav_eoe.or(self.first_epoch))
+        else {
+            return false;
+        };
+
+        if epoch - reference > timeout {
+            warn!(
+                "{} - no NAV-EOE received for {}: deriving end of epochs from the epoch stream",
+                epoch, timeout
+            );
+            self.nav_eoe_fallback = true;
+        }
+
+        self.nav_eoe_fallback
+    }
+
+    /// Latch the signals tracked per [SV], as reported by NAV-SIG at this [Epoch]
+    pub fn latch_signals(&mut self, epoch: Epoch, signals: HashMap<SV, Vec<(u8, u8)>>) {
+        self.signals_epoch = Some(epoch);
+        self.signals = signals;
+    }
+
+    /// Cross-checks the signal identifier of a RXM-RAWX measurement against NAV-SIG.
+    /// Only a NAV-SIG describing this very [Epoch] is considered: returns None otherwise,
+    /// or when NAV-SIG does not report this [SV]. Returns false when NAV-SIG
+    /// does not report this signal with the same C/N0.
+    pub fn signal_agrees(&self, epoch: Epoch, sv: SV, sig_id: u8, cno: u8) -> Option<bool> {
+        let signals_epoch = self.signals_epoch?;
+
+        if (epoch - signals_epoch).abs() >= Duration::from_milliseconds(1.0) {
+            return None;
+        }
+
+        let signals = self.signals.get(&sv)?;
+
+        Some(signals.contains(&(sig_id, cno)))
+    }
+
     /// Latch new SFRBX interpretation
     pub fn latch_sfrbx(
         &mut self,
@@ -77,6 +209,56 @@ impl Runtime {
         }
     }
 
+    /// Latch a complete [GpsQzssEphemeris], obtained by MGA polling.
+    /// This replaces any pending (partial) frame for this [SV].
+    pub fn latch_gps_qzss_ephemeris(&mut self, sv: SV, ephemeris: GpsQzssEphemeris) {
+        self.pending_frames.insert(
+            sv,
+            PendingFrame::GpsQzss(PendingGpsQzssFrame {
+                how: ephemeris.how,
+                frame1: Some(ephemeris.frame1),
+                frame2: Some(ephemeris.frame2),
+                frame3: Some(ephemeris.frame3),
+            }),
+        );
+    }
+
+    /// Returns how long to wait, so the epoch stream is replayed
+    /// at `rate` times real time. The replay starts with the first epoch.
+    pub fn replay_delay(&mut self, rate: f64) -> Option<std::time::Duration> {
+        self.first_epoch?;
+
+        let start = *self.replay_start.get_or_insert_with(Instant::now);
+        replay_delay(self.uptime, rate, start.elapsed())
+    }
+
+    /// Latch the satellites of the latest RXM-RAWX epoch
+    pub fn latch_rawx_svs(&mut self, svs: &[SV]) {
+        let mut svs = svs.to_vec();
+        svs.sort();
+        svs.dedup();
+
+        self.rawx_svs = Some(svs.len());
+    }
+
+    /// Compares the number of satellites used in the navigation solution (NAV-SAT)
+    /// to the satellites of the latest RXM-RAWX epoch. Returns the (RXM-RAWX, NAV-SAT)
+    /// counts when they start to differ, which indicates signals are being dropped.
+    pub fn check_sv_used(&mut self, used: usize) -> Option<(usize, usize)> {
+        let rawx_svs = self.rawx_svs?;
+
+        let mismatch = used > rawx_svs + SV_COUNT_TOLERANCE;
+        let reported = self.sv_count_mismatch;
+
+        self.sv_count_mismatch = mismatch;
+
+        if mismatch && !reported {
+            Some((rawx_svs, used))
+        } else {
+            None
+        }
+    }
+
     // /// Tries to gather a [GpsQzssEphemeris]
     // pub fn gather_gps_qzss_ephemeris(&self) -> Option<GpsQzssEphemeris> {
     //     let pending = self.pending_gps_qzss_frame?;
@@ -108,3 +290,163 @@ impl Runtime {
         self.current_week(TimeScale::GPST)
     }
 }
+
+/// Returns the GPST [Epoch] of this time of week, in the week of the `reference` [Epoch]:
+/// the closest to the reference, so the week rollover is accounted for.
+fn resolve_time_of_week(reference: Epoch, tow_nanos: u64) -> Epoch {
+    let reference = reference.to_time_scale(TimeScale::GPST);
+    let (week, _) = reference.to_time_of_week();
+
+    let t = Epoch::from_time_of_week(week, tow_nanos, TimeScale::GPST);
+
+    let half_week = Duration::from_days(3.5);
+
+    if reference - t > half_week {
+        t + Duration::from_days(7.0)
+    } else if t - reference > half_week {
+        t - Duration::from_days(7.0)
+    } else {
+        t
+    }
+}
+
+/// Remaining wall clock time before the stream reaches `uptime`, at `rate` times real time
+fn replay_delay(
+    uptime: Duration,
+    rate: f64,
+    elapsed: std::time::Duration,
+) -> Option<std::time::Duration> {
+    let target = std::time::Duration::from_secs_f64(uptime.to_seconds().max(0.0) / rate);
+
+    target.checked_sub(elapsed).filter(|delay| !delay.is_zero())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Runtime, replay_delay};
+    use hifitime::prelude::{Duration, Epoch, TimeScale};
+    use rinex::prelude::SV;
+    use std::str::FromStr;
+
+    #[test]
+    fn replay_pacing() {
+        let elapsed = std::time::Duration::from_secs(10);
+
+        // real time: 5 s ahead of the stream
+        assert_eq!(
+            replay_delay(Duration::from_seconds(15.0), 1.0, elapsed),
+            Some(std::time::Duration::from_secs(5))
+        );
+
+        // twice as fast
+        assert_eq!(
+            replay_delay(Duration::from_seconds(30.0), 2.0, elapsed),
+            Some(std::time::Duration::from_secs(5))
+        );
+
+        // late: no delay
+        assert!(replay_delay(Duration::from_seconds(15.0), 2.0, elapsed).is_none());
+        assert!(replay_delay(Duration::from_seconds(10.0), 1.0, elapsed).is_none());
+    }
+
+    #[test]
+    fn nav_eoe_week() {
+        let mut runtime = Runtime::new();
+
+        // neither measurement nor navigation solution: week is unknown
+        assert!(runtime.gpst_time_of_week(345_600_000).is_none());
+
+        // NAV-PVT ahead of the first measurement
+        let t_pvt = Epoch::from_str("2024-01-03T12:00:00 UTC").unwrap();
+        runtime.latch_time_reference(t_pvt);
+
+        let (week, tow_nanos) = t_pvt.to_time_scale(TimeScale::GPST).to_time_of_week();
+        let itow_ms = (tow_nanos / 1_000_000) as u32;
+
+        assert_eq!(
+            runtime.gpst_time_of_week(itow_ms),
+            Some(Epoch::from_time_of_week(
+                week,
+                itow_ms as u64 * 1_000_000,
+                TimeScale::GPST
+            ))
+        );
+
+        // end of week reference: the next week starts
+        let t_end = Epoch::from_time_of_week(week, 604_799_000_000_000, TimeScale::GPST);
+        runtime.new_epoch(t_end, TimeScale::GPST);
+
+        assert_eq!(
+            runtime.gpst_time_of_week(500),
+            Some(Epoch::from_time_of_week(
+                week + 1,
+                500_000_000,
+                TimeScale::GPST
+            ))
+        );
+    }
+
+    #[test]
+    fn nav_sat_sv_count() {
+        let mut runtime = Runtime::new();
+
+        // no RXM-RAWX yet
+        assert!(runtime.check_sv_used(12).is_none());
+
+        let svs = ["G01", "G02", "G02", "G05", "E11"]
+            .iter()
+            .map(|sv| SV::from_str(sv).unwrap())
+            .collect::<Vec<_>>();
+
+        runtime.latch_rawx_svs(&svs);
+
+        assert!(runtime.check_sv_used(4).is_none());
+        assert!(runtime.check_sv_used(6).is_none());
+        assert_eq!(runtime.check_sv_used(9), Some((4, 9)));
+
+        // reported once
+        assert!(runtime.check_sv_used(9).is_none());
+
+        // resolved, then reported again
+        assert!(runtime.check_sv_used(4).is_none());
+        assert_eq!(runtime.check_sv_used(8), Some((4, 8)));
+    }
+
+    #[test]
+    fn nav_sig_signal_cross_check() {
+        let mut runtime = Runtime::new();
+
+        let t0 = Epoch::from_str("2020-01-01T00:00:00 GPST").unwrap();
+        let t1 = t0 + Duration::from_seconds(1.0);
+
+        let g01 = SV::from_str("G01").unwrap();
+        let e05 = SV::from_str("E05").unwrap();
+
+        // no NAV-SIG
+        assert_eq!(runtime.signal_agrees(t0, g01, 0, 40), None);
+
+        runtime.latch_signals(
+            t0,
+            [(g01, vec![(0, 40)]), (e05, vec![(0, 42), (5, 38)])]
+                .into_iter()
+                .collect(),
+        );
+
+        assert_eq!(runtime.signal_agrees(t0, g01, 0, 40), Some(true));
+        assert_eq!(runtime.signal_agrees(t0, e05, 5, 38), Some(true));
+        assert_eq!(runtime.signal_agrees(t0, e05, 5, 42), Some(false));
+        assert_eq!(runtime.signal_agrees(t0, e05, 3, 38), Some(false));
+
+        // not reported by NAV-SIG
+        let c10 = SV::from_str("C10").unwrap();
+        assert_eq!(runtime.signal_agrees(t0, c10, 0, 40), None);
+
+        // sub-millisecond receiver clock offset
+        let t0_meas = t0 + Duration::from_microseconds(250.0);
+        assert_eq!(runtime.signal_agrees(t0_meas, g01, 0, 40), Some(true));
+
+        // stale NAV-SIG is never trusted
+        assert_eq!(runtime.signal_agrees(t1, g01, 0, 40), None);
+        assert_eq!(runtime.signal_agrees(t1, e05, 5, 42), None);
+    }
+}
@@ -1,5 +1,46 @@
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    str::FromStr,
+};
+
 use rinex::prelude::{Constellation, Duration, TimeScale};
-use ublox::packets::cfg_val::{CfgLayerSet, CfgValSetBuilder};
+use ublox::packets::cfg_val::{CfgLayerSet, CfgVal, CfgValSetBuilder};
+
+/// Severity of the UBX-INF messages
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InfLevel {
+    /// INF-ERROR
+    Error,
+
+    /// INF-WARNING
+    Warning,
+
+    /// INF-NOTICE
+    Notice,
+}
+
+impl FromStr for InfLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "error" => Ok(Self::Error),
+            "warning" => Ok(Self::Warning),
+            "notice" => Ok(Self::Notice),
+            _ => Err(format!("invalid INF level \"{}\"", s)),
+        }
+    }
+}
+
+impl Display for InfLevel {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::Error => write!(f, "ERROR"),
+            Self::Warning => write!(f, "WARNING"),
+            Self::Notice => write!(f, "NOTICE"),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Settings {
@@ -24,12 +65,39 @@ pub struct Settings {
     /// Ephemeris enabled
     pub ephemeris: bool,
 
+    /// Periodically poll ephemeris with MGA assistance requests
+    pub poll_ephemeris: bool,
+
+    /// NAV-EOE expected. When false, end of epochs are derived from the epoch stream.
+    pub nav_eoe: bool,
+
+    /// NAV-PVT enabled
+    pub nav_pvt: bool,
+
+    /// NAV-SAT enabled
+    pub nav_sat: bool,
+
+    /// SBAS messages logging
+    pub sbas_log: bool,
+
+    /// Print each decoded UBX packet (debugging)
+    pub dump_packets: bool,
+
+    /// TIM-TP logging
+    pub time_pulse: bool,
+
     /// ratio
     pub solutions_ratio: u16,
 
+    /// Precision of the epochs reported in the logs
+    pub precision: Duration,
+
     /// Active [Constellation]s
     pub constellations: Vec<Constellation>,
 
+    /// [Constellation]s collected in the Navigation RINEX
+    pub nav_constellations: Vec<Constellation>,
+
     /// Serial number
     pub sn: Option<String>,
 
@@ -44,86 +112,232 @@ pub struct Settings {
 
     /// Receiver antenna model/label
     pub antenna: Option<String>,
+
+    /// Configuration is also written to the BBR and Flash layers,
+    /// so it is retained across power cycles
+    pub persist: bool,
+
+    /// UBX-INF messages captured in the Observation RINEX header comments
+    pub capture_inf: Vec<InfLevel>,
+
+    /// [TimeScale] of the RXM-RAWX week and time of week, which does not declare it:
+    /// either specified (--rawx-timescale) or read from the receiver configuration
+    /// (MON-GNSS, active mode). GPST otherwise.
+    pub rawx_timescale: Option<TimeScale>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            l1: true,
+            l2: false,
+            l5: false,
+            timescale: TimeScale::GPST,
+            sampling_period: Duration::from_seconds(30.0),
+            rawxm: true,
+            ephemeris: false,
+            poll_ephemeris: false,
+            nav_eoe: true,
+            nav_pvt: true,
+            nav_sat: true,
+            sbas_log: false,
+            dump_packets: false,
+            time_pulse: false,
+            solutions_ratio: 1,
+            precision: Duration::from_seconds(1.0),
+            constellations: vec![Constellation::GPS],
+            nav_constellations: vec![Constellation::GPS],
+            sn: None,
+            rx_clock: false,
+            model: None,
+            firmware: None,
+            antenna: None,
+            persist: false,
+            capture_inf: Default::default(),
+            rawx_timescale: None,
+        }
+    }
 }
 
 impl Settings {
+    /// Returns the [Duration] without NAV-EOE after which we
+    /// fallback to end of epochs derived from the epoch stream.
+    pub fn nav_eoe_timeout(&self) -> Duration {
+        let timeout = self.sampling_period * 3;
+        let minimum = Duration::from_seconds(10.0);
+
+        if timeout > minimum { timeout } else { minimum }
+    }
+
+    /// Formats the constellation and signal settings, as a single CFG-VALSET
     pub fn to_ram_volatile_cfg(&self, buf: &mut Vec<u8>) {
-        // let mut cfg_data = Vec::<CfgVal>::new();
-        //
-        // if self.constellations.contains(&Constellation::GPS)
-        //     || self.constellations.contains(&Constellation::QZSS)
-        // {
-        //     cfg_data.push(CfgVal::SignalGpsEna(true));
-        //     cfg_data.push(CfgVal::SignalQzssEna(true));
-        // } else {
-        //     cfg_data.push(CfgVal::SignalGpsEna(false));
-        //     cfg_data.push(CfgVal::SignalQzssEna(false));
-        // }
-
-        // if self.constellations.contains(&Constellation::Galileo) {
-        //     cfg_data.push(CfgVal::SignalGalEna(true));
-        //     cfg_data.push(CfgVal::SignalGalE1Ena(true));
-        //     cfg_data.push(CfgVal::SignalGalE5bEna(true));
-        // } else {
-        //     cfg_data.push(CfgVal::SignalGalEna(false));
-        //     cfg_data.push(CfgVal::SignalGalE1Ena(false));
-        //     cfg_data.push(CfgVal::SignalGalE5bEna(false));
-        // }
-
-        // if self.constellations.contains(&Constellation::QZSS) {
-        //     cfg_data.push(CfgVal::SignalQzssL1caEna(true));
-        //     cfg_data.push(CfgVal::SignalQzssL2cEna(true));
-        // } else {
-        //     cfg_data.push(CfgVal::SignalQzssL1caEna(false));
-        //     cfg_data.push(CfgVal::SignalQzssL2cEna(false));
-        // }
-
-        // if self.constellations.contains(&Constellation::Glonass) {
-        //     cfg_data.push(CfgVal::SignalGloEna(true));
-        //     cfg_data.push(CfgVal::SignalGloL1Ena(true));
-        //     cfg_data.push(CfgVal::SignalGLoL2Ena(true));
-        // } else {
-        //     cfg_data.push(CfgVal::SignalGloEna(false));
-        // }
-
-        // if self.constellations.contains(&Constellation::BeiDou) {
-        //     cfg_data.push(CfgVal::SignalBdsEna(true));
-        // } else {
-        //     cfg_data.push(CfgVal::SignalBdsEna(false));
-        // }
-
-        // if self.l1 {
-        //     cfg_data.push(CfgVal::SignalGpsL1caEna(true));
-        //     cfg_data.push(CfgVal::SignalBdsB1Ena(true));
-        //     cfg_data.push(CfgVal::SignalBdsB2Ena(true));
-        //     cfg_data.push(CfgVal::SignalGloL1Ena(true));
-        // } else {
-        //     cfg_data.push(CfgVal::SignalGpsL1caEna(false));
-        //     cfg_data.push(CfgVal::SignalBdsB1Ena(false));
-        //     cfg_data.push(CfgVal::SignalBdsB2Ena(false));
-        //     cfg_data.push(CfgVal::SignalGloL1Ena(false));
-        // }
-
-        // if self.l2 {
-        //     cfg_data.push(CfgVal::SignalGpsL2cEna(true));
-        //     cfg_data.push(CfgVal::SignalGLoL2Ena(true));
-        // } else {
-        //     cfg_data.push(CfgVal::SignalGpsL2cEna(false));
-        //     cfg_data.push(CfgVal::SignalGLoL2Ena(false));
-        // }
-
-        // if self.l5 {
-        //     cfg_data.push(CfgVal::UndocumentedL5Enable(true));
-        // } else {
-        //     cfg_data.push(CfgVal::UndocumentedL5Enable(false));
-        // }
-
-        CfgValSetBuilder {
-            version: 0,
-            layers: CfgLayerSet::RAM,
-            reserved1: 0,
-            cfg_data: &[],
+        cfg_valset(&self.ram_volatile_cfg(), buf);
+    }
+
+    /// Configuration layers the constellation and signal settings are written to:
+    /// RAM, plus BBR and Flash when the configuration should persist
+    pub fn cfg_layers(&self) -> CfgLayerSet {
+        if self.persist {
+            CfgLayerSet::RAM | CfgLayerSet::BBR | CfgLayerSet::FLASH
+        } else {
+            CfgLayerSet::RAM
         }
-        .extend_to(buf);
+    }
+
+    /// Formats the constellation and signal settings, as a single CFG-VALSET
+    /// written to all [Self::cfg_layers]
+    pub fn to_cfg(&self, buf: &mut Vec<u8>) {
+        cfg_valset_layers(&self.ram_volatile_cfg(), self.cfg_layers(), buf);
+    }
+
+    /// Returns the constellation and signal settings, as [CfgVal]s
+    pub fn ram_volatile_cfg(&self) -> Vec<CfgVal> {
+        let mut cfg_data = Vec::<CfgVal>::new();
+
+        let gps = self.constellations.contains(&Constellation::GPS);
+        let qzss = self.constellations.contains(&Constellation::QZSS);
+        let galileo = self.constellations.contains(&Constellation::Galileo);
+        let glonass = self.constellations.contains(&Constellation::Glonass);
+        let bds = self.constellations.contains(&Constellation::BeiDou);
+
+        // GPS
+        cfg_data.push(CfgVal::SignalGpsEna(gps));
+        cfg_data.push(CfgVal::SignalGpsL1caEna(gps && self.l1));
+        cfg_data.push(CfgVal::SignalGpsL2cEna(gps && self.l2));
+        cfg_data.push(CfgVal::SignalGpsL5Ena(gps && self.l5));
+
+        // QZSS: only when requested, not implied by GPS
+        cfg_data.push(CfgVal::SignalQzssEna(qzss));
+        cfg_data.push(CfgVal::SignalQzssL1caEna(qzss && self.l1));
+        cfg_data.push(CfgVal::SignalQzssL2cEna(qzss && self.l2));
+        cfg_data.push(CfgVal::SignalQzssL5Ena(qzss && self.l5));
+
+        // Galileo: E1 (L1) and E5a (L5), E5b sits in the L2 band
+        cfg_data.push(CfgVal::SignalGalEna(galileo));
+        cfg_data.push(CfgVal::SignalGalE1Ena(galileo && self.l1));
+        cfg_data.push(CfgVal::SignalGalE5bEna(galileo && self.l2));
+        cfg_data.push(CfgVal::SignalGalE5aEna(galileo && self.l5));
+
+        // Glonass: L1 / L2 only
+        cfg_data.push(CfgVal::SignalGloEna(glonass));
+        cfg_data.push(CfgVal::SignalGloL1Ena(glonass && self.l1));
+        cfg_data.push(CfgVal::SignalGLoL2Ena(glonass && self.l2));
+
+        // BeiDou: B1 (L1), B2 (L2) and B2a (L5)
+        cfg_data.push(CfgVal::SignalBdsEna(bds));
+        cfg_data.push(CfgVal::SignalBdsB1Ena(bds && self.l1));
+        cfg_data.push(CfgVal::SignalBdsB2Ena(bds && self.l2));
+        cfg_data.push(CfgVal::SignalBdsB2aEna(bds && self.l5));
+
+        // L5 band is not enabled by default on F9/F10 series
+        cfg_data.push(CfgVal::UndocumentedL5Enable(self.l5));
+
+        cfg_data
+    }
+}
+
+/// Formats these [CfgVal]s as a CFG-VALSET, applied to RAM
+pub fn cfg_valset(cfg_data: &[CfgVal], buf: &mut Vec<u8>) {
+    cfg_valset_layers(cfg_data, CfgLayerSet::RAM, buf);
+}
+
+/// Formats these [CfgVal]s as a CFG-VALSET, applied to these [CfgLayerSet]
+pub fn cfg_valset_layers(cfg_data: &[CfgVal], layers: CfgLayerSet, buf: &mut Vec<u8>) {
+    CfgValSetBuilder {
+        version: 0,
+        layers,
+        reserved1: 0,
+        cfg_data,
+    }
+    .extend_to(buf);
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Settings, cfg_valset, cfg_valset_layers};
+    use rinex::prelude::Constellation;
+    use ublox::packets::cfg_val::{CfgLayerSet, CfgVal};
+
+    #[test]
+    fn ram_volatile_cfg_framing() {
+        let settings = Settings::default();
+
+        let mut buf = Vec::new();
+        settings.to_ram_volatile_cfg(&mut buf);
+
+        // UBX-CFG-VALSET
+        assert_eq!(&buf[..4], &[0xb5, 0x62, 0x06, 0x8a]);
+
+        // one key at a time, when looking for a rejected key
+        let cfg_data = settings.ram_volatile_cfg();
+
+        let mut single = Vec::new();
+        cfg_valset(&cfg_data[..1], &mut single);
+
+        assert_eq!(&single[..4], &[0xb5, 0x62, 0x06, 0x8a]);
+        assert!(single.len() < buf.len());
+    }
+
+    #[test]
+    fn persistent_cfg_layers() {
+        let settings = Settings::default();
+        assert_eq!(settings.cfg_layers(), CfgLayerSet::RAM);
+
+        let settings = Settings {
+            persist: true,
+            ..Default::default()
+        };
+
+        let layers = settings.cfg_layers();
+        assert!(layers.contains(CfgLayerSet::RAM | CfgLayerSet::BBR | CfgLayerSet::FLASH));
+
+        let mut ram = Vec::new();
+        settings.to_ram_volatile_cfg(&mut ram);
+
+        let mut persistent = Vec::new();
+        settings.to_cfg(&mut persistent);
+
+        // same keys, only the layers byte differs (payload offset 1)
+        assert_eq!(ram.len(), persistent.len());
+        assert_eq!(ram[7], CfgLayerSet::RAM.bits());
+        assert_eq!(persistent[7], layers.bits());
+
+        let mut single = Vec::new();
+        cfg_valset_layers(
+            &settings.ram_volatile_cfg()[..1],
+            CfgLayerSet::BBR,
+            &mut single,
+        );
+        assert_eq!(single[7], CfgLayerSet::BBR.bits());
+    }
+
+    #[test]
+    fn qzss_is_not_implied_by_gps() {
+        let settings = Settings::default();
+        let cfg_data = settings.ram_volatile_cfg();
+
+        assert!(
+            cfg_data
+                .iter()
+                .any(|cfg| matches!(cfg, CfgVal::SignalGpsEna(true)))
+        );
+        assert!(
+            cfg_data
+                .iter()
+                .any(|cfg| matches!(cfg, CfgVal::SignalQzssEna(false)))
+        );
+
+        let settings = Settings {
+            constellations: vec![Constellation::GPS, Constellation::QZSS],
+            ..Default::default()
+        };
+
+        let cfg_data = settings.ram_volatile_cfg();
+
+        assert!(
+            cfg_data
+                .iter()
+                .any(|cfg| matches!(cfg, CfgVal::SignalQzssEna(true)))
+        );
     }
 }
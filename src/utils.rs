@@ -1,6 +1,17 @@
-use ublox::cfg_rate::AlignmentToReferenceTime;
+use ublox::{cfg_rate::AlignmentToReferenceTime, mon_gnss::MonGnssConstellMask};
 
-use rinex::prelude::{Constellation, TimeScale};
+use rinex::prelude::{Constellation, Observable, SV, TimeScale};
+
+use std::str::FromStr;
+
+/// Kind of [Observable] produced for each [SignalCarrier]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ObservableKind {
+    PseudoRange,
+    PhaseRange,
+    Doppler,
+    SSI,
+}
 
 #[allow(non_camel_case_types)]
 #[derive(Debug, Copy, Clone, Default, PartialEq)]
@@ -35,6 +46,44 @@ pub enum SignalCarrier {
     NAVIC_L5_A,
 }
 
+impl FromStr for SignalCarrier {
+    type Err = String;
+
+    /// Parses a [SignalCarrier] from its name, for example "BDS_B1I_D1"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_uppercase().as_str() {
+            "GPS_L1_CA" => Ok(Self::GPS_L1_CA),
+            "GPS_L2_CL" => Ok(Self::GPS_L2_CL),
+            "GPS_L2_CM" => Ok(Self::GPS_L2_CM),
+            "GPS_L5_I" => Ok(Self::GPS_L5_I),
+            "GPS_L5_Q" => Ok(Self::GPS_L5_Q),
+            "SBAS_L1_CA" => Ok(Self::SBAS_L1_CA),
+            "GAL_E1_C" => Ok(Self::GAL_E1_C),
+            "GAL_E1_B" => Ok(Self::GAL_E1_B),
+            "GAL_E5A_I" => Ok(Self::GAL_E5A_I),
+            "GAL_E5A_Q" => Ok(Self::GAL_E5A_Q),
+            "GAL_E5B_I" => Ok(Self::GAL_E5B_I),
+            "GAL_E5B_Q" => Ok(Self::GAL_E5B_Q),
+            "BDS_B1I_D1" => Ok(Self::BDS_B1I_D1),
+            "BDS_B1I_D2" => Ok(Self::BDS_B1I_D2),
+            "BDS_B2I_D1" => Ok(Self::BDS_B2I_D1),
+            "BDS_B2I_D2" => Ok(Self::BDS_B2I_D2),
+            "BDS_B1C" => Ok(Self::BDS_B1C),
+            "BDS_B2A" => Ok(Self::BDS_B2A),
+            "QZSS_L1_CA" => Ok(Self::QZSS_L1_CA),
+            "QZSS_L1_S" => Ok(Self::QZSS_L1_S),
+            "QZSS_L2_CM" => Ok(Self::QZSS_L2_CM),
+            "QZSS_L2_CL" => Ok(Self::QZSS_L2_CL),
+            "QZSS_L5_I" => Ok(Self::QZSS_L5_I),
+            "QZSS_L5_Q" => Ok(Self::QZSS_L5_Q),
+            "GLO_L1_OF" => Ok(Self::GLO_L1_OF),
+            "GLO_L2_OF" => Ok(Self::GLO_L2_OF),
+            "NAVIC_L5_A" => Ok(Self::NAVIC_L5_A),
+            _ => Err(format!("unknown signal \"{}\"", s)),
+        }
+    }
+}
+
 impl SignalCarrier {
     pub fn from_ubx(constell_id: u8, freq_id: u8) -> SignalCarrier {
         match (constell_id, freq_id) {
@@ -68,6 +117,105 @@ impl SignalCarrier {
         }
     }
 
+    /// Returns the [SignalCarrier] of this [SV]. BeiDou GEO satellites broadcast
+    /// the D2 navigation message, other satellites D1: this follows the PRN,
+    /// whatever the signal ID (which older protocols do not report).
+    pub fn from_ubx_sv(sv: SV, freq_id: u8) -> SignalCarrier {
+        let carrier = Self::from_ubx(from_constellation(&sv.constellation), freq_id);
+
+        if sv.constellation != Constellation::BeiDou {
+            return carrier;
+        }
+
+        let geo = is_beidou_geo(sv.prn);
+
+        match carrier {
+            Self::BDS_B1I_D1 | Self::BDS_B1I_D2 if geo => Self::BDS_B1I_D2,
+            Self::BDS_B1I_D1 | Self::BDS_B1I_D2 => Self::BDS_B1I_D1,
+            Self::BDS_B2I_D1 | Self::BDS_B2I_D2 if geo => Self::BDS_B2I_D2,
+            Self::BDS_B2I_D1 | Self::BDS_B2I_D2 => Self::BDS_B2I_D1,
+            carrier => carrier,
+        }
+    }
+
+    /// [Constellation] broadcasting this [SignalCarrier]
+    pub fn constellation(&self) -> Constellation {
+        match self {
+            Self::GPS_L1_CA
+            | Self::GPS_L2_CL
+            | Self::GPS_L2_CM
+            | Self::GPS_L5_I
+            | Self::GPS_L5_Q => Constellation::GPS,
+            Self::SBAS_L1_CA => Constellation::SBAS,
+            Self::GAL_E1_C
+            | Self::GAL_E1_B
+            | Self::GAL_E5A_I
+            | Self::GAL_E5A_Q
+            | Self::GAL_E5B_I
+            | Self::GAL_E5B_Q => Constellation::Galileo,
+            Self::BDS_B1I_D1
+            | Self::BDS_B1I_D2
+            | Self::BDS_B2I_D1
+            | Self::BDS_B2I_D2
+            | Self::BDS_B1C
+            | Self::BDS_B2A => Constellation::BeiDou,
+            Self::QZSS_L1_CA
+            | Self::QZSS_L1_S
+            | Self::QZSS_L2_CM
+            | Self::QZSS_L2_CL
+            | Self::QZSS_L5_I
+            | Self::QZSS_L5_Q => Constellation::QZSS,
+            Self::GLO_L1_OF | Self::GLO_L2_OF => Constellation::Glonass,
+            Self::NAVIC_L5_A => Constellation::IRNSS,
+        }
+    }
+
+    /// Returns the [Observable] of this [SignalCarrier], in RINEX revision `major`.
+    /// Both the header and the records are defined from this single reference.
+    pub fn to_observable(&self, kind: ObservableKind, major: u8) -> Observable {
+        let v2 = major == 2;
+
+        let code = match kind {
+            ObservableKind::PseudoRange => self.to_pseudo_range_observable(v2),
+            ObservableKind::PhaseRange => self.to_phase_range_observable(v2),
+            ObservableKind::Doppler => self.to_doppler_observable(v2),
+            ObservableKind::SSI => self.to_ssi_observable(v2),
+        };
+
+        Observable::from_str(&code).unwrap_or_else(|e| {
+            panic!("internal error: invalid observable \"{}\": {}", code, e);
+        })
+    }
+
+    /// Carrier frequency (Hz). GLONASS FDMA signals depend on the
+    /// frequency channel of the satellite (channel 0 when unknown).
+    pub fn frequency(&self, glo_channel: Option<i8>) -> f64 {
+        let channel = glo_channel.unwrap_or(0) as f64;
+
+        match self {
+            Self::GPS_L1_CA
+            | Self::SBAS_L1_CA
+            | Self::GAL_E1_C
+            | Self::GAL_E1_B
+            | Self::BDS_B1C
+            | Self::QZSS_L1_CA
+            | Self::QZSS_L1_S => 1575.42E6,
+            Self::GPS_L2_CL | Self::GPS_L2_CM | Self::QZSS_L2_CM | Self::QZSS_L2_CL => 1227.60E6,
+            Self::GPS_L5_I
+            | Self::GPS_L5_Q
+            | Self::GAL_E5A_I
+            | Self::GAL_E5A_Q
+            | Self::BDS_B2A
+            | Self::QZSS_L5_I
+            | Self::QZSS_L5_Q
+            | Self::NAVIC_L5_A => 1176.45E6,
+            Self::GAL_E5B_I | Self::GAL_E5B_Q | Self::BDS_B2I_D1 | Self::BDS_B2I_D2 => 1207.14E6,
+            Self::BDS_B1I_D1 | Self::BDS_B1I_D2 => 1561.098E6,
+            Self::GLO_L1_OF => 1602.0E6 + channel * 0.5625E6,
+            Self::GLO_L2_OF => 1246.0E6 + channel * 0.4375E6,
+        }
+    }
+
     pub fn to_pseudo_range_observable(&self, v2: bool) -> String {
         if v2 {
             match self {
@@ -294,44 +442,65 @@ pub fn from_timescale(ts: TimeScale) -> AlignmentToReferenceTime {
     }
 }
 
-// pub fn constell_mask_to_string(mask: MonGnssConstellMask) -> String {
-//     let mut string = String::with_capacity(16);
-//     if mask.intersects(MonGnssConstellMask::GPS) {
-//         string.push_str("GPS, ");
-//     }
-//     if mask.intersects(MonGnssConstellMask::GAL) {
-//         string.push_str("Galileo, ");
-//     }
-//     if mask.intersects(MonGnssConstellMask::BDC) {
-//         string.push_str("BeiDou, ");
-//     }
-//     if mask.intersects(MonGnssConstellMask::GLO) {
-//         string.push_str("Glonass, ");
-//     }
-//     string
-// }
+/// [Constellation]s described by this MON-GNSS mask
+pub fn mask_constellations(mask: MonGnssConstellMask) -> Vec<Constellation> {
+    let mut constellations = Vec::with_capacity(4);
+
+    if mask.intersects(MonGnssConstellMask::GPS) {
+        constellations.push(Constellation::GPS);
+    }
+    if mask.intersects(MonGnssConstellMask::GAL) {
+        constellations.push(Constellation::Galileo);
+    }
+    if mask.intersects(MonGnssConstellMask::BDC) {
+        constellations.push(Constellation::BeiDou);
+    }
+    if mask.intersects(MonGnssConstellMask::GLO) {
+        constellations.push(Constellation::Glonass);
+    }
 
-// pub fn constellations_to_mask(constellations: &[Constellation]) -> MonGnssConstellMask {
-//     let mut mask = MonGnssConstellMask::empty();
+    constellations
+}
 
-//     if constellations.contains(&Constellation::GPS) {
-//         mask |= MonGnssConstellMask::GPS;
-//     }
+/// [TimeScale] a receiver counts the RXM-RAWX week and time of week in,
+/// from the [Constellation]s it enables (MON-GNSS). Receivers that do not track GPS
+/// count in the system time of the only constellation they track: GST or BDT.
+pub fn receiver_timescale(enabled: &[Constellation]) -> TimeScale {
+    match enabled {
+        [Constellation::Galileo] => TimeScale::GST,
+        [Constellation::BeiDou] => TimeScale::BDT,
+        _ => TimeScale::GPST,
+    }
+}
 
-//     if constellations.contains(&Constellation::Galileo) {
-//         mask |= MonGnssConstellMask::GAL;
-//     }
+/// MON-GNSS mask of these [Constellation]s.
+/// MON-GNSS does not describe the augmentation systems (QZSS, SBAS).
+pub fn constellations_to_mask(constellations: &[Constellation]) -> MonGnssConstellMask {
+    let mut mask = MonGnssConstellMask::empty();
 
-//     if constellations.contains(&Constellation::BeiDou) {
-//         mask |= MonGnssConstellMask::BDC;
-//     }
+    if constellations.contains(&Constellation::GPS) {
+        mask |= MonGnssConstellMask::GPS;
+    }
 
-//     if constellations.contains(&Constellation::Glonass) {
-//         mask |= MonGnssConstellMask::GLO;
-//     }
+    if constellations.contains(&Constellation::Galileo) {
+        mask |= MonGnssConstellMask::GAL;
+    }
 
-//     mask
-// }
+    if constellations.contains(&Constellation::BeiDou) {
+        mask |= MonGnssConstellMask::BDC;
+    }
+
+    if constellations.contains(&Constellation::Glonass) {
+        mask |= MonGnssConstellMask::GLO;
+    }
+
+    mask
+}
+
+/// True for BeiDou GEO satellites (C01-C05 and C59-C63)
+pub fn is_beidou_geo(prn: u8) -> bool {
+    (1..=5).contains(&prn) || (59..=63).contains(&prn)
+}
 
 pub fn to_constellation(id: u8) -> Option<Constellation> {
     match id {
@@ -358,6 +527,163 @@ pub fn from_constellation(constellation: &Constellation) -> u8 {
     }
 }
 
+/// Removes the duplicate [Observable]s, preserving their order. RINEX V2 gathers several
+/// signals under a single observable (C1 for both Galileo E1-C and E1-B), which must only
+/// be declared once. Returns the [Observable]s that collided.
+pub fn dedup_observables(observables: &mut Vec<Observable>) -> Vec<Observable> {
+    let mut unique = Vec::with_capacity(observables.len());
+    let mut collisions = Vec::new();
+
+    for observable in observables.drain(..) {
+        if !unique.contains(&observable) {
+            unique.push(observable);
+        } else if !collisions.contains(&observable) {
+            collisions.push(observable);
+        }
+    }
+
+    *observables = unique;
+    collisions
+}
+
+/// Sorts [Observable]s deterministically: by kind (C, L, D, S), then by band and attribute.
+pub fn sort_observables(observables: &mut [Observable]) {
+    observables.sort_by_key(|observable| {
+        let code = observable.to_string();
+
+        let kind = match code.chars().next() {
+            Some('C') | Some('P') => 0,
+            Some('L') => 1,
+            Some('D') => 2,
+            Some('S') => 3,
+            _ => 4,
+        };
+
+        let band = code.chars().skip(1).collect::<String>();
+        (kind, band, code)
+    });
+}
+
+/// Parses the UBX protocol version (major, minor) from a MON-VER extension,
+/// for example "PROTVER=27.11" (or "PROTVER 18.00" on older firmwares).
+pub fn protocol_version(extension: &str) -> Option<(u8, u8)> {
+    let version = extension.strip_prefix("PROTVER")?;
+    let version = version.trim_start_matches(['=', ' ']);
+
+    let (major, minor) = version.split_once('.').unwrap_or((version, "0"));
+
+    Some((major.trim().parse().ok()?, minor.trim().parse().ok()?))
+}
+
+/// Returns the UBX protocol (14, 23, 27 or 31) that suits this protocol major version.
+pub fn suited_protocol(major: u8) -> u8 {
+    match major {
+        31.. => 31,
+        27.. => 27,
+        23.. => 23,
+        _ => 14,
+    }
+}
+
+/// Coarse country bounding boxes: (ISO 3166 code, lat min, lat max, long min, long max) in degrees.
+/// Boxes overlap, so smaller countries come first and the first match wins.
+const COUNTRY_BOXES: [(&str, f64, f64, f64, f64); 42] = [
+    ("LUX", 49.4, 50.2, 5.7, 6.6),
+    ("BEL", 49.5, 51.5, 2.5, 6.4),
+    ("NLD", 50.75, 53.7, 3.3, 7.2),
+    ("CHE", 45.8, 47.8, 5.9, 10.5),
+    ("AUT", 46.4, 49.0, 9.5, 17.2),
+    ("DNK", 54.5, 57.8, 8.0, 15.2),
+    ("PRT", 36.9, 42.2, -9.6, -6.2),
+    ("IRL", 51.4, 55.4, -10.5, -6.0),
+    ("GBR", 49.9, 60.9, -8.2, 1.8),
+    ("ESP", 36.0, 43.8, -9.3, 3.3),
+    ("FRA", 42.3, 51.1, -5.1, 8.2),
+    ("ITA", 36.6, 47.1, 6.6, 18.5),
+    ("CZE", 48.5, 51.1, 12.1, 18.9),
+    ("HUN", 45.7, 48.6, 16.1, 22.9),
+    ("DEU", 47.3, 55.1, 5.9, 15.0),
+    ("POL", 49.0, 54.9, 14.1, 24.2),
+    ("FIN", 59.8, 70.1, 20.5, 31.6),
+    ("SWE", 55.3, 69.1, 11.1, 24.2),
+    ("NOR", 57.9, 71.2, 4.6, 31.1),
+    ("GRC", 34.8, 41.8, 19.4, 28.3),
+    ("ROU", 43.6, 48.3, 20.2, 29.7),
+    ("ISR", 29.5, 33.3, 34.3, 35.9),
+    ("TUR", 35.8, 42.1, 26.0, 44.8),
+    ("UKR", 44.4, 52.4, 22.1, 40.2),
+    ("SGP", 1.2, 1.5, 103.6, 104.1),
+    ("TWN", 21.9, 25.3, 120.0, 122.0),
+    ("KOR", 33.1, 38.6, 124.6, 131.9),
+    ("JPN", 24.0, 45.6, 122.9, 146.0),
+    ("IND", 6.7, 35.5, 68.1, 97.4),
+    ("CHN", 18.1, 53.6, 73.5, 134.8),
+    ("RUS", 41.2, 81.9, 19.6, 180.0),
+    ("NZL", -47.3, -34.4, 166.4, 178.6),
+    ("AUS", -43.7, -10.6, 113.3, 153.6),
+    ("ZAF", -34.9, -22.1, 16.4, 32.9),
+    ("CHL", -56.0, -17.5, -75.7, -66.4),
+    ("BRA", -33.8, 5.3, -74.0, -34.7),
+    ("ARG", -55.1, -21.8, -73.6, -53.6),
+    ("MEX", 14.5, 32.7, -118.4, -86.7),
+    ("USA", 24.5, 49.4, -124.8, -66.9),
+    ("USA", 51.2, 71.4, -179.1, -129.9), // Alaska
+    ("USA", 18.9, 22.3, -160.3, -154.8), // Hawaii
+    ("CAN", 41.7, 83.1, -141.0, -52.6),
+];
+
+/// Infers the (3 letter) country code from an approximate position, in degrees.
+/// This is coarse and only meant for the V3 file names.
+pub fn country_code(latitude: f64, longitude: f64) -> Option<&'static str> {
+    COUNTRY_BOXES
+        .iter()
+        .find(|(_, lat_min, lat_max, long_min, long_max)| {
+            (*lat_min..=*lat_max).contains(&latitude)
+                && (*long_min..=*long_max).contains(&longitude)
+        })
+        .map(|(code, _, _, _, _)| *code)
+}
+
+/// True if this is a valid (ISO 3166-1 alpha-3) country code, as expected
+/// in V3 file names: exactly 3 upper case letters.
+pub fn is_country_code(code: &str) -> bool {
+    code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase())
+}
+
+/// Returns the IGS antenna type (as in rcvr_ant.tab): model left justified
+/// on 16 characters, followed by the 4 character radome code.
+pub fn igs_antenna(model: &str, radome: &str) -> String {
+    format!("{:<16}{}", model.trim(), radome.trim())
+}
+
+/// True if this antenna type follows the IGS naming convention (rcvr_ant.tab):
+/// an upper case model of up to 15 characters, followed by a 4 character radome code
+/// ("NONE" when there is no radome), for example "TRM59800.00     NONE".
+pub fn is_igs_antenna(antenna: &str) -> bool {
+    let is_model_char =
+        |c: char| c.is_ascii_uppercase() || c.is_ascii_digit() || "._-+/".contains(c);
+
+    let is_radome_char = |c: char| c.is_ascii_uppercase() || c.is_ascii_digit();
+
+    let mut items = antenna.split_whitespace();
+
+    match (items.next(), items.next(), items.next()) {
+        (Some(model), Some(radome), None) => {
+            antenna.len() <= 20
+                && model.len() <= 15
+                && model.chars().all(is_model_char)
+                && radome.len() == 4
+                && radome.chars().all(is_radome_char)
+        },
+        _ => false,
+    }
+}
+
+/// Quantizes C/N0 (dB-Hz) to the RINEX signal strength code (1..9)
+pub fn ssi_code(cno: f64) -> u8 {
+    ((cno / 6.0).floor() as u8).clamp(1, 9)
+}
+
 // pub fn freq_id_to_carrier(constellation: Constellation, freq_id: u8) -> Option<Carrier> {
 //     match constellation {
 //         Constellation::GPS => match freq_id {
@@ -367,3 +693,308 @@ pub fn from_constellation(constellation: &Constellation) -> u8 {
 //         _ => None,
 //     }
 // }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        ObservableKind, SignalCarrier, constellations_to_mask, country_code, dedup_observables,
+        igs_antenna, is_country_code, is_igs_antenna, mask_constellations, protocol_version,
+        receiver_timescale, sort_observables, ssi_code, suited_protocol,
+    };
+    use rinex::prelude::{Constellation, Observable, SV, TimeScale};
+    use std::str::FromStr;
+    use ublox::mon_gnss::MonGnssConstellMask;
+
+    #[test]
+    fn observables_per_revision() {
+        for (carrier, major, kind, expected) in [
+            (
+                SignalCarrier::GPS_L2_CL,
+                2,
+                ObservableKind::PseudoRange,
+                "C2",
+            ),
+            (
+                SignalCarrier::GPS_L2_CL,
+                3,
+                ObservableKind::PseudoRange,
+                "C2L",
+            ),
+            (
+                SignalCarrier::GPS_L2_CL,
+                4,
+                ObservableKind::PseudoRange,
+                "C2L",
+            ),
+            (
+                SignalCarrier::GAL_E5B_Q,
+                2,
+                ObservableKind::PhaseRange,
+                "L7",
+            ),
+            (
+                SignalCarrier::GAL_E5B_Q,
+                3,
+                ObservableKind::PhaseRange,
+                "L7Q",
+            ),
+            (SignalCarrier::GPS_L1_CA, 2, ObservableKind::Doppler, "D1"),
+            (SignalCarrier::GPS_L1_CA, 3, ObservableKind::SSI, "S1C"),
+        ] {
+            assert_eq!(
+                carrier.to_observable(kind, major),
+                Observable::from_str(expected).unwrap(),
+                "{:?} {:?} (V{})",
+                carrier,
+                kind,
+                major
+            );
+        }
+    }
+
+    #[test]
+    fn signal_carrier_names() {
+        for (name, expected) in [
+            ("BDS_B1I_D1", SignalCarrier::BDS_B1I_D1),
+            ("bds_b1c", SignalCarrier::BDS_B1C),
+            ("GLO_L2_OF", SignalCarrier::GLO_L2_OF),
+        ] {
+            let carrier = SignalCarrier::from_str(name).unwrap();
+            assert_eq!(carrier, expected);
+            assert_eq!(format!("{:?}", carrier), name.to_uppercase());
+        }
+
+        assert_eq!(
+            SignalCarrier::BDS_B1I_D2.constellation(),
+            Constellation::BeiDou
+        );
+
+        assert!(SignalCarrier::from_str("BDS_B3I").is_err());
+    }
+
+    #[test]
+    fn beidou_geo_signals() {
+        for (sv, freq_id, expected) in [
+            ("C03", 0, SignalCarrier::BDS_B1I_D2), // GEO
+            ("C03", 1, SignalCarrier::BDS_B1I_D2),
+            ("C03", 2, SignalCarrier::BDS_B2I_D2),
+            ("C60", 0, SignalCarrier::BDS_B1I_D2), // GEO (BDS-3)
+            ("C20", 0, SignalCarrier::BDS_B1I_D1), // MEO
+            ("C20", 1, SignalCarrier::BDS_B1I_D1),
+            ("C20", 3, SignalCarrier::BDS_B2I_D1),
+            ("C20", 5, SignalCarrier::BDS_B1C),
+            ("G03", 0, SignalCarrier::GPS_L1_CA),
+        ] {
+            let sv = SV::from_str(sv).unwrap();
+            assert_eq!(SignalCarrier::from_ubx_sv(sv, freq_id), expected, "{}", sv);
+        }
+
+        let geo = SignalCarrier::from_ubx_sv(SV::from_str("C01").unwrap(), 0);
+        assert_eq!(
+            geo.to_observable(ObservableKind::PseudoRange, 3),
+            Observable::from_str("C2D").unwrap()
+        );
+    }
+
+    #[test]
+    fn v2_observables_collision() {
+        let kinds = [
+            ObservableKind::PhaseRange,
+            ObservableKind::PseudoRange,
+            ObservableKind::Doppler,
+            ObservableKind::SSI,
+        ];
+
+        for (major, expected) in [
+            (2, vec!["L1", "C1", "D1", "S1"]),
+            (
+                3,
+                vec!["L1C", "L1B", "C1C", "C1B", "D1C", "D1B", "S1C", "S1B"],
+            ),
+        ] {
+            // Galileo E1-C and E1-B, like the --l1 header
+            let mut observables = kinds
+                .iter()
+                .flat_map(|kind| {
+                    [SignalCarrier::GAL_E1_C, SignalCarrier::GAL_E1_B]
+                        .map(|carrier| carrier.to_observable(*kind, major))
+                })
+                .collect::<Vec<_>>();
+
+            let collisions = dedup_observables(&mut observables);
+
+            let codes = observables
+                .iter()
+                .map(|observable| observable.to_string())
+                .collect::<Vec<_>>();
+
+            assert_eq!(codes, expected, "V{}", major);
+
+            if major == 2 {
+                assert_eq!(collisions.len(), 4);
+            } else {
+                assert!(collisions.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn gps_l1_l2_observables_order() {
+        let mut observables = ["S2L", "D1C", "L2L", "C2L", "S1C", "L1C", "D2L", "C1C"]
+            .iter()
+            .map(|code| Observable::from_str(code).unwrap())
+            .collect::<Vec<_>>();
+
+        sort_observables(&mut observables);
+
+        let codes = observables
+            .iter()
+            .map(|observable| observable.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            codes,
+            vec!["C1C", "C2L", "L1C", "L2L", "D1C", "D2L", "S1C", "S2L"]
+        );
+    }
+
+    #[test]
+    fn signal_strength_code() {
+        for (cno, code) in [
+            (0.0, 1),
+            (11.9, 1),
+            (12.0, 2),
+            (17.9, 2),
+            (18.0, 3),
+            (35.0, 5),
+            (45.0, 7),
+            (53.9, 8),
+            (54.0, 9),
+            (63.0, 9),
+        ] {
+            assert_eq!(ssi_code(cno), code, "failed for {} dB-Hz", cno);
+        }
+    }
+
+    #[test]
+    fn country_inference() {
+        for (latitude, longitude, code) in [
+            (48.85, 2.35, Some("FRA")),
+            (52.52, 13.40, Some("DEU")),
+            (50.85, 4.35, Some("BEL")),
+            (35.68, 139.69, Some("JPN")),
+            (40.01, -105.27, Some("USA")),
+            (61.22, -149.90, Some("USA")),
+            (51.05, -114.07, Some("CAN")),
+            (-33.87, 151.21, Some("AUS")),
+            (0.0, -30.0, None),
+        ] {
+            assert_eq!(
+                country_code(latitude, longitude),
+                code,
+                "failed for {}°, {}°",
+                latitude,
+                longitude
+            );
+        }
+    }
+
+    #[test]
+    fn country_code_validation() {
+        for (code, valid) in [
+            ("FRA", true),
+            ("USA", true),
+            ("fra", false),
+            ("FR", false),
+            ("FRAN", false),
+            ("F1A", false),
+        ] {
+            assert_eq!(is_country_code(code), valid, "failed for \"{}\"", code);
+        }
+    }
+
+    #[test]
+    fn ubx_protocol_version() {
+        assert_eq!(protocol_version("PROTVER=27.11"), Some((27, 11)));
+        assert_eq!(protocol_version("PROTVER=34.10"), Some((34, 10)));
+        assert_eq!(protocol_version("PROTVER 18.00"), Some((18, 0)));
+        assert_eq!(protocol_version("PROTVER=23"), Some((23, 0)));
+        assert_eq!(protocol_version("MOD=ZED-F9P"), None);
+        assert_eq!(protocol_version("PROTVER=abc"), None);
+
+        assert_eq!(suited_protocol(18), 14);
+        assert_eq!(suited_protocol(23), 23);
+        assert_eq!(suited_protocol(27), 27);
+        assert_eq!(suited_protocol(29), 27);
+        assert_eq!(suited_protocol(34), 31);
+    }
+
+    #[test]
+    fn igs_antenna_naming() {
+        assert_eq!(igs_antenna("TRM59800.00", "NONE"), "TRM59800.00     NONE");
+        assert_eq!(igs_antenna("LEIAR25.R3", "LEIT"), "LEIAR25.R3      LEIT");
+
+        assert!(is_igs_antenna("TRM59800.00     NONE"));
+        assert!(is_igs_antenna("TRM59800.00 NONE"));
+        assert!(is_igs_antenna("AOAD/M_T        NONE"));
+        assert!(is_igs_antenna("ASH701945E_M    SCIS"));
+
+        // radome is missing
+        assert!(!is_igs_antenna("TRM59800.00"));
+        // lower case
+        assert!(!is_igs_antenna("ANN-MB-00       none"));
+        // model is too long
+        assert!(!is_igs_antenna("ANN-MB-00-ANTENNA NONE"));
+        assert!(!is_igs_antenna("u-blox patch"));
+    }
+
+    #[test]
+    fn mon_gnss_mask() {
+        let requested = [
+            Constellation::GPS,
+            Constellation::QZSS,
+            Constellation::Galileo,
+            Constellation::BeiDou,
+        ];
+
+        let mask = constellations_to_mask(&requested);
+        assert_eq!(
+            mask,
+            MonGnssConstellMask::GPS | MonGnssConstellMask::GAL | MonGnssConstellMask::BDC
+        );
+
+        // receiver without BeiDou support
+        let supported =
+            MonGnssConstellMask::GPS | MonGnssConstellMask::GAL | MonGnssConstellMask::GLO;
+
+        assert_eq!(
+            mask_constellations(mask.difference(supported)),
+            vec![Constellation::BeiDou]
+        );
+
+        assert_eq!(
+            mask_constellations(supported),
+            vec![
+                Constellation::GPS,
+                Constellation::Galileo,
+                Constellation::Glonass
+            ]
+        );
+    }
+
+    #[test]
+    fn rawx_receiver_timescale() {
+        assert_eq!(
+            receiver_timescale(&[Constellation::GPS, Constellation::Galileo]),
+            TimeScale::GPST
+        );
+
+        assert_eq!(
+            receiver_timescale(&[Constellation::Galileo]),
+            TimeScale::GST
+        );
+
+        assert_eq!(receiver_timescale(&[Constellation::BeiDou]), TimeScale::BDT);
+        assert_eq!(receiver_timescale(&[]), TimeScale::GPST);
+    }
+}
@@ -0,0 +1,118 @@
+//! Passive conversion of a UBX capture, through the library API:
+//! the produced observation RINEX must parse back with the expected content.
+
+use std::{collections::BTreeSet, fs::File, str::FromStr};
+
+use rinex::prelude::{Constellation, Observable, Rinex, SV};
+
+use ubx2rinex::{RinexSettings, UbloxSettings, UbxToRinex};
+
+/// 30 epochs at 1 s of RXM-RAWX (GPS L1 C/A and Galileo E1-C), NAV-PVT and NAV-EOE,
+/// along with the GPS subframes #1 to #3 (RXM-SFRBX). Produced by data/UBX/rawx-gps-gal-30s.py
+const CAPTURE: &str = "data/UBX/rawx-gps-gal-30s.ubx";
+
+#[tokio::test]
+async fn passive_round_trip() {
+    let observables = ["C1C", "L1C", "D1C", "S1C"]
+        .iter()
+        .map(|code| Observable::from_str(code).unwrap())
+        .collect::<Vec<_>>();
+
+    let settings = RinexSettings {
+        observables: [Constellation::GPS, Constellation::Galileo]
+            .into_iter()
+            .map(|constellation| (constellation, observables.clone()))
+            .collect(),
+        ..Default::default()
+    };
+
+    let ublox = UbloxSettings {
+        constellations: vec![Constellation::GPS, Constellation::Galileo],
+        ..Default::default()
+    };
+
+    let fd = File::open(CAPTURE).unwrap_or_else(|e| panic!("{}: {}", CAPTURE, e));
+
+    let products = UbxToRinex::new(fd)
+        .with_rinex_settings(settings)
+        .with_ublox_settings(ublox)
+        .convert_to_memory()
+        .await;
+
+    // ephemeris are not collected by default: observations only
+    let files = products.files();
+    assert_eq!(files.len(), 1);
+
+    // parse the formatted file back
+    let (filename, content) = &files[0];
+    assert!(filename.ends_with('O'), "{}", filename);
+
+    let path = std::env::temp_dir().join(filename);
+    std::fs::write(&path, content).unwrap();
+
+    let rinex = Rinex::from_file(&path).unwrap_or_else(|e| panic!("{}: {}", filename, e));
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(rinex.header.constellation, Some(Constellation::Mixed));
+
+    let record = rinex.record.as_obs().unwrap();
+    assert_eq!(record.len(), 30);
+
+    let satellites = record
+        .values()
+        .flat_map(|observations| observations.signals.iter().map(|signal| signal.sv))
+        .collect::<BTreeSet<_>>();
+
+    let expected = ["G02", "G05", "G12", "G25", "G29", "E03", "E08", "E13"]
+        .iter()
+        .map(|sv| SV::from_str(sv).unwrap())
+        .collect::<BTreeSet<_>>();
+
+    assert_eq!(satellites, expected);
+
+    for observations in record.values() {
+        assert_eq!(observations.signals.len(), 8 * observables.len());
+    }
+}
+
+#[tokio::test]
+async fn passive_navigation_round_trip() {
+    let ublox = UbloxSettings {
+        constellations: vec![Constellation::GPS, Constellation::Galileo],
+        ephemeris: true,
+        ..Default::default()
+    };
+
+    let fd = File::open(CAPTURE).unwrap_or_else(|e| panic!("{}: {}", CAPTURE, e));
+
+    let products = UbxToRinex::new(fd)
+        .with_ublox_settings(ublox)
+        .convert_to_memory()
+        .await;
+
+    let files = products.files();
+    assert_eq!(files.len(), 2);
+
+    let (filename, content) = files
+        .iter()
+        .find(|(filename, _)| filename.ends_with('N'))
+        .unwrap_or_else(|| panic!("no navigation file"));
+
+    let path = std::env::temp_dir().join(filename);
+    std::fs::write(&path, content).unwrap();
+
+    let rinex = Rinex::from_file(&path).unwrap_or_else(|e| panic!("{}: {}", filename, e));
+    let _ = std::fs::remove_file(&path);
+
+    // one ephemeris per GPS satellite, decoded from its subframes
+    let record = rinex.record.as_nav().unwrap();
+
+    let satellites = record.keys().map(|key| key.sv).collect::<Vec<_>>();
+
+    let expected = ["G02", "G05", "G12", "G25", "G29"]
+        .iter()
+        .map(|sv| SV::from_str(sv).unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(satellites, expected);
+}